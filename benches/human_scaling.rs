@@ -0,0 +1,54 @@
+#![feature(test)]
+
+extern crate test;
+
+use serde::Deserialize;
+
+use test::Bencher;
+use valuable_value::human::VVDeserializer;
+
+#[derive(Deserialize)]
+enum Toggle {
+    On,
+    Off,
+}
+
+// Only ever decoded, never inspected.
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Untagged {
+    Toggle(Toggle),
+    Int(i64),
+    Bool(bool),
+    List(Vec<Untagged>),
+}
+
+// A long list of untagged enums, each of which serde tries against every variant in turn.
+fn untagged_document(n: usize) -> Vec<u8> {
+    let mut enc = b"[".to_vec();
+    for i in 0..n {
+        enc.extend_from_slice(format!(r#"[{}, true, "Off", [[], "On", [false, -{}]]],"#, i, i).as_bytes());
+    }
+    enc.push(b']');
+    enc
+}
+
+fn decode_untagged(b: &mut Bencher, n: usize) {
+    let enc = untagged_document(n);
+    b.iter(|| {
+        let v = Untagged::deserialize(&mut VVDeserializer::new(&enc)).unwrap();
+        test::black_box(v);
+    });
+}
+
+// Compare against the 2000 item variant: eight times the input should take about eight times as long.
+#[bench]
+fn untagged_2000(b: &mut Bencher) {
+    decode_untagged(b, 2_000);
+}
+
+#[bench]
+fn untagged_16000(b: &mut Bencher) {
+    decode_untagged(b, 16_000);
+}
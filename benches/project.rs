@@ -0,0 +1,38 @@
+#![feature(test)]
+
+extern crate test;
+
+use std::collections::BTreeMap;
+
+use test::Bencher;
+use valuable_value::{compact, Value};
+
+// A record with many wide fields, of which only two are of interest.
+fn wide_record() -> Vec<u8> {
+    let mut m = BTreeMap::new();
+    for i in 0..500 {
+        let row = (0..20).map(|j| Value::Array(vec![Value::Int(i * j), Value::Float(j as f64 / 3.0), Value::from("padding")])).collect();
+        m.insert(Value::from(format!("field{}", i).as_str()), Value::Array(row));
+    }
+    m.insert(Value::from("name"), Value::from("wide"));
+    m.insert(Value::from("version"), Value::Int(3));
+    compact::to_vec(&Value::Map(m)).unwrap()
+}
+
+#[bench]
+fn full_decode(b: &mut Bencher) {
+    let enc = wide_record();
+    b.iter(|| {
+        let v: Value = compact::from_slice(&enc).unwrap();
+        test::black_box(v);
+    });
+}
+
+#[bench]
+fn projection(b: &mut Bencher) {
+    let enc = wide_record();
+    b.iter(|| {
+        let v = compact::project(&enc, &["name", "version"]).unwrap();
+        test::black_box(v);
+    });
+}
@@ -0,0 +1,8 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use valuable_value::fuzz::canonic_idempotence;
+
+fuzz_target!(|data: &[u8]| {
+    canonic_idempotence(data);
+});
@@ -0,0 +1,8 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use valuable_value::fuzz::decode_compact;
+
+fuzz_target!(|data: &[u8]| {
+    decode_compact(data);
+});
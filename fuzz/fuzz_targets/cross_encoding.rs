@@ -0,0 +1,14 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use libfuzzer_sys::arbitrary::Arbitrary;
+
+use valuable_value::{
+    Value,
+    test_type::*,
+    testing::*,
+};
+
+fuzz_target!(|data: &[u8]| {
+    check_cross_encoding(data, Value::arbitrary);
+    check_cross_encoding(data, TestType::arbitrary);
+});
@@ -0,0 +1,208 @@
+use core::cmp::Ordering;
+use Ordering::*;
+
+use crate::value::Value;
+
+impl Value {
+    /// Like `==`, but gives up and returns `None` after visiting more than `max_steps` values.
+    ///
+    /// Use this when comparing untrusted values, to bound the amount of work an adversarially large value can cause.
+    pub fn eq_bounded(&self, other: &Self, max_steps: usize) -> Option<bool> {
+        let mut budget = max_steps;
+        eq_bounded(self, other, &mut budget)
+    }
+
+    /// Like [`cmp`](Ord::cmp), but gives up and returns `None` after visiting more than `max_steps` values.
+    ///
+    /// Use this when comparing untrusted values, to bound the amount of work an adversarially large value can cause.
+    pub fn cmp_bounded(&self, other: &Self, max_steps: usize) -> Option<Ordering> {
+        let mut budget = max_steps;
+        cmp_bounded(self, other, &mut budget)
+    }
+
+    /// Return whether this value consists of at most `max_size` values (counting itself and all values nested within it), without visiting more than `max_size + 1` of them.
+    pub fn size_at_most(&self, max_size: usize) -> bool {
+        let mut budget = max_size;
+        count_bounded(self, &mut budget).is_some()
+    }
+}
+
+fn step(budget: &mut usize) -> Option<()> {
+    *budget = budget.checked_sub(1)?;
+    Some(())
+}
+
+fn count_bounded(v: &Value, budget: &mut usize) -> Option<()> {
+    step(budget)?;
+    match v {
+        Value::Array(vs) => {
+            for inner in vs {
+                count_bounded(inner, budget)?;
+            }
+        }
+        Value::Map(m) => {
+            for (k, inner) in m {
+                count_bounded(k, budget)?;
+                count_bounded(inner, budget)?;
+            }
+        }
+        _ => {}
+    }
+    Some(())
+}
+
+fn eq_bounded(v1: &Value, v2: &Value, budget: &mut usize) -> Option<bool> {
+    step(budget)?;
+    match (v1, v2) {
+        (Value::Array(vs1), Value::Array(vs2)) => {
+            if vs1.len() != vs2.len() {
+                return Some(false);
+            }
+            for (x1, x2) in vs1.iter().zip(vs2.iter()) {
+                if !eq_bounded(x1, x2, budget)? {
+                    return Some(false);
+                }
+            }
+            Some(true)
+        }
+        (Value::Map(m1), Value::Map(m2)) => {
+            if m1.len() != m2.len() {
+                return Some(false);
+            }
+            for ((k1, x1), (k2, x2)) in m1.iter().zip(m2.iter()) {
+                if !eq_bounded(k1, k2, budget)? || !eq_bounded(x1, x2, budget)? {
+                    return Some(false);
+                }
+            }
+            Some(true)
+        }
+        (Value::Array(_), _) | (_, Value::Array(_)) | (Value::Map(_), _) | (_, Value::Map(_)) => Some(false),
+        _ => Some(v1 == v2),
+    }
+}
+
+fn cmp_bounded(v1: &Value, v2: &Value, budget: &mut usize) -> Option<Ordering> {
+    step(budget)?;
+    match (v1, v2) {
+        (Value::Array(vs1), Value::Array(vs2)) => {
+            for (x1, x2) in vs1.iter().zip(vs2.iter()) {
+                match cmp_bounded(x1, x2, budget)? {
+                    Equal => {}
+                    other => return Some(other),
+                }
+            }
+            Some(vs1.len().cmp(&vs2.len()))
+        }
+        (Value::Map(m1), Value::Map(m2)) => {
+            let mut es1 = m1.iter();
+            let mut es2 = m2.iter();
+
+            loop {
+                match (es1.next(), es2.next()) {
+                    (None, None) => return Some(Equal),
+                    (None, Some(_)) => return Some(Less),
+                    (Some(_), None) => return Some(Greater),
+                    (Some((k1, x1)), Some((k2, x2))) => {
+                        match cmp_bounded(k1, k2, budget)? {
+                            Less => return Some(Greater),
+                            Greater => return Some(Less),
+                            Equal => {
+                                match cmp_bounded(x1, x2, budget)? {
+                                    Equal => {}
+                                    other => return Some(other),
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // Comparing a collection with anything of a different kind does not inspect its contents.
+        _ => Some(v1.cmp(v2)),
+    }
+}
+
+/// A [`Value`](Value) of bounded size, suitable as a key in maps that hold untrusted data.
+///
+/// Because the size of the value is checked on construction, comparing two `BoundedValue`s takes time proportional to that limit at most.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BoundedValue(Value);
+
+impl BoundedValue {
+    /// Wrap a value, or return `None` if it consists of more than `max_size` values (see [`Value::size_at_most`](Value::size_at_most)).
+    pub fn new(v: Value, max_size: usize) -> Option<Self> {
+        if v.size_at_most(max_size) {
+            Some(BoundedValue(v))
+        } else {
+            None
+        }
+    }
+
+    /// Return a reference to the wrapped value.
+    pub fn value(&self) -> &Value {
+        &self.0
+    }
+
+    /// Unwrap the value.
+    pub fn into_value(self) -> Value {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn large(n: usize) -> Value {
+        let mut m = BTreeMap::new();
+        for i in 0..n {
+            m.insert(Value::Int(i as i64), Value::Array(vec![Value::Int(i as i64); 4]));
+        }
+        Value::Map(m)
+    }
+
+    #[test]
+    fn budget_exceeded() {
+        let (a, b) = (large(1000), large(1000));
+        assert_eq!(a.eq_bounded(&b, 100), None);
+        assert_eq!(a.cmp_bounded(&b, 100), None);
+        assert_eq!(a.eq_bounded(&b, 100_000), Some(true));
+        assert_eq!(a.cmp_bounded(&b, 100_000), Some(Equal));
+    }
+
+    #[test]
+    fn small_values() {
+        let values = vec![
+            Value::Nil,
+            Value::Bool(true),
+            Value::Float(f64::NAN),
+            Value::Float(-0.0),
+            Value::Int(3),
+            Value::Array(vec![]),
+            Value::Array(vec![Value::Int(1), Value::Int(2)]),
+            Value::Array(vec![Value::Int(1), Value::Int(3)]),
+            large(0),
+            large(2),
+            large(3),
+        ];
+
+        for v in values.iter() {
+            for w in values.iter() {
+                assert_eq!(v.eq_bounded(w, 100), Some(v == w));
+                assert_eq!(v.cmp_bounded(w, 100), Some(v.cmp(w)));
+            }
+        }
+    }
+
+    #[test]
+    fn bounded_value() {
+        assert!(BoundedValue::new(large(1000), 100).is_none());
+        assert!(!large(2).size_at_most(12));
+        assert!(large(2).size_at_most(13));
+
+        let mut m = BTreeMap::new();
+        m.insert(BoundedValue::new(large(2), 13).unwrap(), ());
+        assert!(m.contains_key(&BoundedValue::new(large(2), 100).unwrap()));
+    }
+}
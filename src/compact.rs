@@ -1,7 +1,36 @@
+//! Serialization and deserialization for the [compact encoding](https://github.com/AljoschaMeyer/valuable-value#compact-encoding).
+//!
+//! ```
+//! use serde::{Serialize, Deserialize};
+//! use valuable_value::{compact, human, Value};
+//!
+//! #[derive(Serialize, Deserialize, Debug, PartialEq)]
+//! struct Point {
+//!     label: String,
+//!     x: i64,
+//!     y: i64,
+//! }
+//!
+//! let p = Point { label: "origin".to_string(), x: 0, y: -1 };
+//!
+//! let enc = compact::to_vec(&p).unwrap();
+//! assert_eq!(compact::from_slice::<Point>(&enc).unwrap(), p);
+//!
+//! // Inspect the code without knowing its type.
+//! let v: Value = compact::from_slice(&enc).unwrap();
+//! assert_eq!(v.get_str(&[Value::from("label")]).unwrap(), "origin");
+//! assert_eq!(v.get_int(&[Value::from("y")]).unwrap(), -1);
+//!
+//! // Convert to the human-readable encoding, strings become arrays of their bytes.
+//! let enc_human = human::to_vec(&v, 0).unwrap();
+//! assert_eq!(enc_human, b"{[108,97,98,101,108]:[111,114,105,103,105,110],[120]:0,[121]:-1}");
+//! assert_eq!(human::from_slice::<Point>(&enc_human).unwrap(), p);
+//! ```
 mod de;
 pub use de::*;
 mod ser;
 pub use ser::*;
+pub mod tags;
 
 #[cfg(feature = "arbitrary")]
 pub mod test_value;
@@ -1,15 +1,22 @@
 use serde::Deserialize;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
+use std::io;
 use std::fmt;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut, Range};
 
 use thiserror::Error;
 use atm_parser_helper::{ParserHelper, Eoi, Error as ParseError};
 
 use serde::de::{
-    self, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor,
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor, IntoDeserializer,
 };
 
-use crate::helpers::AlwaysNil;
+use crate::compact::tags::*;
+use crate::helpers::{visited_at, AlwaysNil};
+use crate::value::{Value, captured_str, render_value};
 
 /// Everything that can go wrong during deserialization of a valuable value from the compact
 /// encoding.
@@ -66,6 +73,10 @@ pub enum DecodeError {
 
     #[error("expected nil")]
     ExpectedNil,
+    /// Attempted to decode `()` or a unit struct from something other than nil (or, with
+    /// [`VVDeserializer::lenient_units`](VVDeserializer::lenient_units), an empty array, set or map). Holds what was found instead.
+    #[error("expected nil, found {found}")]
+    ExpectedUnit { found: &'static str },
     #[error("expected bool")]
     ExpectedBool,
     #[error("expected float")]
@@ -74,6 +85,9 @@ pub enum DecodeError {
     ExpectedInt,
     #[error("expected option")]
     ExpectedOption,
+    /// An option was encoded with a tag other than `"None"` or `"Some"`. Holds the tag, truncated if it was long.
+    #[error("expected option, found tag {}", render_value(.0))]
+    UnexpectedOptionTag(Value),
     #[error("expected byte string")]
     ExpectedString,
     #[error("expected byte string")]
@@ -86,6 +100,34 @@ pub enum DecodeError {
     ExpectedEnum(String),
     #[error("expected enum variant (either a string or a singleton map)")]
     ExpectedEnumVariant,
+
+    #[error("expected extension")]
+    ExpectedExtension,
+
+    /// Decoded a NaN whose bit pattern is not the canonic `u64::MAX`, see [`VVDeserializer::reject_nan_payloads`](VVDeserializer::reject_nan_payloads). Holds the bit pattern.
+    #[error("NaN with non-canonic bit pattern {0:#018x}")]
+    NanPayload(u64),
+    /// A map or set contains two equal keys, see [`VVDeserializer::require_sorted_keys`](VVDeserializer::require_sorted_keys). Holds the key.
+    #[error("duplicate key {}", render_value(.0))]
+    DuplicateKey(Value),
+    /// A key of a map or set is less than the key before it, see [`VVDeserializer::require_sorted_keys`](VVDeserializer::require_sorted_keys). Holds the key.
+    #[error("key {} is less than the previous key", render_value(.0))]
+    UnsortedKeys(Value),
+
+    /// Attempted to decode an array (e.g. into a tuple) with a different number of items than required.
+    #[error("expected an array of {expected} items, found {found}")]
+    WrongArrayLength { expected: usize, found: usize },
+
+    /// Encountered a tag that the compact encoding does not assign a meaning to, see [`UnknownTagAction`](UnknownTagAction).
+    #[error("unknown tag {byte:#010b} at position {position}")]
+    UnknownTag { byte: u8, position: usize },
+
+    /// Reading the input failed, see [`from_reader`](from_reader).
+    #[error("failed to read input: {0}")]
+    Io(String),
+    /// The input continued after the encoded value, see [`VVDeserializer::end`](VVDeserializer::end).
+    #[error("input contains bytes after the encoded value")]
+    TrailingBytes,
 }
 
 impl Eoi for DecodeError {
@@ -102,11 +144,106 @@ impl de::Error for DecodeError {
 
 pub type Error = ParseError<DecodeError>;
 
+// Strings encoded as arrays of ints of at most this many bytes are decoded without allocating.
+const SHORT_STRING: usize = 64;
+
+// Nesting depth up to which `OpenCollections` does not allocate.
+const SHALLOW_DEPTH: usize = 16;
+
+// A stack of counts whose first `SHALLOW_DEPTH` entries are stored inline, so that creating a `VVDeserializer` for
+// every record (as `from_slice_in_place` does) does not cost an allocation per record.
+#[derive(Default)]
+struct OpenCollections {
+    shallow: [usize; SHALLOW_DEPTH],
+    deep: Vec<usize>,
+    len: usize,
+}
+
+impl OpenCollections {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn last_mut(&mut self) -> Option<&mut usize> {
+        if self.len == 0 {
+            None
+        } else if self.len <= SHALLOW_DEPTH {
+            Some(&mut self.shallow[self.len - 1])
+        } else {
+            self.deep.last_mut()
+        }
+    }
+
+    fn push(&mut self, n: usize) {
+        if self.len < SHALLOW_DEPTH {
+            self.shallow[self.len] = n;
+        } else {
+            self.deep.push(n);
+        }
+        self.len += 1;
+    }
+
+    fn pop(&mut self) {
+        if self.len > SHALLOW_DEPTH {
+            self.deep.pop();
+        }
+        self.len = self.len.saturating_sub(1);
+    }
+}
+
 /// A struct that deserializes valuable values from the [compact coding](https://github.com/AljoschaMeyer/valuable-value#compact-encoding).
 ///
-/// Does not enforce that the input must be empty after the first valid code.
+/// Does not enforce that the input must be empty after the first valid code, call [`end`](VVDeserializer::end) for that, or use [`from_slice`](from_slice).
+///
+/// Strings encoded as byte strings can be deserialized as `&'de str` (e.g., as the keys of a `BTreeMap<&str, _>`), strings encoded as arrays of ints only into owned types.
 pub struct VVDeserializer<'de> {
     p: ParserHelper<'de>,
+    metrics: Metrics,
+    // For each collection that is currently being decoded, how many of its directly nested values have not been started yet.
+    open: OpenCollections,
+    on_unknown_tag: Option<fn(u8, &[u8]) -> UnknownTagAction>,
+    compact_options: bool,
+    reject_nan_payloads: bool,
+    lenient_units: bool,
+    require_sorted_keys: bool,
+    // How many calls to the `Deserializer` methods are currently running.
+    depth: usize,
+    last_span: Option<Range<usize>>,
+    // How many bytes `key_at` decoded a second time, lets tests check that sorted keys cost at most quadratic time.
+    #[cfg(test)]
+    rescanned: usize,
+}
+
+/// What to do when encountering a tag that the compact encoding does not (yet) assign a meaning to.
+///
+/// Not every tag byte denotes a value: of the nil, bool and float tags, only [`NIL`](crate::compact::tags::NIL),
+/// [`FALSE`](crate::compact::tags::FALSE), [`TRUE`](crate::compact::tags::TRUE) and [`FLOAT`](crate::compact::tags::FLOAT)
+/// are in use. Future versions of the encoding might assign meanings to the remaining ones. A protocol
+/// that anticipates such extensions can install a hook via
+/// [`VVDeserializer::on_unknown_tag`](VVDeserializer::on_unknown_tag), so that older decoders can step over new kinds of
+/// values instead of rejecting the whole input. The hook receives the tag and the input following it, and must know (for
+/// example from a length prefix the protocol mandates for extensions) how many bytes belong to the unknown value.
+///
+/// Unknown tags are only handled where any value may occur, i.e. by `deserialize_any` and `deserialize_ignored_any`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UnknownTagAction {
+    /// Fail with [`DecodeError::UnknownTag`](DecodeError::UnknownTag). This is what happens if no hook is installed.
+    Error,
+    /// Skip the tag and the given number of bytes following it, then decode the next value in its place.
+    Skip(usize),
+    /// Skip the tag and the given number of bytes following it, and decode nil in its place.
+    Nil(usize),
+}
+
+/// Statistics about the input consumed by a [`VVDeserializer`](VVDeserializer), see [`from_slice_metered`](from_slice_metered).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Metrics {
+    /// How many bytes of input were read.
+    pub bytes: usize,
+    /// How many values were decoded, counting every value nested inside a collection (including map keys), but not the bytes of strings.
+    pub nodes: usize,
+    /// The deepest nesting of any decoded value, where a top-level value has depth one.
+    pub max_depth: usize,
 }
 
 impl<'de> VVDeserializer<'de> {
@@ -114,68 +251,309 @@ impl<'de> VVDeserializer<'de> {
     pub fn new(input: &'de [u8]) -> Self {
         VVDeserializer {
             p: ParserHelper::new(input),
+            metrics: Metrics::default(),
+            open: OpenCollections::default(),
+            on_unknown_tag: None,
+            compact_options: false,
+            reject_nan_payloads: false,
+            lenient_units: false,
+            require_sorted_keys: false,
+            depth: 0,
+            last_span: None,
+            #[cfg(test)]
+            rescanned: 0,
         }
     }
 
+    /// Set the function that decides what to do with [unknown tags](UnknownTagAction).
+    pub fn on_unknown_tag(mut self, hook: fn(u8, &[u8]) -> UnknownTagAction) -> Self {
+        self.on_unknown_tag = Some(hook);
+        self
+    }
+
+    /// Decode options as encoded by [`VVSerializer::compact_options`](crate::compact::VVSerializer::compact_options): nil as `None`, and anything else as `Some` of that value.
+    ///
+    /// Since `Some(())` and `Some(None)` are encoded as nil as well, they decode as `None`. Together with
+    /// [`lenient_units`](VVDeserializer::lenient_units), an empty array, set or map decodes as `Some(())` where an
+    /// `Option<()>` is expected, but nil still decodes as `None`.
+    pub fn compact_options(mut self, compact: bool) -> Self {
+        self.compact_options = compact;
+        self
+    }
+
+    /// Accept empty arrays, sets and maps (with any count width) wherever `()` or a unit struct is expected, in addition to nil.
+    ///
+    /// Off by default. Encoders always write units as nil, but some producers write them as empty arrays or maps instead.
+    pub fn lenient_units(mut self, lenient: bool) -> Self {
+        self.lenient_units = lenient;
+        self
+    }
+
+    /// Reject NaNs whose bit pattern is not the canonic `u64::MAX` with [`DecodeError::NanPayload`](DecodeError::NanPayload).
+    ///
+    /// The compact encoding preserves the payload of a NaN, but the human-readable encoding and [`Value::eq`](crate::Value) do not, so a value that contains such a NaN may silently change when it passes through them. This option lets a pipeline that hashes or signs encodings refuse such values right away.
+    pub fn reject_nan_payloads(mut self, reject: bool) -> Self {
+        self.reject_nan_payloads = reject;
+        self
+    }
+
+    /// Require the keys of every map and set to be in strictly ascending [order](crate::Value), as in the canonic encoding,
+    /// and fail with [`DecodeError::DuplicateKey`](DecodeError::DuplicateKey) or
+    /// [`DecodeError::UnsortedKeys`](DecodeError::UnsortedKeys) at the first key that is not greater than the one before it.
+    ///
+    /// Off by default, in which case the type being decoded decides what happens, e.g., a `BTreeMap` keeps the last value
+    /// of a duplicate key. Every key is decoded a second time as a [`Value`](crate::Value) for the comparison. Note that
+    /// the [`VVSerializer`](crate::compact::VVSerializer) writes the fields of structs in declaration order, which need not
+    /// be sorted.
+    pub fn require_sorted_keys(mut self, require: bool) -> Self {
+        self.require_sorted_keys = require;
+        self
+    }
+
+    // Decode the key in the given range of the input as a `Value`, to compare it with the previous key of its map. Maps and
+    // sets nested in the key have already been checked while decoding the key itself, checking them again here would decode
+    // keys nested `d` deep `2^d` times. This way, every byte is decoded once more for every key it is nested in.
+    fn key_at(&mut self, range: Range<usize>) -> Result<Value, Error> {
+        #[cfg(test)]
+        {
+            self.rescanned += range.len();
+        }
+        let mut des = VVDeserializer {
+            p: ParserHelper::new(self.p.slice(range.clone())),
+            on_unknown_tag: self.on_unknown_tag,
+            ..VVDeserializer::new(b"")
+        };
+        Value::deserialize(&mut des).map_err(|err| ParseError { position: range.start + err.position, e: err.e })
+    }
+
+    // If keys must be sorted, check that the key that was just decoded from `start` onwards is greater than `previous`,
+    // and make it the new `previous`.
+    fn check_key_order(&mut self, previous: &mut Option<Value>, start: usize) -> Result<(), Error> {
+        if self.require_sorted_keys {
+            let key = self.key_at(start..self.p.position())?;
+            match previous.as_ref().map(|previous| key.cmp(previous)) {
+                Some(Ordering::Equal) => return self.p.fail_at_position(DecodeError::DuplicateKey(key), start),
+                Some(Ordering::Less) => return self.p.fail_at_position(DecodeError::UnsortedKeys(key), start),
+                _ => *previous = Some(key),
+            }
+        }
+        Ok(())
+    }
+
     /// Return how many input bytes have been already read.
     pub fn position(&self) -> usize {
         self.p.position()
     }
 
+    /// Check that the whole input has been read, and report [`DecodeError::TrailingBytes`](DecodeError::TrailingBytes) otherwise.
+    ///
+    /// Call this after deserializing the last value, unless the input may continue with arbitrary data.
+    pub fn end(&self) -> Result<(), Error> {
+        if self.p.rest().is_empty() {
+            Ok(())
+        } else {
+            self.p.fail(DecodeError::TrailingBytes)
+        }
+    }
+
+    /// Return the range of input bytes occupied by the last value that was deserialized from this [`VVDeserializer`](VVDeserializer).
+    ///
+    /// Only values deserialized directly count, not the values nested inside them. Returns `None` if no value has been deserialized yet, or if the last deserialization failed.
+    pub fn last_value_span(&self) -> Option<Range<usize>> {
+        self.last_span.clone()
+    }
+
+    // Run a deserialization, recording its span if it is not nested in another one.
+    fn spanned<T>(&mut self, f: impl FnOnce(Raw<'_, 'de>) -> Result<T, Error>) -> Result<T, Error> {
+        let start = self.p.position();
+        self.depth += 1;
+        let result = f(Raw(self));
+        self.depth -= 1;
+        if self.depth == 0 {
+            self.last_span = result.as_ref().ok().map(|_| start..self.p.position());
+        }
+        result
+    }
+
+    /// Return how many input bytes have been already read, same as [`position`](VVDeserializer::position).
+    pub fn bytes_consumed(&self) -> usize {
+        self.position()
+    }
+
+    /// Return statistics about the input that has been read so far.
+    pub fn metrics(&self) -> Metrics {
+        Metrics {
+            bytes: self.position(),
+            ..self.metrics
+        }
+    }
+
+    // Record that the header of a value with the given number of directly nested values has been read.
+    fn node(&mut self, children: usize) {
+        while self.open.last_mut() == Some(&mut 0) {
+            self.open.pop();
+        }
+        if let Some(remaining) = self.open.last_mut() {
+            *remaining -= 1;
+        }
+
+        self.metrics.nodes += 1;
+        self.metrics.max_depth = self.metrics.max_depth.max(self.open.len() + 1);
+        if children > 0 {
+            self.open.push(children);
+        }
+    }
+
+    // Handle any unknown tags at the start of the remaining input, return whether they should be decoded as nil.
+    fn unknown_tags(&mut self) -> Result<bool, Error> {
+        loop {
+            let byte = self.p.peek()?;
+            let known = match byte & KIND_MASK {
+                NIL => byte == NIL,
+                FALSE => byte <= TRUE,
+                FLOAT => byte == FLOAT,
+                _ => true,
+            };
+            if known {
+                return Ok(false);
+            }
+
+            let position = self.p.position();
+            let action = match self.on_unknown_tag {
+                Some(hook) => hook(byte, &self.p.rest()[1..]),
+                None => UnknownTagAction::Error,
+            };
+            match action {
+                UnknownTagAction::Error => return self.p.fail(DecodeError::UnknownTag { byte, position }),
+                UnknownTagAction::Skip(len) => self.skip_bytes(len.saturating_add(1))?,
+                UnknownTagAction::Nil(len) => {
+                    self.skip_bytes(len.saturating_add(1))?;
+                    self.node(0);
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    // Skip over the next value without decoding it, using only the headers to determine its extent.
+    fn skip_value(&mut self) -> Result<(), Error> {
+        let mut pending: usize = 1;
+        while pending > 0 {
+            pending -= 1;
+            if self.unknown_tags()? {
+                continue;
+            }
+
+            match type_of(self.p.peek()?) {
+                ValueKind::Nil | ValueKind::Bool => self.p.advance(1),
+                ValueKind::Float => self.skip_bytes(9)?,
+                ValueKind::Int => {
+                    self.parse_int_payload()?;
+                }
+                ValueKind::Bytes => {
+                    let count = self.parse_count(BYTES, DecodeError::ExpectedBytes, DecodeError::OutOfBoundsString)?;
+                    self.skip_bytes(count)?;
+                }
+                ValueKind::Array => pending = pending.saturating_add(self.parse_count(ARRAY, DecodeError::ExpectedArray, DecodeError::OutOfBoundsArray)?),
+                ValueKind::Set => pending = pending.saturating_add(self.parse_count(SET, DecodeError::ExpectedMap, DecodeError::OutOfBoundsSet)?),
+                ValueKind::Map => {
+                    let count = self.parse_count(MAP, DecodeError::ExpectedMap, DecodeError::OutOfBoundsMap)?;
+                    pending = pending.saturating_add(count.saturating_mul(2));
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn parse_nil(&mut self) -> Result<(), Error> {
-        self.p.expect(0b000_00000, DecodeError::ExpectedNil)
+        self.p.expect(NIL, DecodeError::ExpectedNil)?;
+        self.node(0);
+        Ok(())
     }
 
-    fn parse_bool(&mut self) -> Result<bool, Error> {
-        match self.p.next()? {
-            0b001_00000 => Ok(false),
-            0b001_00001 => Ok(true),
-            _ => self.p.fail_at_position(DecodeError::ExpectedBool, self.p.position() - 1),
+    fn parse_unit(&mut self) -> Result<(), Error> {
+        let start = self.p.position();
+        let tag = self.p.peek()?;
+        let found = match type_of(tag) {
+            ValueKind::Nil if tag == NIL => return self.parse_nil(),
+            ValueKind::Nil if tag == EXTENSION => "extension",
+            ValueKind::Bool if tag == FALSE || tag == TRUE => "bool",
+            ValueKind::Float if tag == FLOAT => "float",
+            ValueKind::Int => "int",
+            ValueKind::Bytes => "byte string",
+            ValueKind::Array if self.lenient_units => "non-empty array",
+            ValueKind::Set if self.lenient_units => "non-empty set",
+            ValueKind::Map if self.lenient_units => "non-empty map",
+            ValueKind::Array => "array",
+            ValueKind::Set => "set",
+            ValueKind::Map => "map",
+            _ => "unknown tag",
+        };
+
+        let collection = matches!(type_of(tag), ValueKind::Array | ValueKind::Set | ValueKind::Map);
+        if !(self.lenient_units && collection) {
+            return self.p.fail_at_position(DecodeError::ExpectedUnit { found }, start);
+        }
+        let unit = DecodeError::ExpectedUnit { found };
+        if self.parse_count(tag & KIND_MASK, unit.clone(), unit.clone())? != 0 {
+            return self.p.fail_at_position(unit, start);
         }
+        self.node(0);
+        Ok(())
+    }
+
+    fn parse_bool(&mut self) -> Result<bool, Error> {
+        let b = match self.p.next()? {
+            FALSE => false,
+            TRUE => true,
+            _ => return self.p.fail_at_position(DecodeError::ExpectedBool, self.p.position() - 1),
+        };
+        self.node(0);
+        Ok(b)
     }
 
     fn parse_float(&mut self) -> Result<f64, Error> {
-        self.p.expect(0b010_00000, DecodeError::ExpectedFloat)?;
+        self.p.expect(FLOAT, DecodeError::ExpectedFloat)?;
 
         let start = self.p.position();
-        self.p.advance_or(8, DecodeError::Eoi)?;
-        let n = f64::from_bits(u64::from_be_bytes(self.p.slice(start..start + 8).try_into().unwrap()));
+        self.skip_bytes(8)?;
+        let bits = u64::from_be_bytes(self.p.slice(start..start + 8).try_into().unwrap());
+        let n = f64::from_bits(bits);
+        if self.reject_nan_payloads && n.is_nan() && bits != u64::MAX {
+            return self.p.fail_at_position(DecodeError::NanPayload(bits), start - 1);
+        }
+        self.node(0);
         return Ok(n);
     }
 
     fn parse_int(&mut self) -> Result<i64, Error> {
-        match self.p.next()? {
-            b if b & 0b111_00000 == 0b011_00000 => {
-                if b == 0b011_11111 {
-                    let start = self.p.position();
-                    self.p.advance_or(8, DecodeError::Eoi)?;
-                    let n = i64::from_be_bytes(self.p.slice(start..start + 8).try_into().unwrap());
-                    return Ok(n);
-                } else if b == 0b011_11110 {
-                    let start = self.p.position();
-                    self.p.advance_or(4, DecodeError::Eoi)?;
-                    let n = i32::from_be_bytes(self.p.slice(start..start + 4).try_into().unwrap()) as i64;
-                    return Ok(n);
-                } else if b == 0b011_11101 {
-                    let start = self.p.position();
-                    self.p.advance_or(2, DecodeError::Eoi)?;
-                    let n = i16::from_be_bytes(self.p.slice(start..start + 2).try_into().unwrap()) as i64;
-                    return Ok(n);
-                } else if b == 0b011_11100 {
-                    let start = self.p.position();
-                    self.p.advance_or(1, DecodeError::Eoi)?;
-                    let n = i8::from_be_bytes(self.p.slice(start..start + 1).try_into().unwrap()) as i64;
-                    return Ok(n);
-                } else {
-                    return Ok((u8::from_be_bytes([b & 0b000_11111])) as i64);
-                }
-            }
-            _ => self.p.fail_at_position(DecodeError::ExpectedInt, self.p.position() - 1),
+        let n = self.parse_int_payload()?;
+        self.node(0);
+        Ok(n)
+    }
+
+    fn parse_int_payload(&mut self) -> Result<i64, Error> {
+        let b = self.p.next()?;
+        if type_of(b) != ValueKind::Int {
+            return self.p.fail_at_position(DecodeError::ExpectedInt, self.p.position() - 1);
         }
+
+        let start = self.p.position();
+        let width = count_width(b);
+        self.skip_bytes(width.payload_len())?;
+        let bytes = self.p.slice(start..self.p.position());
+        Ok(match width {
+            CountWidth::Inline(n) => n as i64,
+            CountWidth::U8 => i8::from_be_bytes(bytes.try_into().unwrap()) as i64,
+            CountWidth::U16 => i16::from_be_bytes(bytes.try_into().unwrap()) as i64,
+            CountWidth::U32 => i32::from_be_bytes(bytes.try_into().unwrap()) as i64,
+            CountWidth::U64 => i64::from_be_bytes(bytes.try_into().unwrap()),
+        })
     }
 
-    fn parse_bytes(&mut self) -> Result<&[u8], Error> {
-        let count = self.parse_count(0b100_00000, DecodeError::ExpectedBytes, DecodeError::OutOfBoundsString)?;
+    fn parse_bytes(&mut self) -> Result<&'de [u8], Error> {
+        let count = self.parse_count(BYTES, DecodeError::ExpectedBytes, DecodeError::OutOfBoundsString)?;
+        self.node(0);
         let start = self.p.position();
         if self.p.rest().len() < count {
             return self.p.unexpected_end_of_input();
@@ -186,40 +564,243 @@ impl<'de> VVDeserializer<'de> {
     }
 
     fn parse_count(&mut self, tag: u8, expected: DecodeError, out_of_bounds: DecodeError) -> Result<usize, Error> {
-        match self.p.next()? {
-            b if b & 0b111_00000 == tag => {
-                let len = if b == (tag | 0b000_11111) {
-                    let start = self.p.position();
-                    self.p.advance_or(8, DecodeError::Eoi)?;
-                    let n = u64::from_be_bytes(self.p.slice(start..start + 8).try_into().unwrap());
-                    if n > (i64::MAX as u64) {
-                        return self.p.fail(out_of_bounds);
-                    }
-                    n
-                } else if b == (tag | 0b000_11110) {
-                    let start = self.p.position();
-                    self.p.advance_or(4, DecodeError::Eoi)?;
-                    let n = u32::from_be_bytes(self.p.slice(start..start + 4).try_into().unwrap()) as u64;
-                    n
-                } else if b == (tag | 0b000_11101) {
-                    let start = self.p.position();
-                    self.p.advance_or(2, DecodeError::Eoi)?;
-                    let n = u16::from_be_bytes(self.p.slice(start..start + 2).try_into().unwrap()) as u64;
-                    n
-                } else if b == (tag | 0b000_11100) {
-                    let start = self.p.position();
-                    self.p.advance_or(1, DecodeError::Eoi)?;
-                    let n = u8::from_be_bytes(self.p.slice(start..start + 1).try_into().unwrap()) as u64;
-                    n
-                } else {
-                    u8::from_be_bytes([b & 0b000_11111]) as u64
-                };
+        let b = self.p.next()?;
+        if b & KIND_MASK != tag {
+            return self.p.fail_at_position(expected, self.p.position() - 1);
+        }
+
+        let start = self.p.position();
+        let width = count_width(b);
+        self.skip_bytes(width.payload_len())?;
+        let bytes = self.p.slice(start..self.p.position());
+        let len = match width {
+            CountWidth::Inline(n) => n as u64,
+            CountWidth::U8 => u8::from_be_bytes(bytes.try_into().unwrap()) as u64,
+            CountWidth::U16 => u16::from_be_bytes(bytes.try_into().unwrap()) as u64,
+            CountWidth::U32 => u32::from_be_bytes(bytes.try_into().unwrap()) as u64,
+            CountWidth::U64 => u64::from_be_bytes(bytes.try_into().unwrap()),
+        };
+        if len > (i64::MAX as u64) {
+            return self.p.fail(out_of_bounds);
+        }
+
+        // Counts that fit into an i64 might still not fit into a usize on 32-bit targets.
+        match usize::try_from(len) {
+            Ok(len) => Ok(len),
+            Err(_) => self.p.fail(out_of_bounds),
+        }
+    }
+
+    // Advance over the next `n` bytes, or fail with `Eoi` if there are fewer. Unlike `ParserHelper::advance_or`, this
+    // never moves the position past the end of the input, so no count read from the input can overflow it.
+    fn skip_bytes(&mut self, n: usize) -> Result<(), Error> {
+        if n > self.p.rest().len() {
+            self.p.fail(DecodeError::Eoi)
+        } else {
+            self.p.advance(n);
+            Ok(())
+        }
+    }
+}
+
+/// The kind of a number in the compact encoding, as determined by its tag.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NumberKind {
+    Int,
+    Float,
+}
+
+/// Inspect the tag at the start of the input and report whether it begins an int or a float, without decoding anything.
+///
+/// Returns `None` if the input is empty or does not start with a number.
+pub fn peek_number(input: &[u8]) -> Option<NumberKind> {
+    match input.first()? & KIND_MASK {
+        FLOAT => Some(NumberKind::Float),
+        INT => Some(NumberKind::Int),
+        _ => None,
+    }
+}
+
+/// Decode an [extension](crate::compact::serialize_extension) from the start of the input slice, returning its type id and payload.
+///
+/// Like [`VVDeserializer`](VVDeserializer), this does not enforce that the input must be empty after the extension.
+pub fn deserialize_extension(input: &[u8]) -> Result<(i64, Vec<u8>), Error> {
+    VVDeserializer::new(input).deserialize_extension()
+}
+
+/// Decode only the entries of the map at the start of the input slice whose keys are among the given strings, skipping over all other entries without decoding them.
+///
+/// Keys match both if they are encoded as byte strings and if they are encoded as arrays of ints. If a key occurs multiple times, the last entry wins. Like [`VVDeserializer`](VVDeserializer), this does not enforce that the input must be empty after the map.
+pub fn project(input: &[u8], keys: &[&str]) -> Result<BTreeMap<String, crate::Value>, Error> {
+    project_seed(input, keys, PhantomData)
+}
+
+/// Decode only the entries of the map at the start of the input slice whose keys are among the given strings, like [`project`](project), into an arbitrary type.
+///
+/// The seed is given a deserializer that presents the matching entries as a map with string keys, so any type that can be deserialized from such a map (e.g. a struct whose fields are the keys) can be used.
+pub fn project_seed<'de, S>(input: &'de [u8], keys: &[&str], seed: S) -> Result<S::Value, Error>
+where
+    S: DeserializeSeed<'de>,
+{
+    let mut des = VVDeserializer::new(input);
+    seed.deserialize(Projection { des: &mut des, keys })
+}
+
+/// Deserialize a value from the compact encoding in the input slice.
+///
+/// Unlike [`VVDeserializer`](VVDeserializer), this enforces that the input must be empty after the first valid code, and
+/// reports any further bytes as [`DecodeError::TrailingBytes`](DecodeError::TrailingBytes).
+///
+/// ```
+/// use valuable_value::compact::{self, DecodeError};
+///
+/// let mut enc = compact::to_vec(&vec![true, false]).unwrap();
+/// assert_eq!(compact::from_slice::<Vec<bool>>(&enc).unwrap(), vec![true, false]);
+///
+/// enc.push(0);
+/// assert_eq!(compact::from_slice::<Vec<bool>>(&enc).unwrap_err().e, DecodeError::TrailingBytes);
+/// ```
+pub fn from_slice<'de, T>(input: &'de [u8]) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    let mut des = VVDeserializer::new(input);
+    let value = T::deserialize(&mut des)?;
+    des.end()?;
+    Ok(value)
+}
 
-                return Ok(len as usize);
+/// Deserialize a value from the compact encoding in the input slice into an existing value.
+///
+/// This uses [`Deserialize::deserialize_in_place`](serde::Deserialize::deserialize_in_place), so types that implement it
+/// (such as `String`, `Vec` and tuples thereof) reuse their existing allocations. Decoding many records into the same
+/// place thus avoids allocating for every record. If decoding fails, `place` is left in a valid but unspecified state.
+///
+/// Like [`VVDeserializer`](VVDeserializer), this does not enforce that the input must be empty after the first valid code.
+pub fn from_slice_in_place<'de, T>(place: &mut T, input: &'de [u8]) -> Result<(), Error>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize_in_place(&mut VVDeserializer::new(input), place)
+}
+
+/// Read all bytes from the reader, then deserialize a value from their compact encoding.
+///
+/// Like [`from_slice`](from_slice), this enforces that the input must be empty after the first valid code. Errors of the reader are reported as [`DecodeError::Io`](DecodeError::Io), at the position up to which the input had been read.
+pub fn from_reader<R, T>(mut r: R) -> Result<T, Error>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    let mut input = Vec::new();
+    if let Err(e) = r.read_to_end(&mut input) {
+        return Err(Error::new(input.len(), DecodeError::Io(e.to_string())));
+    }
+
+    let mut des = VVDeserializer::new(&input);
+    let value = T::deserialize(&mut des)?;
+    des.end()?;
+    Ok(value)
+}
+
+/// Deserialize a value from the compact encoding in the input slice, and report [statistics](Metrics) about the decoding.
+///
+/// Like [`VVDeserializer`](VVDeserializer), this does not enforce that the input must be empty after the first valid code.
+pub fn from_slice_metered<'de, T>(input: &'de [u8]) -> Result<(T, Metrics), Error>
+where
+    T: Deserialize<'de>,
+{
+    let mut des = VVDeserializer::new(input);
+    let value = T::deserialize(&mut des)?;
+    Ok((value, des.metrics()))
+}
+
+impl<'de> VVDeserializer<'de> {
+    /// Report whether the next value in the input is an int or a float, without consuming any input.
+    pub fn peek_number(&self) -> Option<NumberKind> {
+        peek_number(self.p.rest())
+    }
+
+    // Deserialize a sequence, which must have exactly `len` items if `len` is given.
+    fn deserialize_seq_len<V>(&mut self, len: Option<usize>, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let start = self.p.position();
+        match self.p.peek()? & KIND_MASK {
+            BYTES => {
+                let bytes = self.parse_bytes()?;
+                self.check_len(len, bytes.len(), start)?;
+                let seq = crate::helpers::BytesAsSeq::new(bytes, self.p.position(), DecodeError::OutOfBoundsI8, DecodeError::ExpectedInt);
+                return visitor.visit_seq(seq);
+            }
+            ARRAY => {
+                let count = self.parse_count(ARRAY, DecodeError::ExpectedArray, DecodeError::OutOfBoundsArray)?;
+                self.check_len(len, count, start)?;
+                self.node(count);
+                return visitor.visit_seq(SequenceAccessor::new(self, count));
+            }
+            MAP => {
+                let count = self.parse_count(MAP, DecodeError::ExpectedArray, DecodeError::OutOfBoundsMap)?;
+                self.check_len(len, count, start)?;
+                self.node(count.saturating_mul(2));
+                return visitor.visit_seq(MapAsSeq::new(self, count));
+            }
+            _ => self.p.fail(DecodeError::ExpectedArray),
+        }
+    }
+
+    // Parse an array of ints that must form a UTF-8 string, and pass it to the visitor. Strings of at most
+    // `SHORT_STRING` bytes are decoded into a buffer on the stack rather than into a heap allocation.
+    fn visit_int_array_str<V>(&mut self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let start = self.p.position();
+        let count = self.parse_count(ARRAY, DecodeError::ExpectedString, DecodeError::OutOfBoundsString)?;
+        // Every int takes up at least one byte, so this bounds the allocation by the size of the input.
+        if count > self.p.rest().len() {
+            return self.p.unexpected_end_of_input();
+        }
+        self.node(count);
+
+        if count <= SHORT_STRING {
+            let mut buf = [0; SHORT_STRING];
+            for b in buf[..count].iter_mut() {
+                *b = u8::deserialize(&mut *self)?;
+            }
+            match std::str::from_utf8(&buf[..count]) {
+                Ok(s) => visited_at(visitor.visit_str(s), start),
+                Err(_) => self.p.fail_at_position(DecodeError::Utf8, start),
+            }
+        } else {
+            let mut bytes = Vec::with_capacity(count);
+            for _ in 0..count {
+                bytes.push(u8::deserialize(&mut *self)?);
+            }
+            match String::from_utf8(bytes) {
+                Ok(s) => visited_at(visitor.visit_string(s), start),
+                Err(_) => self.p.fail_at_position(DecodeError::Utf8, start),
             }
-            _ => return self.p.fail_at_position(expected, self.p.position() - 1),
         }
     }
+
+    fn check_len(&self, len: Option<usize>, found: usize, start: usize) -> Result<(), Error> {
+        match len {
+            Some(expected) if expected != found => self.p.fail_at_position(DecodeError::WrongArrayLength { expected, found }, start),
+            _ => Ok(()),
+        }
+    }
+
+    /// Decode an [extension](crate::compact::serialize_extension), returning its type id and payload.
+    ///
+    /// Extensions are not valuable values, the serde deserialization methods treat them as [unknown tags](UnknownTagAction).
+    pub fn deserialize_extension(&mut self) -> Result<(i64, Vec<u8>), Error> {
+        self.p.expect(EXTENSION, DecodeError::ExpectedExtension)?;
+        let type_id = self.parse_int_payload()?;
+        let payload = self.parse_bytes()?.to_vec();
+        Ok((type_id, payload))
+    }
 }
 
 impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
@@ -229,30 +810,261 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        match self.p.peek()? & 0b111_00000 {
-            0b000_00000 => {
+        self.spanned(|des| des.deserialize_any(visitor))
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_bool(visitor))
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_i8(visitor))
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_i16(visitor))
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_i32(visitor))
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_i64(visitor))
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_u8(visitor))
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_u16(visitor))
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_u32(visitor))
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_u64(visitor))
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_f32(visitor))
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_f64(visitor))
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_char(visitor))
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_str(visitor))
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_string(visitor))
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_bytes(visitor))
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_byte_buf(visitor))
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_option(visitor))
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_unit(visitor))
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_unit_struct(name, visitor))
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_newtype_struct(name, visitor))
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_seq(visitor))
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_tuple(len, visitor))
+    }
+
+    fn deserialize_tuple_struct<V>(self, name: &'static str, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_tuple_struct(name, len, visitor))
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_map(visitor))
+    }
+
+    fn deserialize_struct<V>(self, name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_struct(name, fields, visitor))
+    }
+
+    fn deserialize_enum<V>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_enum(name, variants, visitor))
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_identifier(visitor))
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_ignored_any(visitor))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+// The actual deserialization logic. The implementation for `&mut VVDeserializer` wraps it to keep track of the span of
+// the outermost value, nested values are decoded through `Raw` directly or through `&mut VVDeserializer` at a greater depth.
+struct Raw<'a, 'de>(&'a mut VVDeserializer<'de>);
+
+impl<'a, 'de> Deref for Raw<'a, 'de> {
+    type Target = VVDeserializer<'de>;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl<'a, 'de> DerefMut for Raw<'a, 'de> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0
+    }
+}
+
+impl<'a, 'de> de::Deserializer<'de> for Raw<'a, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.unknown_tags()? {
+            return visitor.visit_unit();
+        }
+
+        match type_of(self.p.peek()?) {
+            ValueKind::Nil => {
                 self.parse_nil()?;
                 visitor.visit_unit()
             }
-            0b001_00000 => self.deserialize_bool(visitor),
-            0b010_00000 => self.deserialize_f64(visitor),
-            0b011_00000 => self.deserialize_i64(visitor),
-            0b100_00000 => self.deserialize_bytes(visitor),
-            0b101_00000 => self.deserialize_seq(visitor),
-            0b110_00000 => self.deserialize_map(visitor),
-            0b111_00000 => self.deserialize_map(visitor),
-            _ => unreachable!(),
+            ValueKind::Bool => self.deserialize_bool(visitor),
+            ValueKind::Float => self.deserialize_f64(visitor),
+            ValueKind::Int => self.deserialize_i64(visitor),
+            ValueKind::Bytes => self.deserialize_bytes(visitor),
+            ValueKind::Array => self.deserialize_seq(visitor),
+            ValueKind::Set | ValueKind::Map => self.deserialize_map(visitor),
         }
     }
 
-    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_bool<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
         visitor.visit_bool(self.parse_bool()?)
     }
 
-    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_i8<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -265,7 +1077,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_i16<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -278,7 +1090,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_i32<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -291,14 +1103,14 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_i64<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
         visitor.visit_i64(self.parse_int()?)
     }
 
-    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_u8<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -311,7 +1123,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_u16<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -324,7 +1136,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_u32<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -337,7 +1149,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_u64<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -350,21 +1162,21 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_f32<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
         visitor.visit_f64(self.parse_float()?)
     }
 
-    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_f64<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
         visitor.visit_f64(self.parse_float()?)
     }
 
-    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_char<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -380,49 +1192,43 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_str<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        if (self.p.peek()? & 0b111_00000) == 0b101_00000 {
-            let v = Vec::deserialize(&mut *self)?;
-            match String::from_utf8(v) {
-                Ok(s) => visitor.visit_string(s),
-                Err(_) => self.p.fail(DecodeError::Utf8),
-            }
+        if (self.p.peek()? & KIND_MASK) == ARRAY {
+            self.visit_int_array_str(visitor)
         } else {
+            let start = self.p.position();
             let bytes = self.parse_bytes()?;
             match std::str::from_utf8(bytes) {
-                Ok(s) => visitor.visit_str(s),
+                Ok(s) => visited_at(visitor.visit_borrowed_str(s), start),
                 Err(_) => self.p.fail(DecodeError::Utf8),
             }
         }
     }
 
-    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_string<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        if (self.p.peek()? & 0b111_00000) == 0b101_00000 {
-            let v = Vec::deserialize(&mut *self)?;
-            match String::from_utf8(v) {
-                Ok(s) => visitor.visit_string(s),
-                Err(_) => self.p.fail(DecodeError::Utf8),
-            }
+        if (self.p.peek()? & KIND_MASK) == ARRAY {
+            self.visit_int_array_str(visitor)
         } else {
+            let start = self.p.position();
             let bytes = self.parse_bytes()?;
             match std::str::from_utf8(bytes) {
-                Ok(s) => visitor.visit_string(s.to_string()),
+                Ok(s) => visited_at(visitor.visit_borrowed_str(s), start),
                 Err(_) => self.p.fail(DecodeError::Utf8),
             }
         }
     }
 
-    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_bytes<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        if (self.p.peek()? & 0b111_00000) == 0b101_00000 {
+        if (self.p.peek()? & KIND_MASK) == ARRAY {
             let v = Vec::deserialize(self)?;
             return visitor.visit_byte_buf(v);
         } else {
@@ -431,11 +1237,11 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
 
     }
 
-    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_byte_buf<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        if (self.p.peek()? & 0b111_00000) == 0b101_00000 {
+        if (self.p.peek()? & KIND_MASK) == ARRAY {
             let v = Vec::deserialize(self)?;
             return visitor.visit_byte_buf(v);
         } else {
@@ -444,30 +1250,40 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_option<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
+        if self.compact_options {
+            if self.p.peek()? == NIL {
+                self.parse_nil()?;
+                return visitor.visit_none();
+            } else {
+                return visitor.visit_some(self);
+            }
+        }
+
         let position = self.p.position();
-        match self.p.peek()? & 0b111_00000 {
-            0b100_00000 | 0b101_00000 => {
+        match self.p.peek()? & KIND_MASK {
+            BYTES | ARRAY => {
                 let tag = String::deserialize(&mut *self)?;
                 if tag == "None" {
                     return visitor.visit_none();
                 } else {
-                    return self.p.fail_at_position(DecodeError::ExpectedOption, position);
+                    return self.p.fail_at_position(DecodeError::UnexpectedOptionTag(captured_str(&tag)), position);
                 }
             }
 
-            0b110_00000 => {
+            SET => {
                 let b  = self.p.next()?;
-                if b != 0b110_00001 {
+                if b != SINGLETON_SET {
                     return self.p.fail_at_position(DecodeError::ExpectedOption, position);
                 }
+                self.node(1);
 
                 let tag = String::deserialize(&mut *self)?;
                 if tag != "Some" {
-                    return self.p.fail_at_position(DecodeError::ExpectedOption, position);
+                    return self.p.fail_at_position(DecodeError::UnexpectedOptionTag(captured_str(&tag)), position);
                 }
 
                 match visitor.visit_some(AlwaysNil::new()) {
@@ -476,15 +1292,16 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
                 }
             }
 
-            0b111_00000 => {
+            MAP => {
                 let b  = self.p.next()?;
-                if b != 0b111_00001 {
+                if b != SINGLETON_MAP {
                     return self.p.fail_at_position(DecodeError::ExpectedOption, position);
                 }
+                self.node(2);
 
                 let tag = String::deserialize(&mut *self)?;
                 if tag != "Some" {
-                    return self.p.fail_at_position(DecodeError::ExpectedOption, position);
+                    return self.p.fail_at_position(DecodeError::UnexpectedOptionTag(captured_str(&tag)), position);
                 }
 
                 return visitor.visit_some(self);
@@ -494,11 +1311,11 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_unit<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        self.parse_nil()?;
+        self.parse_unit()?;
         visitor.visit_unit()
     }
 
@@ -528,50 +1345,41 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        match self.p.peek()? & 0b111_00000 {
-            0b100_00000 => {
-                let bytes = self.parse_bytes()?;
-                let seq = crate::helpers::BytesAsSeq::new(bytes.to_vec(), self.p.position(), DecodeError::OutOfBoundsI8, DecodeError::ExpectedInt);
-                return visitor.visit_seq(seq);
-            }
-            0b101_00000 => {
-                let count = self.parse_count(0b101_00000, DecodeError::ExpectedArray, DecodeError::OutOfBoundsArray)?;
-                return visitor.visit_seq(SequenceAccessor::new(&mut self, count));
-            }
-            _ => self.p.fail(DecodeError::ExpectedArray),
-        }
+        self.deserialize_seq_len(None, visitor)
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_tuple<V>(mut self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_seq_len(Some(len), visitor)
     }
 
     fn deserialize_tuple_struct<V>(
-        self,
+        mut self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_seq_len(Some(len), visitor)
     }
 
     fn deserialize_map<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        match self.p.peek()? & 0b111_00000 {
-            0b110_00000 => {
-                let count = self.parse_count(0b110_00000, DecodeError::ExpectedMap, DecodeError::OutOfBoundsSet)?;
+        match self.p.peek()? & KIND_MASK {
+            SET => {
+                let count = self.parse_count(SET, DecodeError::ExpectedMap, DecodeError::OutOfBoundsSet)?;
+                self.node(count);
                 return visitor.visit_map(MapAccessor::new(&mut self, count, true));
             }
-            0b111_00000 => {
-                let count = self.parse_count(0b111_00000, DecodeError::ExpectedMap, DecodeError::OutOfBoundsMap)?;
+            MAP => {
+                let count = self.parse_count(MAP, DecodeError::ExpectedMap, DecodeError::OutOfBoundsMap)?;
+                self.node(count.saturating_mul(2));
                 return visitor.visit_map(MapAccessor::new(&mut self, count, false));
             }
             _ => return self.p.fail(DecodeError::ExpectedMap),
@@ -599,9 +1407,8 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        match self.p.peek()? & 0b111_00000 {
-            0b100_00000 | 0b110_00000 | 0b111_00000 => Ok(visitor.visit_enum(Enum::new(self))?),
-            0b101_00000 => Ok(visitor.visit_enum(Enum::new(self))?),
+        match self.p.peek()? & KIND_MASK {
+            BYTES | ARRAY | SET | MAP => Ok(visitor.visit_enum(Enum::new(self.0))?),
             _ => self.p.fail(DecodeError::ExpectedEnum(name.to_string()))
         }
     }
@@ -610,7 +1417,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_string(visitor)
+        self.deserialize_str(visitor)
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -652,6 +1459,171 @@ impl<'a, 'de> SeqAccess<'de> for SequenceAccessor<'a, 'de> {
             return Ok(None);
         }
     }
+
+    fn size_hint(&self) -> Option<usize> {
+        // The count comes from the input, so do not let it claim more items than there are bytes left.
+        Some((self.len - self.read).min(self.des.p.rest().len()))
+    }
+}
+
+// Presents the entries of a map as a sequence of key-value pairs, so that a map can be decoded into e.g. a `Vec<(K, V)>`.
+struct MapAsSeq<'a, 'de> {
+    des: &'a mut VVDeserializer<'de>,
+    len: usize,
+    read: usize,
+    // The previously decoded key, if keys must be sorted.
+    previous: Option<Value>,
+}
+
+impl<'a, 'de> MapAsSeq<'a, 'de> {
+    fn new(des: &'a mut VVDeserializer<'de>, len: usize) -> MapAsSeq<'a, 'de> {
+        MapAsSeq { des, len, read: 0, previous: None }
+    }
+}
+
+impl<'a, 'de> SeqAccess<'de> for MapAsSeq<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.read < self.len {
+            let entry = seed.deserialize(Entry { des: &mut *self.des, previous: &mut self.previous })?;
+            self.read += 1;
+            return Ok(Some(entry));
+        } else {
+            return Ok(None);
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.len - self.read).min(self.des.p.rest().len()))
+    }
+}
+
+// A single map entry, deserializable as a tuple of the key and the value.
+struct Entry<'a, 'de> {
+    des: &'a mut VVDeserializer<'de>,
+    previous: &'a mut Option<Value>,
+}
+
+impl<'a, 'de> de::Deserializer<'de> for Entry<'a, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(EntryAccessor { des: self.des, previous: self.previous, read: 0 })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct EntryAccessor<'a, 'de> {
+    des: &'a mut VVDeserializer<'de>,
+    // The key of the previous entry, if keys must be sorted.
+    previous: &'a mut Option<Value>,
+    read: usize,
+}
+
+impl<'a, 'de> SeqAccess<'de> for EntryAccessor<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.read < 2 {
+            let start = self.des.p.position();
+            let inner = seed.deserialize(&mut *self.des)?;
+            if self.read == 0 {
+                self.des.check_key_order(self.previous, start)?;
+            }
+            self.read += 1;
+            Ok(Some(inner))
+        } else {
+            return Ok(None);
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(2 - self.read)
+    }
+}
+
+// A map whose entries are filtered by their keys, see `project`.
+struct Projection<'a, 'de> {
+    des: &'a mut VVDeserializer<'de>,
+    keys: &'a [&'a str],
+}
+
+impl<'a, 'de> de::Deserializer<'de> for Projection<'a, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let remaining = self.des.parse_count(MAP, DecodeError::ExpectedMap, DecodeError::OutOfBoundsMap)?;
+        visitor.visit_map(ProjectionAccessor { des: self.des, keys: self.keys, remaining })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct ProjectionAccessor<'a, 'de> {
+    des: &'a mut VVDeserializer<'de>,
+    keys: &'a [&'a str],
+    remaining: usize,
+}
+
+impl<'a, 'de> ProjectionAccessor<'a, 'de> {
+    // Read a key, returning it if it is a string.
+    fn key(&mut self) -> Result<Option<String>, Error> {
+        match self.des.p.peek()? & KIND_MASK {
+            BYTES => Ok(std::str::from_utf8(self.des.parse_bytes()?).ok().map(str::to_string)),
+            ARRAY => Ok(crate::Value::deserialize(&mut *self.des)?.get_str(&[]).ok()),
+            _ => {
+                self.des.skip_value()?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl<'a, 'de> MapAccess<'de> for ProjectionAccessor<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        while self.remaining > 0 {
+            self.remaining -= 1;
+            match self.key()? {
+                Some(key) if self.keys.contains(&key.as_str()) => return Ok(Some(seed.deserialize(key.into_deserializer())?)),
+                _ => self.des.skip_value()?,
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut *self.des)
+    }
 }
 
 struct MapAccessor<'a, 'de> {
@@ -659,11 +1631,13 @@ struct MapAccessor<'a, 'de> {
     len: usize,
     read: usize,
     set: bool,
+    // The previously decoded key, if keys must be sorted.
+    previous: Option<Value>,
 }
 
 impl<'a, 'de> MapAccessor<'a, 'de> {
     fn new(des: &'a mut VVDeserializer<'de>, len: usize, set: bool) -> MapAccessor<'a, 'de> {
-        MapAccessor { des, len, read: 0, set }
+        MapAccessor { des, len, read: 0, set, previous: None }
     }
 }
 
@@ -675,8 +1649,11 @@ impl<'a, 'de> MapAccess<'de> for MapAccessor<'a, 'de> {
         K: DeserializeSeed<'de>,
     {
         if self.read < self.len {
+            let start = self.des.p.position();
             let inner = seed.deserialize(&mut *self.des)?;
-            return Ok(Some(inner));
+
+            self.des.check_key_order(&mut self.previous, start)?;
+            Ok(Some(inner))
         } else {
             return Ok(None);
         }
@@ -719,14 +1696,16 @@ impl<'a, 'de> EnumAccess<'de> for Enum<'a, 'de> {
         V: DeserializeSeed<'de>,
     {
         match self.des.p.peek()? {
-            b if (b & 0b111_00000 == 0b100_00000) || (b & 0b111_00000 == 0b101_00000) => Ok((seed.deserialize(&mut *self.des)?, self)),
-            0b110_00001 => {
+            b if (b & KIND_MASK == BYTES) || (b & KIND_MASK == ARRAY) => Ok((seed.deserialize(&mut *self.des)?, self)),
+            SINGLETON_SET => {
                 self.set = true;
                 self.des.p.advance(1);
+                self.des.node(1);
                 Ok((seed.deserialize(&mut *self.des)?, self))
             }
-            0b111_00001 => {
+            SINGLETON_MAP => {
                 self.des.p.advance(1);
+                self.des.node(2);
                 Ok((seed.deserialize(&mut *self.des)?, self))
             }
             _ => self.des.p.fail(DecodeError::ExpectedEnumVariant),
@@ -788,6 +1767,328 @@ mod tests {
         assert!(f.is_sign_negative());
     }
 
+    #[test]
+    fn metered() {
+        let string = |s: &str| crate::Value::Array(s.bytes().map(|b| crate::Value::Int(b as i64)).collect());
+        let mut m = BTreeMap::new();
+        m.insert(string("a"), crate::Value::Array(vec![
+            crate::Value::Int(1),
+            crate::Value::Array(vec![crate::Value::Float(2.5), crate::Value::Nil]),
+        ]));
+        m.insert(string("b"), crate::Value::Bool(true));
+        let v = crate::Value::Map(m);
+        let enc = crate::compact::to_vec(&v).unwrap();
+
+        let (decoded, metrics) = from_slice_metered::<crate::Value>(&enc).unwrap();
+        assert_eq!(decoded, v);
+        // The keys are arrays of ints, not byte strings, so each of them counts as two nodes.
+        assert_eq!(metrics, Metrics { bytes: enc.len(), nodes: 11, max_depth: 4 });
+
+        let mut trailing = enc.clone();
+        trailing.push(0);
+        let mut des = VVDeserializer::new(&trailing);
+        crate::Value::deserialize(&mut des).unwrap();
+        assert_eq!(des.bytes_consumed(), enc.len());
+
+        let enc = crate::compact::to_vec(&Some(vec![1u8])).unwrap();
+        assert_eq!(from_slice_metered::<Option<Vec<u8>>>(&enc).unwrap(), (Some(vec![1]), Metrics { bytes: enc.len(), nodes: 4, max_depth: 3 }));
+        assert_eq!(from_slice_metered::<u8>(&[0b011_00011]).unwrap(), (3, Metrics { bytes: 1, nodes: 1, max_depth: 1 }));
+
+        // Nesting deeper than the collections that are tracked without allocating.
+        let mut deep = crate::Value::Nil;
+        for _ in 0..40 {
+            deep = crate::Value::Array(vec![deep]);
+        }
+        let v = crate::Value::Array(vec![deep, crate::Value::Array(vec![crate::Value::Int(1)])]);
+        let enc = crate::compact::to_vec(&v).unwrap();
+        assert_eq!(from_slice_metered::<crate::Value>(&enc).unwrap(), (v, Metrics { bytes: enc.len(), nodes: 44, max_depth: 42 }));
+    }
+
+    #[test]
+    fn map_as_pairs() {
+        let mut m = BTreeMap::new();
+        m.insert(-3i64, true);
+        m.insert(1, false);
+        m.insert(200, true);
+        let enc = crate::compact::to_vec(&m).unwrap();
+
+        assert_eq!(Vec::<(i64, bool)>::deserialize(&mut VVDeserializer::new(&enc)).unwrap(), vec![(-3, true), (1, false), (200, true)]);
+        assert_eq!(Vec::<(i64, bool)>::deserialize(&mut VVDeserializer::new(&[0b111_00000])).unwrap(), vec![]);
+        assert_eq!(Vec::<(i64, i64)>::deserialize(&mut VVDeserializer::new(&enc)).unwrap_err().e, DecodeError::ExpectedInt);
+        assert!(Vec::<(i64, bool, u8)>::deserialize(&mut VVDeserializer::new(&enc)).is_err());
+
+        let enc = crate::compact::to_vec(&vec![(1i64, true)]).unwrap();
+        assert_eq!(Vec::<(i64, bool)>::deserialize(&mut VVDeserializer::new(&enc)).unwrap(), vec![(1, true)]);
+    }
+
+    #[test]
+    fn unknown_tags() {
+        use crate::Value;
+
+        // An array containing an unknown tag with two bytes of payload, followed by an int.
+        let enc = [0b101_00010, 0b010_00001, 0xAA, 0xBB, 0b011_00111];
+
+        let err = Value::deserialize(&mut VVDeserializer::new(&enc)).unwrap_err();
+        assert_eq!(err.e, DecodeError::UnknownTag { byte: 0b010_00001, position: 1 });
+        assert_eq!(err.position, 1);
+
+        let error = |_: u8, _: &[u8]| UnknownTagAction::Error;
+        let err = Value::deserialize(&mut VVDeserializer::new(&enc).on_unknown_tag(error)).unwrap_err();
+        assert_eq!(err.e, DecodeError::UnknownTag { byte: 0b010_00001, position: 1 });
+
+        let nil = |byte: u8, rest: &[u8]| {
+            assert_eq!(byte, 0b010_00001);
+            assert_eq!(rest, &[0xAA, 0xBB, 0b011_00111]);
+            UnknownTagAction::Nil(2)
+        };
+        let mut des = VVDeserializer::new(&enc).on_unknown_tag(nil);
+        assert_eq!(Value::deserialize(&mut des).unwrap(), Value::Array(vec![Value::Nil, Value::Int(7)]));
+        assert_eq!(des.position(), enc.len());
+
+        // Skipping the unknown value leaves the array one element short, so the next value is consumed in its place.
+        let skip = |_: u8, _: &[u8]| UnknownTagAction::Skip(2);
+        let enc = [0b101_00001, 0b010_00001, 0xAA, 0xBB, 0b011_00111];
+        let mut des = VVDeserializer::new(&enc).on_unknown_tag(skip);
+        assert_eq!(Value::deserialize(&mut des).unwrap(), Value::Array(vec![Value::Int(7)]));
+        assert_eq!(des.position(), enc.len());
+
+        let too_long = |_: u8, _: &[u8]| UnknownTagAction::Skip(9);
+        let err = Value::deserialize(&mut VVDeserializer::new(&enc).on_unknown_tag(too_long)).unwrap_err();
+        assert_eq!(err.e, DecodeError::Eoi);
+    }
+
+    #[test]
+    fn huge_counts() {
+        use crate::Value;
+
+        let header = |kind: u8, count: u64| {
+            let mut enc = vec![kind | EIGHT_BYTES];
+            enc.extend_from_slice(&count.to_be_bytes());
+            enc.push(NIL);
+            enc
+        };
+
+        for count in [i64::MAX as u64, i64::MAX as u64 - 1, usize::MAX as u64 >> 1, u32::MAX as u64].iter() {
+            for kind in [BYTES, ARRAY, SET, MAP].iter() {
+                let enc = header(*kind, *count);
+                assert!(Value::deserialize(&mut VVDeserializer::new(&enc)).is_err());
+                assert!(de::IgnoredAny::deserialize(&mut VVDeserializer::new(&enc)).is_err());
+            }
+            assert_eq!(<&[u8]>::deserialize(&mut VVDeserializer::new(&header(BYTES, *count))).unwrap_err().e, DecodeError::Eoi);
+            assert_eq!(String::deserialize(&mut VVDeserializer::new(&header(ARRAY, *count))).unwrap_err().e, DecodeError::Eoi);
+        }
+
+        for count in [i64::MAX as u64 + 1, u64::MAX].iter() {
+            assert_eq!(Value::deserialize(&mut VVDeserializer::new(&header(BYTES, *count))).unwrap_err().e, DecodeError::OutOfBoundsString);
+            assert_eq!(Value::deserialize(&mut VVDeserializer::new(&header(ARRAY, *count))).unwrap_err().e, DecodeError::OutOfBoundsArray);
+        }
+
+        // Unknown tags whose hook claims more bytes than any input could have.
+        let enc = [0b010_00001, 0xAA];
+        let hooks: [fn(u8, &[u8]) -> UnknownTagAction; 3] = [
+            |_, _| UnknownTagAction::Skip(usize::MAX),
+            |_, _| UnknownTagAction::Nil(usize::MAX - 1),
+            |_, _| UnknownTagAction::Skip(isize::MAX as usize),
+        ];
+        for hook in hooks.iter() {
+            let err = Value::deserialize(&mut VVDeserializer::new(&enc).on_unknown_tag(*hook)).unwrap_err();
+            assert_eq!(err.e, DecodeError::Eoi);
+            assert_eq!(err.position, 0);
+        }
+    }
+
+    #[test]
+    fn extensions() {
+        let mut out = crate::compact::to_vec(&true).unwrap();
+        crate::compact::serialize_extension(-300, b"blob", &mut out).unwrap();
+        crate::compact::serialize_extension(7, &[0; 300], &mut out).unwrap();
+
+        let mut des = VVDeserializer::new(&out);
+        assert!(bool::deserialize(&mut des).unwrap());
+        assert_eq!(des.deserialize_extension().unwrap(), (-300, b"blob".to_vec()));
+        assert_eq!(des.deserialize_extension().unwrap(), (7, vec![0; 300]));
+        assert_eq!(des.position(), out.len());
+
+        assert_eq!(deserialize_extension(&out).unwrap_err().e, DecodeError::ExpectedExtension);
+        assert_eq!(deserialize_extension(&out[1..]).unwrap(), (-300, b"blob".to_vec()));
+        assert_eq!(crate::Value::deserialize(&mut VVDeserializer::new(&out[1..])).unwrap_err().e, DecodeError::UnknownTag { byte: 0b000_00001, position: 0 });
+    }
+
+    #[test]
+    fn borrowed_keys() {
+        let m: BTreeMap<&str, i64> = vec![("ab", 1), ("c", 2)].into_iter().collect();
+        let enc = crate::compact::to_vec(&m).unwrap();
+        let decoded = from_slice::<BTreeMap<&str, i64>>(&enc).unwrap();
+        assert_eq!(decoded, m);
+        assert!(decoded.keys().all(|k| enc.as_ptr_range().contains(&k.as_ptr())));
+
+        // Strings encoded as arrays of ints cannot be borrowed, the error points at the key.
+        let enc = [0b111_00010, 0b100_00001, b'c', 0b011_00010, 0b101_00001, 0b011_11100, b'x', 0b011_00001];
+        assert_eq!(from_slice::<BTreeMap<String, i64>>(&enc).unwrap().len(), 2);
+        let err = from_slice::<BTreeMap<&str, i64>>(&enc).unwrap_err();
+        assert_eq!(err.position, 4);
+        assert_eq!(err.e.to_string(), "invalid type: string \"x\", expected a borrowed string");
+    }
+
+    #[test]
+    fn fixed_lengths() {
+        let enc = crate::compact::to_vec(&vec![1u8, 2, 3, 4]).unwrap();
+        assert_eq!(<[u8; 4]>::deserialize(&mut VVDeserializer::new(&enc)).unwrap(), [1, 2, 3, 4]);
+        let err = <[u8; 5]>::deserialize(&mut VVDeserializer::new(&enc)).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::WrongArrayLength { expected: 5, found: 4 }, 0));
+        let err = <[u8; 3]>::deserialize(&mut VVDeserializer::new(&enc)).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::WrongArrayLength { expected: 3, found: 4 }, 0));
+
+        let enc = crate::compact::to_vec(&(1i32, -2i32, 3i32)).unwrap();
+        assert_eq!(<(i32, i32, i32)>::deserialize(&mut VVDeserializer::new(&enc)).unwrap(), (1, -2, 3));
+        let enc = crate::compact::to_vec(&(1i32, 2i32)).unwrap();
+        let err = <(i32, i32, i32)>::deserialize(&mut VVDeserializer::new(&enc)).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::WrongArrayLength { expected: 3, found: 2 }, 0));
+        let enc = crate::compact::to_vec(&(1i32, 2i32, 3i32, 4i32)).unwrap();
+        let err = <(i32, i32, i32)>::deserialize(&mut VVDeserializer::new(&enc)).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::WrongArrayLength { expected: 3, found: 4 }, 0));
+
+        let enc = crate::compact::to_vec(&vec![(1i32, 2i32, 3i32)]).unwrap();
+        assert_eq!(Vec::<(i32, i32, i32)>::deserialize(&mut VVDeserializer::new(&enc)).unwrap(), vec![(1, 2, 3)]);
+
+        let enc = [0b100_00011, b'a', b'b', b'c'];
+        assert_eq!(<[u8; 3]>::deserialize(&mut VVDeserializer::new(&enc)).unwrap(), *b"abc");
+        let err = <[u8; 4]>::deserialize(&mut VVDeserializer::new(&enc)).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::WrongArrayLength { expected: 4, found: 3 }, 0));
+        let err = <[u8; 2]>::deserialize(&mut VVDeserializer::new(&enc)).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::WrongArrayLength { expected: 2, found: 3 }, 0));
+
+        // Byte strings and arrays of ints decode alike, also when nested.
+        let bytes = [0b101_00010, 0b100_00100, 0, 127, 128, 255, 0b011_00111];
+        let ints = crate::compact::to_vec(&([0u8, 127, 128, 255], 7u8)).unwrap();
+        for enc in [&bytes[..], &ints[..]] {
+            assert_eq!(<([u8; 4], u8)>::deserialize(&mut VVDeserializer::new(enc)).unwrap(), ([0, 127, 128, 255], 7));
+            let err = <([u8; 3], u8)>::deserialize(&mut VVDeserializer::new(enc)).unwrap_err();
+            assert_eq!((err.e, err.position), (DecodeError::WrongArrayLength { expected: 3, found: 4 }, 1));
+        }
+    }
+
+    #[test]
+    fn last_value_span() {
+        let first = vec![Some(1u8), None, Some(3)];
+        let mut second = BTreeMap::new();
+        second.insert("a".to_string(), (true, -7i64));
+        let enc = [crate::compact::to_vec(&first).unwrap(), crate::compact::to_vec(&second).unwrap()].concat();
+
+        let mut des = VVDeserializer::new(&enc);
+        assert_eq!(des.last_value_span(), None);
+
+        assert_eq!(Vec::<Option<u8>>::deserialize(&mut des).unwrap(), first);
+        let span = des.last_value_span().unwrap();
+        assert_eq!(span.start, 0);
+        assert_eq!(Vec::<Option<u8>>::deserialize(&mut VVDeserializer::new(&enc[span])).unwrap(), first);
+
+        assert_eq!(crate::Value::deserialize(&mut des).unwrap(), crate::compact::from_slice::<crate::Value>(&enc[des.last_value_span().unwrap()]).unwrap());
+        let span = des.last_value_span().unwrap();
+        assert_eq!(span.end, enc.len());
+        assert_eq!(BTreeMap::<String, (bool, i64)>::deserialize(&mut VVDeserializer::new(&enc[span])).unwrap(), second);
+
+        assert!(bool::deserialize(&mut des).is_err());
+        assert_eq!(des.last_value_span(), None);
+    }
+
+    #[test]
+    fn int_array_strings() {
+        let enc = crate::compact::to_vec(&vec![0x68u8, 0xC3, 0xA9]).unwrap();
+        assert_eq!(String::deserialize(&mut VVDeserializer::new(&enc)).unwrap(), "h\u{e9}");
+        let (s, metrics) = from_slice_metered::<String>(&enc).unwrap();
+        assert_eq!(s, "h\u{e9}");
+        assert_eq!(metrics.nodes, 4);
+
+        // Claims four billion items, but the input ends right away.
+        let enc = [0b101_11110, 0xff, 0xff, 0xff, 0xff, 0b011_00001];
+        assert_eq!(String::deserialize(&mut VVDeserializer::new(&enc)).unwrap_err().e, DecodeError::Eoi);
+        let enc = [0b101_00011, 0b011_00001, 0b011_00010];
+        assert_eq!(String::deserialize(&mut VVDeserializer::new(&enc)).unwrap_err().e, DecodeError::Eoi);
+
+        let enc = crate::compact::to_vec(&vec![0x61u8, 0xff, 0x61]).unwrap();
+        let err = String::deserialize(&mut VVDeserializer::new(&enc)).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::Utf8, 0));
+        let enc = crate::compact::to_vec(&vec![0x61i32, 256]).unwrap();
+        assert_eq!(String::deserialize(&mut VVDeserializer::new(&enc)).unwrap_err().e, DecodeError::OutOfBoundsU8);
+    }
+
+    #[test]
+    fn projection() {
+        use crate::Value;
+
+        #[derive(Serialize)]
+        struct Record {
+            name: String,
+            payload: Vec<(f64, Option<bool>, BTreeMap<u8, ()>)>,
+            version: u32,
+            blob: Vec<u8>,
+            other: Value,
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Header {
+            name: String,
+            version: u32,
+        }
+
+        let mut set = BTreeMap::new();
+        set.insert(3, ());
+        let mut other = BTreeMap::new();
+        other.insert(Value::Nil, Value::Array(vec![Value::Int(-300), Value::Float(1.5), Value::Bool(false)]));
+        let record = Record {
+            name: "vv".to_string(),
+            payload: vec![(0.5, None, set.clone()), (-1.0, Some(true), BTreeMap::new())],
+            version: 1000,
+            blob: vec![1, 2, 3],
+            other: Value::Map(other),
+        };
+        let enc = crate::compact::to_vec(&record).unwrap();
+
+        let projected = project(&enc, &["name", "version"]).unwrap();
+        let mut expected = BTreeMap::new();
+        expected.insert("name".to_string(), Value::from("vv"));
+        expected.insert("version".to_string(), Value::Int(1000));
+        assert_eq!(projected, expected);
+        assert_eq!(project(&enc, &["version", "name", "name"]).unwrap(), expected);
+        assert_eq!(project(&enc, &["missing", "Name"]).unwrap(), BTreeMap::new());
+        assert_eq!(project_seed(&enc, &["name", "version"], PhantomData::<Header>).unwrap(), Header { name: "vv".to_string(), version: 1000 });
+
+        // Keys encoded as arrays of ints.
+        let mut m = BTreeMap::new();
+        m.insert(Value::from("name"), Value::Int(1));
+        m.insert(Value::from("skipped"), Value::Int(2));
+        m.insert(Value::Array(vec![Value::Int(999)]), Value::Int(3));
+        let enc = crate::compact::to_vec(&Value::Map(m)).unwrap();
+        let mut expected = BTreeMap::new();
+        expected.insert("name".to_string(), Value::Int(1));
+        assert_eq!(project(&enc, &["name"]).unwrap(), expected);
+
+        // Duplicate keys, the last one wins.
+        let enc = [0b111_00011, 0b100_00001, b'a', 0b011_00001, 0b100_00001, b'b', 0b000_00000, 0b100_00001, b'a', 0b011_00010];
+        let mut expected = BTreeMap::new();
+        expected.insert("a".to_string(), Value::Int(2));
+        assert_eq!(project(&enc, &["a"]).unwrap(), expected);
+
+        assert_eq!(project(&[0b101_00000], &["a"]).unwrap_err().e, DecodeError::ExpectedMap);
+        assert_eq!(project(&enc[..enc.len() - 4], &["a"]).unwrap_err().e, DecodeError::Eoi);
+    }
+
+    #[test]
+    fn peek_numbers() {
+        assert_eq!(peek_number(&[0b011_00101]), Some(NumberKind::Int));
+        assert_eq!(peek_number(&[0b011_11111, 0, 0, 0, 0, 0, 0, 0, 0]), Some(NumberKind::Int));
+        assert_eq!(peek_number(&[0b010_00000, 0, 0, 0, 0, 0, 0, 0, 0]), Some(NumberKind::Float));
+        assert_eq!(peek_number(&[0b001_00000]), None);
+        assert_eq!(peek_number(&[]), None);
+
+        let mut d = VVDeserializer::new(&[0b010_00000, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(d.peek_number(), Some(NumberKind::Float));
+        assert_eq!(d.position(), 0);
+        assert_eq!(f64::deserialize(&mut d).unwrap(), 0.0);
+        assert_eq!(d.peek_number(), None);
+    }
+
     #[test]
     fn arrays() {
         let mut d = VVDeserializer::new(&[0b101_11111, 255, 255, 255, 255, 255, 255, 255, 255, 0, 0, 0, 0, 0]);
@@ -864,6 +2165,299 @@ mod tests {
         assert_eq!(v.foo, ());
     }
 
+    #[test]
+    fn option_tag_errors() {
+        let err = Option::<u8>::deserialize(&mut VVDeserializer::new(&[0b100_00101, b'N', b'o', b'n', b'e', b'e'])).unwrap_err();
+        assert_eq!((err.e.clone(), err.position), (DecodeError::UnexpectedOptionTag(crate::Value::from("Nonee")), 0));
+        assert_eq!(err.e.to_string(), r#"expected option, found tag [78,111,110,101,101] ("Nonee")"#);
+
+        let err = Option::<u8>::deserialize(&mut VVDeserializer::new(&[0b111_00001, 0b100_00011, b'f', b'o', b'o', 0b011_00001])).unwrap_err();
+        assert!(err.e.to_string().contains(r#"[102,111,111] ("foo")"#));
+
+        let long = "x".repeat(1000);
+        let enc = crate::compact::to_vec(&long).unwrap();
+        let err = Option::<u8>::deserialize(&mut VVDeserializer::new(&enc)).unwrap_err();
+        assert!(err.e.to_string().len() < 300);
+    }
+
+    #[test]
+    fn compact_options() {
+        let encode = |v: &dyn Fn(&mut crate::compact::VVSerializer)| {
+            let mut serializer = crate::compact::VVSerializer::new(Vec::new()).compact_options(true);
+            v(&mut serializer);
+            serializer.into_inner()
+        };
+
+        let options = vec![None, Some(0i64), Some(-5), None, Some(i64::MAX)];
+        let enc = encode(&|s| options.serialize(s).unwrap());
+        assert_eq!(&enc[..4], &[0b101_00101, 0b000_00000, 0b011_00000, 0b011_11100]);
+        assert!(enc.len() < crate::compact::to_vec(&options).unwrap().len());
+        let decoded = Vec::<Option<i64>>::deserialize(&mut VVDeserializer::new(&enc).compact_options(true)).unwrap();
+        assert_eq!(decoded, options);
+
+        // Options that wrap nil cannot be told apart from `None`.
+        let enc = encode(&|s| (Some(()), Some(None::<u8>)).serialize(s).unwrap());
+        let decoded = <(Option<()>, Option<Option<u8>>)>::deserialize(&mut VVDeserializer::new(&enc).compact_options(true)).unwrap();
+        assert_eq!(decoded, (None, None));
+
+        // With lenient units, an `Option<()>` still decodes nil as `None`, and only an explicitly empty collection as `Some(())`.
+        let mut des = VVDeserializer::new(&[ARRAY | 2, NIL, ARRAY]).compact_options(true).lenient_units(true);
+        assert_eq!(<(Option<()>, Option<()>)>::deserialize(&mut des).unwrap(), (None, Some(())));
+    }
+
+    #[derive(PartialEq, Eq, Deserialize, Debug)]
+    struct Marker;
+
+    #[test]
+    fn lenient_units() {
+        let lenient = |input: &'static [u8]| VVDeserializer::new(input).lenient_units(true);
+
+        let inputs: [&[u8]; 7] = [&[NIL], &[ARRAY], &[SET], &[MAP], &[ARRAY | ONE_BYTE, 0], &[MAP | TWO_BYTES, 0, 0], &[SET | EIGHT_BYTES, 0, 0, 0, 0, 0, 0, 0, 0]];
+        for input in inputs {
+            let mut des = lenient(input);
+            assert_eq!(<()>::deserialize(&mut des).unwrap(), (), "{:?}", input);
+            des.end().unwrap();
+            assert_eq!(des.metrics(), Metrics { bytes: input.len(), nodes: 1, max_depth: 1 });
+            assert_eq!(Marker::deserialize(&mut lenient(input)).unwrap(), Marker);
+        }
+        assert_eq!(<(Marker, u8)>::deserialize(&mut lenient(&[ARRAY | 2, MAP, INT | 1])).unwrap(), (Marker, 1));
+
+        let found = |des: &mut VVDeserializer, found: &'static str| {
+            let err = <()>::deserialize(des).unwrap_err();
+            assert_eq!((err.e, err.position), (DecodeError::ExpectedUnit { found }, 0));
+        };
+        found(&mut VVDeserializer::new(&[ARRAY]), "array");
+        found(&mut VVDeserializer::new(&[SET]), "set");
+        found(&mut VVDeserializer::new(&[MAP | ONE_BYTE, 0]), "map");
+        found(&mut VVDeserializer::new(&[BYTES]), "byte string");
+        found(&mut VVDeserializer::new(&[FALSE]), "bool");
+        found(&mut VVDeserializer::new(&[FLOAT, 0, 0, 0, 0, 0, 0, 0, 0]), "float");
+        found(&mut VVDeserializer::new(&[INT]), "int");
+        found(&mut VVDeserializer::new(&[EXTENSION]), "extension");
+        found(&mut VVDeserializer::new(&[NIL | 2]), "unknown tag");
+        found(&mut lenient(&[ARRAY | 1, NIL]), "non-empty array");
+        found(&mut lenient(&[MAP | ONE_BYTE, 1, NIL, NIL]), "non-empty map");
+        found(&mut lenient(&[SET | 1, NIL]), "non-empty set");
+        found(&mut lenient(&[BYTES]), "byte string");
+    }
+
+    #[test]
+    fn sorted_keys() {
+        let strict = |input: &'static [u8]| VVDeserializer::new(input).require_sorted_keys(true);
+
+        let v: Value = crate::human::from_str(r#"{nil: 1, 2: @{-1, 0, "a"}, [97]: {{}: [], [1]: {}}, {}: nil}"#).unwrap();
+        let enc = crate::compact::to_vec(&v).unwrap();
+        let mut des = VVDeserializer::new(&enc).require_sorted_keys(true);
+        assert_eq!(Value::deserialize(&mut des).unwrap(), v);
+        des.end().unwrap();
+
+        let duplicate: &[u8] = &[MAP | 2, INT | 1, FALSE, INT | 1, TRUE];
+        let mut last_wins = BTreeMap::new();
+        last_wins.insert(1u8, true);
+        assert_eq!(BTreeMap::<u8, bool>::deserialize(&mut VVDeserializer::new(duplicate)).unwrap(), last_wins);
+        let err = BTreeMap::<u8, bool>::deserialize(&mut strict(duplicate)).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::DuplicateKey(Value::Int(1)), 3));
+
+        let err = Value::deserialize(&mut strict(&[MAP | 2, INT | 2, NIL, INT | 1, NIL])).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::UnsortedKeys(Value::Int(1)), 3));
+        let err = Value::deserialize(&mut strict(&[SET | ONE_BYTE, 3, NIL, INT | 1, INT | 1])).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::DuplicateKey(Value::Int(1)), 4));
+        let err = Value::deserialize(&mut strict(&[ARRAY | 2, MAP | 1, INT, NIL, MAP | 2, FALSE, NIL, NIL, NIL])).unwrap_err();
+        assert_eq!(err.to_string(), "parse error at position 7: key nil is less than the previous key");
+        assert_eq!((err.e, err.position), (DecodeError::UnsortedKeys(Value::Nil), 7));
+
+        // Each map is checked on its own, and a key may equal the value before it.
+        assert!(Value::deserialize(&mut strict(&[ARRAY | 2, MAP | 1, INT, INT, MAP | 2, INT, INT, INT | 1, INT | 1])).is_ok());
+
+        // Maps decoded as sequences of entries are checked as well.
+        assert_eq!(Vec::<(u8, bool)>::deserialize(&mut VVDeserializer::new(duplicate)).unwrap(), vec![(1, false), (1, true)]);
+        let err = Vec::<(u8, bool)>::deserialize(&mut strict(duplicate)).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::DuplicateKey(Value::Int(1)), 3));
+        let err = Vec::<(u8, ())>::deserialize(&mut strict(&[MAP | 2, INT | 2, NIL, INT | 1, NIL])).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::UnsortedKeys(Value::Int(1)), 3));
+
+        // So are maps nested in keys.
+        let nested: &[u8] = &[MAP | 1, MAP | 2, INT | 2, NIL, INT | 1, NIL, NIL];
+        assert!(BTreeMap::<Vec<(u8, ())>, ()>::deserialize(&mut VVDeserializer::new(nested)).is_ok());
+        let err = BTreeMap::<Vec<(u8, ())>, ()>::deserialize(&mut strict(nested)).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::UnsortedKeys(Value::Int(1)), 4));
+
+        // Keys nested in keys are decoded once more for every key they are nested in, not exponentially often.
+        let depth = 64;
+        let mut deep = vec![MAP | 1; depth];
+        deep.extend(std::iter::repeat_n(NIL, depth + 1));
+        let mut des = VVDeserializer::new(&deep).require_sorted_keys(true);
+        Value::deserialize(&mut des).unwrap();
+        assert!(des.rescanned <= deep.len() * depth, "{} bytes rescanned", des.rescanned);
+    }
+
+    #[test]
+    fn nan_payloads() {
+        let payload = f64::from_bits(0x7FF8_0000_0000_0001);
+        let values = vec![Value::Float(f64::from_bits(u64::MAX)), Value::Float(payload), Value::Float(1.5)];
+        let enc = crate::compact::to_vec(&values).unwrap();
+
+        // By default, payloads survive decoding, but `Value::eq` does not see them.
+        let decoded = Vec::<Value>::deserialize(&mut VVDeserializer::new(&enc)).unwrap();
+        assert_eq!(decoded, values);
+        match decoded[1] {
+            Value::Float(n) => assert_eq!(n.to_bits(), payload.to_bits()),
+            _ => unreachable!(),
+        }
+
+        let err = Vec::<Value>::deserialize(&mut VVDeserializer::new(&enc).reject_nan_payloads(true)).unwrap_err();
+        assert_eq!(err.e, DecodeError::NanPayload(0x7FF8_0000_0000_0001));
+        assert_eq!(err.position, 10);
+        let err = f64::deserialize(&mut VVDeserializer::new(&enc[10..]).reject_nan_payloads(true)).unwrap_err();
+        assert_eq!(err.e, DecodeError::NanPayload(0x7FF8_0000_0000_0001));
+
+        let canonic = crate::compact::to_vec(&[values[0].clone(), values[2].clone()]).unwrap();
+        assert!(Vec::<Value>::deserialize(&mut VVDeserializer::new(&canonic).reject_nan_payloads(true)).is_ok());
+    }
+
+    struct FailingReader;
+
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("nope"))
+        }
+    }
+
+    #[test]
+    fn readers() {
+        let mut enc = crate::compact::to_vec(&(1u8, "a", vec![true])).unwrap();
+        let v: (u8, String, Vec<bool>) = from_reader(std::io::Cursor::new(&enc)).unwrap();
+        assert_eq!(v, (1, "a".to_string(), vec![true]));
+
+        let len = enc.len();
+        enc.push(0);
+        let err = from_reader::<_, (u8, String, Vec<bool>)>(std::io::Cursor::new(&enc)).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::TrailingBytes, len));
+
+        let err = from_reader::<_, (u8, String, Vec<bool>)>(std::io::Cursor::new(&enc[..len - 1])).unwrap_err();
+        assert_eq!(err.e, DecodeError::Eoi);
+
+        let err = from_reader::<_, u8>(FailingReader).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::Io("nope".to_string()), 0));
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        let mut enc = crate::compact::to_vec(&(1u8, "a")).unwrap();
+        assert_eq!(from_slice::<(u8, &str)>(&enc).unwrap(), (1, "a"));
+
+        let len = enc.len();
+        enc.extend_from_slice(&crate::compact::to_vec(&7u8).unwrap());
+        let err = from_slice::<(u8, &str)>(&enc).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::TrailingBytes, len));
+
+        let mut des = VVDeserializer::new(&enc);
+        assert_eq!(<(u8, &str)>::deserialize(&mut des).unwrap(), (1, "a"));
+        assert_eq!(des.end().unwrap_err().position, len);
+        assert_eq!(u8::deserialize(&mut des).unwrap(), 7);
+        des.end().unwrap();
+    }
+
+    #[derive(PartialEq, Eq, Serialize, Deserialize, Debug)]
+    struct Row {
+        id: u32,
+        visible: bool,
+    }
+
+    #[test]
+    fn struct_keys_do_not_allocate() {
+        let rows: Vec<Row> = (0..100_000).map(|id| Row { id, visible: id % 2 == 0 }).collect();
+
+        // Keys as byte strings. The array header is written by hand, with a four-byte count.
+        let mut enc = vec![0b101_11110];
+        enc.extend_from_slice(&(rows.len() as u32).to_be_bytes());
+        for row in rows.iter() {
+            enc.extend_from_slice(&crate::compact::to_vec(row).unwrap());
+        }
+        let (decoded, allocations) = crate::alloc_counter::count_allocations(|| {
+            Vec::<Row>::deserialize(&mut VVDeserializer::new(&enc)).unwrap()
+        });
+        assert_eq!(decoded, rows);
+        assert!(allocations < 100, "{} allocations", allocations);
+
+        // Keys as arrays of ints.
+        let mut enc = vec![0b101_11110];
+        enc.extend_from_slice(&(rows.len() as u32).to_be_bytes());
+        for row in rows.iter() {
+            let mut m = BTreeMap::new();
+            m.insert(crate::Value::from("id"), crate::Value::Int(row.id as i64));
+            m.insert(crate::Value::from("visible"), crate::Value::Bool(row.visible));
+            enc.extend_from_slice(&crate::compact::to_vec(&crate::Value::Map(m)).unwrap());
+        }
+        let (decoded, allocations) = crate::alloc_counter::count_allocations(|| {
+            Vec::<Row>::deserialize(&mut VVDeserializer::new(&enc)).unwrap()
+        });
+        assert_eq!(decoded, rows);
+        assert!(allocations < 100, "{} allocations", allocations);
+    }
+
+    type Record = (String, Vec<i64>, Vec<String>, Vec<u8>);
+
+    fn record(i: usize) -> Record {
+        (
+            format!("record {}", i),
+            (0..(i % 7) as i64).map(|n| n * 1000 - 3).collect(),
+            vec!["a".repeat(i % 5), "b".repeat(i % 11), "c".repeat(i % 3)],
+            vec![i as u8; i % 13],
+        )
+    }
+
+    #[test]
+    fn in_place_reuses_allocations() {
+        let encs: Vec<Vec<u8>> = (0..10_000).map(|i| crate::compact::to_vec(&record(i)).unwrap()).collect();
+
+        let mut place = Record::default();
+        let mut decode = |encs: &[Vec<u8>], offset: usize| {
+            for (i, enc) in encs.iter().enumerate() {
+                from_slice_in_place(&mut place, enc).unwrap();
+                if place != record(offset + i) {
+                    return Err(offset + i);
+                }
+            }
+            Ok(())
+        };
+
+        let (warm_up, _) = crate::alloc_counter::count_allocations(|| decode(&encs[..100], 0));
+        assert_eq!(warm_up, Ok(()));
+        // `record` itself allocates, so compare against the allocations of building the expected records.
+        let (_, expected) = crate::alloc_counter::count_allocations(|| (100..10_000).map(record).for_each(drop));
+        let (result, allocations) = crate::alloc_counter::count_allocations(|| decode(&encs[100..], 100));
+        assert_eq!(result, Ok(()));
+        assert_eq!(allocations, expected);
+    }
+
+    #[test]
+    fn in_place_resizes() {
+        let records = [
+            (String::from("long name, longer than the next one"), vec![1, 2, 3, 4, 5], vec!["x".repeat(100), "y".into(), "z".into()], vec![1, 2, 3]),
+            (String::from("short"), vec![6, 7], vec![String::new()], vec![]),
+            (String::new(), vec![], vec![], vec![4]),
+            (String::from("longer than any name before, and also longer than sixty four bytes"), (0..100).collect(), vec!["u".into(), "v".repeat(200), "w".into(), "x".into()], vec![9; 300]),
+            (String::from("short again"), vec![-1], vec!["v".into()], vec![7, 7]),
+        ];
+
+        let mut place = Record::default();
+        for r in records.iter() {
+            from_slice_in_place(&mut place, &crate::compact::to_vec(r).unwrap()).unwrap();
+            assert_eq!(&place, r);
+        }
+
+        // Strings and bytes as arrays of ints.
+        let mut place: (String, Vec<u8>) = (String::from("some previous string"), vec![1, 2, 3, 4]);
+        let s = "ä".repeat(40);
+        let enc = crate::compact::to_vec(&(crate::Value::from(s.as_str()), crate::Value::from("ab"))).unwrap();
+        from_slice_in_place(&mut place, &enc).unwrap();
+        assert_eq!(place, (s, b"ab".to_vec()));
+
+        let mut place = vec![vec![1u8, 2], vec![3]];
+        assert!(from_slice_in_place(&mut place, &[0b101_00001, 0b000_00000]).is_err());
+    }
+
     #[derive(PartialEq, Eq, Serialize, Deserialize, Debug)]
     pub enum NilEnum {
         A,
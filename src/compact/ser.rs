@@ -1,8 +1,12 @@
+use std::convert::TryFrom;
 use std::fmt;
+use std::io;
 
 use serde::ser::{self, Serializer, Serialize};
 use thiserror::Error;
 
+use crate::compact::tags::*;
+
 /// Everything that can go wrong during serialization of a valuable value into the compact encoding.
 #[derive(Error, Debug, PartialEq, Eq, Clone)]
 pub enum EncodeError {
@@ -12,8 +16,19 @@ pub enum EncodeError {
     OutOfBoundsInt,
     #[error("collection length cannot exceed 2^63 - 1")]
     OutOfBoundsCollection,
+    /// Not produced anymore, the headers of collections of unknown length are inserted once their length is known.
     #[error("collections must have a known length")]
     UnknownLength,
+    #[error("int {value} does not fit into {width:?}")]
+    IntTooWide { value: i64, width: IntWidth },
+    /// A collection was written with a different number of items (or map entries, or struct fields) than announced up front,
+    /// e.g., by a `Serialize` impl that passes a wrong length to `serialize_map`, or by an iterator passed to
+    /// [`serialize_seq_streaming`](VVSerializer::serialize_seq_streaming). Too long iterators are only pulled once more than expected.
+    #[error("announced a collection of {declared} items, but {actual} were written")]
+    LengthMismatch { declared: usize, actual: usize },
+    /// Writing the output failed, see [`write_all`](write_all).
+    #[error("failed to write output: {0}")]
+    Io(String),
 }
 
 impl serde::ser::Error for EncodeError {
@@ -22,31 +37,235 @@ impl serde::ser::Error for EncodeError {
     }
 }
 
+/// How many bytes the [`VVSerializer`](VVSerializer) uses for encoding an int.
+///
+/// By default, ints use the shortest possible encoding. Some peers require ints of a fixed width regardless of their value, so that records have predictable sizes. A fixed width pads small ints to that width, and ints that do not fit into it cannot be serialized. All widths decode just the same, so fixed-width encodings need no special decoder. Use [`VVSerializer::int_width`](VVSerializer::int_width) to pin the width of all ints, or the [`fixed_width`](fixed_width) helpers to pin it for individual fields.
+///
+/// Implements `Serialize` and `Deserialize`, so that it can be part of a configuration file.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub enum IntWidth {
+    /// The shortest encoding of each int, ints from 0 to 27 are stored in the tag itself.
+    #[default]
+    Minimal,
+    /// One byte after the tag, for ints from `i8::MIN` to `i8::MAX`.
+    Fixed1,
+    /// Two bytes after the tag, for ints from `i16::MIN` to `i16::MAX`.
+    Fixed2,
+    /// Four bytes after the tag, for ints from `i32::MIN` to `i32::MAX`.
+    Fixed4,
+    /// Eight bytes after the tag, for all ints.
+    Fixed8,
+}
+
+// The names of the newtype structs by which the `fixed_width` helpers tell the serializer which width to use.
+const FIXED1_NAME: &str = "$valuable_value::compact::Fixed1";
+const FIXED2_NAME: &str = "$valuable_value::compact::Fixed2";
+const FIXED4_NAME: &str = "$valuable_value::compact::Fixed4";
+const FIXED8_NAME: &str = "$valuable_value::compact::Fixed8";
+
+impl IntWidth {
+    fn of_newtype_name(name: &str) -> Option<IntWidth> {
+        match name {
+            FIXED1_NAME => Some(IntWidth::Fixed1),
+            FIXED2_NAME => Some(IntWidth::Fixed2),
+            FIXED4_NAME => Some(IntWidth::Fixed4),
+            FIXED8_NAME => Some(IntWidth::Fixed8),
+            _ => None,
+        }
+    }
+}
+
+/// Helpers for pinning the [width](IntWidth) of the ints in a single field with `#[serde(with = "...")]`.
+///
+/// The width applies to all ints in the field, including those nested in options or collections. Serializers other than the [`VVSerializer`](VVSerializer) encode the field as usual, and deserialization is unaffected.
+///
+/// ```
+/// use serde::{Serialize, Deserialize};
+/// use valuable_value::compact;
+///
+/// #[derive(Serialize, Deserialize, Debug, PartialEq)]
+/// struct Reading {
+///     #[serde(with = "compact::fixed_width::fixed4")]
+///     sensor: u16,
+///     celsius: i8,
+/// }
+///
+/// let reading = Reading { sensor: 3, celsius: 21 };
+/// let enc = compact::to_vec(&reading).unwrap();
+/// assert_eq!(compact::to_vec(&Reading { sensor: 60_000, ..reading }).unwrap().len(), enc.len());
+/// assert_eq!(compact::from_slice::<Reading>(&enc).unwrap(), reading);
+/// ```
+pub mod fixed_width {
+    macro_rules! fixed_width {
+        ($name:ident, $newtype_name:expr, $doc:expr) => {
+            #[doc = $doc]
+            pub mod $name {
+                use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+                pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    T: ?Sized + Serialize,
+                    S: Serializer,
+                {
+                    serializer.serialize_newtype_struct($newtype_name, value)
+                }
+
+                pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+                where
+                    T: Deserialize<'de>,
+                    D: Deserializer<'de>,
+                {
+                    T::deserialize(deserializer)
+                }
+            }
+        };
+    }
+
+    fixed_width!(fixed1, super::super::FIXED1_NAME, "Encode the ints of a field with [`IntWidth::Fixed1`](super::IntWidth::Fixed1).");
+    fixed_width!(fixed2, super::super::FIXED2_NAME, "Encode the ints of a field with [`IntWidth::Fixed2`](super::IntWidth::Fixed2).");
+    fixed_width!(fixed4, super::super::FIXED4_NAME, "Encode the ints of a field with [`IntWidth::Fixed4`](super::IntWidth::Fixed4).");
+    fixed_width!(fixed8, super::super::FIXED8_NAME, "Encode the ints of a field with [`IntWidth::Fixed8`](super::IntWidth::Fixed8).");
+}
+
+// Large byte strings are written to a sink in chunks of at most this many bytes, instead of being buffered as a whole.
+const BYTES_CHUNK_SIZE: usize = 64 * 1024;
+
 /// A structure that serializes valuable values in the [compact encoding](https://github.com/AljoschaMeyer/valuable-value#compact-encoding).
-pub struct VVSerializer {
+///
+/// Created with [`new`](VVSerializer::new), it writes into a Vec. Created with [`with_writer`](VVSerializer::with_writer),
+/// it writes into an [`io::Write`](std::io::Write) sink instead, see [`to_writer`](to_writer).
+pub struct VVSerializer<W = io::Sink> {
     out: Vec<u8>,
+    // Where `out` is written to, if anywhere.
+    sink: Option<W>,
+    compact_options: bool,
+    int_width: IntWidth,
+    open: Vec<OpenCollection>,
+    // The headers of all collections of unknown length since the outermost open one started, in the order they were opened.
+    deferred: Vec<DeferredHeader>,
+}
+
+// An array or map that is currently being written.
+struct OpenCollection {
+    len: Length,
+    // How many items or entries have been written so far.
+    count: usize,
+}
+
+// How the length of an open collection was given.
+enum Length {
+    // Announced up front, the header has already been written.
+    Known(usize),
+    // Unknown up front, holds the index of the header in `VVSerializer::deferred`.
+    Deferred(usize),
+}
+
+// The header of a collection of unknown length: where it belongs in the output, and, once the collection has ended, its count.
+struct DeferredHeader {
+    position: usize,
+    tag: u8,
+    count: usize,
 }
 
 impl VVSerializer {
     /// Create a new serializer, writing compact encoding into the given Vec.
     pub fn new(out: Vec<u8>) -> Self {
-        VVSerializer { out }
+        VVSerializer { out, sink: None, compact_options: false, int_width: IntWidth::Minimal, open: vec![], deferred: vec![] }
+    }
+
+    /// Return the encoding written so far.
+    pub fn into_inner(self) -> Vec<u8> {
+        self.out
+    }
+}
+
+impl<W: io::Write> VVSerializer<W> {
+    /// Create a new serializer, writing compact encoding into the given sink.
+    ///
+    /// The encoding is buffered until [`finish`](VVSerializer::finish) is called, except for byte strings larger than 64 KiB,
+    /// which are passed on to the sink right away, in chunks of at most 64 KiB. Errors of the sink are reported as
+    /// [`EncodeError::Io`](EncodeError::Io).
+    pub fn with_writer(writer: W) -> Self {
+        VVSerializer { out: Vec::new(), sink: Some(writer), compact_options: false, int_width: IntWidth::Minimal, open: vec![], deferred: vec![] }
+    }
+
+    /// Write the remaining encoding into the sink, and return the sink.
+    pub fn finish(mut self) -> Result<W, EncodeError> {
+        self.write_out()?;
+        Ok(self.sink.expect("finish is only available for serializers with a sink"))
+    }
+
+    // Pass everything written so far on to the sink, if there is one.
+    fn write_out(&mut self) -> Result<(), EncodeError> {
+        if let Some(sink) = &mut self.sink {
+            sink.write_all(&self.out).map_err(|e| EncodeError::Io(e.to_string()))?;
+            self.out.clear();
+        }
+        Ok(())
+    }
+
+    /// Encode `None` as nil and `Some(v)` as the encoding of `v`, instead of as the string `"None"` and the map `{"Some": v}`.
+    ///
+    /// This saves space, but loses information: `None`, `Some(())` and `Some(None)` all become nil, and decode as `None`. Only use this if the values you encode do not contain such options, and decode them with [`VVDeserializer::compact_options`](crate::compact::VVDeserializer::compact_options). Other decoders do not know about this mode.
+    pub fn compact_options(mut self, compact: bool) -> Self {
+        self.compact_options = compact;
+        self
+    }
+
+    /// Set the [width](IntWidth) of all ints, [`fixed_width`](fixed_width) fields override it.
+    pub fn int_width(mut self, width: IntWidth) -> Self {
+        self.int_width = width;
+        self
+    }
+
+    /// Write an array of `len` items, serializing each item as it is pulled from the iterator.
+    ///
+    /// This encodes sequences that are produced on the fly, e.g., by a database cursor, without ever holding all their items at once. The count comes first in the encoding, so it must be known up front. If the iterator yields fewer or more than `len` items, this fails with [`EncodeError::LengthMismatch`](EncodeError::LengthMismatch) and leaves an invalid encoding in the output. At most `len + 1` items are pulled from the iterator.
+    ///
+    /// ```
+    /// use valuable_value::compact::{self, VVSerializer};
+    ///
+    /// let mut serializer = VVSerializer::new(Vec::new());
+    /// serializer.serialize_seq_streaming((0..1000u32).map(|i| i * i), 1000).unwrap();
+    /// let squares: Vec<u32> = compact::from_slice(&serializer.into_inner()).unwrap();
+    /// assert_eq!(squares[999], 998_001);
+    /// ```
+    pub fn serialize_seq_streaming<I>(&mut self, items: I, len: usize) -> Result<(), EncodeError>
+    where
+        I: IntoIterator,
+        I::Item: Serialize,
+    {
+        self.serialize_count(len, ARRAY)?;
+        let mut actual = 0;
+        for item in items {
+            if actual == len {
+                return Err(EncodeError::LengthMismatch { declared: len, actual: len + 1 });
+            }
+            item.serialize(&mut *self)?;
+            actual += 1;
+        }
+
+        if actual == len {
+            Ok(())
+        } else {
+            Err(EncodeError::LengthMismatch { declared: len, actual })
+        }
     }
 
     fn serialize_count(&mut self, n: usize, tag: u8) -> Result<(), EncodeError> {
-        if n <= 27 {
+        if n <= MAX_INLINE as usize {
             self.out.push(tag | (n as u8));
         } else if n <= (u8::MAX as usize) {
-            self.out.push(tag | 0b000_11100);
+            self.out.push(tag | ONE_BYTE);
             self.out.extend_from_slice(&(n as u8).to_be_bytes());
         } else if n <= (u16::MAX as usize) {
-            self.out.push(tag | 0b000_11101);
+            self.out.push(tag | TWO_BYTES);
             self.out.extend_from_slice(&(n as u16).to_be_bytes());
         } else if n <= (u32::MAX as usize) {
-            self.out.push(tag | 0b000_11101);
+            self.out.push(tag | FOUR_BYTES);
             self.out.extend_from_slice(&(n as u32).to_be_bytes());
         } else if n <= (i64::MAX as usize) {
-            self.out.push(tag | 0b000_11111);
+            self.out.push(tag | EIGHT_BYTES);
             self.out.extend_from_slice(&(n as u64).to_be_bytes());
         } else {
             return Err(EncodeError::OutOfBoundsCollection)
@@ -54,6 +273,72 @@ impl VVSerializer {
 
         Ok(())
     }
+
+    fn open_collection(&mut self, len: Option<usize>, tag: u8) -> Result<(), EncodeError> {
+        let len = match len {
+            Some(len) => {
+                self.serialize_count(len, tag)?;
+                Length::Known(len)
+            }
+            None => {
+                self.deferred.push(DeferredHeader { position: self.out.len(), tag, count: 0 });
+                Length::Deferred(self.deferred.len() - 1)
+            }
+        };
+        self.open.push(OpenCollection { len, count: 0 });
+        Ok(())
+    }
+
+    fn count_item(&mut self) {
+        if let Some(open) = self.open.last_mut() {
+            open.count += 1;
+        }
+    }
+
+    // A header that has already been written must match the number of items or entries. Deferred headers are inserted
+    // once the outermost collection of unknown length ends, all at once, so that the contents are moved only once no
+    // matter how deeply such collections are nested.
+    fn close_collection(&mut self) -> Result<(), EncodeError> {
+        match self.open.pop() {
+            Some(OpenCollection { len: Length::Known(len), count }) if len != count => {
+                Err(EncodeError::LengthMismatch { declared: len, actual: count })
+            }
+            Some(OpenCollection { len: Length::Deferred(0), count }) => {
+                self.deferred[0].count = count;
+                self.insert_deferred_headers()
+            }
+            Some(OpenCollection { len: Length::Deferred(i), count }) => {
+                self.deferred[i].count = count;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn insert_deferred_headers(&mut self) -> Result<(), EncodeError> {
+        let start = self.deferred[0].position;
+        let contents = self.out.split_off(start);
+        let mut copied = 0;
+        for header in std::mem::take(&mut self.deferred) {
+            self.out.extend_from_slice(&contents[copied..header.position - start]);
+            copied = header.position - start;
+            self.serialize_count(header.count, header.tag)?;
+        }
+        self.out.extend_from_slice(&contents[copied..]);
+        Ok(())
+    }
+}
+
+/// Append an extension to the output: an opaque, application-defined blob of bytes together with an int identifying its type.
+///
+/// An extension is encoded as the [`EXTENSION`](crate::compact::tags::EXTENSION) tag, followed by the compact encoding of the type id as an int and the compact encoding of the payload as a byte string. This is not part of the valuable value specification: other implementations reject extensions, and extensions have no canonic encoding. Decode them with [`deserialize_extension`](crate::compact::deserialize_extension).
+pub fn serialize_extension(type_id: i64, payload: &[u8], out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    let mut serializer = VVSerializer::new(std::mem::take(out));
+    serializer.out.push(EXTENSION);
+    serializer.serialize_i64(type_id)?;
+    let result = serializer.serialize_bytes(payload);
+    *out = serializer.out;
+    result
 }
 
 /// Write compact encoding into a Vec.
@@ -61,14 +346,66 @@ pub fn to_vec<T>(value: &T) -> Result<Vec<u8>, EncodeError>
 where
     T: Serialize,
 {
-    let mut serializer = VVSerializer {
-        out: Vec::new(),
-    };
+    let mut serializer = VVSerializer::new(Vec::new());
     value.serialize(&mut serializer)?;
     Ok(serializer.out)
 }
 
-impl<'a> Serializer for &'a mut VVSerializer {
+/// Write compact encoding into a writer.
+///
+/// Large byte strings are written in bounded chunks rather than copied into an intermediate buffer, see
+/// [`VVSerializer::with_writer`](VVSerializer::with_writer). The writer is not flushed.
+///
+/// ```
+/// use valuable_value::compact;
+///
+/// let mut out = Vec::new();
+/// compact::to_writer(&mut out, &(1u8, "two")).unwrap();
+/// assert_eq!(out, compact::to_vec(&(1u8, "two")).unwrap());
+/// ```
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<(), EncodeError>
+where
+    W: io::Write,
+    T: Serialize,
+{
+    let mut serializer = VVSerializer::with_writer(writer);
+    value.serialize(&mut serializer)?;
+    serializer.finish()?;
+    Ok(())
+}
+
+/// Write the compact encodings of all values into the writer, back to back, and return the total number of bytes written.
+///
+/// Each value is encoded completely before any of it is written. If `flush_each` is set, the writer is flushed after every value, otherwise it is never flushed. On an error, all values before the one that caused it have been written (and flushed if `flush_each` is set), errors of the writer are reported as [`EncodeError::Io`](EncodeError::Io).
+///
+/// ```
+/// use valuable_value::compact;
+///
+/// let mut out = Vec::new();
+/// assert_eq!(compact::write_all(&mut out, vec![1u8, 2, 3], false).unwrap(), 3);
+/// assert_eq!(out, [compact::to_vec(&1u8).unwrap(), compact::to_vec(&2u8).unwrap(), compact::to_vec(&3u8).unwrap()].concat());
+/// ```
+pub fn write_all<W, I>(mut writer: W, values: I, flush_each: bool) -> Result<u64, EncodeError>
+where
+    W: io::Write,
+    I: IntoIterator,
+    I::Item: Serialize,
+{
+    let mut serializer = VVSerializer::new(Vec::new());
+    let mut total = 0;
+    for value in values {
+        serializer.out.clear();
+        value.serialize(&mut serializer)?;
+        writer.write_all(&serializer.out).map_err(|e| EncodeError::Io(e.to_string()))?;
+        if flush_each {
+            writer.flush().map_err(|e| EncodeError::Io(e.to_string()))?;
+        }
+        total += serializer.out.len() as u64;
+    }
+    Ok(total)
+}
+
+impl<'a, W: io::Write> Serializer for &'a mut VVSerializer<W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -81,7 +418,7 @@ impl<'a> Serializer for &'a mut VVSerializer {
     type SerializeStructVariant = Self;
 
     fn serialize_bool(self, v: bool) -> Result<(), EncodeError> {
-        Ok(self.out.push(if v { 0b001_00001 } else { 0b001_00000 }))
+        Ok(self.out.push(if v { TRUE } else { FALSE }))
     }
 
     fn serialize_i8(self, v: i8) -> Result<(), EncodeError> {
@@ -97,20 +434,45 @@ impl<'a> Serializer for &'a mut VVSerializer {
     }
 
     fn serialize_i64(self, v: i64) -> Result<(), EncodeError> {
-        if 0 <= v && v <= 27 {
-            self.out.push(0b011_00000 | (v as u8));
-        } else if (i8::MIN as i64) <= v && v <= (i8::MAX as i64) {
-            self.out.push(0b011_11100);
-            self.out.extend_from_slice(&(v as i8).to_be_bytes());
-        } else if (i16::MIN as i64) <= v && v <= (i16::MAX as i64) {
-            self.out.push(0b011_11101);
-            self.out.extend_from_slice(&(v as i16).to_be_bytes());
-        } else if (i32::MIN as i64) <= v && v <= (i32::MAX as i64) {
-            self.out.push(0b011_11110);
-            self.out.extend_from_slice(&(v as i32).to_be_bytes());
-        } else {
-            self.out.push(0b011_11111);
-            self.out.extend_from_slice(&(v as i64).to_be_bytes());
+        let width = self.int_width;
+        let too_wide = |_| EncodeError::IntTooWide { value: v, width };
+        match width {
+            IntWidth::Minimal => {
+                if 0 <= v && v <= MAX_INLINE as i64 {
+                    self.out.push(INT | (v as u8));
+                } else if (i8::MIN as i64) <= v && v <= (i8::MAX as i64) {
+                    self.out.push(INT | ONE_BYTE);
+                    self.out.extend_from_slice(&(v as i8).to_be_bytes());
+                } else if (i16::MIN as i64) <= v && v <= (i16::MAX as i64) {
+                    self.out.push(INT | TWO_BYTES);
+                    self.out.extend_from_slice(&(v as i16).to_be_bytes());
+                } else if (i32::MIN as i64) <= v && v <= (i32::MAX as i64) {
+                    self.out.push(INT | FOUR_BYTES);
+                    self.out.extend_from_slice(&(v as i32).to_be_bytes());
+                } else {
+                    self.out.push(INT | EIGHT_BYTES);
+                    self.out.extend_from_slice(&(v as i64).to_be_bytes());
+                }
+            }
+            IntWidth::Fixed1 => {
+                let n = i8::try_from(v).map_err(too_wide)?;
+                self.out.push(INT | ONE_BYTE);
+                self.out.extend_from_slice(&n.to_be_bytes());
+            }
+            IntWidth::Fixed2 => {
+                let n = i16::try_from(v).map_err(too_wide)?;
+                self.out.push(INT | TWO_BYTES);
+                self.out.extend_from_slice(&n.to_be_bytes());
+            }
+            IntWidth::Fixed4 => {
+                let n = i32::try_from(v).map_err(too_wide)?;
+                self.out.push(INT | FOUR_BYTES);
+                self.out.extend_from_slice(&n.to_be_bytes());
+            }
+            IntWidth::Fixed8 => {
+                self.out.push(INT | EIGHT_BYTES);
+                self.out.extend_from_slice(&v.to_be_bytes());
+            }
         }
 
         Ok(())
@@ -141,7 +503,7 @@ impl<'a> Serializer for &'a mut VVSerializer {
     }
 
     fn serialize_f64(self, v: f64) -> Result<(), EncodeError> {
-        self.out.push(0b010_00000);
+        self.out.push(FLOAT);
         self.out.extend_from_slice(&v.to_bits().to_be_bytes());
         Ok(())
     }
@@ -155,12 +517,26 @@ impl<'a> Serializer for &'a mut VVSerializer {
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<(), EncodeError> {
-        self.serialize_count(v.len(), 0b100_00000)?;
-        self.out.extend_from_slice(v);
+        self.serialize_count(v.len(), BYTES)?;
+        // The headers of collections of unknown length still have to be inserted in front of what has been written, so
+        // nothing can be passed on to the sink while one is open.
+        if self.sink.is_some() && self.deferred.is_empty() && v.len() > BYTES_CHUNK_SIZE {
+            self.write_out()?;
+            if let Some(sink) = &mut self.sink {
+                for chunk in v.chunks(BYTES_CHUNK_SIZE) {
+                    sink.write_all(chunk).map_err(|e| EncodeError::Io(e.to_string()))?;
+                }
+            }
+        } else {
+            self.out.extend_from_slice(v);
+        }
         return Ok(());
     }
 
     fn serialize_none(self) -> Result<(), EncodeError> {
+        if self.compact_options {
+            return self.serialize_unit();
+        }
         self.serialize_str("None")
     }
 
@@ -168,13 +544,16 @@ impl<'a> Serializer for &'a mut VVSerializer {
     where
         T: ?Sized + Serialize,
     {
-        self.out.push(0b111_00001);
+        if self.compact_options {
+            return value.serialize(self);
+        }
+        self.out.push(SINGLETON_MAP);
         self.serialize_str("Some")?;
         value.serialize(self)
     }
 
     fn serialize_unit(self) -> Result<(), EncodeError> {
-        Ok(self.out.push(0b000_00000))
+        Ok(self.out.push(NIL))
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<(), EncodeError> {
@@ -192,13 +571,21 @@ impl<'a> Serializer for &'a mut VVSerializer {
 
     fn serialize_newtype_struct<T>(
         self,
-        _name: &'static str,
+        name: &'static str,
         value: &T,
     ) -> Result<(), EncodeError>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        match IntWidth::of_newtype_name(name) {
+            Some(width) => {
+                let outer = std::mem::replace(&mut self.int_width, width);
+                let result = value.serialize(&mut *self);
+                self.int_width = outer;
+                result
+            }
+            None => value.serialize(self),
+        }
     }
 
     fn serialize_newtype_variant<T>(
@@ -211,23 +598,19 @@ impl<'a> Serializer for &'a mut VVSerializer {
     where
         T: ?Sized + Serialize,
     {
-        self.out.push(0b111_00001);
+        self.out.push(SINGLETON_MAP);
         variant.serialize(&mut *self)?;
         value.serialize(&mut *self)
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        match len {
-            None => return Err(EncodeError::UnknownLength),
-            Some(len) => {
-                self.serialize_count(len, 0b101_00000)?;
-                return Ok(self);
-            }
-        }
+        self.open_collection(len, ARRAY)?;
+        Ok(self)
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        self.serialize_seq(Some(len))
+        self.open_collection(Some(len), ARRAY)?;
+        Ok(self)
     }
 
     fn serialize_tuple_struct(
@@ -235,7 +618,8 @@ impl<'a> Serializer for &'a mut VVSerializer {
         _name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        self.serialize_seq(Some(len))
+        self.open_collection(Some(len), ARRAY)?;
+        Ok(self)
     }
 
     fn serialize_tuple_variant(
@@ -245,20 +629,15 @@ impl<'a> Serializer for &'a mut VVSerializer {
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        self.out.push(0b111_00001);
+        self.out.push(SINGLETON_MAP);
         variant.serialize(&mut *self)?;
-        self.serialize_count(len, 0b101_00000)?;
+        self.open_collection(Some(len), ARRAY)?;
         Ok(self)
     }
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-        match len {
-            None => return Err(EncodeError::UnknownLength),
-            Some(len) => {
-                self.serialize_count(len, 0b111_00000)?;
-                return Ok(self);
-            }
-        }
+        self.open_collection(len, MAP)?;
+        Ok(self)
     }
 
     fn serialize_struct(
@@ -266,7 +645,8 @@ impl<'a> Serializer for &'a mut VVSerializer {
         _name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
-        self.serialize_map(Some(len))
+        self.open_collection(Some(len), MAP)?;
+        Ok(self)
     }
 
     fn serialize_struct_variant(
@@ -274,15 +654,16 @@ impl<'a> Serializer for &'a mut VVSerializer {
         _name: &'static str,
         _variant_index: u32,
         variant: &'static str,
-        _len: usize,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        self.out.push(0b111_00001);
+        self.out.push(SINGLETON_MAP);
         variant.serialize(&mut *self)?;
+        self.open_collection(Some(len), MAP)?;
         Ok(self)
     }
 }
 
-impl<'a> ser::SerializeSeq for &'a mut VVSerializer {
+impl<'a, W: io::Write> ser::SerializeSeq for &'a mut VVSerializer<W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -290,15 +671,16 @@ impl<'a> ser::SerializeSeq for &'a mut VVSerializer {
     where
         T: ?Sized + Serialize,
     {
+        self.count_item();
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<(), EncodeError> {
-        Ok(())
+        self.close_collection()
     }
 }
 
-impl<'a> ser::SerializeTuple for &'a mut VVSerializer {
+impl<'a, W: io::Write> ser::SerializeTuple for &'a mut VVSerializer<W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -306,15 +688,16 @@ impl<'a> ser::SerializeTuple for &'a mut VVSerializer {
     where
         T: ?Sized + Serialize,
     {
+        self.count_item();
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<(), EncodeError> {
-        Ok(())
+        self.close_collection()
     }
 }
 
-impl<'a> ser::SerializeTupleStruct for &'a mut VVSerializer {
+impl<'a, W: io::Write> ser::SerializeTupleStruct for &'a mut VVSerializer<W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -322,15 +705,16 @@ impl<'a> ser::SerializeTupleStruct for &'a mut VVSerializer {
     where
         T: ?Sized + Serialize,
     {
+        self.count_item();
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<(), EncodeError> {
-        Ok(())
+        self.close_collection()
     }
 }
 
-impl<'a> ser::SerializeTupleVariant for &'a mut VVSerializer {
+impl<'a, W: io::Write> ser::SerializeTupleVariant for &'a mut VVSerializer<W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -338,15 +722,16 @@ impl<'a> ser::SerializeTupleVariant for &'a mut VVSerializer {
     where
         T: ?Sized + Serialize,
     {
+        self.count_item();
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<(), EncodeError> {
-        Ok(())
+        self.close_collection()
     }
 }
 
-impl<'a> ser::SerializeMap for &'a mut VVSerializer {
+impl<'a, W: io::Write> ser::SerializeMap for &'a mut VVSerializer<W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -354,6 +739,7 @@ impl<'a> ser::SerializeMap for &'a mut VVSerializer {
     where
         T: ?Sized + Serialize,
     {
+        self.count_item();
         key.serialize(&mut **self)
     }
 
@@ -365,11 +751,11 @@ impl<'a> ser::SerializeMap for &'a mut VVSerializer {
     }
 
     fn end(self) -> Result<(), EncodeError> {
-        Ok(())
+        self.close_collection()
     }
 }
 
-impl<'a> ser::SerializeStruct for &'a mut VVSerializer {
+impl<'a, W: io::Write> ser::SerializeStruct for &'a mut VVSerializer<W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -377,16 +763,17 @@ impl<'a> ser::SerializeStruct for &'a mut VVSerializer {
     where
         T: ?Sized + Serialize,
     {
+        self.count_item();
         key.serialize(&mut **self)?;
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<(), EncodeError> {
-        Ok(())
+        self.close_collection()
     }
 }
 
-impl<'a> ser::SerializeStructVariant for &'a mut VVSerializer {
+impl<'a, W: io::Write> ser::SerializeStructVariant for &'a mut VVSerializer<W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -394,12 +781,478 @@ impl<'a> ser::SerializeStructVariant for &'a mut VVSerializer {
     where
         T: ?Sized + Serialize,
     {
-        self.out.push(0b111_00001);
+        self.count_item();
         key.serialize(&mut **self)?;
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<(), EncodeError> {
-        Ok(())
+        self.close_collection()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    // Accepts `limit` bytes, then fails.
+    struct LimitedWriter {
+        out: Vec<u8>,
+        limit: usize,
+        flushes: usize,
+    }
+
+    impl io::Write for LimitedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.limit - self.out.len());
+            if n == 0 && !buf.is_empty() {
+                return Err(io::Error::other("full"));
+            }
+            self.out.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    fn records() -> Vec<(u32, String, Vec<bool>)> {
+        (0..1000u32).map(|i| (i * 7919, format!("record {}", i), vec![i % 2 == 0; (i % 5) as usize])).collect()
+    }
+
+    // Decode values written back to back until the input is exhausted.
+    fn read_all(input: &[u8]) -> Vec<(u32, String, Vec<bool>)> {
+        let mut des = crate::compact::VVDeserializer::new(input);
+        let mut values = Vec::new();
+        while des.end().is_err() {
+            values.push(Deserialize::deserialize(&mut des).unwrap());
+        }
+        values
+    }
+
+    #[test]
+    fn write_all_values() {
+        let records = records();
+        let mut writer = LimitedWriter { out: Vec::new(), limit: usize::MAX, flushes: 0 };
+        let written = write_all(&mut writer, &records, false).unwrap();
+        assert_eq!(written, writer.out.len() as u64);
+        assert_eq!(writer.flushes, 0);
+        assert_eq!(read_all(&writer.out), records);
+
+        let mut flushing = LimitedWriter { out: Vec::new(), limit: usize::MAX, flushes: 0 };
+        assert_eq!(write_all(&mut flushing, &records, true).unwrap(), written);
+        assert_eq!(flushing.flushes, 1000);
+        assert_eq!(flushing.out, writer.out);
+
+        // The writer fails in the middle of the 501st value.
+        let limit = records[..500].iter().map(|r| to_vec(r).unwrap().len()).sum::<usize>() + 3;
+        let mut failing = LimitedWriter { out: Vec::new(), limit, flushes: 0 };
+        assert_eq!(write_all(&mut failing, &records, true).unwrap_err(), EncodeError::Io("full".to_string()));
+        assert_eq!(failing.flushes, 500);
+        assert_eq!(read_all(&failing.out[..limit - 3]), &records[..500]);
+
+        // Encoding errors stop the writing just the same.
+        let mut out = Vec::new();
+        assert_eq!(write_all(&mut out, vec![1, u64::MAX, 2], false).unwrap_err(), EncodeError::OutOfBoundsInt);
+        assert_eq!(out, to_vec(&1).unwrap());
+    }
+
+    #[test]
+    fn seq_streaming() {
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Row {
+            id: u32,
+            name: String,
+            score: Option<f64>,
+        }
+
+        let row = |i: u32| Row { id: i, name: format!("row {}", i), score: if i < 10 { None } else { Some(i as f64 / 2.0) } };
+        let mut pulled = 0;
+        let rows = (0..1000).map(|i| {
+            pulled += 1;
+            row(i)
+        });
+
+        let mut serializer = VVSerializer::new(Vec::new());
+        serializer.serialize_seq_streaming(rows, 1000).unwrap();
+        let enc = serializer.into_inner();
+        assert_eq!(pulled, 1000);
+
+        let all: Vec<Row> = (0..1000).map(row).collect();
+        assert_eq!(enc, crate::compact::to_vec(&all).unwrap());
+        assert_eq!(crate::compact::from_slice::<Vec<Row>>(&enc).unwrap(), all);
+
+        let mut serializer = VVSerializer::new(Vec::new()).compact_options(true);
+        serializer.serialize_seq_streaming(vec![Some(1u8), None], 2).unwrap();
+        assert_eq!(serializer.into_inner(), vec![ARRAY | 2, INT | 1, NIL]);
+
+        let mut serializer = VVSerializer::new(Vec::new());
+        assert_eq!(serializer.serialize_seq_streaming(0..3, 5), Err(EncodeError::LengthMismatch { declared: 5, actual: 3 }));
+        assert_eq!(serializer.serialize_seq_streaming(0.., 2), Err(EncodeError::LengthMismatch { declared: 2, actual: 3 }));
+        assert_eq!(
+            EncodeError::LengthMismatch { declared: 2, actual: 3 }.to_string(),
+            "announced a collection of 2 items, but 3 were written",
+        );
+    }
+
+    // Announces `announced` items or entries, but writes `written` of them.
+    struct Lying {
+        kind: &'static str,
+        announced: usize,
+        written: usize,
+    }
+
+    impl Serialize for Lying {
+        fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::{SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple, SerializeTupleVariant};
+
+            const FIELDS: [&str; 4] = ["a", "b", "c", "d"];
+            match self.kind {
+                "seq" => {
+                    let mut s = serializer.serialize_seq(Some(self.announced))?;
+                    (0..self.written).try_for_each(|i| s.serialize_element(&i))?;
+                    s.end()
+                }
+                "tuple" => {
+                    let mut s = serializer.serialize_tuple(self.announced)?;
+                    (0..self.written).try_for_each(|i| s.serialize_element(&i))?;
+                    s.end()
+                }
+                "tuple_variant" => {
+                    let mut s = serializer.serialize_tuple_variant("Lying", 0, "V", self.announced)?;
+                    (0..self.written).try_for_each(|i| s.serialize_field(&i))?;
+                    s.end()
+                }
+                "map" => {
+                    let mut s = serializer.serialize_map(Some(self.announced))?;
+                    (0..self.written).try_for_each(|i| s.serialize_entry(&i, &()))?;
+                    s.end()
+                }
+                "struct" => {
+                    let mut s = serializer.serialize_struct("Lying", self.announced)?;
+                    FIELDS[..self.written].iter().try_for_each(|f| s.serialize_field(f, &()))?;
+                    s.end()
+                }
+                _ => {
+                    let mut s = serializer.serialize_struct_variant("Lying", 0, "V", self.announced)?;
+                    FIELDS[..self.written].iter().try_for_each(|f| s.serialize_field(f, &()))?;
+                    s.end()
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lying_lengths() {
+        for kind in ["seq", "tuple", "tuple_variant", "map", "struct", "struct_variant"] {
+            let enc = to_vec(&Lying { kind, announced: 3, written: 3 }).unwrap();
+            assert!(crate::compact::from_slice::<crate::Value>(&enc).is_ok(), "{}", kind);
+
+            for written in [2, 4] {
+                let err = to_vec(&Lying { kind, announced: 3, written }).unwrap_err();
+                assert_eq!(err, EncodeError::LengthMismatch { declared: 3, actual: written }, "{}", kind);
+            }
+
+            // Lies in nested values are caught as well.
+            assert_eq!(
+                to_vec(&vec![vec![], vec![Lying { kind, announced: 0, written: 1 }]]).unwrap_err(),
+                EncodeError::LengthMismatch { declared: 0, actual: 1 },
+            );
+        }
+
+        // The human-readable encoding does not write lengths, so it does not care.
+        assert_eq!(crate::human::to_vec(&Lying { kind: "map", announced: 3, written: 1 }, 0).unwrap(), b"{0:nil}");
+    }
+
+    #[test]
+    fn unknown_length() {
+        // A sequence of unknown length inside a map of unknown length, inside an array of known length, with an inner
+        // sequence of unknown length that starts at the same position as its parent.
+        struct Unknown;
+
+        impl Serialize for Unknown {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use ser::{SerializeMap, SerializeSeq};
+
+                struct Inner(usize);
+
+                impl Serialize for Inner {
+                    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                        let mut s = serializer.serialize_seq(None)?;
+                        for i in 0..self.0 {
+                            s.serialize_element(&i)?;
+                        }
+                        s.end()
+                    }
+                }
+
+                let mut m = serializer.serialize_map(None)?;
+                m.serialize_entry(&(), &Inner(30))?;
+                m.serialize_entry(&vec![Inner(2)], &Inner(0))?;
+                m.end()
+            }
+        }
+
+        let enc = crate::compact::to_vec(&(true, Unknown)).unwrap();
+        let mut expected = vec![ARRAY | 2, TRUE, MAP | 2, NIL, ARRAY | ONE_BYTE, 30];
+        expected.extend((0..28u8).map(|i| INT | i));
+        expected.extend_from_slice(&[INT | ONE_BYTE, 28, INT | ONE_BYTE, 29]);
+        expected.extend_from_slice(&[ARRAY | 1, ARRAY | 2, INT, INT | 1, ARRAY]);
+        assert_eq!(enc, expected);
+    }
+
+    #[test]
+    fn large_bytes() {
+        let payload: Vec<u8> = (0..10_000_000u32).map(|i| i as u8).collect();
+        let mut serializer = VVSerializer::new(Vec::new());
+        serializer.serialize_bytes(&payload).unwrap();
+        let enc = serializer.out;
+
+        assert_eq!(enc.len(), 5 + payload.len());
+        assert_eq!(enc[0], BYTES | FOUR_BYTES);
+        assert_eq!(&enc[1..5], &(payload.len() as u32).to_be_bytes());
+        assert_eq!(&enc[5..], &payload[..]);
+    }
+
+    #[test]
+    fn large_bytes_are_written_in_chunks() {
+        // Records the size of every write.
+        struct Recording {
+            out: Vec<u8>,
+            writes: Vec<usize>,
+        }
+
+        impl io::Write for Recording {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.writes.push(buf.len());
+                self.out.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // A byte string, alone or in a sequence of unknown length.
+        struct Bytes<'a>(&'a [u8], bool);
+
+        impl<'a> Serialize for Bytes<'a> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use ser::SerializeSeq;
+
+                if self.1 {
+                    let mut seq = serializer.serialize_seq(None)?;
+                    seq.serialize_element(&Bytes(self.0, false))?;
+                    seq.end()
+                } else {
+                    serializer.serialize_bytes(self.0)
+                }
+            }
+        }
+
+        let payload: Vec<u8> = (0..10_000_000u32).map(|i| i as u8).collect();
+        let mut serializer = VVSerializer::with_writer(Recording { out: vec![], writes: vec![] });
+        (true, Bytes(&payload, false)).serialize(&mut serializer).unwrap();
+        assert!(serializer.out.capacity() < BYTES_CHUNK_SIZE);
+        let rec = serializer.finish().unwrap();
+
+        assert_eq!(rec.out.len(), 2 + 5 + payload.len());
+        assert_eq!(&rec.out[..3], &[ARRAY | 2, TRUE, BYTES | FOUR_BYTES]);
+        assert_eq!(&rec.out[3..7], &(payload.len() as u32).to_be_bytes());
+        assert!(rec.out[7..] == payload[..]);
+        assert!(rec.writes.iter().all(|&len| len <= BYTES_CHUNK_SIZE));
+
+        // Byte strings inside collections of unknown length are buffered until the length is known.
+        let mut out = vec![];
+        to_writer(&mut out, &Bytes(&payload, true)).unwrap();
+        assert!(out == to_vec(&Bytes(&payload, true)).unwrap());
+        assert_eq!(&out[..6], &[ARRAY | 1, BYTES | FOUR_BYTES, 0x00, 0x98, 0x96, 0x80]);
+    }
+
+    #[test]
+    fn string_count_widths() {
+        for (len, tag, count) in [
+            (27, BYTES | 27, &[][..]),
+            (28, BYTES | ONE_BYTE, &[28][..]),
+            (255, BYTES | ONE_BYTE, &[255][..]),
+            (256, BYTES | TWO_BYTES, &[1, 0][..]),
+            (65535, BYTES | TWO_BYTES, &[255, 255][..]),
+            (65536, BYTES | FOUR_BYTES, &[0, 1, 0, 0][..]),
+        ] {
+            let s: String = (0..len).map(|i| (b'a' + (i % 26) as u8) as char).collect();
+            let enc = crate::compact::to_vec(&s).unwrap();
+            assert_eq!(enc[0], tag, "{}", len);
+            assert_eq!(&enc[1..1 + count.len()], count, "{}", len);
+            assert_eq!(&enc[1 + count.len()..], s.as_bytes());
+            assert_eq!(crate::compact::from_slice::<String>(&enc).unwrap(), s);
+
+            let mut serializer = VVSerializer::new(Vec::new());
+            serializer.serialize_bytes(s.as_bytes()).unwrap();
+            assert_eq!(serializer.into_inner(), enc);
+            assert_eq!(crate::compact::from_slice::<Vec<u8>>(&enc).unwrap(), s.as_bytes());
+        }
+    }
+
+    #[test]
+    fn four_byte_counts() {
+        use crate::compact::tags::{count_width, type_of, CountWidth, ValueKind};
+
+        let counts = [u16::MAX as usize + 1, 70_000, 1 << 24, u32::MAX as usize - 1, u32::MAX as usize];
+        let kinds = [(BYTES, ValueKind::Bytes), (ARRAY, ValueKind::Array), (SET, ValueKind::Set), (MAP, ValueKind::Map)];
+        for n in counts {
+            assert_eq!(CountWidth::of_count(n as u64), CountWidth::U32);
+            for (tag, kind) in kinds {
+                let mut serializer = VVSerializer::new(Vec::new());
+                serializer.serialize_count(n, tag).unwrap();
+                let enc = serializer.into_inner();
+                assert_eq!(enc[0], tag | FOUR_BYTES, "{}", n);
+                assert_eq!((type_of(enc[0]), count_width(enc[0])), (kind, CountWidth::U32));
+                assert_eq!(&enc[1..], &(n as u32).to_be_bytes());
+
+                // The decoder reads the whole count, and then runs out of input for the contents.
+                let err = crate::Value::deserialize(&mut crate::compact::VVDeserializer::new(&enc)).unwrap_err();
+                assert_eq!(err.e, crate::compact::DecodeError::Eoi);
+                assert_eq!(err.position, 5);
+            }
+        }
+
+        let mut serializer = VVSerializer::new(Vec::new());
+        serializer.serialize_count(u32::MAX as usize + 1, ARRAY).unwrap();
+        assert_eq!(serializer.into_inner(), [&[ARRAY | EIGHT_BYTES][..], &(u32::MAX as u64 + 1).to_be_bytes()].concat());
+    }
+
+    #[test]
+    fn large_array() {
+        let items = vec![true; 70_000];
+        let enc = crate::compact::to_vec(&items).unwrap();
+        assert_eq!(enc[0], ARRAY | FOUR_BYTES);
+        assert_eq!(&enc[1..5], &70_000u32.to_be_bytes());
+        assert_eq!(enc.len(), 5 + items.len());
+        assert_eq!(crate::compact::from_slice::<Vec<bool>>(&enc).unwrap().len(), 70_000);
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Weird {
+        #[serde(rename = "weird \"name\"\n")]
+        Unit,
+        #[serde(rename = "new\\type\t")]
+        Newtype(Option<u8>),
+        #[serde(rename = "\0tuple")]
+        Tuple(u8, u8),
+        #[serde(rename = "{struct}: \u{7f}")]
+        Struct { x: u8, y: Vec<u8> },
+    }
+
+    fn encode_with_width<T: Serialize>(width: IntWidth, value: &T) -> Result<Vec<u8>, EncodeError> {
+        let mut serializer = VVSerializer::new(Vec::new()).int_width(width);
+        value.serialize(&mut serializer)?;
+        Ok(serializer.into_inner())
+    }
+
+    #[test]
+    fn int_widths() {
+        let widths = [(IntWidth::Fixed1, ONE_BYTE, 1), (IntWidth::Fixed2, TWO_BYTES, 2), (IntWidth::Fixed4, FOUR_BYTES, 4), (IntWidth::Fixed8, EIGHT_BYTES, 8)];
+        let values = [0, 1, 27, 28, -1, i8::MIN as i64, i8::MAX as i64, i16::MIN as i64, i16::MAX as i64, i32::MIN as i64, i32::MAX as i64, i64::MIN, i64::MAX];
+
+        for (width, bits, len) in widths.iter() {
+            let max = i64::MAX >> (64 - 8 * len);
+            for v in values.iter() {
+                let enc = encode_with_width(*width, v);
+                if *v > max || *v < -max - 1 {
+                    assert_eq!(enc, Err(EncodeError::IntTooWide { value: *v, width: *width }));
+                } else {
+                    let enc = enc.unwrap();
+                    assert_eq!(enc.len(), 1 + len);
+                    assert_eq!(enc[0], INT | bits);
+                    assert_eq!(crate::compact::from_slice::<i64>(&enc).unwrap(), *v);
+                }
+            }
+        }
+
+        // The boundaries just outside of each width.
+        assert!(encode_with_width(IntWidth::Fixed1, &128u8).is_err());
+        assert!(encode_with_width(IntWidth::Fixed1, &-129i16).is_err());
+        assert!(encode_with_width(IntWidth::Fixed2, &32_768u16).is_err());
+        assert!(encode_with_width(IntWidth::Fixed4, &(u32::MAX)).is_err());
+        assert_eq!(encode_with_width(IntWidth::Fixed8, &u64::MAX), Err(EncodeError::OutOfBoundsInt));
+
+        for v in values.iter() {
+            assert_eq!(encode_with_width(IntWidth::Minimal, v).unwrap(), crate::compact::to_vec(v).unwrap());
+        }
+
+        // Fixed widths only apply to ints, not to counts.
+        let enc = encode_with_width(IntWidth::Fixed2, &vec![1u8, 2]).unwrap();
+        assert_eq!(enc, vec![ARRAY | 2, INT | TWO_BYTES, 0, 1, INT | TWO_BYTES, 0, 2]);
+        assert_eq!(crate::compact::from_slice::<Vec<u8>>(&enc).unwrap(), vec![1, 2]);
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Record {
+        #[serde(with = "fixed_width::fixed8")]
+        id: u32,
+        #[serde(with = "fixed_width::fixed2")]
+        readings: Vec<Option<i16>>,
+        #[serde(with = "fixed_width::fixed1")]
+        small: i64,
+        count: u8,
+    }
+
+    #[test]
+    fn fixed_width_fields() {
+        let small = Record { id: 1, readings: vec![Some(0), None], small: 2, count: 3 };
+        let large = Record { id: u32::MAX, readings: vec![Some(i16::MIN), None], small: -128, count: 3 };
+        let enc_small = crate::compact::to_vec(&small).unwrap();
+        let enc_large = crate::compact::to_vec(&large).unwrap();
+        assert_eq!(enc_small.len(), enc_large.len());
+        assert_eq!(crate::compact::from_slice::<Record>(&enc_small).unwrap(), small);
+        assert_eq!(crate::compact::from_slice::<Record>(&enc_large).unwrap(), large);
+
+        // Fields override the width of the serializer, and the serializer's width applies again after them.
+        let enc = encode_with_width(IntWidth::Fixed4, &small).unwrap();
+        assert_eq!(crate::compact::from_slice::<Record>(&enc).unwrap(), small);
+        assert_eq!(enc.len(), enc_small.len() + 4);
+
+        let too_large = Record { small: 128, readings: vec![], ..small };
+        assert_eq!(crate::compact::to_vec(&too_large), Err(EncodeError::IntTooWide { value: 128, width: IntWidth::Fixed1 }));
+
+        // Other serializers ignore the width.
+        let enc_human = crate::human::to_vec(&small, 0).unwrap();
+        assert_eq!(crate::human::from_slice::<Record>(&enc_human).unwrap(), small);
+        assert_eq!(enc_human, br#"{"id":1,"readings":[{"Some":0},"None"],"small":2,"count":3}"#.to_vec());
+    }
+
+    #[test]
+    fn escaped_variant_names() {
+        let variants = vec![
+            Some(Weird::Unit),
+            Some(Weird::Newtype(Some(3))),
+            Some(Weird::Tuple(1, 2)),
+            Some(Weird::Struct { x: 4, y: vec![5, 6] }),
+            None,
+        ];
+        let enc = to_vec(&variants).unwrap();
+        assert_eq!(Vec::<Option<Weird>>::deserialize(&mut crate::compact::VVDeserializer::new(&enc)).unwrap(), variants);
+    }
+
+    #[test]
+    // A struct variant is a singleton map from the variant name to the map of its fields, like in the human-readable encoding.
+    fn struct_variants() {
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Shape {
+            Rect { w: u8, tall: bool },
+        }
+
+        let shape = Shape::Rect { w: 3, tall: true };
+        let enc = to_vec(&shape).unwrap();
+        assert_eq!(enc, [SINGLETON_MAP, BYTES | 4, b'R', b'e', b'c', b't', MAP | 2, BYTES | 1, b'w', INT | 3, BYTES | 4, b't', b'a', b'l', b'l', TRUE]);
+        assert_eq!(crate::compact::from_slice::<Shape>(&enc).unwrap(), shape);
+        assert_eq!(
+            crate::compact::from_slice::<crate::Value>(&enc).unwrap(),
+            crate::human::from_slice::<crate::Value>(&crate::human::to_vec(&shape, 0).unwrap()).unwrap(),
+        );
     }
 }
@@ -0,0 +1,217 @@
+//! Named tag bytes of the [compact encoding](https://github.com/AljoschaMeyer/valuable-value#compact-encoding), and helpers for taking tags apart.
+//!
+//! The three most significant bits of a tag determine the [kind](ValueKind) of the value it introduces. For ints, byte strings, arrays, sets and maps, the five least significant bits determine the [width](CountWidth) of the int or count: values up to 27 are stored in the tag itself, the values 28, 29, 30 and 31 indicate that the tag is followed by a big-endian number of one, two, four or eight bytes respectively.
+//!
+//! ```
+//! use valuable_value::compact::tags::{self, CountWidth, ValueKind};
+//!
+//! assert_eq!(tags::type_of(0b101_11101), ValueKind::Array);
+//! assert_eq!(tags::count_width(0b101_11101), CountWidth::U16);
+//! assert_eq!(tags::ARRAY | CountWidth::U16.bits(), 0b101_11101);
+//! ```
+
+/// Masks the bits of a tag that determine the [kind](ValueKind) of the value.
+pub const KIND_MASK: u8 = 0b111_00000;
+/// Masks the bits of a tag that determine the [width](CountWidth) of an int or count.
+pub const WIDTH_MASK: u8 = 0b000_11111;
+
+/// The tag of nil.
+pub const NIL: u8 = 0b000_00000;
+/// The tag that introduces an extension, see [`serialize_extension`](crate::compact::serialize_extension).
+pub const EXTENSION: u8 = 0b000_00001;
+/// The tag of `false`.
+pub const FALSE: u8 = 0b001_00000;
+/// The tag of `true`.
+pub const TRUE: u8 = 0b001_00001;
+/// The tag of a float, followed by its eight bytes in big-endian order.
+pub const FLOAT: u8 = 0b010_00000;
+/// The kind bits of an int.
+pub const INT: u8 = 0b011_00000;
+/// The kind bits of a byte string.
+pub const BYTES: u8 = 0b100_00000;
+/// The kind bits of an array.
+pub const ARRAY: u8 = 0b101_00000;
+/// The kind bits of a set.
+pub const SET: u8 = 0b110_00000;
+/// The kind bits of a map.
+pub const MAP: u8 = 0b111_00000;
+
+/// The largest int or count that is stored in the tag itself.
+pub const MAX_INLINE: u8 = 27;
+/// The width bits indicating that a one-byte int or count follows the tag.
+pub const ONE_BYTE: u8 = 0b000_11100;
+/// The width bits indicating that a two-byte int or count follows the tag.
+pub const TWO_BYTES: u8 = 0b000_11101;
+/// The width bits indicating that a four-byte int or count follows the tag.
+pub const FOUR_BYTES: u8 = 0b000_11110;
+/// The width bits indicating that an eight-byte int or count follows the tag.
+pub const EIGHT_BYTES: u8 = 0b000_11111;
+
+/// The tag of a set with a single entry, as used for wrapping enum variants.
+pub const SINGLETON_SET: u8 = SET | 1;
+/// The tag of a map with a single entry, as used for wrapping enum variants and `Some`.
+pub const SINGLETON_MAP: u8 = MAP | 1;
+
+/// The kind of value introduced by a tag.
+///
+/// Not every tag of a kind denotes a valid value, the nil, bool and float kinds only use the tags [`NIL`](NIL), [`FALSE`](FALSE), [`TRUE`](TRUE) and [`FLOAT`](FLOAT).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ValueKind {
+    Nil,
+    Bool,
+    Float,
+    Int,
+    Bytes,
+    Array,
+    Set,
+    Map,
+}
+
+impl ValueKind {
+    /// The kind bits of tags of this kind.
+    pub fn bits(self) -> u8 {
+        match self {
+            ValueKind::Nil => NIL,
+            ValueKind::Bool => FALSE,
+            ValueKind::Float => FLOAT,
+            ValueKind::Int => INT,
+            ValueKind::Bytes => BYTES,
+            ValueKind::Array => ARRAY,
+            ValueKind::Set => SET,
+            ValueKind::Map => MAP,
+        }
+    }
+}
+
+/// Determine the kind of value introduced by a tag.
+pub fn type_of(tag: u8) -> ValueKind {
+    match tag & KIND_MASK {
+        NIL => ValueKind::Nil,
+        FALSE => ValueKind::Bool,
+        FLOAT => ValueKind::Float,
+        INT => ValueKind::Int,
+        BYTES => ValueKind::Bytes,
+        ARRAY => ValueKind::Array,
+        SET => ValueKind::Set,
+        _ => ValueKind::Map,
+    }
+}
+
+/// Where the int or count of a tag is stored.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CountWidth {
+    /// The number is stored in the tag itself.
+    Inline(u8),
+    /// The tag is followed by a one-byte number.
+    U8,
+    /// The tag is followed by a two-byte number.
+    U16,
+    /// The tag is followed by a four-byte number.
+    U32,
+    /// The tag is followed by an eight-byte number.
+    U64,
+}
+
+impl CountWidth {
+    /// The width bits of tags with this width.
+    pub fn bits(self) -> u8 {
+        match self {
+            CountWidth::Inline(n) => n & WIDTH_MASK,
+            CountWidth::U8 => ONE_BYTE,
+            CountWidth::U16 => TWO_BYTES,
+            CountWidth::U32 => FOUR_BYTES,
+            CountWidth::U64 => EIGHT_BYTES,
+        }
+    }
+
+    /// How many bytes follow the tag.
+    pub fn payload_len(self) -> usize {
+        match self {
+            CountWidth::Inline(_) => 0,
+            CountWidth::U8 => 1,
+            CountWidth::U16 => 2,
+            CountWidth::U32 => 4,
+            CountWidth::U64 => 8,
+        }
+    }
+
+    /// The narrowest width that can hold the count `n`.
+    pub fn of_count(n: u64) -> CountWidth {
+        if n <= MAX_INLINE as u64 {
+            CountWidth::Inline(n as u8)
+        } else if n <= u8::MAX as u64 {
+            CountWidth::U8
+        } else if n <= u16::MAX as u64 {
+            CountWidth::U16
+        } else if n <= u32::MAX as u64 {
+            CountWidth::U32
+        } else {
+            CountWidth::U64
+        }
+    }
+}
+
+/// Determine where the int or count of a tag is stored.
+///
+/// Only meaningful for tags of the int, byte string, array, set and map [kinds](ValueKind).
+pub fn count_width(tag: u8) -> CountWidth {
+    match tag & WIDTH_MASK {
+        ONE_BYTE => CountWidth::U8,
+        TWO_BYTES => CountWidth::U16,
+        FOUR_BYTES => CountWidth::U32,
+        EIGHT_BYTES => CountWidth::U64,
+        n => CountWidth::Inline(n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds() {
+        let kinds = [
+            ValueKind::Nil,
+            ValueKind::Bool,
+            ValueKind::Float,
+            ValueKind::Int,
+            ValueKind::Bytes,
+            ValueKind::Array,
+            ValueKind::Set,
+            ValueKind::Map,
+        ];
+        for kind in kinds.iter() {
+            assert_eq!(kind.bits() & WIDTH_MASK, 0);
+            for width in 0..=WIDTH_MASK {
+                assert_eq!(type_of(kind.bits() | width), *kind);
+            }
+        }
+
+        assert_eq!(type_of(NIL), ValueKind::Nil);
+        assert_eq!(type_of(EXTENSION), ValueKind::Nil);
+        assert_eq!(type_of(FALSE), ValueKind::Bool);
+        assert_eq!(type_of(TRUE), ValueKind::Bool);
+        assert_eq!(type_of(FLOAT), ValueKind::Float);
+        assert_eq!(type_of(SINGLETON_SET), ValueKind::Set);
+        assert_eq!(type_of(SINGLETON_MAP), ValueKind::Map);
+    }
+
+    #[test]
+    fn widths() {
+        for n in 0..=MAX_INLINE {
+            assert_eq!(count_width(INT | n), CountWidth::Inline(n));
+            assert_eq!(CountWidth::Inline(n).bits(), n);
+        }
+        for width in [CountWidth::U8, CountWidth::U16, CountWidth::U32, CountWidth::U64].iter() {
+            assert_eq!(count_width(ARRAY | width.bits()), *width);
+        }
+        assert_eq!(count_width(ONE_BYTE), CountWidth::U8);
+        assert_eq!(count_width(TWO_BYTES), CountWidth::U16);
+        assert_eq!(count_width(FOUR_BYTES), CountWidth::U32);
+        assert_eq!(count_width(EIGHT_BYTES), CountWidth::U64);
+
+        for (n, len) in [(0, 0), (27, 0), (28, 1), (255, 1), (256, 2), (65535, 2), (65536, 4), (u32::MAX as u64, 4), (u32::MAX as u64 + 1, 8)].iter() {
+            assert_eq!(CountWidth::of_count(*n).payload_len(), *len);
+        }
+    }
+}
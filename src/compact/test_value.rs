@@ -5,6 +5,7 @@ use std::collections::BTreeMap;
 
 use arbitrary::Arbitrary;
 
+use crate::compact::tags::*;
 use crate::value::Value;
 
 /// A valuable value of arbitrary shape, together with information on how to encode it. Intended for generating varied but valid compact encodings for testing purposes.
@@ -39,13 +40,13 @@ impl TestValue {
     pub fn encode(&self, out: &mut Vec<u8>) {
         match self {
             TestValue::Nil => {
-                out.push(0b000_00000);
+                out.push(NIL);
             }
             TestValue::Bool(b) => {
-                out.push(if *b { 0b001_00001 } else { 0b001_00000 });
+                out.push(if *b { TRUE } else { FALSE });
             }
             TestValue::Float(n) => {
-                out.push(0b010_00000);
+                out.push(FLOAT);
                 out.extend_from_slice(&n.to_bits().to_be_bytes());
             }
             TestValue::Int(v) => v.encode(out),
@@ -71,7 +72,7 @@ impl Int {
     pub fn encode(&self, out: &mut Vec<u8>) {
         let mut bytes = self.bytes;
 
-        if 0 <= self.n && self.n <= 27 {
+        if 0 <= self.n && self.n <= MAX_INLINE as i64 {
             bytes = max(0, bytes);
         } else if (i8::MIN as i64) <= self.n && self.n <= (i8::MAX as i64) {
             bytes = max(1, bytes);
@@ -92,18 +93,18 @@ impl Int {
         }
 
         if bytes == 0 {
-            out.push(0b011_00000 ^ (self.n as u8));
+            out.push(INT | (self.n as u8));
         } else if bytes == 1 {
-            out.push(0b011_11100);
+            out.push(INT | ONE_BYTE);
             out.extend_from_slice(&(self.n as i8).to_be_bytes());
         } else if bytes == 2 {
-            out.push(0b011_11101);
+            out.push(INT | TWO_BYTES);
             out.extend_from_slice(&(self.n as i16).to_be_bytes());
         } else if bytes == 4 {
-            out.push(0b011_11110);
+            out.push(INT | FOUR_BYTES);
             out.extend_from_slice(&(self.n as i32).to_be_bytes());
         } else if bytes == 8 {
-            out.push(0b011_11111);
+            out.push(INT | EIGHT_BYTES);
             out.extend_from_slice(&(self.n as i64).to_be_bytes());
         } else {
             unreachable!();
@@ -127,7 +128,7 @@ impl ByteString {
     }
 
     pub fn encode(&self, out: &mut Vec<u8>) {
-        encode_count(self.elements.len(), self.count_width, 0b100_00000, out);
+        encode_count(self.elements.len(), self.count_width, BYTES, out);
         for v in self.elements.iter() {
             out.push(*v);
         }
@@ -150,7 +151,7 @@ impl Array {
     }
 
     pub fn encode(&self, out: &mut Vec<u8>) {
-        encode_count(self.elements.len(), self.count_width, 0b101_00000, out);
+        encode_count(self.elements.len(), self.count_width, ARRAY, out);
         for v in self.elements.iter() {
             v.encode(out);
         }
@@ -173,7 +174,7 @@ impl Set {
     }
 
     pub fn encode(&self, out: &mut Vec<u8>) {
-        encode_count(self.elements.len(), self.count_width, 0b110_00000, out);
+        encode_count(self.elements.len(), self.count_width, SET, out);
         for v in self.elements.iter() {
             v.encode(out);
         }
@@ -196,7 +197,7 @@ impl Map {
     }
 
     pub fn encode(&self, out: &mut Vec<u8>) {
-        encode_count(self.elements.len(), self.count_width, 0b111_00000, out);
+        encode_count(self.elements.len(), self.count_width, MAP, out);
         for (k, v) in self.elements.iter() {
             k.encode(out);
             v.encode(out);
@@ -205,7 +206,7 @@ impl Map {
 }
 
 fn encode_count(n: usize, mut width: u8, mask: u8, out: &mut Vec<u8>) {
-    if n <= 27 {
+    if n <= MAX_INLINE as usize {
         width = max(0, width);
     } else if n <= u8::MAX as usize {
         width = max(1, width);
@@ -228,16 +229,16 @@ fn encode_count(n: usize, mut width: u8, mask: u8, out: &mut Vec<u8>) {
     if width == 0 {
         out.push(mask | (n as u8));
     } else if width == 1 {
-        out.push(mask | 0b000_11100);
+        out.push(mask | ONE_BYTE);
         out.extend_from_slice(&(n as u8).to_be_bytes());
     } else if width == 2 {
-        out.push(mask | 0b000_11101);
+        out.push(mask | TWO_BYTES);
         out.extend_from_slice(&(n as u16).to_be_bytes());
     } else if width == 4 {
-        out.push(mask | 0b000_11110);
+        out.push(mask | FOUR_BYTES);
         out.extend_from_slice(&(n as u32).to_be_bytes());
     } else if width == 8 {
-        out.push(mask | 0b000_11111);
+        out.push(mask | EIGHT_BYTES);
         out.extend_from_slice(&(n as u64).to_be_bytes());
     } else {
         unreachable!();
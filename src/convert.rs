@@ -0,0 +1,674 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserialize, Deserializer, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use thiserror::Error;
+
+use crate::value::{array_as_string, is_option, Value};
+
+/// Everything that can go wrong when converting between a rust value and a [`Value`](Value), see [`to_value`](to_value) and [`from_value`](from_value).
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum ConvertError {
+    #[error("{0}")]
+    Message(String),
+    #[error("valuable value ints cannot exceed 2^63 - 1")]
+    OutOfBoundsInt,
+}
+
+impl ser::Error for ConvertError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ConvertError::Message(msg.to_string())
+    }
+}
+
+impl de::Error for ConvertError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ConvertError::Message(msg.to_string())
+    }
+}
+
+/// Convert a rust value into a [`Value`](Value), without going through an encoding.
+///
+/// The result is the value that encoding and then decoding as a [`Value`](Value) would produce: strings and byte strings
+/// become arrays of ints, options become the string `"None"` or a map from `"Some"` to the wrapped value, and so on.
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use valuable_value::{from_value, to_value, Value};
+///
+/// #[derive(Serialize, Deserialize, Debug, PartialEq)]
+/// struct Point {
+///     name: String,
+///     x: i32,
+/// }
+///
+/// let p = Point { name: "origin".to_string(), x: 0 };
+/// let v = to_value(&p).unwrap();
+/// assert_eq!(v.get_str(&[Value::from("name")]).unwrap(), "origin");
+/// assert_eq!(from_value::<Point>(&v).unwrap(), p);
+/// ```
+pub fn to_value<T>(value: &T) -> Result<Value, ConvertError>
+where
+    T: ?Sized + Serialize,
+{
+    value.serialize(ValueSerializer)
+}
+
+/// Convert a [`Value`](Value) into a rust value, without going through an encoding, see [`ValueRefDeserializer`](ValueRefDeserializer).
+pub fn from_value<'de, T>(value: &'de Value) -> Result<T, ConvertError>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize(ValueRefDeserializer::<ConvertError>::new(value))
+}
+
+/// A serializer whose output is a [`Value`](Value), see [`to_value`](to_value).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValueSerializer;
+
+fn string(s: &str) -> Value {
+    Value::from(s)
+}
+
+// A map from the variant name to the contents of the variant, the shape of all enum variants except for unit variants.
+fn variant(name: &'static str, contents: Value) -> Value {
+    let mut m = BTreeMap::new();
+    m.insert(string(name), contents);
+    Value::Map(m)
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeMap;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, ConvertError> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, ConvertError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, ConvertError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, ConvertError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, ConvertError> {
+        Ok(Value::Int(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, ConvertError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, ConvertError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, ConvertError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, ConvertError> {
+        i64::try_from(v).map(Value::Int).map_err(|_| ConvertError::OutOfBoundsInt)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, ConvertError> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, ConvertError> {
+        Ok(Value::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, ConvertError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, ConvertError> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, ConvertError> {
+        Ok(Value::Array(v.iter().map(|b| Value::Int(i64::from(*b))).collect()))
+    }
+
+    fn serialize_none(self) -> Result<Value, ConvertError> {
+        Ok(string("None"))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Value, ConvertError>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(variant("Some", value.serialize(self)?))
+    }
+
+    fn serialize_unit(self) -> Result<Value, ConvertError> {
+        Ok(Value::Nil)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, ConvertError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str) -> Result<Value, ConvertError> {
+        Ok(string(variant))
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Value, ConvertError>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(self, _name: &'static str, _variant_index: u32, name: &'static str, value: &T) -> Result<Value, ConvertError>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(variant(name, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, ConvertError> {
+        Ok(SerializeArray { items: Vec::with_capacity(len.unwrap_or(0)), variant: None })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, ConvertError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeArray, ConvertError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str, len: usize) -> Result<SerializeArray, ConvertError> {
+        Ok(SerializeArray { items: Vec::with_capacity(len), variant: Some(variant) })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap, ConvertError> {
+        Ok(SerializeMap { entries: BTreeMap::new(), key: None, variant: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<SerializeMap, ConvertError> {
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str, _len: usize) -> Result<SerializeMap, ConvertError> {
+        Ok(SerializeMap { entries: BTreeMap::new(), key: None, variant: Some(variant) })
+    }
+}
+
+/// Collects the items of an array for [`ValueSerializer`](ValueSerializer).
+pub struct SerializeArray {
+    items: Vec<Value>,
+    // The name of the variant if this is a tuple variant.
+    variant: Option<&'static str>,
+}
+
+impl SerializeArray {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ConvertError> {
+        let items = Value::Array(self.items);
+        Ok(match self.variant {
+            Some(name) => variant(name, items),
+            None => items,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConvertError> {
+        self.finish()
+    }
+}
+
+/// Collects the entries of a map for [`ValueSerializer`](ValueSerializer). Later entries replace earlier ones with an equal key.
+pub struct SerializeMap {
+    entries: BTreeMap<Value, Value>,
+    // The key whose value comes next.
+    key: Option<Value>,
+    // The name of the variant if this is a struct variant.
+    variant: Option<&'static str>,
+}
+
+impl SerializeMap {
+    fn finish(self) -> Result<Value, ConvertError> {
+        let entries = Value::Map(self.entries);
+        Ok(match self.variant {
+            Some(name) => variant(name, entries),
+            None => entries,
+        })
+    }
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), ConvertError> {
+        self.key = Some(key.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ConvertError> {
+        match self.key.take() {
+            Some(key) => {
+                self.entries.insert(key, value.serialize(ValueSerializer)?);
+                Ok(())
+            }
+            None => Err(ConvertError::Message("serialize_value called before serialize_key".to_string())),
+        }
+    }
+
+    fn end(self) -> Result<Value, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), ConvertError> {
+        self.entries.insert(string(key), value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeMap {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), ConvertError> {
+        self.entries.insert(string(key), value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, ConvertError> {
+        self.finish()
+    }
+}
+
+impl<'a, 'de, E: de::Error> IntoDeserializer<'de, E> for &'a Value {
+    type Deserializer = ValueRefDeserializer<'a, E>;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        ValueRefDeserializer::new(self)
+    }
+}
+
+impl<'a, 'de, E: de::Error> IntoDeserializer<'de, E> for ValueRefDeserializer<'a, E> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+/// A deserializer that decodes a rust value from a borrowed [`Value`](Value), see [`from_value`](from_value).
+///
+/// This is also what the [`ValueDeserializer`](crate::ValueDeserializer) of an owned value uses. Like the compact decoder,
+/// it hands arrays of ints between 0 and 255 to visitors that ask for strings or bytes as owned strings or byte buffers.
+/// Since a [`Value`](Value) does not store strings contiguously, nothing can be borrowed from it, so types such as
+/// `&str` cannot be decoded, and the deserializer works for any lifetime `'de`.
+pub struct ValueRefDeserializer<'a, E> {
+    value: &'a Value,
+    // Whether the value is a map key, which are never options, and are reported as strings whenever they form valid UTF-8.
+    key: bool,
+    _error: PhantomData<E>,
+}
+
+impl<'a, E> ValueRefDeserializer<'a, E> {
+    /// Create a deserializer that decodes from the given value.
+    pub fn new(value: &'a Value) -> Self {
+        ValueRefDeserializer { value, key: false, _error: PhantomData }
+    }
+
+    fn key(value: &'a Value) -> Self {
+        ValueRefDeserializer { value, key: true, _error: PhantomData }
+    }
+}
+
+// Interpret an array as a byte string, i.e., as an array of ints between 0 and 255.
+fn array_as_bytes(vs: &[Value]) -> Option<Vec<u8>> {
+    vs.iter().map(|b| match b {
+        Value::Int(n) => u8::try_from(*n).ok(),
+        _ => None,
+    }).collect()
+}
+
+impl<'a, 'de, E: de::Error> Deserializer<'de> for ValueRefDeserializer<'a, E> {
+    type Error = E;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Nil => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(*b),
+            Value::Int(n) => visitor.visit_i64(*n),
+            Value::Float(n) => visitor.visit_f64(*n),
+            Value::Array(vs) => match array_as_string(vs) {
+                Some(s) if self.key => visitor.visit_string(s),
+                _ => {
+                    // The visitor may stop early (e.g. for tuples), so check that nothing was left over.
+                    let mut seq = de::value::SeqDeserializer::new(vs.iter());
+                    let v = visitor.visit_seq(&mut seq)?;
+                    seq.end()?;
+                    Ok(v)
+                }
+            },
+            Value::Map(m) => {
+                let mut map = de::value::MapDeserializer::new(m.iter().map(|(k, v)| (ValueRefDeserializer::<E>::key(k), v)));
+                let v = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(v)
+            }
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Array(vs) => match array_as_string(vs) {
+                Some(s) => visitor.visit_string(s),
+                None => self.deserialize_any(visitor),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Array(vs) => match array_as_bytes(vs) {
+                Some(b) => visitor.visit_byte_buf(b),
+                None => self.deserialize_any(visitor),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        if !is_option(self.value) {
+            return self.deserialize_any(visitor);
+        }
+
+        match self.value {
+            Value::Map(m) => match m.values().next() {
+                Some(inner) => visitor.visit_some(ValueRefDeserializer::<E>::new(inner)),
+                None => unreachable!("options are never empty maps"),
+            },
+            _ => visitor.visit_none(),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Array(vs) => match array_as_string(vs) {
+                Some(s) => visitor.visit_enum(de::value::StringDeserializer::new(s)),
+                None => Err(E::invalid_type(de::Unexpected::Seq, &visitor)),
+            },
+            Value::Map(m) if m.len() == 1 => visitor.visit_enum(de::value::MapAccessDeserializer::new(de::value::MapDeserializer::new(m.iter()))),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64
+        unit unit_struct seq tuple tuple_struct map struct
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Rect(u32, u32),
+        Polygon { corners: Vec<(i8, i8)>, label: Option<String> },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+    struct Unit;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+    struct Meters(u16);
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+    struct Drawing {
+        name: String,
+        shapes: Vec<Shape>,
+        tags: BTreeMap<String, Option<Meters>>,
+        by_id: BTreeMap<u8, bool>,
+        #[serde(with = "bytes")]
+        thumbnail: Vec<u8>,
+        unit: Unit,
+        nothing: (),
+        nested: Option<Option<i64>>,
+    }
+
+    mod bytes {
+        pub fn serialize<S: serde::Serializer>(b: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(b)
+        }
+
+        pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+            struct BytesVisitor;
+
+            impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+                type Value = Vec<u8>;
+
+                fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    formatter.write_str("a byte string")
+                }
+
+                fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+                    Ok(v)
+                }
+            }
+
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    fn drawing() -> Drawing {
+        Drawing {
+            name: "sketch ✏".to_string(),
+            shapes: vec![
+                Shape::Empty,
+                Shape::Circle(0.5),
+                Shape::Rect(2, 3),
+                Shape::Polygon { corners: vec![(0, 0), (-1, 5)], label: Some("tri".to_string()) },
+                Shape::Polygon { corners: vec![], label: None },
+            ],
+            tags: vec![("a".to_string(), Some(Meters(7))), ("b".to_string(), None)].into_iter().collect(),
+            by_id: vec![(1, true), (200, false)].into_iter().collect(),
+            thumbnail: vec![0, 255, 16],
+            unit: Unit,
+            nothing: (),
+            nested: Some(None),
+        }
+    }
+
+    #[test]
+    fn agrees_with_encodings() {
+        let d = drawing();
+        let v = to_value(&d).unwrap();
+
+        assert_eq!(v, crate::compact::from_slice::<Value>(&crate::compact::to_vec(&d).unwrap()).unwrap());
+        assert_eq!(v, crate::human::from_slice::<Value>(&crate::human::to_vec(&d, 0).unwrap()).unwrap());
+        assert_eq!(from_value::<Drawing>(&v).unwrap(), d);
+        assert_eq!(crate::compact::from_slice::<Drawing>(&crate::compact::to_vec(&v).unwrap()).unwrap(), d);
+
+        // Converting a `Value` yields the same value.
+        assert_eq!(to_value(&v).unwrap(), v);
+        assert_eq!(from_value::<Value>(&v).unwrap(), v);
+
+        // Chars are strings, like in the human-readable encoding. The compact encoding writes them as ints instead.
+        let v = to_value(&'é').unwrap();
+        assert_eq!(v, crate::human::from_slice::<Value>(&crate::human::to_vec(&'é', 0).unwrap()).unwrap());
+        assert_eq!(crate::compact::from_slice::<Value>(&crate::compact::to_vec(&'é').unwrap()).unwrap(), Value::Int(0xe9));
+        assert_eq!(from_value::<char>(&v).unwrap(), 'é');
+    }
+
+    #[test]
+    fn shapes() {
+        assert_eq!(to_value(&"hi").unwrap(), Value::Array(vec![Value::Int(104), Value::Int(105)]));
+        assert_eq!(to_value(&Option::<u8>::None).unwrap(), Value::from("None"));
+        assert_eq!(to_value(&Some(1u8)).unwrap(), variant("Some", Value::Int(1)));
+        assert_eq!(to_value(&Shape::Empty).unwrap(), Value::from("Empty"));
+        assert_eq!(to_value(&Shape::Rect(1, 2)).unwrap(), variant("Rect", Value::Array(vec![Value::Int(1), Value::Int(2)])));
+        assert_eq!(to_value(&Meters(3)).unwrap(), Value::Int(3));
+        assert_eq!(to_value(&Unit).unwrap(), Value::Nil);
+
+        // Strings are read from arrays of ints, in any context that expects them.
+        let v = Value::Array(vec![Value::from("x"), Value::Array(vec![Value::Int(0x79)])]);
+        assert_eq!(from_value::<Vec<String>>(&v).unwrap(), vec!["x".to_string(), "y".to_string()]);
+        assert_eq!(from_value::<(char, Vec<u8>)>(&v).unwrap(), ('x', vec![0x79]));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(to_value(&u64::MAX).unwrap_err(), ConvertError::OutOfBoundsInt);
+        assert_eq!(to_value(&u64::MAX.to_string()).map(|_| ()), Ok(()));
+        assert!(matches!(to_value(&1u128).unwrap_err(), ConvertError::Message(_)));
+
+        assert_eq!(from_value::<u8>(&Value::Int(256)).unwrap_err().to_string(), "invalid value: integer `256`, expected u8");
+        assert_eq!(from_value::<String>(&Value::Array(vec![Value::Int(0xff)])).unwrap_err().to_string(), "invalid type: sequence, expected a string");
+        assert_eq!(from_value::<bool>(&Value::Nil).unwrap_err().to_string(), "invalid type: unit value, expected a boolean");
+        assert!(from_value::<Drawing>(&Value::Map(BTreeMap::new())).unwrap_err().to_string().starts_with("missing field"));
+        let three = Value::Array(vec![Value::Int(1), Value::Int(2), Value::Int(3)]);
+        assert_eq!(from_value::<(u8, u8)>(&three).unwrap_err().to_string(), "invalid length 3, expected 2 elements in sequence");
+        assert_eq!(from_value::<[u8; 2]>(&three).unwrap_err().to_string(), "invalid length 3, expected 2 elements in sequence");
+        assert_eq!(from_value::<(u8, u8, u8)>(&three).unwrap(), (1, 2, 3));
+        assert_eq!(from_value::<Shape>(&Value::from("Hexagon")).unwrap_err().to_string(), "unknown variant `Hexagon`, expected one of `Empty`, `Circle`, `Rect`, `Polygon`");
+    }
+}
@@ -0,0 +1,300 @@
+//! Golden-output tests for the human-readable encoding.
+//!
+//! Every fixture is serialized with indentation 0 and 2, and the results are compared byte-for-byte against the files `golden/<name>.<indentation>.vv` in the crate root. These files are the contract for output stability: any change to them must be intentional, and any new formatting option must come with fixtures that exercise it. Fixtures for an option are named after it and serialize some of the default fixtures with the option set.
+//!
+//! After an intentional change to the output, regenerate the files by running the tests with the environment variable `VV_UPDATE_GOLDEN` set (e.g., `VV_UPDATE_GOLDEN=1 cargo test fixtures`), and review the resulting diff.
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Serialize, Serializer};
+
+use crate::Value;
+use crate::human::{FloatStyle, LineEnding, Profile, VVSerializer};
+
+const UPDATE_VAR: &str = "VV_UPDATE_GOLDEN";
+
+fn golden_path(name: &str, indentation: usize) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("golden");
+    path.push(format!("{}.{}.vv", name, indentation));
+    path
+}
+
+fn check_golden<T: Serialize>(name: &str, value: &T) {
+    check_golden_with(name, value, |serializer| serializer);
+}
+
+// Like `check_golden`, but with the serializer configured by `configure`.
+fn check_golden_with<T: Serialize>(name: &str, value: &T, configure: fn(VVSerializer) -> VVSerializer) {
+    for indentation in [0, 2] {
+        let mut serializer = configure(VVSerializer::new(Vec::new(), indentation));
+        value.serialize(&mut serializer).unwrap();
+        let actual = serializer.into_inner();
+        let path = golden_path(name, indentation);
+
+        if std::env::var_os(UPDATE_VAR).is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &actual).unwrap();
+            continue;
+        }
+
+        let expected = match fs::read(&path) {
+            Ok(expected) => expected,
+            Err(e) => panic!("cannot read golden file {}: {}\nset {} to create it", path.display(), e, UPDATE_VAR),
+        };
+        if actual != expected {
+            panic!(
+                "output for fixture `{}` at indentation {} differs from {}\nexpected:\n{}\nactual:\n{}\nset {} to regenerate the golden files if this change is intentional",
+                name, indentation, path.display(), String::from_utf8_lossy(&expected), String::from_utf8_lossy(&actual), UPDATE_VAR,
+            );
+        }
+    }
+}
+
+struct Bytes(&'static [u8]);
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+#[derive(Serialize)]
+struct Config {
+    name: String,
+    version: (u8, u8, u8),
+    server: Server,
+    limits: BTreeMap<String, u32>,
+    empty: Vec<u8>,
+    unit: (),
+}
+
+#[derive(Serialize)]
+struct Server {
+    host: String,
+    port: u16,
+    tags: Vec<String>,
+    fallback: Option<Box<Server>>,
+}
+
+#[derive(Serialize)]
+struct Wrapper(i64);
+
+#[derive(Serialize)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Serialize)]
+struct Segment {
+    from: Point,
+    to: Point,
+    label: Option<String>,
+}
+
+#[derive(Serialize)]
+enum Shape {
+    Empty,
+    Circle(f64),
+    Line(i32, i32),
+    Rect { w: u32, h: u32 },
+    Nested(Box<Shape>),
+}
+
+fn config() -> Config {
+    let mut limits = BTreeMap::new();
+    limits.insert("connections".to_string(), 128);
+    limits.insert("timeout".to_string(), 30);
+
+    Config {
+        name: "fixture".to_string(),
+        version: (1, 0, 2),
+        server: Server {
+            host: "localhost".to_string(),
+            port: 8080,
+            tags: vec!["a".to_string(), "b".to_string()],
+            fallback: Some(Box::new(Server {
+                host: "backup".to_string(),
+                port: 8081,
+                tags: vec![],
+                fallback: None,
+            })),
+        },
+        limits,
+        empty: vec![],
+        unit: (),
+    }
+}
+
+fn segments() -> Vec<Segment> {
+    vec![
+        Segment { from: Point { x: 0, y: 0 }, to: Point { x: 3, y: -4 }, label: None },
+        Segment { from: Point { x: -1, y: 2 }, to: Point { x: 5, y: 8 }, label: Some("diagonal".to_string()) },
+    ]
+}
+
+fn string_corpus() -> Vec<&'static str> {
+    vec!["", "plain", "quote\"backslash\\", "tab\tnewline\nreturn\r", "nul\0control\u{1}\u{1f}", "unicode é ∑ 🦀"]
+}
+
+#[test]
+fn nested_struct() {
+    check_golden("nested_struct", &config());
+}
+
+#[test]
+fn inline_structs_up_to() {
+    check_golden("segments", &segments());
+    check_golden_with("inline_structs_up_to_2", &(segments(), config()), |serializer| serializer.inline_structs_up_to(2));
+    check_golden_with("inline_structs_up_to_3", &(segments(), config()), |serializer| serializer.inline_structs_up_to(3));
+}
+
+#[test]
+fn max_width() {
+    check_golden_with("max_width_31", &(segments(), config()), |serializer| serializer.max_width(31));
+    check_golden_with("max_width_60", &(segments(), config()), |serializer| serializer.max_width(60));
+}
+
+#[test]
+fn line_ending() {
+    check_golden_with("line_ending_crlf", &(config(), segments(), string_corpus()), |serializer| serializer.line_ending(LineEnding::CrLf));
+    check_golden_with("line_ending_lf", &string_corpus(), |serializer| serializer.line_ending(LineEnding::Lf));
+}
+
+#[test]
+fn escape_line_feeds() {
+    check_golden_with("escape_line_feeds", &string_corpus(), |serializer| serializer.escape_line_feeds(true));
+    check_golden_with("escape_line_feeds_crlf", &string_corpus(), |serializer| serializer.line_ending(LineEnding::CrLf).escape_line_feeds(true));
+}
+
+#[test]
+fn options() {
+    check_golden("options", &(None::<u8>, Some(3u8), Some(Some(vec![1u8, 2])), Some(None::<u8>), Some(Wrapper(-7))));
+}
+
+#[test]
+fn enums() {
+    check_golden("enums", &vec![
+        Shape::Empty,
+        Shape::Circle(0.5),
+        Shape::Line(-1, 1),
+        Shape::Rect { w: 3, h: 4 },
+        Shape::Nested(Box::new(Shape::Line(2, 3))),
+    ]);
+}
+
+#[test]
+fn byte_strings() {
+    check_golden("byte_strings", &(Bytes(&[]), Bytes(&[42]), Bytes(&[0, 127, 255])));
+}
+
+#[test]
+fn floats() {
+    check_golden("floats", &vec![
+        0.0,
+        -0.0,
+        1.5,
+        -2.0,
+        0.1,
+        1e21,
+        f64::MIN_POSITIVE,
+        f64::from_bits(1),
+        f64::MAX,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NAN,
+    ]);
+}
+
+// Floats whose shortest representations are easy to get wrong: rounding boundaries, the switch to exponential notation,
+// the ends of the exactly representable ints, subnormals and the extreme finite values.
+fn tricky_floats() -> Vec<f64> {
+    let two_53 = 9007199254740992.0;
+    vec![
+        0.0,
+        -0.0,
+        1.0,
+        -1.5,
+        0.1,
+        0.3,
+        0.1 + 0.2,
+        100.0,
+        123.456,
+        1e-5,
+        1.5e-6,
+        1e15,
+        1e16,
+        1e23,
+        1e300,
+        -1e300,
+        123456789012345680.0,
+        two_53 - 1.0,
+        two_53,
+        two_53 + 2.0,
+        5e-324,
+        1e-323,
+        2.225073858507201e-308,
+        f64::MIN_POSITIVE,
+        f64::MAX,
+        f64::MIN,
+        f64::EPSILON,
+        1.0 / 3.0,
+        2.0 / 3.0,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NAN,
+    ]
+}
+
+#[test]
+fn float_style() {
+    check_golden_with("float_style_deterministic", &tricky_floats(), |serializer| serializer.float_style(FloatStyle::Deterministic));
+}
+
+#[test]
+fn preserve_nan_payloads() {
+    // Only the canonic NaN can be encoded with this option set.
+    let floats = (vec![1.5, -0.0, f64::INFINITY, f64::NEG_INFINITY, f64::from_bits(u64::MAX)], Some(f64::from_bits(u64::MAX)));
+    check_golden_with("preserve_nan_payloads", &floats, |serializer| serializer.preserve_nan_payloads(true));
+}
+
+#[test]
+fn profile_json_compat() {
+    let json = (
+        segments(),
+        Bytes(&[0, 127, 255]),
+        vec!["plain", "quote\"backslash\\", "tab\tnewline\n", "del\u{7f}", "unicode é ∑ 🦀"],
+        vec![Some(Wrapper(-7)), None],
+        vec![1.5, -0.0, 1e300],
+        // Map keys of a `Value` are arrays of ints, written as strings since they form valid UTF-8.
+        crate::vv!({"a" => 1, "nested" => {"é" => [true, 2.5]}}),
+    );
+    check_golden_with("profile_json_compat", &json, |serializer| serializer.profile(Profile::JsonCompat { non_finite_placeholder: None }));
+
+    let non_finite = vec![1.5, f64::INFINITY, f64::NEG_INFINITY, f64::NAN];
+    check_golden_with("profile_json_compat_placeholder", &non_finite, |serializer| {
+        serializer.profile(Profile::JsonCompat { non_finite_placeholder: Some("non-finite".to_string()) })
+    });
+}
+
+#[test]
+fn strings() {
+    check_golden("strings", &string_corpus());
+}
+
+#[test]
+fn values() {
+    let pair = Value::Array(vec![Value::Int(1), Value::Int(2)]);
+    let mut inner = BTreeMap::new();
+    inner.insert(pair.clone(), Value::Nil);
+
+    let mut m = BTreeMap::new();
+    m.insert(Value::Nil, Value::Bool(false));
+    m.insert(Value::Int(i64::MIN), Value::Int(i64::MAX));
+    m.insert(Value::Float(f64::NAN), Value::Float(-0.0));
+    m.insert(pair.clone(), Value::Map(inner.clone()));
+    m.insert(Value::Map(inner), pair);
+    check_golden("values", &Value::Map(m));
+}
@@ -0,0 +1,94 @@
+//! Entry points for fuzzing the decoders with arbitrary bytes.
+//!
+//! Each function accepts any input and panics only if it finds a violated invariant, so a fuzz target merely has to forward its data. The seed corpus in `fuzz/seeds` is run through all of them by the tests of this module, so inputs that once exposed a bug can be added there as regression tests.
+use serde::Deserialize;
+
+use crate::{compact, human, testing, Value};
+
+/// Decode `data` as a [`Value`](Value) in the compact encoding. If that succeeds, check that encoding the value and decoding it again yields an equal value.
+pub fn decode_compact(data: &[u8]) {
+    if let Ok(v) = compact::from_slice::<Value>(data) {
+        let enc = compact::to_vec(&v).unwrap();
+        match compact::from_slice::<Value>(&enc) {
+            Ok(decoded) => assert_eq!(decoded, v, "compact round trip is lossy\ninput: {:?}\nencoding: {:?}", data, enc),
+            Err(e) => panic!("failed to decode compact encoding {:?} of input {:?}\nerror: {:?}", enc, data, e),
+        }
+    }
+}
+
+/// If `data` is valid UTF-8, decode it as a [`Value`](Value) in the human-readable encoding. If that succeeds, check that encoding the value (both with and without pretty-printing) and decoding it again yields an equal value.
+pub fn decode_human(data: &[u8]) {
+    if std::str::from_utf8(data).is_err() {
+        return;
+    }
+
+    if let Ok(v) = Value::deserialize(&mut human::VVDeserializer::new(data)) {
+        for indentation in [0, 2].iter() {
+            let enc = human::to_vec(&v, *indentation).unwrap();
+            match Value::deserialize(&mut human::VVDeserializer::new(&enc)) {
+                Ok(decoded) => assert_eq!(decoded, v, "human-readable round trip is lossy\ninput: {}\nencoding: {}", String::from_utf8_lossy(data), String::from_utf8_lossy(&enc)),
+                Err(e) => panic!("failed to decode human-readable encoding {} of input {}\nerror: {:?}", String::from_utf8_lossy(&enc), String::from_utf8_lossy(data), e),
+            }
+        }
+    }
+}
+
+/// Decode `data` as a [`Value`](Value) in the compact encoding. If that succeeds, check that normalizing the value (see [`assert_compact_roundtrip_stable`](testing::assert_compact_roundtrip_stable)) is idempotent: encoding the normalized value, decoding it and encoding it again must reproduce the same bytes.
+///
+/// The crate does not implement the [canonic encoding](https://github.com/AljoschaMeyer/valuable-value#canonic-encoding), the closest it gets is the compact encoding of a value whose NaNs have been [fixed](Value::fix_nan).
+pub fn canonic_idempotence(data: &[u8]) {
+    if let Ok(v) = compact::from_slice::<Value>(data) {
+        testing::assert_compact_roundtrip_stable(&v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    // Read all files in a directory, relative to the crate root.
+    fn seeds(dir: &str) -> Vec<(PathBuf, Vec<u8>)> {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push(dir);
+        let mut seeds: Vec<(PathBuf, Vec<u8>)> = fs::read_dir(&path).unwrap().map(|entry| {
+            let path = entry.unwrap().path();
+            let data = fs::read(&path).unwrap();
+            (path, data)
+        }).collect();
+        seeds.sort();
+        assert!(!seeds.is_empty(), "no seeds in {}", path.display());
+        seeds
+    }
+
+    #[test]
+    fn compact_seeds() {
+        let seeds = seeds("fuzz/seeds/compact");
+        assert!(seeds.iter().any(|(_, data)| compact::from_slice::<Value>(data).is_ok()));
+        for (_, data) in seeds.iter() {
+            decode_compact(data);
+            canonic_idempotence(data);
+            decode_human(data);
+        }
+    }
+
+    #[test]
+    fn human_seeds() {
+        let seeds = seeds("fuzz/seeds/human");
+        assert!(seeds.iter().any(|(_, data)| human::from_slice::<Value>(data).is_ok()));
+        for (_, data) in seeds.iter() {
+            decode_human(data);
+            decode_compact(data);
+            canonic_idempotence(data);
+        }
+    }
+
+    #[test]
+    fn golden_files() {
+        for (path, data) in seeds("golden").iter() {
+            assert!(human::from_slice::<Value>(data).is_ok(), "cannot decode {}", path.display());
+            decode_human(data);
+        }
+    }
+}
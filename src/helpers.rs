@@ -8,28 +8,34 @@ use serde::de::{
 
 use atm_parser_helper::Error;
 
-pub struct BytesAsSeq<E> {
-    b: Vec<u8>,
+/// Errors that a visitor reports do not know where in the input they occurred, attribute them to the value
+/// that started at `position` instead.
+pub fn visited_at<T, E>(result: Result<T, Error<E>>, position: usize) -> Result<T, Error<E>> {
+    result.map_err(|err| Error::new(position, err.e))
+}
+
+pub struct BytesAsSeq<B, E> {
+    b: B,
     i: usize,
     err_position: usize,
     e_bounds: E,
     e_int: E,
 }
 
-impl<E: Clone> BytesAsSeq<E> {
-    pub fn new(b: Vec<u8>, err_position: usize, e_bounds: E, e_int: E) -> Self {
+impl<B: AsRef<[u8]>, E: Clone> BytesAsSeq<B, E> {
+    pub fn new(b: B, err_position: usize, e_bounds: E, e_int: E) -> Self {
         BytesAsSeq { b, i: 0, err_position, e_bounds, e_int }
     }
 }
 
-impl<'de, E: Clone + serde::de::Error> SeqAccess<'de> for BytesAsSeq<E> {
+impl<'de, B: AsRef<[u8]>, E: Clone + serde::de::Error> SeqAccess<'de> for BytesAsSeq<B, E> {
     type Error = Error<E>;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
     where
         T: DeserializeSeed<'de>,
     {
-        match self.b.get(self.i) {
+        match self.b.as_ref().get(self.i) {
             Some(b) => {
                 self.i += 1;
                 return seed.deserialize(IntFromByte {
@@ -42,6 +48,10 @@ impl<'de, E: Clone + serde::de::Error> SeqAccess<'de> for BytesAsSeq<E> {
             None => Ok(None),
         }
     }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.b.as_ref().len() - self.i)
+    }
 }
 
 struct IntFromByte<E> {
@@ -540,3 +550,37 @@ impl<'a, 'de> de::Deserializer<'de> for AlwaysNil<'de> {
         false
     }
 }
+
+/// A visitor that accepts any array, set or map, and reports whether it was empty. Skips over the items of a non-empty
+/// collection.
+pub struct IsEmpty;
+
+impl<'de> Visitor<'de> for IsEmpty {
+    type Value = bool;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array, set or map")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut empty = true;
+        while seq.next_element::<de::IgnoredAny>()?.is_some() {
+            empty = false;
+        }
+        Ok(empty)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let mut empty = true;
+        while map.next_entry::<de::IgnoredAny, de::IgnoredAny>()?.is_some() {
+            empty = false;
+        }
+        Ok(empty)
+    }
+}
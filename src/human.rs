@@ -1,7 +1,49 @@
+//! Serialization and deserialization for the [human-readable encoding](https://github.com/AljoschaMeyer/valuable-value#human-readable-encoding).
+//!
+//! ```
+//! use serde::{Serialize, Deserialize};
+//! use valuable_value::{compact, human, Value};
+//!
+//! #[derive(Serialize, Deserialize, Debug, PartialEq)]
+//! struct Config {
+//!     name: String,
+//!     retries: Option<u8>,
+//!     ratio: f64,
+//! }
+//!
+//! let c = Config { name: "server".to_string(), retries: Some(3), ratio: 0.5 };
+//!
+//! let enc = human::to_vec(&c, 2).unwrap();
+//! assert_eq!(std::str::from_utf8(&enc).unwrap(), r#"{
+//!   "name": "server",
+//!   "retries": {"Some": 3},
+//!   "ratio": 0.5,
+//! }"#);
+//! assert_eq!(human::from_slice::<Config>(&enc).unwrap(), c);
+//!
+//! // Inspect the code without knowing its type.
+//! let v: Value = human::from_slice(b"{\"ratio\": 0.5, \"retries\": \"None\", \"name\": \"edge\"}").unwrap();
+//! assert_eq!(v.get_float(&[Value::from("ratio")]).unwrap(), 0.5);
+//!
+//! // Convert to the compact encoding.
+//! let enc_compact = compact::to_vec(&v).unwrap();
+//! assert_eq!(compact::from_slice::<Config>(&enc_compact).unwrap(), Config {
+//!     name: "edge".to_string(),
+//!     retries: None,
+//!     ratio: 0.5,
+//! });
+//! ```
 mod de;
 pub use de::*;
 mod ser;
 pub use ser::*;
+mod lossless;
+pub use lossless::*;
+mod escape;
+pub use escape::{escape_str, unescape_str, unescape_raw, EscapeOptions};
+mod float;
+mod syntax_stats;
+pub use syntax_stats::*;
 
 #[cfg(feature = "arbitrary")]
 pub mod test_value;
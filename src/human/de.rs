@@ -1,6 +1,10 @@
 use serde::Deserialize;
+use std::borrow::Cow;
+use std::convert::TryFrom;
 use std::str::FromStr;
 use std::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::{Deref, DerefMut, Range};
 
 use thiserror::Error;
 use atm_parser_helper::{ParserHelper, Eoi, Error as ParseError};
@@ -8,9 +12,13 @@ use atm_parser_helper_common_syntax::*;
 
 use serde::de::{
     self, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor, IntoDeserializer,
+    value::BorrowedStrDeserializer,
 };
 
-use crate::helpers::AlwaysNil;
+use crate::helpers::{visited_at, AlwaysNil, IsEmpty};
+use crate::Value;
+use crate::value::{captured_str, render_value};
+use super::SyntaxStats;
 
 /// Everything that can go wrong during deserialization of a valuable value from the human-readable encoding.
 #[derive(Error, Debug, PartialEq, Eq, Clone)]
@@ -62,9 +70,16 @@ pub enum DecodeError {
 
     #[error("can only decode a set where a map whose values are all nil would be valid")]
     InvalidSet,
+    /// A map or set contains two equal keys, see [`VVDeserializer::reject_duplicate_keys`](VVDeserializer::reject_duplicate_keys). Holds the key.
+    #[error("duplicate key {}", render_value(.0))]
+    DuplicateKey(Value),
 
     #[error("expected nil")]
     ExpectedNil,
+    /// Attempted to decode `()` or a unit struct from something other than nil (or, with
+    /// [`VVDeserializer::lenient_units`](VVDeserializer::lenient_units), an empty array, set or map). Holds what was found instead.
+    #[error("expected nil, found {found}")]
+    ExpectedUnit { found: &'static str },
     #[error("expected bool")]
     ExpectedBool,
     #[error("expected float")]
@@ -73,6 +88,9 @@ pub enum DecodeError {
     ExpectedInt,
     #[error("expected option")]
     ExpectedOption,
+    /// An option was encoded with a tag other than `"None"` or `"Some"`. Holds the tag, truncated if it was long.
+    #[error("expected option, found tag {}", render_value(.0))]
+    UnexpectedOptionTag(Value),
     #[error("expected UTF-8 string")]
     ExpectedUtf8String,
     #[error("expected byte string")]
@@ -88,18 +106,30 @@ pub enum DecodeError {
 
     #[error("comments must be valid UTF-8")]
     CommentUtf8,
+    /// Encountered a NUL byte outside of a string literal, which often means that the input is binary data rather than the human-readable encoding.
+    #[error("unexpected NUL byte at position {position}, the input might not be text")]
+    UnexpectedNul { position: usize },
 
     #[error("integer literals must have at least one digit")]
     IntDigits,
+    #[error("a sign must be immediately followed by a digit")]
+    SignDigit,
+    #[error("the hexadecimal prefix `0x` must be followed by at least one hexadecimal digit")]
+    HexDigits,
+    #[error("the binary prefix `0b` must be followed by at least one binary digit")]
+    BinaryDigits,
 
     #[error("floating-point literals must have at least one digit before the decimal point")]
     FloatLeadingDigits,
     #[error("floating-point literals must have a decimal point")]
     FloatPoint,
-    #[error("floating-point literals must have at least one digit after the decimal point")]
+    #[error("a decimal point must be followed by a digit")]
     FloatTrailingDigits,
     #[error("floating-point literals with an exponent must have at least one exponent digit")]
     FloatExponentDigit,
+    /// A hexadecimal or binary literal continued with a point or a `p` exponent, like `0x1.8p3`. Floats can only be written in decimal.
+    #[error("hexadecimal and binary float literals are not part of the grammar, floats must be written in decimal")]
+    NonDecimalFloat,
 
     #[error("hexadecimal byte string literals must have an even number of digits")]
     ByteStringHexOdd,
@@ -134,6 +164,20 @@ pub enum DecodeError {
 
     #[error("chars must be encoded as UTF-8 strings containing exactly one unicode codepoint")]
     CharLength,
+
+    /// Attempted to decode an array (e.g. into a tuple) with a different number of items than required.
+    #[error("expected an array of {expected} items, found {found}")]
+    WrongArrayLength { expected: usize, found: usize },
+
+    #[error("the input contains no value, only whitespace and comments")]
+    EmptyInput,
+    /// The input continued after the encoded value, see [`VVDeserializer::end`](VVDeserializer::end).
+    #[error("input contains bytes after the encoded value")]
+    TrailingBytes,
+
+    /// A [scalar extension](ScalarExtension) rejected its literal.
+    #[error("invalid scalar extension literal: {0}")]
+    ScalarExtension(String),
 }
 
 impl Eoi for DecodeError {
@@ -231,77 +275,953 @@ impl Utf8StringLiteralE for DecodeError {
         Self::UnicodeClosing
     }
 
-    fn not_utf8_string_literal() -> Self {
-        Self::ExpectedUtf8String
+    fn not_utf8_string_literal() -> Self {
+        Self::ExpectedUtf8String
+    }
+}
+
+impl de::Error for DecodeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DecodeError::Message(msg.to_string())
+    }
+}
+
+pub type Error = ParseError<DecodeError>;
+
+/// A struct that deserializes valuable values from the [human-readable encoding](https://github.com/AljoschaMeyer/valuable-value#encodings).
+///
+/// Does not enforce that the input must be empty after the first valid code, call [`end`](VVDeserializer::end) for that, or use [`from_slice`](from_slice).
+///
+/// Quoted strings without escape sequences and raw strings can be deserialized as `&'de str` (e.g., as the keys of a `BTreeMap<&str, _>`), other strings and byte strings only into owned types.
+pub struct VVDeserializer<'de> {
+    p: ParserHelper<'de>,
+    started: bool,
+    lossless: bool,
+    case_insensitive_keywords: bool,
+    lenient_units: bool,
+    reject_duplicate_keys: bool,
+    // How many calls to the `Deserializer` methods are currently running.
+    depth: usize,
+    // Where the outermost value that is currently being deserialized starts, once its leading whitespace has been skipped.
+    value_start: Option<usize>,
+    last_span: Option<Range<usize>>,
+    scalar_extensions: BTreeMap<u8, ScalarExtension>,
+    syntax_stats: Option<SyntaxStats>,
+}
+
+/// A function that turns the string of a scalar extension literal into a value, or explains why the string is invalid.
+///
+/// A scalar extension literal is an `@`, followed by a tag byte and a string literal, e.g. `@t"2024-01-01"`. It is not
+/// part of the human-readable encoding, but lets an application support domain-specific literals such as timestamps or
+/// durations, see [`VVDeserializer::scalar_extension`](VVDeserializer::scalar_extension). Scalar extension literals are
+/// only recognized where any value may occur, i.e. by `deserialize_any` and `deserialize_ignored_any`.
+pub type ScalarExtension = fn(&str) -> Result<Value, String>;
+
+impl<'de> VVDeserializer<'de> {
+    /// Create a new [`VVDeserializer`](VVDeserializer) that deserializes from the input slice.
+    pub fn new(input: &'de [u8]) -> Self {
+        VVDeserializer {
+            p: ParserHelper::new(input),
+            started: false,
+            lossless: false,
+            case_insensitive_keywords: false,
+            lenient_units: false,
+            reject_duplicate_keys: false,
+            depth: 0,
+            value_start: None,
+            last_span: None,
+            scalar_extensions: BTreeMap::new(),
+            syntax_stats: None,
+        }
+    }
+
+    /// Register a [scalar extension](ScalarExtension) for the given tag byte, replacing any previous one.
+    ///
+    /// Tags that already have a meaning after an `@` (`[`, `{`, `"`, `@`, `b` and `x`) keep that meaning, extensions for them are never invoked.
+    pub fn scalar_extension(mut self, tag: u8, extension: ScalarExtension) -> Self {
+        self.scalar_extensions.insert(tag, extension);
+        self
+    }
+
+    /// Accept the keywords `nil`, `true`, `false`, `Inf` and `NaN` in any mix of upper- and lowercase, e.g. `NIL`, `True` or `inf`.
+    ///
+    /// Off by default, since the human-readable encoding only allows the exact spellings.
+    pub fn case_insensitive_keywords(mut self, lenient: bool) -> Self {
+        self.case_insensitive_keywords = lenient;
+        self
+    }
+
+    /// Skip a UTF-8 byte order mark at the very start of the input.
+    ///
+    /// Off by default, since the human-readable encoding does not allow one. Some editors write it at the start of text files though.
+    /// Has no effect once deserialization has started.
+    pub fn skip_bom(mut self, skip: bool) -> Self {
+        if skip && self.p.position() == 0 {
+            self.p.advance_over(b"\xEF\xBB\xBF");
+        }
+        self
+    }
+
+    /// Accept empty arrays, sets and maps (`[]`, `@{}` or `{}`) wherever `()` or a unit struct is expected, in addition to `nil`.
+    ///
+    /// Off by default. Encoders always write units as `nil`, but hand-written configuration files often contain `{}` instead.
+    pub fn lenient_units(mut self, lenient: bool) -> Self {
+        self.lenient_units = lenient;
+        self
+    }
+
+    /// Reject maps and sets that contain two equal keys (according to the [equality of values](crate::Value), so `"a"`
+    /// and `[97]` are equal) with [`DecodeError::DuplicateKey`](DecodeError::DuplicateKey), pointing at the second one.
+    ///
+    /// Off by default, in which case the type being decoded decides what happens, e.g., a `BTreeMap` keeps the last
+    /// value. Keys in maps and sets are distinct by definition, so this lets applications that handle untrusted input
+    /// reject such malformed input instead of silently collapsing it. Every key is decoded a second time as a
+    /// [`Value`](crate::Value) for the comparison.
+    pub fn reject_duplicate_keys(mut self, reject: bool) -> Self {
+        self.reject_duplicate_keys = reject;
+        self
+    }
+
+    /// Count which [syntactic forms](super::SyntaxForm) the deserialized values use, e.g., to warn about comments or
+    /// trailing commas before tightening which inputs an application accepts. The counts can be read with
+    /// [`syntax_stats`](VVDeserializer::syntax_stats).
+    ///
+    /// Off by default. Only values that are deserialized successfully are counted, together with the whitespace and
+    /// comments before them.
+    pub fn collect_syntax_stats(mut self, collect: bool) -> Self {
+        self.syntax_stats = if collect { Some(SyntaxStats::default()) } else { None };
+        self
+    }
+
+    /// Return the [`SyntaxStats`](super::SyntaxStats) of all values deserialized so far, or `None` unless
+    /// [`collect_syntax_stats`](VVDeserializer::collect_syntax_stats) was enabled.
+    pub fn syntax_stats(&self) -> Option<&SyntaxStats> {
+        self.syntax_stats.as_ref()
+    }
+
+    // A deserializer whose `deserialize_any` hands decimal integer literals that do not fit into an `i64` to `visit_newtype_struct` as a string of their digits, instead of failing.
+    pub(crate) fn new_lossless(input: &'de [u8]) -> Self {
+        VVDeserializer {
+            lossless: true,
+            ..VVDeserializer::new(input)
+        }
+    }
+
+    // Skip whitespace before a value, and report an input without any value as `EmptyInput` rather than as an unexpected end of input.
+    fn value_spaces(&mut self) -> Result<(), Error> {
+        skip_spaces(&mut self.p)?;
+        if self.value_start.is_none() {
+            self.value_start = Some(self.p.position());
+        }
+        if !self.started {
+            self.started = true;
+            if self.p.rest().is_empty() {
+                return self.p.fail(DecodeError::EmptyInput);
+            }
+        }
+        Ok(())
+    }
+
+    // Report malformed number literals with specific errors that point at the first offending byte, before handing well-formed ones to the shared literal parsers.
+    fn check_number_literal(&self) -> Result<(), Error> {
+        let rest = self.p.rest();
+        let start = self.p.position();
+
+        let mut i = 0;
+        match rest.first() {
+            Some(0x2b | 0x2d) => {
+                i = 1;
+                match rest.get(1) {
+                    Some(b) if b.is_ascii_digit() || *b == 0x49 || *b == 0x4e => {}
+                    _ => return self.p.fail_at_position(DecodeError::SignDigit, start + 1),
+                }
+            }
+            Some(0x30) => match rest.get(1) {
+                Some(0x78) => {
+                    if rest.get(2).is_some_and(|b| b.is_ascii_hexdigit()) {
+                        return self.check_non_decimal_float(|b| b.is_ascii_hexdigit());
+                    } else {
+                        return self.p.fail_at_position(DecodeError::HexDigits, start + 2);
+                    }
+                }
+                Some(0x62) => {
+                    if let Some(0x30 | 0x31) = rest.get(2) {
+                        return self.check_non_decimal_float(|b| b == 0x30 || b == 0x31);
+                    } else {
+                        return self.p.fail_at_position(DecodeError::BinaryDigits, start + 2);
+                    }
+                }
+                _ => {}
+            }
+            _ => {}
+        }
+
+        let digits = |mut i: usize| {
+            while rest.get(i).is_some_and(|b| b.is_ascii_digit() || *b == 0x5f) {
+                i += 1;
+            }
+            i
+        };
+
+        if !rest.get(i).is_some_and(|b| b.is_ascii_digit()) {
+            return Ok(());
+        }
+        i = digits(i);
+
+        if rest.get(i) != Some(&0x2e) {
+            return Ok(());
+        }
+        if !rest.get(i + 1).is_some_and(|b| b.is_ascii_digit()) {
+            return self.p.fail_at_position(DecodeError::FloatTrailingDigits, start + i + 1);
+        }
+        i = digits(i + 1);
+
+        if let Some(0x45 | 0x65) = rest.get(i) {
+            i += 1;
+            if let Some(0x2b | 0x2d) = rest.get(i) {
+                i += 1;
+            }
+            if !rest.get(i).is_some_and(|b| b.is_ascii_digit()) {
+                return self.p.fail_at_position(DecodeError::FloatExponentDigit, start + i);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Reject a hexadecimal or binary int literal that continues like a float, pointing at where the float syntax starts.
+    fn check_non_decimal_float(&self, is_digit: fn(u8) -> bool) -> Result<(), Error> {
+        let rest = self.p.rest();
+        let mut i = 2;
+        while rest.get(i).is_some_and(|b| is_digit(*b) || *b == 0x5f) {
+            i += 1;
+        }
+        match rest.get(i) {
+            Some(0x2e | 0x50 | 0x70) => self.p.fail_at_position(DecodeError::NonDecimalFloat, self.p.position() + i),
+            _ => Ok(()),
+        }
+    }
+
+    fn parse_int(&mut self) -> Result<i64, Error> {
+        self.check_number_literal()?;
+        parse_int(&mut self.p, i64_from_decimal, i64_from_hex, i64_from_binary)
+    }
+
+    fn parse_number(&mut self) -> Result<Number<i64, f64>, Error> {
+        if let Some(f) = self.parse_float_keyword_ignoring_case() {
+            return Ok(Number::Float(f));
+        }
+        self.check_number_literal()?;
+        parse_number(&mut self.p, i64_from_decimal, i64_from_hex, i64_from_binary, f64_from_s, f64::NEG_INFINITY, f64::INFINITY, f64::from_bits(u64::MAX))
+    }
+
+    // Parse a float, or an int that is then converted to the nearest float.
+    fn parse_float_or_int(&mut self) -> Result<f64, Error> {
+        if let Some(f) = self.parse_float_keyword_ignoring_case() {
+            return Ok(f);
+        }
+        match self.p.peek()? {
+            b'-' | b'+' | b'0'..=b'9' | b'I' | b'N' => match self.parse_number()? {
+                Number::Float(n) => Ok(n),
+                Number::Integer(n) => Ok(n as f64),
+            },
+            _ => self.p.fail(DecodeError::ExpectedFloat),
+        }
+    }
+
+    /// Return how many input bytes have been already read.
+    pub fn position(&self) -> usize {
+        self.p.position()
+    }
+
+    /// Skip whitespace and comments, then check that the whole input has been read, and report
+    /// [`DecodeError::TrailingBytes`](DecodeError::TrailingBytes) otherwise.
+    ///
+    /// Call this after deserializing the last value, unless the input may continue with arbitrary data.
+    pub fn end(&mut self) -> Result<(), Error> {
+        skip_spaces(&mut self.p)?;
+        if self.p.rest().is_empty() {
+            Ok(())
+        } else {
+            self.p.fail(DecodeError::TrailingBytes)
+        }
+    }
+
+    /// Return the range of input bytes occupied by the last value that was deserialized from this [`VVDeserializer`](VVDeserializer).
+    ///
+    /// Only values deserialized directly count, not the values nested inside them. Returns `None` if no value has been deserialized yet, or if the last deserialization failed.
+    pub fn last_value_span(&self) -> Option<Range<usize>> {
+        self.last_span.clone()
+    }
+
+    /// Deserialize a [`Value`](Value), together with the range of input bytes occupied by it and by every value nested inside it, see [`parse_spanned`](parse_spanned).
+    pub fn deserialize_spanned(&mut self) -> Result<Spanned<Value>, Error> {
+        let (value, spans) = self.spanned(|mut des| des.parse_spanned_value())?;
+        Ok(Spanned { value, spans })
+    }
+
+    // Parse a value, recording the spans of all nested values. Arrays, maps and sets are parsed here, following the
+    // same rules as `SequenceAccessor` and `MapAccessor`, everything else is a leaf that is parsed as a `Value`.
+    fn parse_spanned_value(&mut self) -> Result<(Value, Spans), Error> {
+        self.value_spaces()?;
+        let start = self.p.position();
+        let set = match self.p.peek()? {
+            0x5b => None,
+            0x7b => Some(false),
+            0x40 if self.p.rest().get(1) == Some(&0x7b) => Some(true),
+            _ => {
+                let value = Value::deserialize(&mut *self)?;
+                return Ok((value, Spans { span: start..self.p.position(), nested: Vec::new() }));
+            }
+        };
+
+        match set {
+            None => {
+                self.p.advance(1);
+                let mut items = Vec::new();
+                let mut nested = Vec::new();
+                loop {
+                    skip_spaces(&mut self.p)?;
+                    match self.p.peek()? {
+                        0x5d => break,
+                        0x2c if items.is_empty() => {
+                            self.p.advance(1);
+                            skip_spaces(&mut self.p)?;
+                            match self.p.peek::<DecodeError>() {
+                                Ok(0x5d) => break,
+                                _ => return self.p.fail(DecodeError::ArrayClosing),
+                            }
+                        }
+                        _ => {
+                            let (item, spans) = self.parse_spanned_value()?;
+                            items.push(item);
+                            nested.push(spans);
+                            skip_spaces(&mut self.p)?;
+                            self.p.advance_over(b",");
+                        }
+                    }
+                }
+                self.p.advance(1);
+                Ok((Value::Array(items), Spans { span: start..self.p.position(), nested }))
+            }
+            Some(set) => {
+                self.p.advance(if set { 2 } else { 1 });
+                let mut entries = Vec::new();
+                loop {
+                    skip_spaces(&mut self.p)?;
+                    match self.p.peek()? {
+                        0x7d => break,
+                        0x2c if entries.is_empty() => {
+                            self.p.advance(1);
+                            skip_spaces(&mut self.p)?;
+                            match self.p.peek::<DecodeError>() {
+                                Ok(0x7d) => break,
+                                _ => return self.p.fail(DecodeError::MapClosing),
+                            }
+                        }
+                        _ => {
+                            let (key, key_spans) = self.parse_spanned_value()?;
+                            skip_spaces(&mut self.p)?;
+                            if set {
+                                self.p.advance_over(b",");
+                                let value_spans = Spans { span: key_spans.span.clone(), nested: Vec::new() };
+                                entries.push((key, Value::Nil, key_spans, value_spans));
+                            } else {
+                                self.p.expect(0x3a, DecodeError::ExpectedColon)?;
+                                let (value, value_spans) = self.parse_spanned_value()?;
+                                skip_spaces(&mut self.p)?;
+                                self.p.advance_over(b",");
+                                entries.push((key, value, key_spans, value_spans));
+                            }
+                        }
+                    }
+                }
+                self.p.advance(1);
+
+                // Order the entries like the map does, where later entries replace earlier ones with an equal key.
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                let mut m = BTreeMap::new();
+                let mut nested = Vec::with_capacity(entries.len() * 2);
+                let mut entries = entries.into_iter().peekable();
+                while let Some((key, value, key_spans, value_spans)) = entries.next() {
+                    if entries.peek().is_some_and(|next| next.0 == key) {
+                        continue;
+                    }
+                    m.insert(key, value);
+                    nested.push(key_spans);
+                    nested.push(value_spans);
+                }
+                Ok((Value::Map(m), Spans { span: start..self.p.position(), nested }))
+            }
+        }
+    }
+
+    // Run a deserialization, recording its span if it is not nested in another one.
+    fn spanned<T>(&mut self, f: impl FnOnce(Raw<'_, 'de>) -> Result<T, Error>) -> Result<T, Error> {
+        let start = self.p.position();
+        if self.depth == 0 {
+            self.value_start = None;
+        }
+        self.depth += 1;
+        let result = f(Raw(self));
+        self.depth -= 1;
+        if self.depth == 0 {
+            self.last_span = match (&result, self.value_start) {
+                (Ok(_), Some(start)) => Some(start..self.p.position()),
+                _ => None,
+            };
+            if let (Ok(_), Some(stats)) = (&result, self.syntax_stats.as_mut()) {
+                stats.scan(self.p.slice(start..self.p.position()), start);
+            }
+        }
+        result
+    }
+
+    // Parse a quoted string without escape sequences or a raw string, borrowing it from the input. Leaves the input
+    // untouched and returns `None` for all other strings, which must then be parsed in full.
+    fn parse_borrowed_str(&mut self) -> Option<&'de str> {
+        let rest = self.p.rest();
+        let ats = rest.iter().take_while(|b| **b == 0x40).count();
+        if rest.get(ats) != Some(&0x22) || ats > 255 {
+            return None;
+        }
+        let content = &rest[ats + 1..];
+        let len = if ats == 0 {
+            let len = content.iter().position(|b| *b == 0x22 || *b == 0x5c)?;
+            if content[len] != 0x22 {
+                return None;
+            }
+            len
+        } else {
+            // A raw string ends at the first quote that is followed by as many @s as preceded the opening quote.
+            (0..content.len()).find(|i| {
+                content[*i] == 0x22 && content.get(i + 1..i + 1 + ats).is_some_and(|closing| closing.iter().all(|b| *b == 0x40))
+            })?
+        };
+        let s = std::str::from_utf8(&content[..len]).ok()?;
+        self.p.advance(2 * ats + len + 2);
+        Some(s)
+    }
+
+    // If `case_insensitive_keywords` is set and the input starts with the keyword in any case, advance over it.
+    fn advance_over_keyword_ignoring_case(&mut self, keyword: &[u8]) -> bool {
+        let found = self.case_insensitive_keywords
+            && self.p.rest().get(..keyword.len()).is_some_and(|word| word.eq_ignore_ascii_case(keyword));
+        if found {
+            self.p.advance(keyword.len());
+        }
+        found
+    }
+
+    // If `case_insensitive_keywords` is set, parse an optionally signed `Inf` or `NaN` in any case. Leaves the input
+    // untouched and returns `None` for everything else.
+    fn parse_float_keyword_ignoring_case(&mut self) -> Option<f64> {
+        if !self.case_insensitive_keywords {
+            return None;
+        }
+        let rest = self.p.rest();
+        let sign = match rest.first() {
+            Some(0x2b | 0x2d) => 1,
+            _ => 0,
+        };
+        let keyword = rest.get(sign..sign + 3)?;
+        let f = if keyword.eq_ignore_ascii_case(b"inf") {
+            if rest[0] == 0x2d { f64::NEG_INFINITY } else { f64::INFINITY }
+        } else if keyword.eq_ignore_ascii_case(b"nan") {
+            f64::from_bits(u64::MAX)
+        } else {
+            return None;
+        };
+        self.p.advance(sign + 3);
+        Some(f)
+    }
+
+    // Parse the tag of an option or of a unit variant. Probing a tag (possibly many times, e.g. while trying the variants
+    // of an untagged enum) only allocates if the tag is not a quoted string without escape sequences.
+    fn parse_tag(&mut self) -> Result<Cow<'de, str>, Error> {
+        self.value_spaces()?;
+        match self.parse_borrowed_str() {
+            Some(tag) => Ok(Cow::Borrowed(tag)),
+            None => Ok(Cow::Owned(String::deserialize(&mut *self)?)),
+        }
+    }
+
+    // Deserialize a sequence, which must have exactly `len` items if `len` is given.
+    fn deserialize_seq_len<V>(&mut self, len: Option<usize>, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.value_spaces()?;
+        let start = self.p.position();
+        let bytes = match self.p.peek()? {
+            0x22 => parse_utf8_string(&mut self.p)?.into_bytes(),
+            0x5b => {
+                self.p.advance(1);
+                let mut seq = SequenceAccessor::new(self, len, start);
+                let value = visitor.visit_seq(&mut seq)?;
+                if let Some(expected) = len {
+                    // Count any items the visitor did not ask for.
+                    while seq.next_element::<de::IgnoredAny>()?.is_some() {}
+                    let found = seq.read;
+                    if found != expected {
+                        return self.p.fail_at_position(DecodeError::WrongArrayLength { expected, found }, start);
+                    }
+                }
+                skip_spaces(&mut self.p)?;
+                self.p.expect(']' as u8, DecodeError::ArrayClosing)?;
+                return Ok(value);
+            }
+            0x40 => {
+                match self.p.rest().get(1) {
+                    None => return self.p.fail(DecodeError::Eoi),
+                    Some(0x5b | 0x62 | 0x78) => parse_byte_string(&mut self.p)?,
+                    Some(0x22 | 0x40) => parse_utf8_string(&mut self.p)?.into_bytes(),
+                    Some(_) => return self.p.fail(DecodeError::Syntax),
+                }
+            }
+            _ => return self.p.fail(DecodeError::ExpectedArray),
+        };
+
+        if let Some(expected) = len {
+            if bytes.len() != expected {
+                return self.p.fail_at_position(DecodeError::WrongArrayLength { expected, found: bytes.len() }, start);
+            }
+        }
+        let seq = crate::helpers::BytesAsSeq::new(bytes, self.p.position(), DecodeError::OutOfBoundsI8, DecodeError::ExpectedInt);
+        visitor.visit_seq(seq)
+    }
+
+    fn parse_nil(&mut self) -> Result<(), Error> {
+        if self.advance_over_keyword_ignoring_case(b"nil") {
+            return Ok(());
+        }
+        self.p.expect_bytes(b"nil", DecodeError::ExpectedNil)
+    }
+
+    fn parse_unit(&mut self) -> Result<(), Error> {
+        let start = self.p.position();
+        if self.advance_over_keyword_ignoring_case(b"nil") {
+            return Ok(());
+        }
+
+        let rest = self.p.rest();
+        let found = match rest.first() {
+            None => return self.p.fail(DecodeError::Eoi),
+            Some(_) if rest.starts_with(b"nil") => return self.parse_nil(),
+            Some(0x66 | 0x74) => "bool",
+            Some(0x30..=0x39 | 0x2b | 0x2d) => "number",
+            Some(_) if rest.starts_with(b"Inf") || rest.starts_with(b"NaN") => "float",
+            Some(0x22) => "string",
+            Some(0x5b) => "array",
+            Some(0x7b) => "map",
+            Some(0x40) => match rest.get(1) {
+                Some(0x7b) => "set",
+                Some(0x5b | 0x62 | 0x78) => "byte string",
+                Some(0x22 | 0x40) => "string",
+                _ => "extension",
+            },
+            Some(_) => "invalid syntax",
+        };
+
+        if self.lenient_units && (found == "array" || found == "map" || found == "set") {
+            if visited_at(de::Deserializer::deserialize_any(&mut *self, IsEmpty), start)? {
+                return Ok(());
+            }
+            let found = match found {
+                "array" => "non-empty array",
+                "map" => "non-empty map",
+                _ => "non-empty set",
+            };
+            return self.p.fail_at_position(DecodeError::ExpectedUnit { found }, start);
+        }
+        self.p.fail_at_position(DecodeError::ExpectedUnit { found }, start)
+    }
+
+    // Decode the key in the given range of the input as a `Value`, to compare it with the other keys of its map.
+    fn key_at(&self, range: Range<usize>) -> Result<Value, Error> {
+        let mut des = VVDeserializer {
+            p: ParserHelper::new(self.p.slice(range.clone())),
+            case_insensitive_keywords: self.case_insensitive_keywords,
+            scalar_extensions: self.scalar_extensions.clone(),
+            ..VVDeserializer::new(b"")
+        };
+        Value::deserialize(&mut des).map_err(|err| ParseError { position: range.start + err.position, e: err.e })
+    }
+
+    fn parse_bool(&mut self) -> Result<bool, Error> {
+        if self.advance_over_keyword_ignoring_case(b"true") {
+            Ok(true)
+        } else if self.advance_over_keyword_ignoring_case(b"false") || self.p.advance_over(b"false") {
+            Ok(false)
+        } else {
+            self.p.expect_bytes(b"true", DecodeError::ExpectedBool)?;
+            Ok(true)
+        }
+    }
+}
+
+// Skip whitespace and comments. A NUL byte cannot start any token and usually means that binary data was fed to the
+// decoder, so it is reported with a dedicated error.
+fn skip_spaces(p: &mut ParserHelper) -> Result<(), Error> {
+    spaces(p)?;
+    if p.peek_or_end() == Some(0) {
+        let position = p.position();
+        return p.fail(DecodeError::UnexpectedNul { position });
+    }
+    Ok(())
+}
+
+fn i64_from_decimal(s: &str) -> Result<i64, DecodeError> {
+    i64::from_str_radix(s, 10).map_err(|_| DecodeError::OutOfBoundsI64)
+}
+
+fn i64_from_hex(s: &str) -> Result<i64, DecodeError> {
+    i64::from_str_radix(s, 16).map_err(|_| DecodeError::OutOfBoundsI64)
+}
+
+fn i64_from_binary(s: &str) -> Result<i64, DecodeError> {
+    i64::from_str_radix(s, 2).map_err(|_| DecodeError::OutOfBoundsI64)
+}
+
+fn f64_from_s(s: &str) -> Result<f64, DecodeError> {
+    f64::from_str(s).map_err(|_| panic!())
+}
+
+/// Deserialize a value from the human-readable encoding in the input slice.
+///
+/// Unlike [`VVDeserializer`](VVDeserializer), this enforces that only whitespace and comments may follow the value, and
+/// reports anything else as [`DecodeError::TrailingBytes`](DecodeError::TrailingBytes).
+///
+/// ```
+/// use valuable_value::human::{self, DecodeError};
+///
+/// assert_eq!(human::from_slice::<Vec<u8>>(b"[1, 2] # done").unwrap(), vec![1, 2]);
+/// assert_eq!(human::from_slice::<Vec<u8>>(b"[1, 2] [3]").unwrap_err().e, DecodeError::TrailingBytes);
+/// ```
+pub fn from_slice<'de, T>(input: &'de [u8]) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    let mut des = VVDeserializer::new(input);
+    let value = T::deserialize(&mut des)?;
+    des.end()?;
+    Ok(value)
+}
+
+/// Deserialize a value from the human-readable encoding in the input string, see [`from_slice`](from_slice).
+pub fn from_str<'de, T>(input: &'de str) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    from_slice(input.as_bytes())
+}
+
+/// A decoded value, together with the ranges of input bytes it was decoded from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    /// The decoded value.
+    pub value: T,
+    /// Where the value and the values nested inside it were decoded from.
+    pub spans: Spans,
+}
+
+/// The range of input bytes occupied by a value, and the ranges of the values nested inside it.
+///
+/// For arrays, `nested` holds the spans of the items. For maps, it alternately holds the spans of the keys and values, in ascending order of the keys (the order in which the map iterates over them). The implicit nil values of sets have the span of their key. Strings, byte strings and all other values that are not written as arrays or maps have no nested spans.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spans {
+    /// The range of input bytes occupied by the value, excluding surrounding whitespace and comments.
+    pub span: Range<usize>,
+    /// The spans of the nested values.
+    pub nested: Vec<Spans>,
+}
+
+impl Spanned<Value> {
+    /// Return the span of the value at the given [path](Value::pointer), or `None` if there is no such value or if it lies inside a string or byte string.
+    pub fn span_of(&self, path: &[Value]) -> Option<Range<usize>> {
+        let mut value = &self.value;
+        let mut spans = &self.spans;
+        for component in path {
+            let (i, inner) = match (value, component) {
+                (Value::Array(items), Value::Int(n)) => {
+                    let i = usize::try_from(*n).ok()?;
+                    (i, items.get(i)?)
+                }
+                (Value::Map(m), key) => {
+                    let i = m.keys().position(|k| k == key)?;
+                    (2 * i + 1, &m[key])
+                }
+                _ => return None,
+            };
+            spans = spans.nested.get(i)?;
+            value = inner;
+        }
+        Some(spans.span.clone())
+    }
+}
+
+/// Decode a [`Value`](Value) from the human-readable encoding, together with the range of input bytes occupied by it and by every value nested inside it, e.g., for pointing at the source of a value in an error message.
+///
+/// ```
+/// use valuable_value::{human, Value};
+///
+/// let input = b"{\"xs\": [1, [true, 2.5]]}";
+/// let spanned = human::parse_spanned(input).unwrap();
+/// let span = spanned.span_of(&[Value::from("xs"), Value::Int(1), Value::Int(1)]).unwrap();
+/// assert_eq!(&input[span], b"2.5");
+/// ```
+pub fn parse_spanned(input: &[u8]) -> Result<Spanned<Value>, Error> {
+    VVDeserializer::new(input).deserialize_spanned()
+}
+
+impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_any(visitor))
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_bool(visitor))
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_i8(visitor))
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_i16(visitor))
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_i32(visitor))
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_i64(visitor))
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_u8(visitor))
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_u16(visitor))
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_u32(visitor))
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_u64(visitor))
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_f32(visitor))
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_f64(visitor))
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_char(visitor))
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_str(visitor))
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_string(visitor))
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_bytes(visitor))
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_byte_buf(visitor))
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_option(visitor))
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_unit(visitor))
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_unit_struct(name, visitor))
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_newtype_struct(name, visitor))
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_seq(visitor))
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_tuple(len, visitor))
     }
-}
 
-impl de::Error for DecodeError {
-    fn custom<T: fmt::Display>(msg: T) -> Self {
-        DecodeError::Message(msg.to_string())
+    fn deserialize_tuple_struct<V>(self, name: &'static str, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_tuple_struct(name, len, visitor))
     }
-}
 
-pub type Error = ParseError<DecodeError>;
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_map(visitor))
+    }
 
-/// A struct that deserializes valuable values from the [human-readable encoding](https://github.com/AljoschaMeyer/valuable-value#encodings).
-///
-/// Does not enforce that the input must be empty after the first valid code.
-pub struct VVDeserializer<'de> {
-    p: ParserHelper<'de>,
-}
+    fn deserialize_struct<V>(self, name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_struct(name, fields, visitor))
+    }
 
-impl<'de> VVDeserializer<'de> {
-    /// Create a new [`VVDeserializer`](VVDeserializer) that deserializes from the input slice.
-    pub fn new(input: &'de [u8]) -> Self {
-        VVDeserializer {
-            p: ParserHelper::new(input),
-        }
+    fn deserialize_enum<V>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_enum(name, variants, visitor))
     }
 
-    /// Return how many input bytes have been already read.
-    pub fn position(&self) -> usize {
-        self.p.position()
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_identifier(visitor))
     }
 
-    fn parse_nil(&mut self) -> Result<(), Error> {
-        self.p.expect_bytes(b"nil", DecodeError::ExpectedNil)
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.spanned(|des| des.deserialize_ignored_any(visitor))
     }
 
-    fn parse_bool(&mut self) -> Result<bool, Error> {
-        if self.p.advance_over(b"false") {
-            Ok(false)
-        } else {
-            self.p.expect_bytes(b"true", DecodeError::ExpectedBool)?;
-            Ok(true)
-        }
+    fn is_human_readable(&self) -> bool {
+        true
     }
 }
 
-fn i64_from_decimal(s: &str) -> Result<i64, DecodeError> {
-    i64::from_str_radix(s, 10).map_err(|_| DecodeError::OutOfBoundsI64)
-}
+// The actual deserialization logic. The implementation for `&mut VVDeserializer` wraps it to keep track of the span of
+// the outermost value, nested values are decoded through `Raw` directly or through `&mut VVDeserializer` at a greater depth.
+struct Raw<'a, 'de>(&'a mut VVDeserializer<'de>);
 
-fn i64_from_hex(s: &str) -> Result<i64, DecodeError> {
-    i64::from_str_radix(s, 16).map_err(|_| DecodeError::OutOfBoundsI64)
-}
+impl<'a, 'de> Deref for Raw<'a, 'de> {
+    type Target = VVDeserializer<'de>;
 
-fn i64_from_binary(s: &str) -> Result<i64, DecodeError> {
-    i64::from_str_radix(s, 2).map_err(|_| DecodeError::OutOfBoundsI64)
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
 }
 
-fn f64_from_s(s: &str) -> Result<f64, DecodeError> {
-    f64::from_str(s).map_err(|_| panic!())
+impl<'a, 'de> DerefMut for Raw<'a, 'de> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0
+    }
 }
 
-impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
+impl<'a, 'de> de::Deserializer<'de> for Raw<'a, 'de> {
     type Error = Error;
 
-    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_any<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
+        if self.case_insensitive_keywords {
+            if self.advance_over_keyword_ignoring_case(b"nil") {
+                return visitor.visit_unit();
+            } else if self.advance_over_keyword_ignoring_case(b"true") {
+                return visitor.visit_bool(true);
+            } else if self.advance_over_keyword_ignoring_case(b"false") {
+                return visitor.visit_bool(false);
+            } else if let Some(f) = self.parse_float_keyword_ignoring_case() {
+                return visitor.visit_f64(f);
+            }
+        }
         match self.p.peek()? {
             0x6e => {
                 self.parse_nil()?;
@@ -309,7 +1229,25 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
             }
             0x66 | 0x74 => self.deserialize_bool(visitor),
             0x30..=0x39 | 0x2b | 0x2d | 0x49 | 0x4e => {
-                match parse_number(&mut self.p, i64_from_decimal, i64_from_hex, i64_from_binary, f64_from_s, f64::NEG_INFINITY, f64::INFINITY, f64::from_bits(u64::MAX))? {
+                let start = self.p.position();
+                let n = match self.parse_number() {
+                    Err(err) if self.lossless && err.e == DecodeError::OutOfBoundsI64 => {
+                        let literal = self.p.slice(start..self.p.position());
+                        // Like the non-lossless decoder, reject out-of-range mantissas of floats with an exponent.
+                        if literal.starts_with(b"0x") || literal.starts_with(b"0b") || matches!(self.p.rest().first(), Some(b'e' | b'E')) {
+                            return Err(err);
+                        }
+                        let digits: String = literal.iter().filter(|b| **b != ('_' as u8)).map(|b| *b as char).collect();
+                        let (sign, digits) = match digits.strip_prefix('-') {
+                            Some(digits) => ("-", digits),
+                            None => ("", digits.trim_start_matches('+')),
+                        };
+                        let digits = format!("{}{}", sign, digits.trim_start_matches('0'));
+                        return visitor.visit_newtype_struct(digits.into_deserializer());
+                    }
+                    n => n?,
+                };
+                match n {
                     Number::Float(f) => visitor.visit_f64(f),
                     Number::Integer(n) => visitor.visit_i64(n),
                 }
@@ -323,28 +1261,39 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
                     Some(0x5b | 0x62 | 0x78) => self.deserialize_bytes(visitor),
                     Some(0x22 | 0x40) => self.deserialize_str(visitor),
                     Some(0x7b) => self.deserialize_map(visitor),
-                    Some(_) => self.p.fail(DecodeError::Syntax),
+                    Some(tag) => match self.scalar_extensions.get(tag).copied() {
+                        Some(extension) => {
+                            let start = self.p.position();
+                            self.p.advance(2);
+                            let literal = parse_utf8_string(&mut self.p)?;
+                            match extension(&literal) {
+                                Ok(v) => v.into_deserializer().deserialize_any(visitor),
+                                Err(reason) => self.p.fail_at_position(DecodeError::ScalarExtension(reason), start),
+                            }
+                        }
+                        None => self.p.fail(DecodeError::Syntax),
+                    },
                 }
             }
             _ => self.p.fail(DecodeError::Syntax),
         }
     }
 
-    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_bool<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
         visitor.visit_bool(self.parse_bool()?)
     }
 
-    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_i8<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
         let start = self.p.position();
-        let n = parse_int(&mut self.p, i64_from_decimal, i64_from_hex, i64_from_binary)?;
+        let n = self.parse_int()?;
         if n < std::i8::MIN as i64 || n > std::i8::MAX as i64 {
             return self.p.fail_at_position(DecodeError::OutOfBoundsI8, start);
         } else {
@@ -352,13 +1301,13 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_i16<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
         let start = self.p.position();
-        let n = parse_int(&mut self.p, i64_from_decimal, i64_from_hex, i64_from_binary)?;
+        let n = self.parse_int()?;
         if n < std::i16::MIN as i64 || n > std::i16::MAX as i64 {
             return self.p.fail_at_position(DecodeError::OutOfBoundsI16, start);
         } else {
@@ -366,13 +1315,13 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_i32<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
         let start = self.p.position();
-        let n = parse_int(&mut self.p, i64_from_decimal, i64_from_hex, i64_from_binary)?;
+        let n = self.parse_int()?;
         if n < std::i32::MIN as i64 || n > std::i32::MAX as i64 {
             return self.p.fail_at_position(DecodeError::OutOfBoundsI32, start);
         } else {
@@ -380,21 +1329,21 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_i64<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
-        visitor.visit_i64(parse_int(&mut self.p, i64_from_decimal, i64_from_hex, i64_from_binary)?)
+        self.value_spaces()?;
+        visitor.visit_i64(self.parse_int()?)
     }
 
-    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_u8<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
         let start = self.p.position();
-        let n = parse_int(&mut self.p, i64_from_decimal, i64_from_hex, i64_from_binary)?;
+        let n = self.parse_int()?;
         if n < 0 || n > std::u8::MAX as i64 {
             return self.p.fail_at_position(DecodeError::OutOfBoundsU8, start);
         } else {
@@ -402,13 +1351,13 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_u16<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
         let start = self.p.position();
-        let n = parse_int(&mut self.p, i64_from_decimal, i64_from_hex, i64_from_binary)?;
+        let n = self.parse_int()?;
         if n < 0 || n > std::u16::MAX as i64 {
             return self.p.fail_at_position(DecodeError::OutOfBoundsU16, start);
         } else {
@@ -416,13 +1365,13 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_u32<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
         let start = self.p.position();
-        let n = parse_int(&mut self.p, i64_from_decimal, i64_from_hex, i64_from_binary)?;
+        let n = self.parse_int()?;
         if n < 0 || n > std::u32::MAX as i64 {
             return self.p.fail_at_position(DecodeError::OutOfBoundsU32, start);
         } else {
@@ -430,13 +1379,13 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_u64<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
         let start = self.p.position();
-        let n = parse_int(&mut self.p, i64_from_decimal, i64_from_hex, i64_from_binary)?;
+        let n = self.parse_int()?;
         if n < 0 {
             return self.p.fail_at_position(DecodeError::OutOfBoundsU64, start);
         } else {
@@ -444,27 +1393,27 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_f32<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
-        visitor.visit_f64(parse_float(&mut self.p, f64_from_s, f64::NEG_INFINITY, f64::INFINITY, f64::from_bits(u64::MAX))?)
+        self.value_spaces()?;
+        visitor.visit_f64(self.parse_float_or_int()?)
     }
 
-    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_f64<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
-        visitor.visit_f64(parse_float(&mut self.p, f64_from_s, f64::NEG_INFINITY, f64::INFINITY, f64::from_bits(u64::MAX))?)
+        self.value_spaces()?;
+        visitor.visit_f64(self.parse_float_or_int()?)
     }
 
-    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_char<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
         let s = String::deserialize(&mut *self)?;
         let mut cs = s.chars();
         match cs.next() {
@@ -479,11 +1428,15 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_str<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
+        let start = self.p.position();
+        if let Some(s) = self.parse_borrowed_str() {
+            return visited_at(visitor.visit_borrowed_str(s), start);
+        }
         let b = match self.p.peek()? {
             0x22 => parse_utf8_string(&mut self.p)?,
             0x5b => {
@@ -505,7 +1458,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
             }
             _ => return self.p.fail(DecodeError::ExpectedUtf8String),
         };
-        visitor.visit_str(&b)
+        visited_at(visitor.visit_str(&b), start)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -515,11 +1468,11 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         self.deserialize_str(visitor)
     }
 
-    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_bytes<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
         let b = match self.p.peek()? {
             0x22 => parse_utf8_string(&mut self.p)?.into_bytes(),
             0x5b => Vec::<u8>::deserialize(&mut *self)?,
@@ -543,34 +1496,34 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         self.deserialize_bytes(visitor)
     }
 
-    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_option<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
         let position = self.p.position();
         match self.p.peek()? {
             0x22 | 0x5b => {
-                let tag = String::deserialize(&mut *self)?;
+                let tag = self.parse_tag()?;
                 if tag == "None" {
                     return visitor.visit_none();
                 } else {
-                    return self.p.fail_at_position(DecodeError::ExpectedOption, position);
+                    return self.p.fail_at_position(DecodeError::UnexpectedOptionTag(captured_str(&tag)), position);
                 }
             }
             0x7b => {
                 self.p.advance(1);
-                let tag = String::deserialize(&mut *self)?;
+                let tag = self.parse_tag()?;
                 if tag != "Some" {
-                    return self.p.fail_at_position(DecodeError::ExpectedOption, position);
+                    return self.p.fail_at_position(DecodeError::UnexpectedOptionTag(captured_str(&tag)), position);
                 } else {
-                    spaces(&mut self.p)?;
+                    skip_spaces(&mut self.p)?;
                     self.p.expect(':' as u8, DecodeError::ExpectedColon)?;
-                    spaces(&mut self.p)?;
+                    skip_spaces(&mut self.p)?;
                     let value = visitor.visit_some(&mut *self)?;
-                    spaces(&mut self.p)?;
+                    skip_spaces(&mut self.p)?;
                     if self.p.advance_over(b",") {
-                        spaces(&mut self.p)?;
+                        skip_spaces(&mut self.p)?;
                     }
                     self.p.expect('}' as u8, DecodeError::MapClosing)?;
                     return Ok(value);
@@ -580,25 +1533,25 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
                 match self.p.rest().get(1) {
                     None => return self.p.fail(DecodeError::Eoi),
                     Some(0x5b | 0x62 | 0x78 | 0x22 | 0x40) => {
-                        let tag = String::deserialize(&mut *self)?;
+                        let tag = self.parse_tag()?;
                         if tag == "None" {
                             return visitor.visit_none();
                         } else {
-                            return self.p.fail_at_position(DecodeError::ExpectedOption, position);
+                            return self.p.fail_at_position(DecodeError::UnexpectedOptionTag(captured_str(&tag)), position);
                         }
                     }
                     Some(0x7b) => {
                         self.p.advance(2);
-                        let tag = String::deserialize(&mut *self)?;
+                        let tag = self.parse_tag()?;
                         if tag != "Some" {
-                            return self.p.fail_at_position(DecodeError::ExpectedOption, position);
+                            return self.p.fail_at_position(DecodeError::UnexpectedOptionTag(captured_str(&tag)), position);
                         } else {
                             match visitor.visit_some(AlwaysNil::new()) {
                                 Ok(value) => {
 
-                                    spaces(&mut self.p)?;
+                                    skip_spaces(&mut self.p)?;
                                     if self.p.advance_over(b",") {
-                                        spaces(&mut self.p)?;
+                                        skip_spaces(&mut self.p)?;
                                     }
                                     self.p.expect('}' as u8, DecodeError::MapClosing)?;
                                     return Ok(value);
@@ -614,12 +1567,12 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
         }
     }
 
-    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_unit<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
-        self.parse_nil()?;
+        self.value_spaces()?;
+        self.parse_unit()?;
         visitor.visit_unit()
     }
 
@@ -649,64 +1602,33 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
-        match self.p.peek()? {
-            0x22 => {
-                let bytes = parse_utf8_string(&mut self.p)?.into_bytes();
-                let seq = crate::helpers::BytesAsSeq::new(bytes, self.p.position(), DecodeError::OutOfBoundsI8, DecodeError::ExpectedInt);
-                return visitor.visit_seq(seq);
-            }
-            0x5b => {
-                self.p.advance(1);
-                let value = visitor.visit_seq(SequenceAccessor::new(&mut self))?;
-                spaces(&mut self.p)?;
-                self.p.expect(']' as u8, DecodeError::ArrayClosing)?;
-                return Ok(value);
-            }
-            0x40 => {
-                match self.p.rest().get(1) {
-                    None => return self.p.fail(DecodeError::Eoi),
-                    Some(0x5b | 0x62 | 0x78) => {
-                        let bytes = parse_byte_string(&mut self.p)?;
-                        let seq = crate::helpers::BytesAsSeq::new(bytes, self.p.position(), DecodeError::OutOfBoundsI8, DecodeError::ExpectedInt);
-                        return visitor.visit_seq(seq);
-                    }
-                    Some(0x22 | 0x40) => {
-                        let bytes = parse_utf8_string(&mut self.p)?.into_bytes();
-                        let seq = crate::helpers::BytesAsSeq::new(bytes, self.p.position(), DecodeError::OutOfBoundsI8, DecodeError::ExpectedInt);
-                        return visitor.visit_seq(seq);
-                    }
-                    Some(_) => return self.p.fail(DecodeError::Syntax),
-                }
-            }
-            _ => return self.p.fail(DecodeError::ExpectedArray),
-        }
+        self.deserialize_seq_len(None, visitor)
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_tuple<V>(mut self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_seq_len(Some(len), visitor)
     }
 
     fn deserialize_tuple_struct<V>(
-        self,
+        mut self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_seq_len(Some(len), visitor)
     }
 
     fn deserialize_map<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
         let value = if self.p.advance_over(b"@{") {
             visitor.visit_map(MapAccessor::new(&mut self, true))?
         } else if self.p.advance_over(b"{") {
@@ -715,7 +1637,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
             return self.p.fail(DecodeError::ExpectedMap);
         };
 
-        spaces(&mut self.p)?;
+        skip_spaces(&mut self.p)?;
         self.p.expect('}' as u8, DecodeError::MapClosing)?;
         return Ok(value);
     }
@@ -733,7 +1655,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
     }
 
     fn deserialize_enum<V>(
-        self,
+        mut self,
         name: &'static str,
         _variants: &'static [&'static str],
         visitor: V,
@@ -741,17 +1663,20 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        spaces(&mut self.p)?;
+        self.value_spaces()?;
         match self.p.peek()? {
             0x22 | 0x5b => {
-                return visitor.visit_enum(String::deserialize(&mut *self)?.into_deserializer());
+                return match self.parse_tag()? {
+                    Cow::Borrowed(tag) => visitor.visit_enum(BorrowedStrDeserializer::new(tag)),
+                    Cow::Owned(tag) => visitor.visit_enum(tag.into_deserializer()),
+                };
             }
             0x7b => {
                 self.p.advance(1);
-                let value = visitor.visit_enum(Enum::new(self, false))?;
-                spaces(&mut self.p)?;
+                let value = visitor.visit_enum(Enum::new(self.0, false))?;
+                skip_spaces(&mut self.p)?;
                 if self.p.advance_over(b",") {
-                    spaces(&mut self.p)?;
+                    skip_spaces(&mut self.p)?;
                 }
                 self.p.expect('}' as u8, DecodeError::MapClosing)?;
                 return Ok(value);
@@ -760,14 +1685,17 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
                 match self.p.rest().get(1) {
                     None => return self.p.fail(DecodeError::Eoi),
                     Some(0x5b | 0x62 | 0x78 | 0x22 | 0x40) => {
-                        return visitor.visit_enum(String::deserialize(&mut *self)?.into_deserializer());
+                        return match self.parse_tag()? {
+                            Cow::Borrowed(tag) => visitor.visit_enum(BorrowedStrDeserializer::new(tag)),
+                            Cow::Owned(tag) => visitor.visit_enum(tag.into_deserializer()),
+                        };
                     }
                     Some(0x7b) => {
                         self.p.advance(2);
-                        let value = visitor.visit_enum(Enum::new(self, true))?;
-                        spaces(&mut self.p)?;
+                        let value = visitor.visit_enum(Enum::new(self.0, true))?;
+                        skip_spaces(&mut self.p)?;
                         if self.p.advance_over(b",") {
-                            spaces(&mut self.p)?;
+                            skip_spaces(&mut self.p)?;
                         }
                         self.p.expect('}' as u8, DecodeError::MapClosing)?;
                         return Ok(value);
@@ -801,11 +1729,24 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut VVDeserializer<'de> {
 struct SequenceAccessor<'a, 'de> {
     des: &'a mut VVDeserializer<'de>,
     first: bool,
+    read: usize,
+    // The number of items that the sequence must have, if any, and the position at which it starts.
+    len: Option<usize>,
+    start: usize,
 }
 
 impl<'a, 'de> SequenceAccessor<'a, 'de> {
-    fn new(des: &'a mut VVDeserializer<'de>) -> SequenceAccessor<'a, 'de> {
-        SequenceAccessor { des, first: true }
+    fn new(des: &'a mut VVDeserializer<'de>, len: Option<usize>, start: usize) -> SequenceAccessor<'a, 'de> {
+        SequenceAccessor { des, first: true, read: 0, len, start }
+    }
+
+    fn end<T>(&self) -> Result<Option<T>, Error> {
+        match self.len {
+            Some(expected) if self.read < expected => {
+                self.des.p.fail_at_position(DecodeError::WrongArrayLength { expected, found: self.read }, self.start)
+            }
+            _ => Ok(None),
+        }
     }
 }
 
@@ -816,22 +1757,23 @@ impl<'a, 'de> SeqAccess<'de> for SequenceAccessor<'a, 'de> {
     where
         T: DeserializeSeed<'de>,
     {
-        spaces(&mut self.des.p)?;
+        skip_spaces(&mut self.des.p)?;
         let c = self.des.p.peek::<DecodeError>()?;
 
         if c == (']' as u8) {
-            return Ok(None);
+            return self.end();
         } else if c == (',' as u8) && self.first {
             self.des.p.advance(1);
-            spaces(&mut self.des.p)?;
+            skip_spaces(&mut self.des.p)?;
             match self.des.p.peek::<DecodeError>() {
-                Ok(0x5d) => return Ok(None),
+                Ok(0x5d) => return self.end(),
                 _ => return self.des.p.fail(DecodeError::ArrayClosing),
             }
         } else {
             self.first = false;
+            self.read += 1;
             let value = seed.deserialize(&mut *self.des)?;
-            spaces(&mut self.des.p)?;
+            skip_spaces(&mut self.des.p)?;
             self.des.p.advance_over(b",");
             return Ok(Some(value));
         }
@@ -842,11 +1784,14 @@ struct MapAccessor<'a, 'de> {
     des: &'a mut VVDeserializer<'de>,
     set: bool,
     first: bool,
+    // The keys decoded so far, if duplicates are rejected.
+    keys: Option<BTreeSet<Value>>,
 }
 
 impl<'a, 'de> MapAccessor<'a, 'de> {
     fn new(des: &'a mut VVDeserializer<'de>, set: bool) -> MapAccessor<'a, 'de> {
-        MapAccessor { des, set, first: true }
+        let keys = des.reject_duplicate_keys.then(BTreeSet::new);
+        MapAccessor { des, set, first: true, keys }
     }
 }
 
@@ -857,22 +1802,31 @@ impl<'a, 'de> MapAccess<'de> for MapAccessor<'a, 'de> {
     where
         K: DeserializeSeed<'de>,
     {
-        spaces(&mut self.des.p)?;
+        skip_spaces(&mut self.des.p)?;
         let c = self.des.p.peek::<DecodeError>()?;
 
         if c == ('}' as u8) {
             return Ok(None);
         } else if c == (',' as u8) && self.first {
             self.des.p.advance(1);
-            spaces(&mut self.des.p)?;
+            skip_spaces(&mut self.des.p)?;
             match self.des.p.peek::<DecodeError>() {
                 Ok(0x7d) => return Ok(None),
                 _ => return self.des.p.fail(DecodeError::MapClosing),
             }
         } else {
             self.first = false;
+            let start = self.des.p.position();
             let value = seed.deserialize(&mut *self.des)?;
-            return Ok(Some(value));
+
+            if let Some(keys) = &mut self.keys {
+                let key = self.des.key_at(start..self.des.p.position())?;
+                if keys.contains(&key) {
+                    return self.des.p.fail_at_position(DecodeError::DuplicateKey(key), start);
+                }
+                keys.insert(key);
+            }
+            Ok(Some(value))
         }
     }
 
@@ -881,18 +1835,18 @@ impl<'a, 'de> MapAccess<'de> for MapAccessor<'a, 'de> {
         V: DeserializeSeed<'de>,
     {
         if self.set {
-            spaces(&mut self.des.p)?;
+            skip_spaces(&mut self.des.p)?;
             self.des.p.advance_over(b",");
             match seed.deserialize(AlwaysNil::new()) {
                 Ok(nil) => return Ok(nil),
                 Err(_) => return self.des.p.fail(DecodeError::InvalidSet),
             }
         } else {
-            spaces(&mut self.des.p)?;
+            skip_spaces(&mut self.des.p)?;
             self.des.p.expect(':' as u8, DecodeError::ExpectedColon)?;
-            spaces(&mut self.des.p)?;
+            skip_spaces(&mut self.des.p)?;
             let value = seed.deserialize(&mut *self.des)?;
-            spaces(&mut self.des.p)?;
+            skip_spaces(&mut self.des.p)?;
             self.des.p.advance_over(b",");
             return Ok(value);
         }
@@ -924,7 +1878,7 @@ impl<'a, 'de> EnumAccess<'de> for Enum<'a, 'de> {
             return Ok((value, self));
         } else {
             let value = seed.deserialize(&mut *self.des)?;
-            spaces(&mut self.des.p)?;
+            skip_spaces(&mut self.des.p)?;
             self.des.p.expect(':' as u8, DecodeError::ExpectedColon)?;
             return Ok((value, self));
         }
@@ -976,8 +1930,312 @@ mod tests {
     use super::*;
     use std::collections::BTreeMap;
 
+    use crate::Value;
+    use crate::human::SyntaxForm;
+    use crate::testing::XorShift;
+
     use serde::{Serialize, Deserialize};
 
+    #[test]
+    fn option_tag_errors() {
+        let err = Option::<u8>::deserialize(&mut VVDeserializer::new(br#"  "Nonee""#)).unwrap_err();
+        assert_eq!((err.e.clone(), err.position), (DecodeError::UnexpectedOptionTag(Value::from("Nonee")), 2));
+        assert_eq!(err.e.to_string(), r#"expected option, found tag [78,111,110,101,101] ("Nonee")"#);
+
+        let err = Option::<u8>::deserialize(&mut VVDeserializer::new(br#"{"foo": 1}"#)).unwrap_err();
+        assert!(err.e.to_string().contains(r#"[102,111,111] ("foo")"#));
+        let err = Option::<()>::deserialize(&mut VVDeserializer::new(br#"@{"foo"}"#)).unwrap_err();
+        assert!(err.e.to_string().contains(r#"[102,111,111] ("foo")"#));
+
+        let long = format!("\"{}\"", "\u{e9}".repeat(1000));
+        let err = Option::<u8>::deserialize(&mut VVDeserializer::new(long.as_bytes())).unwrap_err();
+        assert!(err.e.to_string().len() < 300);
+    }
+
+    #[test]
+    fn nul_bytes() {
+        let err = Vec::<u8>::deserialize(&mut VVDeserializer::new(b"[1, 2,\x003]")).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::UnexpectedNul { position: 6 }, 6));
+        let err = Vec::<u8>::deserialize(&mut VVDeserializer::new(b"[1, 2\x00]")).unwrap_err();
+        assert_eq!(err.e, DecodeError::UnexpectedNul { position: 5 });
+        let err = crate::Value::deserialize(&mut VVDeserializer::new(b"{\x00: 1}")).unwrap_err();
+        assert_eq!(err.e, DecodeError::UnexpectedNul { position: 1 });
+        let err = crate::Value::deserialize(&mut VVDeserializer::new(b"{1 \x00: 1}")).unwrap_err();
+        assert_eq!(err.e, DecodeError::UnexpectedNul { position: 3 });
+        let err = crate::Value::deserialize(&mut VVDeserializer::new(b"  \x00")).unwrap_err();
+        assert_eq!(err.e, DecodeError::UnexpectedNul { position: 2 });
+
+        // NUL bytes in string literals are not affected.
+        assert_eq!(String::deserialize(&mut VVDeserializer::new(b"\"a\x00b\"")).unwrap(), "a\0b");
+    }
+
+    #[test]
+    fn whitespace_and_comments() {
+        let v = Vec::<u8>::deserialize(&mut VVDeserializer::new(b" \t[\r1,\n2 ,\r\n# comment\n3\r]\r")).unwrap();
+        assert_eq!(v, vec![1, 2, 3]);
+
+        // Carriage returns are whitespace, but do not end comments.
+        let v = Vec::<u8>::deserialize(&mut VVDeserializer::new(b"[1, # a\r2, 3\n4]")).unwrap();
+        assert_eq!(v, vec![1, 4]);
+        let v = Vec::<u8>::deserialize(&mut VVDeserializer::new(b"[1,\r2,\r3]\r")).unwrap();
+        assert_eq!(v, vec![1, 2, 3]);
+
+        // Comments may end the input.
+        let v = u8::deserialize(&mut VVDeserializer::new(b"# a\n1 # b")).unwrap();
+        assert_eq!(v, 1);
+        let err = u8::deserialize(&mut VVDeserializer::new(b"# a\r1")).unwrap_err();
+        assert_eq!(err.e, DecodeError::EmptyInput);
+
+        // Invalid UTF-8 is reported at the start of the comment.
+        let err = Vec::<u8>::deserialize(&mut VVDeserializer::new(b"[1, # a\xff\n2]")).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::CommentUtf8, 4));
+        let err = u8::deserialize(&mut VVDeserializer::new(b"  #\xc3")).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::CommentUtf8, 2));
+    }
+
+    #[derive(PartialEq, Eq, Serialize, Deserialize, Debug)]
+    struct Row {
+        visible: bool,
+        archived: bool,
+    }
+
+    #[test]
+    fn struct_keys_do_not_allocate() {
+        let rows: Vec<Row> = (0..100_000).map(|i| Row { visible: i % 2 == 0, archived: i % 3 == 0 }).collect();
+        let enc = crate::human::to_vec(&rows, 0).unwrap();
+        let (decoded, allocations) = crate::alloc_counter::count_allocations(|| {
+            Vec::<Row>::deserialize(&mut VVDeserializer::new(&enc)).unwrap()
+        });
+        assert_eq!(decoded, rows);
+        assert!(allocations < 100, "{} allocations", allocations);
+
+        // Keys that cannot be borrowed decode just the same.
+        assert_eq!(Row::deserialize(&mut VVDeserializer::new(br#"{"vi\{73}ible": true, @"archived"@: false}"#)).unwrap(), Row { visible: true, archived: false });
+        assert_eq!(Row::deserialize(&mut VVDeserializer::new(br#"{@[118, 105, 115, 105, 98, 108, 101]: true, @x6172636869766564: false}"#)).unwrap(), Row { visible: true, archived: false });
+    }
+
+    #[derive(PartialEq, Eq, Serialize, Deserialize, Debug)]
+    enum Toggle {
+        On,
+        Off,
+    }
+
+    #[test]
+    fn spanned() {
+        let input = b"# config\n{\n  \"xs\": [1, [true,  -2.5 ], \"s\"],\n  2: @{nil, 3},\n  2: {},\n  \"a\": [,],\n}";
+        let spanned = parse_spanned(input).unwrap();
+        assert_eq!(spanned.value, from_slice::<Value>(input).unwrap());
+        let text = |path: &[Value]| std::str::from_utf8(&input[spanned.span_of(path).unwrap()]).unwrap();
+
+        assert_eq!(spanned.spans.span, 9..input.len());
+        assert_eq!(text(&[Value::from("xs"), Value::Int(1), Value::Int(1)]), "-2.5");
+        assert_eq!(spanned.span_of(&[Value::from("xs"), Value::Int(1), Value::Int(1)]), Some(31..35));
+        assert_eq!(text(&[Value::from("xs"), Value::Int(1)]), "[true,  -2.5 ]");
+        assert_eq!(text(&[Value::from("xs"), Value::Int(2)]), r#""s""#);
+        assert_eq!(text(&[Value::from("a")]), "[,]");
+        // The later of two equal keys wins.
+        assert_eq!(text(&[Value::Int(2)]), "{}");
+
+        assert_eq!(spanned.span_of(&[Value::from("xs"), Value::Int(3)]), None);
+        assert_eq!(spanned.span_of(&[Value::from("xs"), Value::Int(2), Value::Int(0)]), None);
+        assert_eq!(spanned.span_of(&[Value::from("nope")]), None);
+
+        // Keys and values of a map alternate in key order, set entries have the span of their key.
+        let spanned = parse_spanned(b"@{3, nil}").unwrap();
+        let spans: Vec<Range<usize>> = spanned.spans.nested.iter().map(|s| s.span.clone()).collect();
+        assert_eq!(spans, vec![5..8, 5..8, 2..3, 2..3]);
+        let spanned = parse_spanned(br#"{"b": 1, "a": [2]}"#).unwrap();
+        let spans: Vec<Range<usize>> = spanned.spans.nested.iter().map(|s| s.span.clone()).collect();
+        assert_eq!(spans, vec![9..12, 14..17, 1..4, 6..7]);
+        assert_eq!(spanned.spans.nested[1].nested[0].span, 15..16);
+
+        let mut des = VVDeserializer::new(b" 1 [2]");
+        assert_eq!(des.deserialize_spanned().unwrap().spans.span, 1..2);
+        assert_eq!(des.deserialize_spanned().unwrap().spans.span, 3..6);
+        assert_eq!(des.last_value_span(), Some(3..6));
+
+        for input in [&b"[1 ,, 2]"[..], b"{1 2}", b"[1, 2", b"@{1: 2}", b"", b"[,1]", b"{,1: 2}"] {
+            assert_eq!(parse_spanned(input).unwrap_err(), from_slice::<Value>(input).unwrap_err(), "{:?}", std::str::from_utf8(input));
+        }
+        for input in [&b"[1 2]"[..], b"{1: 2 3: 4}", b"[1, 2,]", b"@x00ff", b"[@{}, {}]"] {
+            assert_eq!(parse_spanned(input).unwrap().value, from_slice::<Value>(input).unwrap(), "{:?}", std::str::from_utf8(input));
+        }
+    }
+
+    #[test]
+    fn borrowed_keys() {
+        let input = br#"{"ab": 1, @"c\"@: 2, @@"d"@"@@: 3}"#;
+        let decoded = from_slice::<BTreeMap<&str, i64>>(input).unwrap();
+        assert_eq!(decoded.into_iter().collect::<Vec<_>>(), vec![("ab", 1), ("c\\", 2), ("d\"@", 3)]);
+        let decoded = from_slice::<BTreeMap<&str, i64>>(input).unwrap();
+        assert!(decoded.keys().all(|k| input.as_ptr_range().contains(&k.as_ptr())));
+
+        // Keys with escape sequences, and byte strings, must be decoded into owned strings, the error points at the key.
+        for (input, position) in [(&br#"{"x": 1, "a\nb": 2}"#[..], 9), (&br#"{"x": 1, @x6162: 2}"#[..], 9), (&br#"{@[97]: 1}"#[..], 1)] {
+            assert_eq!(from_slice::<BTreeMap<String, i64>>(input).unwrap().len(), if position == 1 { 1 } else { 2 });
+            let err = from_slice::<BTreeMap<&str, i64>>(input).unwrap_err();
+            assert_eq!(err.position, position);
+            assert!(err.e.to_string().ends_with("expected a borrowed string"), "{}", err.e);
+        }
+    }
+
+    #[test]
+    fn tags_do_not_allocate() {
+        let options: Vec<Option<bool>> = (0..100_000).map(|i| if i % 3 == 0 { None } else { Some(i % 2 == 0) }).collect();
+        let enc = crate::human::to_vec(&options, 0).unwrap();
+        let (decoded, allocations) = crate::alloc_counter::count_allocations(|| {
+            Vec::<Option<bool>>::deserialize(&mut VVDeserializer::new(&enc)).unwrap()
+        });
+        assert_eq!(decoded, options);
+        assert!(allocations < 100, "{} allocations", allocations);
+
+        let toggles: Vec<Toggle> = (0..100_000).map(|i| if i % 2 == 0 { Toggle::On } else { Toggle::Off }).collect();
+        let enc = crate::human::to_vec(&toggles, 0).unwrap();
+        let (decoded, allocations) = crate::alloc_counter::count_allocations(|| {
+            Vec::<Toggle>::deserialize(&mut VVDeserializer::new(&enc)).unwrap()
+        });
+        assert_eq!(decoded, toggles);
+        assert!(allocations < 100, "{} allocations", allocations);
+
+        // Tags that cannot be borrowed decode just the same.
+        assert_eq!(Option::<bool>::deserialize(&mut VVDeserializer::new(br#""N\{6f}ne""#)).unwrap(), None);
+        assert_eq!(Option::<bool>::deserialize(&mut VVDeserializer::new(br#"{ [83, 111, 109, 101]: true}"#)).unwrap(), Some(true));
+        assert_eq!(Toggle::deserialize(&mut VVDeserializer::new(br#"@x4f6666"#)).unwrap(), Toggle::Off);
+    }
+
+    #[derive(PartialEq, Eq, Deserialize, Debug)]
+    #[serde(untagged)]
+    enum Untagged {
+        Toggle(Toggle),
+        Int(i64),
+        Bool(bool),
+        List(Vec<Untagged>),
+    }
+
+    // Serde buffers every item once and then tries the variants against the buffered content, so decoding stays linear.
+    // Wall-clock scaling is measured by the `human_scaling` benchmarks.
+    #[test]
+    fn untagged_enums() {
+        let mut enc = b"[".to_vec();
+        for i in 0..2_000 {
+            enc.extend_from_slice(format!(r#"[{}, true, "Off", [[], "On", [false, -{}]]],"#, i, i).as_bytes());
+        }
+        enc.push(b']');
+
+        match Untagged::deserialize(&mut VVDeserializer::new(&enc)).unwrap() {
+            Untagged::List(items) => {
+                assert_eq!(items.len(), 2_000);
+                assert_eq!(items[1], Untagged::List(vec![
+                    Untagged::Int(1),
+                    Untagged::Bool(true),
+                    Untagged::Toggle(Toggle::Off),
+                    Untagged::List(vec![Untagged::List(vec![]), Untagged::Toggle(Toggle::On), Untagged::List(vec![Untagged::Bool(false), Untagged::Int(-1)])]),
+                ]));
+            }
+            other => panic!("expected a list, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn case_insensitive_keywords() {
+        let lenient = |input: &'static [u8]| VVDeserializer::new(input).case_insensitive_keywords(true);
+
+        assert!(bool::deserialize(&mut lenient(b"TRUE")).unwrap());
+        assert!(!bool::deserialize(&mut lenient(b"False")).unwrap());
+        assert_eq!(<()>::deserialize(&mut lenient(b"NIL")).unwrap(), ());
+        assert_eq!(f64::deserialize(&mut lenient(b"Inf")).unwrap(), f64::INFINITY);
+        assert_eq!(f64::deserialize(&mut lenient(b"INF")).unwrap(), f64::INFINITY);
+        assert_eq!(f64::deserialize(&mut lenient(b"-inf")).unwrap(), f64::NEG_INFINITY);
+        assert!(f64::deserialize(&mut lenient(b"nan")).unwrap().is_nan());
+        assert_eq!(
+            Value::deserialize(&mut lenient(b"[Nil, tRUE, FALSE, +INF, NAN, nil, 1.5, Inf]")).unwrap(),
+            Value::Array(vec![Value::Nil, Value::Bool(true), Value::Bool(false), Value::Float(f64::INFINITY), Value::Float(f64::NAN), Value::Nil, Value::Float(1.5), Value::Float(f64::INFINITY)]),
+        );
+
+        assert_eq!(bool::deserialize(&mut VVDeserializer::new(b"TRUE")).unwrap_err().e, DecodeError::ExpectedBool);
+        assert_eq!(<()>::deserialize(&mut VVDeserializer::new(b"NIL")).unwrap_err().e, DecodeError::ExpectedUnit { found: "invalid syntax" });
+        assert!(f64::deserialize(&mut VVDeserializer::new(b"INF")).is_err());
+        assert!(f64::deserialize(&mut VVDeserializer::new(b"inf")).is_err());
+        assert!(Value::deserialize(&mut VVDeserializer::new(b"True")).is_err());
+        assert!(Value::deserialize(&mut lenient(b"Truth")).is_err());
+    }
+
+    #[derive(PartialEq, Eq, Deserialize, Debug)]
+    struct Marker;
+
+    #[test]
+    fn duplicate_keys() {
+        let strict = |input: &'static str| VVDeserializer::new(input.as_bytes()).reject_duplicate_keys(true);
+        let duplicate = |input: &'static str| {
+            let err = Value::deserialize(&mut strict(input)).unwrap_err();
+            match err.e {
+                DecodeError::DuplicateKey(key) => (key, err.position),
+                e => panic!("{:?}", e),
+            }
+        };
+
+        let mut last_wins = BTreeMap::new();
+        last_wins.insert(1u8, 3u8);
+        assert_eq!(BTreeMap::<u8, u8>::deserialize(&mut VVDeserializer::new(b"{1: 2, 1: 3}")).unwrap(), last_wins);
+        assert_eq!(BTreeMap::<u8, u8>::deserialize(&mut strict("{1: 2, 1: 3}")).unwrap_err().e, DecodeError::DuplicateKey(Value::Int(1)));
+
+        assert_eq!(duplicate("{1: 2, 1: 3}"), (Value::Int(1), 7));
+        assert_eq!(duplicate(r#"{"a": 1, [97]: 2}"#), (Value::from("a"), 9));
+        assert_eq!(duplicate("@{1, 2, 0x1}"), (Value::Int(1), 8));
+        assert_eq!(duplicate("{NaN: 1, NaN: 2}"), (Value::Float(f64::NAN), 9));
+        assert_eq!(duplicate("{[1, {}]: 1, [1, {,}]: 2}"), (Value::Array(vec![Value::Int(1), Value::Map(BTreeMap::new())]), 13));
+        assert_eq!(duplicate("[{1: {2: nil, 2: nil}}]").1, 14);
+        assert_eq!(Value::deserialize(&mut strict("{1: nil, 1: nil}")).unwrap_err().to_string(), "parse error at position 9: duplicate key 1");
+
+        assert_eq!(Value::deserialize(&mut strict("[{1: nil}, {1: nil}, {0: nil, 0.0: nil, -0.0: nil}]")).unwrap().as_array().unwrap().len(), 3);
+        let err = <Vec<SingleField>>::deserialize(&mut strict(r#"[{"x": 1, "x": 2}]"#)).unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::DuplicateKey(Value::from("x")), 10));
+    }
+
+    #[derive(PartialEq, Eq, Deserialize, Debug)]
+    struct SingleField {
+        x: u8,
+    }
+
+    #[test]
+    fn lenient_units() {
+        let strict = |input: &'static [u8]| VVDeserializer::new(input);
+        let lenient = |input: &'static [u8]| VVDeserializer::new(input).lenient_units(true);
+
+        for input in [&b"nil"[..], b" nil ", b"[]", b"[,]", b"[ # nothing\n]", b"{}", b"{,}", b"@{}", b"@{ , }"] {
+            let mut des = lenient(input);
+            assert_eq!(<()>::deserialize(&mut des).unwrap(), (), "{:?}", input);
+            des.end().unwrap();
+            assert_eq!(Marker::deserialize(&mut lenient(input)).unwrap(), Marker);
+        }
+        assert_eq!(Vec::<()>::deserialize(&mut lenient(b"[nil, [], {}, @{}]")).unwrap(), vec![(); 4]);
+        assert_eq!(<(Marker, u8)>::deserialize(&mut lenient(b"[{}, 1]")).unwrap(), (Marker, 1));
+
+        let found = |des: &mut VVDeserializer, found: &'static str| {
+            assert_eq!(<()>::deserialize(des).unwrap_err().e, DecodeError::ExpectedUnit { found });
+        };
+        found(&mut strict(b"[]"), "array");
+        found(&mut strict(b"{}"), "map");
+        found(&mut strict(b"@{}"), "set");
+        found(&mut strict(b"@[]"), "byte string");
+        found(&mut strict(b"\"\""), "string");
+        found(&mut strict(b"false"), "bool");
+        found(&mut strict(b"0"), "number");
+        found(&mut strict(b"NaN"), "float");
+        found(&mut strict(b"null"), "invalid syntax");
+        found(&mut lenient(b"[nil]"), "non-empty array");
+        found(&mut lenient(b"{nil: nil}"), "non-empty map");
+        found(&mut lenient(b"@{nil}"), "non-empty set");
+        found(&mut lenient(b"@[]"), "byte string");
+        assert_eq!(Marker::deserialize(&mut strict(b" []")).unwrap_err().position, 1);
+        assert_eq!(Marker::deserialize(&mut lenient(b" [1]")).unwrap_err().position, 1);
+
+        // `Option<()>` stays unambiguous: `"None"` is `None`, everything else that decodes as `()` is `Some(())`.
+        assert_eq!(Option::<()>::deserialize(&mut lenient(b"\"None\"")).unwrap(), None);
+        assert_eq!(Option::<()>::deserialize(&mut lenient(b"{\"Some\": {}}")).unwrap(), Some(()));
+        assert_eq!(Option::<()>::deserialize(&mut lenient(b"{\"Some\": nil}")).unwrap(), Some(()));
+    }
+
     #[test]
     fn floats() {
         let f = f64::deserialize(&mut VVDeserializer::new(b"00_6____.2_7E2_")).unwrap();
@@ -1052,6 +2310,339 @@ mod tests {
         assert_eq!(v, m);
     }
 
+    #[test]
+    fn leading_commas() {
+        assert_eq!(Vec::<u8>::deserialize(&mut VVDeserializer::new(b"[,]")).unwrap(), Vec::<u8>::new());
+        assert_eq!(Vec::<u8>::deserialize(&mut VVDeserializer::new(b"[ , ]")).unwrap(), Vec::<u8>::new());
+        assert_eq!(Vec::<u8>::deserialize(&mut VVDeserializer::new(b"[, 1]")).unwrap_err().e, DecodeError::ArrayClosing);
+        assert_eq!(Vec::<u8>::deserialize(&mut VVDeserializer::new(b"[,, 1]")).unwrap_err().e, DecodeError::ArrayClosing);
+
+        assert_eq!(BTreeMap::<u8, u8>::deserialize(&mut VVDeserializer::new(b"{,}")).unwrap(), BTreeMap::new());
+        assert_eq!(BTreeMap::<u8, u8>::deserialize(&mut VVDeserializer::new(b"{, 1: 1}")).unwrap_err().e, DecodeError::MapClosing);
+        assert_eq!(BTreeMap::<u8, ()>::deserialize(&mut VVDeserializer::new(b"@{, 1}")).unwrap_err().e, DecodeError::MapClosing);
+        assert_eq!(NilStruct::deserialize(&mut VVDeserializer::new(b"{, \"x\": nil}")).unwrap_err().e, DecodeError::MapClosing);
+        assert_eq!(Value::deserialize(&mut VVDeserializer::new(b"{, \"x\": 1}")).unwrap_err().e, DecodeError::MapClosing);
+    }
+
+    #[test]
+    fn bom() {
+        assert_eq!(Vec::<u8>::deserialize(&mut VVDeserializer::new(b"\xEF\xBB\xBF[1, 2]").skip_bom(true)).unwrap(), vec![1, 2]);
+        assert_eq!(Value::deserialize(&mut VVDeserializer::new(b"\xEF\xBB\xBF  nil").skip_bom(true)).unwrap(), Value::Nil);
+        assert_eq!(bool::deserialize(&mut VVDeserializer::new(b"\xEF\xBB\xBF\xEF\xBB\xBFtrue").skip_bom(true)).unwrap_err().e, DecodeError::ExpectedBool);
+        assert_eq!(Value::deserialize(&mut VVDeserializer::new(b"nil").skip_bom(true)).unwrap(), Value::Nil);
+
+        // Off by default.
+        assert!(Vec::<u8>::deserialize(&mut VVDeserializer::new(b"\xEF\xBB\xBF[1, 2]")).is_err());
+        assert!(from_slice::<bool>(b"\xEF\xBB\xBFtrue").is_err());
+        assert!(Vec::<u8>::deserialize(&mut VVDeserializer::new(b"\xEF\xBB\xBF[1, 2]").skip_bom(false)).is_err());
+    }
+
+    #[test]
+    fn malformed_number_literals() {
+        let cases: &[(&[u8], DecodeError, usize)] = &[
+            (b"-", DecodeError::SignDigit, 1),
+            (b"+", DecodeError::SignDigit, 1),
+            (b"- 5", DecodeError::SignDigit, 1),
+            (b"[1, -]", DecodeError::SignDigit, 5),
+            (b"0x", DecodeError::HexDigits, 2),
+            (b"0x]", DecodeError::HexDigits, 2),
+            (b"0xg", DecodeError::HexDigits, 2),
+            (b"0b", DecodeError::BinaryDigits, 2),
+            (b"0b2", DecodeError::BinaryDigits, 2),
+            (b"1.", DecodeError::FloatTrailingDigits, 2),
+            (b"1_000.e5", DecodeError::FloatTrailingDigits, 6),
+            (b"-1.5e", DecodeError::FloatExponentDigit, 5),
+            (b"1.5e+", DecodeError::FloatExponentDigit, 5),
+            (b"{\"a\": 1.}", DecodeError::FloatTrailingDigits, 8),
+            (b"0x1.8p3", DecodeError::NonDecimalFloat, 3),
+            (b"0xA_b.c", DecodeError::NonDecimalFloat, 5),
+            (b"0x1p-3", DecodeError::NonDecimalFloat, 3),
+            (b"0x1P3", DecodeError::NonDecimalFloat, 3),
+            (b"0b1.1", DecodeError::NonDecimalFloat, 3),
+            (b"[0b1_0p3]", DecodeError::NonDecimalFloat, 6),
+        ];
+
+        for (input, e, position) in cases {
+            let err = Value::deserialize(&mut VVDeserializer::new(input)).unwrap_err();
+            assert_eq!((&err.e, err.position), (e, *position), "{}", String::from_utf8_lossy(input));
+        }
+
+        assert_eq!(i8::deserialize(&mut VVDeserializer::new(b"+ 1")).unwrap_err().e, DecodeError::SignDigit);
+        assert_eq!(u64::deserialize(&mut VVDeserializer::new(b"0x")).unwrap_err().e, DecodeError::HexDigits);
+        assert_eq!(f64::deserialize(&mut VVDeserializer::new(b"-.5")).unwrap_err().e, DecodeError::SignDigit);
+        assert_eq!(f64::deserialize(&mut VVDeserializer::new(b"2.")).unwrap_err().e, DecodeError::FloatTrailingDigits);
+        assert_eq!(f64::deserialize(&mut VVDeserializer::new(b"0x1.8p3")).unwrap_err().e, DecodeError::NonDecimalFloat);
+        assert_eq!(i64::deserialize(&mut VVDeserializer::new(b"0x1.8")).unwrap_err().e, DecodeError::NonDecimalFloat);
+        // Other trailing characters are left to the caller, as with decimal ints.
+        assert_eq!(Value::deserialize(&mut VVDeserializer::new(b"0x1e3")).unwrap(), Value::Int(0x1e3));
+        assert_eq!(Value::deserialize(&mut VVDeserializer::new(b"0b1e3")).unwrap(), Value::Int(1));
+
+        assert_eq!(f64::deserialize(&mut VVDeserializer::new(b"-Inf")).unwrap(), f64::NEG_INFINITY);
+        assert_eq!(f64::deserialize(&mut VVDeserializer::new(b"+1.5e-3")).unwrap(), 1.5e-3);
+        assert_eq!(i64::deserialize(&mut VVDeserializer::new(b"0xfF")).unwrap(), 255);
+        assert_eq!(i64::deserialize(&mut VVDeserializer::new(b"0b101")).unwrap(), 5);
+        assert_eq!(i64::deserialize(&mut VVDeserializer::new(b"-0")).unwrap(), 0);
+    }
+
+    #[test]
+    fn integer_floats() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Scale {
+            factor: f64,
+        }
+
+        assert_eq!(f64::deserialize(&mut VVDeserializer::new(b"5")).unwrap(), 5.0);
+        assert_eq!(f64::deserialize(&mut VVDeserializer::new(b"5.0")).unwrap(), 5.0);
+        assert_eq!(f32::deserialize(&mut VVDeserializer::new(b"0x10")).unwrap(), 16.0);
+        assert_eq!(Scale::deserialize(&mut VVDeserializer::new(b"{\"factor\": 5}")).unwrap(), Scale { factor: 5.0 });
+        assert_eq!(Scale::deserialize(&mut VVDeserializer::new(b"{\"factor\": 5.0}")).unwrap(), Scale { factor: 5.0 });
+        assert_eq!(f64::deserialize(&mut VVDeserializer::new(b"true")).unwrap_err().e, DecodeError::ExpectedFloat);
+
+        // The distinction between ints and floats is kept when the target type does not ask for a float.
+        assert_eq!(Value::deserialize(&mut VVDeserializer::new(b"5")).unwrap(), Value::Int(5));
+    }
+
+    #[test]
+    fn fixed_lengths() {
+        let wrong = |input: &[u8], expected: usize, found: usize, position: usize| {
+            let err = <[u8; 4]>::deserialize(&mut VVDeserializer::new(input)).unwrap_err();
+            assert_eq!((err.e, err.position), (DecodeError::WrongArrayLength { expected, found }, position), "{}", String::from_utf8_lossy(input));
+        };
+
+        assert_eq!(<[u8; 4]>::deserialize(&mut VVDeserializer::new(b"[1, 2, 3, 4]")).unwrap(), [1, 2, 3, 4]);
+        assert_eq!(<[u8; 4]>::deserialize(&mut VVDeserializer::new(b"@x01020304")).unwrap(), [1, 2, 3, 4]);
+        assert_eq!(<[u8; 4]>::deserialize(&mut VVDeserializer::new(b"\"abcd\"")).unwrap(), *b"abcd");
+        wrong(b"[1, 2, 3]", 4, 3, 0);
+        wrong(b" [1, 2, 3, 4, 5,]", 4, 5, 1);
+        wrong(b"@[1, 2, 3]", 4, 3, 0);
+        wrong(b"\"abcde\"", 4, 5, 0);
+
+        assert_eq!(<(i32, i32, i32)>::deserialize(&mut VVDeserializer::new(b"[1, -2, 3]")).unwrap(), (1, -2, 3));
+        assert_eq!(<(i32, i32, i32)>::deserialize(&mut VVDeserializer::new(b"[1, -2, 3,]")).unwrap(), (1, -2, 3));
+        assert_eq!(<(i32, i32, i32)>::deserialize(&mut VVDeserializer::new(b"[1, 2]")).unwrap_err().e, DecodeError::WrongArrayLength { expected: 3, found: 2 });
+        assert_eq!(<(i32, i32, i32)>::deserialize(&mut VVDeserializer::new(b"[]")).unwrap_err().e, DecodeError::WrongArrayLength { expected: 3, found: 0 });
+        assert_eq!(<(i32, i32, i32)>::deserialize(&mut VVDeserializer::new(b"[1, 2, 3, [4]]")).unwrap_err().e, DecodeError::WrongArrayLength { expected: 3, found: 4 });
+        assert_eq!(Vec::<(i32, i32, i32)>::deserialize(&mut VVDeserializer::new(b"[[1, 2, 3], [4, 5]]")).unwrap_err().position, 12);
+    }
+
+    #[test]
+    fn last_value_span() {
+        let input = b"# leading comment\n  {\"a\": [\"None\", {\"Some\": 2}]} # another one\n [@[3], \"x\"]";
+        let mut des = VVDeserializer::new(input);
+        assert_eq!(des.last_value_span(), None);
+
+        let first = BTreeMap::<String, Vec<Option<u8>>>::deserialize(&mut des).unwrap();
+        let span = des.last_value_span().unwrap();
+        assert_eq!(&input[span.clone()], &b"{\"a\": [\"None\", {\"Some\": 2}]}"[..]);
+        assert_eq!(BTreeMap::<String, Vec<Option<u8>>>::deserialize(&mut VVDeserializer::new(&input[span])).unwrap(), first);
+
+        let second = Value::deserialize(&mut des).unwrap();
+        let span = des.last_value_span().unwrap();
+        assert_eq!(&input[span.clone()], &b"[@[3], \"x\"]"[..]);
+        assert_eq!(Value::deserialize(&mut VVDeserializer::new(&input[span])).unwrap(), second);
+
+        let mut des = VVDeserializer::new(b"\xEF\xBB\xBF 42 [").skip_bom(true);
+        assert_eq!(u8::deserialize(&mut des).unwrap(), 42);
+        assert_eq!(des.last_value_span(), Some(4..6));
+        assert!(Vec::<u8>::deserialize(&mut des).is_err());
+        assert_eq!(des.last_value_span(), None);
+    }
+
+    #[test]
+    fn scalar_extensions() {
+        // Toy timestamps: days since 2000-01-01, assuming every month has 30 days.
+        fn timestamp(s: &str) -> Result<Value, String> {
+            let parts: Vec<i64> = s.split('-').map(|part| part.parse::<i64>().map_err(|e| e.to_string())).collect::<Result<_, _>>()?;
+            match parts[..] {
+                [y, m, d] => Ok(Value::Int((y - 2000) * 360 + (m - 1) * 30 + (d - 1))),
+                _ => Err(format!("expected a date, got {:?}", s)),
+            }
+        }
+
+        let input = b"[@t\"2024-01-01\", @t\"2000-02-03\"]";
+        let v = Value::deserialize(&mut VVDeserializer::new(input).scalar_extension(b't', timestamp)).unwrap();
+        assert_eq!(v, Value::Array(vec![Value::Int(24 * 360), Value::Int(32)]));
+        // Deserializing into a specific type does not consider extensions.
+        assert_eq!(Vec::<u32>::deserialize(&mut VVDeserializer::new(input).scalar_extension(b't', timestamp)).unwrap_err().e, DecodeError::ExpectedInt);
+
+        assert_eq!(Value::deserialize(&mut VVDeserializer::new(input)).unwrap_err().e, DecodeError::Syntax);
+        let err = Value::deserialize(&mut VVDeserializer::new(b"[1, @t\"2024-01\"]").scalar_extension(b't', timestamp)).unwrap_err();
+        assert_eq!(err.e, DecodeError::ScalarExtension("expected a date, got \"2024-01\"".to_string()));
+        assert_eq!(err.position, 4);
+
+        let hex = |_: &str| Ok(Value::Nil);
+        assert_eq!(Value::deserialize(&mut VVDeserializer::new(b"@x01").scalar_extension(b'x', hex)).unwrap(), Value::Array(vec![Value::Int(1)]));
+    }
+
+    #[test]
+    fn syntax_stats() {
+        let input = br#"# Every form that is counted.
+{
+    "raw": @"a\b"@, # a comment after a value
+    "nested raw": @@"x"@"y"@@,
+    "escaped": ["a\nb", "\{41}", "plain"],
+    "ints": [0xff, 0b101, -7, 1_0.5e-3],
+    "bytes": [@x0b0f, @b00001011, @[1, 0x2,]],
+    "set": @{1, 2},
+    "ext": @t"@x0b",
+    "empty": [,],
+}
+"#;
+        let position = |needle: &str| std::str::from_utf8(input).unwrap().find(needle).unwrap();
+
+        let mut des = VVDeserializer::new(input).scalar_extension(b't', |_| Ok(Value::Nil)).collect_syntax_stats(true);
+        Value::deserialize(&mut des).unwrap();
+        let stats = des.syntax_stats().unwrap();
+
+        let expected = [
+            (SyntaxForm::Comment, 2, 0),
+            (SyntaxForm::TrailingComma, 3, position("0x2,") + 3),
+            (SyntaxForm::HexInt, 2, position("0xff")),
+            (SyntaxForm::BinaryInt, 1, position("0b101")),
+            (SyntaxForm::EscapedString, 2, position("\"a\\nb")),
+            (SyntaxForm::RawString, 2, position("@\"a")),
+            (SyntaxForm::ByteString, 3, position("@x0b0f")),
+            (SyntaxForm::Set, 1, position("@{")),
+            (SyntaxForm::ScalarExtension, 1, position("@t")),
+        ];
+        for (form, count, first) in expected.iter() {
+            assert_eq!(stats.count(*form), *count, "{:?}", form);
+            assert_eq!(stats.first_position(*form), Some(*first), "{:?}", form);
+        }
+        assert_eq!(stats.iter().map(|(form, stats)| (form, stats.count, stats.first)).collect::<Vec<_>>(), expected.to_vec());
+
+        // Looking ahead for options does not count anything twice, and further values add to the counts.
+        let mut des = VVDeserializer::new(b"{\"Some\": {\"Some\": 0x1}} [{\"Some\": 0x2},]").collect_syntax_stats(true);
+        assert_eq!(Option::<Option<u8>>::deserialize(&mut des).unwrap(), Some(Some(1)));
+        assert_eq!(des.syntax_stats().unwrap().count(SyntaxForm::HexInt), 1);
+        Value::deserialize(&mut des).unwrap();
+        assert_eq!(des.syntax_stats().unwrap().count(SyntaxForm::HexInt), 2);
+        assert_eq!(des.syntax_stats().unwrap().first_position(SyntaxForm::TrailingComma), Some(38));
+
+        // Values that fail to deserialize are not counted.
+        let mut des = VVDeserializer::new(b"[0x1, # comment\n 2.5]").collect_syntax_stats(true);
+        assert!(Vec::<u8>::deserialize(&mut des).is_err());
+        assert!(des.syntax_stats().unwrap().is_empty());
+
+        let mut des = VVDeserializer::new(b"# comment\n@{0x1}").collect_syntax_stats(true);
+        des.deserialize_spanned().unwrap();
+        assert_eq!(des.syntax_stats().unwrap().iter().count(), 3);
+
+        let mut des = VVDeserializer::new(input).scalar_extension(b't', |_| Ok(Value::Nil));
+        Value::deserialize(&mut des).unwrap();
+        assert_eq!(des.syntax_stats(), None);
+    }
+
+    #[test]
+    fn float_round_trips() {
+        let round_trip = |f: f64| {
+            let enc = crate::human::to_vec(&f, 0).unwrap();
+            let decoded = f64::deserialize(&mut VVDeserializer::new(&enc)).unwrap();
+            assert_eq!(decoded.to_bits(), f.to_bits(), "{}", String::from_utf8_lossy(&enc));
+            match Value::deserialize(&mut VVDeserializer::new(&enc)).unwrap() {
+                Value::Float(decoded) => assert_eq!(decoded.to_bits(), f.to_bits(), "{}", String::from_utf8_lossy(&enc)),
+                other => panic!("{} decoded to {:?}", String::from_utf8_lossy(&enc), other),
+            }
+        };
+
+        let special = [
+            0.0, -0.0, 1.0, 0.1, 0.3, 0.1 + 0.2, 1e23, 9007199254740993.0, 123456789012345680.0,
+            f64::MIN_POSITIVE, f64::MIN_POSITIVE - f64::from_bits(1), f64::from_bits(1), f64::from_bits(0x000F_FFFF_FFFF_FFFF),
+            f64::MAX, f64::MIN, f64::EPSILON,
+        ];
+        for f in special.iter() {
+            round_trip(*f);
+            round_trip(-*f);
+        }
+
+        let mut rng = XorShift::new(0x2545_F491_4F6C_DD1D);
+        for _ in 0..100_000 {
+            let f = f64::from_bits(rng.next_u64());
+            if f.is_finite() {
+                round_trip(f);
+            }
+        }
+    }
+
+    // Float literals are converted with `f64::from_str`, which rounds correctly, to the nearest float and ties to even. The compact
+    // encoding stores the bits themselves, so both encodings agree on every value.
+    #[test]
+    fn float_literal_rounding() {
+        let cases: &[(&str, u64)] = &[
+            ("0.0", 0x0000_0000_0000_0000),
+            ("-0.0", 0x8000_0000_0000_0000),
+            ("-0.000e-5", 0x8000_0000_0000_0000),
+            ("0.1", 0x3FB9_9999_9999_999A),
+            // The exact value of the float nearest to 0.1.
+            ("0.1000000000000000055511151231257827021181583404541015625", 0x3FB9_9999_9999_999A),
+            // Smallest subnormal, and literals just below and just above half of it.
+            ("4.9406564584124654e-324", 0x0000_0000_0000_0001),
+            ("5.0e-324", 0x0000_0000_0000_0001),
+            ("2.4703282292062327e-324", 0x0000_0000_0000_0000),
+            ("2.4703282292062328e-324", 0x0000_0000_0000_0001),
+            ("-2.4703282292062328e-324", 0x8000_0000_0000_0001),
+            ("-1.0e-400", 0x8000_0000_0000_0000),
+            // Largest subnormal and smallest normal float, and a literal in between that rounds up.
+            ("2.2250738585072009e-308", 0x000F_FFFF_FFFF_FFFF),
+            ("2.2250738585072011e-308", 0x000F_FFFF_FFFF_FFFF),
+            ("2.2250738585072012e-308", 0x0010_0000_0000_0000),
+            ("2.2250738585072014e-308", 0x0010_0000_0000_0000),
+            ("1.0e-310", 0x0000_1268_8B70_E62B),
+            // Ties between two floats round to the one with an even mantissa.
+            ("9007199254740993.0", 0x4340_0000_0000_0000),
+            ("9007199254740995.0", 0x4340_0000_0000_0002),
+            ("9_007_199_254_740_997.0", 0x4340_0000_0000_0002),
+            ("1.00000000000000011102230246251565404236316680908203125", 0x3FF0_0000_0000_0000),
+            ("1.00000000000000033306690738754696212708950042724609375", 0x3FF0_0000_0000_0002),
+            // Largest float, a literal just below the midpoint to the next power of two, and one just above it.
+            ("1.7976931348623157e308", 0x7FEF_FFFF_FFFF_FFFF),
+            ("1.7976931348623158e308", 0x7FEF_FFFF_FFFF_FFFF),
+            ("1.7976931348623159e308", 0x7FF0_0000_0000_0000),
+            ("-1.0e309", 0xFFF0_0000_0000_0000),
+        ];
+
+        for (literal, bits) in cases {
+            let f = f64::deserialize(&mut VVDeserializer::new(literal.as_bytes())).unwrap();
+            assert_eq!(f.to_bits(), *bits, "{}", literal);
+            match Value::deserialize(&mut VVDeserializer::new(literal.as_bytes())).unwrap() {
+                Value::Float(v) => assert_eq!(v.to_bits(), *bits, "{}", literal),
+                other => panic!("{} decoded to {:?}", literal, other),
+            }
+
+            let compact = crate::compact::to_vec(&f).unwrap();
+            assert_eq!(crate::compact::from_slice::<f64>(&compact).unwrap().to_bits(), *bits, "{}", literal);
+            let human = crate::human::to_vec(&f, 0).unwrap();
+            assert_eq!(f64::deserialize(&mut VVDeserializer::new(&human)).unwrap().to_bits(), *bits, "{}", literal);
+        }
+    }
+
+    #[test]
+    fn free_functions() {
+        assert_eq!(from_str::<Vec<u8>>("[1, 2]").unwrap(), vec![1, 2]);
+        assert_eq!(from_slice::<Vec<u8>>(b"[1, 2]").unwrap(), vec![1, 2]);
+        assert_eq!(from_str::<&str>(" \"hi\" # greeting\n\t").unwrap(), "hi");
+
+        let err = from_str::<Vec<u8>>("[1, 2] # comment\n 3").unwrap_err();
+        assert_eq!((err.e, err.position), (DecodeError::TrailingBytes, 18));
+        assert_eq!(from_slice::<u8>(b"1 2").unwrap_err().e, DecodeError::TrailingBytes);
+        assert_eq!(from_slice::<Value>(b"nil,").unwrap_err().e, DecodeError::TrailingBytes);
+
+        // The deserializer itself leaves the rest of the input alone, until asked to check it.
+        let mut des = VVDeserializer::new(b"1 2 ");
+        assert_eq!(u8::deserialize(&mut des).unwrap(), 1);
+        assert_eq!(des.end().unwrap_err().position, 2);
+        assert_eq!(u8::deserialize(&mut des).unwrap(), 2);
+        des.end().unwrap();
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(Value::deserialize(&mut VVDeserializer::new(b"")).unwrap_err().e, DecodeError::EmptyInput);
+        assert_eq!(Value::deserialize(&mut VVDeserializer::new(b"  \n\t # comment")).unwrap_err().e, DecodeError::EmptyInput);
+        assert_eq!(u8::deserialize(&mut VVDeserializer::new(b"\xEF\xBB\xBF ").skip_bom(true)).unwrap_err().e, DecodeError::EmptyInput);
+        assert_eq!(Vec::<u8>::deserialize(&mut VVDeserializer::new(b"[1,")).unwrap_err().e, DecodeError::Eoi);
+    }
+
     #[test]
     fn option() {
         let v = Option::<()>::deserialize(&mut VVDeserializer::new(b"\"None\"")).unwrap();
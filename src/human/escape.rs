@@ -0,0 +1,251 @@
+// Escaping and unescaping of string literal contents, shared by the serializer, the deserializer and code that generates
+// the human-readable encoding by templating.
+
+use atm_parser_helper::ParserHelper;
+use atm_parser_helper_common_syntax::parse_utf8_string;
+
+use super::{DecodeError, EncodeError, Error};
+
+/// Which characters [`escape_str`](escape_str) escapes beyond those it always has to.
+///
+/// Quotes, backslashes, NUL, DEL and all control characters other than line feeds, tabs and carriage returns are always
+/// escaped. The defaults match what [`VVSerializer`](super::VVSerializer) writes by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EscapeOptions {
+    line_feeds: bool,
+    tabs: bool,
+}
+
+impl EscapeOptions {
+    /// Create [`EscapeOptions`](EscapeOptions) that escape only what has to be escaped.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Escape line feeds as `\n`, so that the escaped string fits on a single line. This is what
+    /// [`VVSerializer::escape_line_feeds`](super::VVSerializer::escape_line_feeds) does.
+    pub fn escape_line_feeds(mut self, escape: bool) -> Self {
+        self.line_feeds = escape;
+        self
+    }
+
+    /// Escape tabs as `\t`.
+    pub fn escape_tabs(mut self, escape: bool) -> Self {
+        self.tabs = escape;
+        self
+    }
+}
+
+/// Escape `s` so that it can be placed between the quotes of a string literal of the human-readable encoding.
+///
+/// This is exactly how [`VVSerializer`](super::VVSerializer) writes strings, and [`unescape_str`](unescape_str) undoes it.
+///
+/// ```
+/// use valuable_value::human::{escape_str, unescape_str, EscapeOptions};
+///
+/// let s = "say \"hi\"\n\u{1}";
+/// let escaped = escape_str(s, &EscapeOptions::new().escape_line_feeds(true));
+/// assert_eq!(escaped, r#"say \"hi\"\n\{01}"#);
+/// assert_eq!(unescape_str(&escaped).unwrap(), s);
+/// ```
+pub fn escape_str(s: &str, options: &EscapeOptions) -> String {
+    let mut out = Vec::with_capacity(s.len());
+    escape_into(s, options, false, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+// Append the escaped form of `s` to `out`. With `json`, the output is also a valid JSON string body: line feeds and tabs
+// are escaped regardless of the options, other control characters are rejected, and DEL is written raw.
+pub(crate) fn escape_into(s: &str, options: &EscapeOptions, json: bool, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    for c in s.chars() {
+        if json && c <= '\u{1f}' {
+            match c {
+                '\n' => out.extend_from_slice(b"\\n"),
+                '\t' => out.extend_from_slice(b"\\t"),
+                _ => return Err(EncodeError::JsonControlCharacter(c)),
+            }
+        } else if c == '\0' {
+            out.extend_from_slice(b"\\0");
+        } else if c == '\n' {
+            if options.line_feeds {
+                out.extend_from_slice(b"\\n");
+            } else {
+                out.push('\n' as u8);
+            }
+        } else if c == '\t' {
+            if options.tabs {
+                out.extend_from_slice(b"\\t");
+            } else {
+                out.push('\t' as u8);
+            }
+        } else if c == '\r' {
+            out.push('\r' as u8);
+        } else if c <= '\u{1f}' {
+            out.extend_from_slice(b"\\{");
+            if c <= '\u{0f}' {
+                out.push('0' as u8);
+            } else {
+                out.push('1' as u8);
+            }
+            let nibble = (c as u8) & 0x0f;
+            if nibble <= 9 {
+                out.push(nibble + 0x30);
+            } else {
+                out.push(nibble + 0x37);
+            }
+            out.push('}' as u8);
+        } else if c == '\u{7f}' && !json {
+            out.extend_from_slice(b"\\{7f}");
+        } else if c == '\\' {
+            out.push('\\' as u8);
+            out.push('\\' as u8);
+        } else if c == '"' {
+            out.push('\\' as u8);
+            out.push('"' as u8);
+        } else {
+            let mut buf = [0; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Resolve the escape sequences in the contents of a string literal of the human-readable encoding, i.e., in the text
+/// between its quotes.
+///
+/// Accepts exactly what [`VVDeserializer`](super::VVDeserializer) accepts inside quotes: the escapes `\"`, `\\`, `\0`,
+/// `\n`, `\t` and `\{...}` with one to six hexadecimal digits of a unicode scalar value, and any other character
+/// verbatim, except for an unescaped quote, which is reported as [`DecodeError::TrailingBytes`](DecodeError::TrailingBytes)
+/// since it would end the literal. Error positions are relative to `s`.
+pub fn unescape_str(s: &str) -> Result<String, Error> {
+    let mut quoted = Vec::with_capacity(s.len() + 2);
+    quoted.push(b'"');
+    quoted.extend_from_slice(s.as_bytes());
+    quoted.push(b'"');
+
+    let mut p = ParserHelper::new(&quoted);
+    match parse_utf8_string::<DecodeError>(&mut p) {
+        Ok(unescaped) if p.position() == quoted.len() => Ok(unescaped),
+        Ok(_) => Err(Error { position: p.position() - 2, e: DecodeError::TrailingBytes }),
+        Err(err) => Err(Error { position: err.position.saturating_sub(1).min(s.len()), e: err.e }),
+    }
+}
+
+/// Return the contents of a complete raw string literal of the human-readable encoding, such as `@"a\b"@` or
+/// `@@"say "@ "@@`.
+///
+/// The literal ends at the first quote that is followed by as many `@`s as precede its opening quote, anything after
+/// that is reported as [`DecodeError::TrailingBytes`](DecodeError::TrailingBytes). Quoted literals without `@`s are
+/// rejected with [`DecodeError::ExpectedUtf8String`](DecodeError::ExpectedUtf8String), use
+/// [`unescape_str`](unescape_str) for their contents.
+pub fn unescape_raw(literal: &str) -> Result<String, Error> {
+    let mut p = ParserHelper::new(literal.as_bytes());
+    if !literal.starts_with('@') {
+        return p.fail(DecodeError::ExpectedUtf8String);
+    }
+    let contents = parse_utf8_string::<DecodeError>(&mut p)?;
+    if p.position() != literal.len() {
+        return p.fail(DecodeError::TrailingBytes);
+    }
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::human::{self, VVSerializer};
+    use crate::testing::XorShift;
+    use serde::Serialize;
+
+    fn serialize(s: &str, escape_line_feeds: bool) -> String {
+        let mut serializer = VVSerializer::new(Vec::new(), 0).escape_line_feeds(escape_line_feeds);
+        s.serialize(&mut serializer).unwrap();
+        String::from_utf8(serializer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn escape_classes() {
+        let cases = [
+            ("plain ünïcödé 🦀", "plain ünïcödé 🦀", "plain ünïcödé 🦀"),
+            ("\"", "\\\"", "\\\""),
+            ("\\", "\\\\", "\\\\"),
+            ("\0", "\\0", "\\0"),
+            ("\n", "\n", "\\n"),
+            ("\t", "\t", "\\t"),
+            ("\r", "\r", "\r"),
+            ("\u{1}\u{b}\u{1f}", "\\{01}\\{0B}\\{1F}", "\\{01}\\{0B}\\{1F}"),
+            ("\u{7f}", "\\{7f}", "\\{7f}"),
+        ];
+        for (s, minimal, everything) in cases {
+            assert_eq!(escape_str(s, &EscapeOptions::new()), minimal);
+            assert_eq!(escape_str(s, &EscapeOptions::new().escape_line_feeds(true).escape_tabs(true)), everything);
+            assert_eq!(unescape_str(minimal).unwrap(), s);
+            assert_eq!(unescape_str(everything).unwrap(), s);
+        }
+
+        // The serializer writes exactly the escaped string between quotes.
+        for s in ["a\nb\tc", "\"\\\0\u{7f}\u{1}"] {
+            assert_eq!(serialize(s, false), format!("\"{}\"", escape_str(s, &EscapeOptions::new())));
+            assert_eq!(serialize(s, true), format!("\"{}\"", escape_str(s, &EscapeOptions::new().escape_line_feeds(true))));
+        }
+    }
+
+    #[test]
+    fn unescape() {
+        assert_eq!(unescape_str("\\{41}\\{1F980}\\{0000e9}").unwrap(), "A🦀é");
+        assert_eq!(unescape_str("").unwrap(), "");
+
+        let err = |s: &str| {
+            let err = unescape_str(s).unwrap_err();
+            (err.position, err.e)
+        };
+        assert_eq!(err("ab\"c"), (2, DecodeError::TrailingBytes));
+        assert_eq!(err("a\\r"), (3, DecodeError::Utf8StringEscape));
+        assert_eq!(err("\\{}"), (2, DecodeError::UnicodeDigits));
+        assert_eq!(err("\\{1234567}"), (9, DecodeError::UnicodeDigits));
+        assert_eq!(err("\\{d800}"), (6, DecodeError::UnicodeScalar));
+        assert_eq!(err("\\{41"), (4, DecodeError::UnicodeClosing));
+        assert_eq!(err("a\\"), (2, DecodeError::Eoi));
+    }
+
+    #[test]
+    fn raw() {
+        assert_eq!(unescape_raw("@\"a\\b\"@").unwrap(), "a\\b");
+        assert_eq!(unescape_raw("@@\"say \"@ \"@@").unwrap(), "say \"@ ");
+        assert_eq!(unescape_raw("@\"\"@").unwrap(), "");
+        assert_eq!(human::from_str::<String>("@@\"say \"@ \"@@").unwrap(), "say \"@ ");
+
+        assert_eq!(unescape_raw("\"a\"").unwrap_err().e, DecodeError::ExpectedUtf8String);
+        assert_eq!(unescape_raw("@\"a\"@ ").unwrap_err().position, 5);
+        assert_eq!(unescape_raw("@\"a\"@ ").unwrap_err().e, DecodeError::TrailingBytes);
+        assert_eq!(unescape_raw("@\"a\"").unwrap_err().e, DecodeError::Eoi);
+    }
+
+    // Draw characters from every escape class, and from the rest of the unicode range.
+    fn random_string(rng: &mut XorShift) -> String {
+        let mut next = || rng.next_u64();
+        let len = next() % 20;
+        (0..len).map(|_| {
+            let r = next();
+            match r % 4 {
+                0 => "\"\\\0\n\t\r\u{7f}@{}".chars().nth((r / 4 % 10) as usize).unwrap(),
+                1 => char::from_u32((r / 4 % 0x20) as u32).unwrap(),
+                2 => char::from_u32((r / 4 % 0x80) as u32).unwrap(),
+                _ => char::from_u32((r / 4 % 0x11_0000) as u32).unwrap_or('\u{fffd}'),
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut rng = XorShift::new(0x2545_f491_4f6c_dd1d);
+        for _ in 0..10_000 {
+            let s = random_string(&mut rng);
+            for options in [EscapeOptions::new(), EscapeOptions::new().escape_line_feeds(true).escape_tabs(true)] {
+                let escaped = escape_str(&s, &options);
+                assert_eq!(unescape_str(&escaped).unwrap(), s, "{:?}", escaped);
+                assert_eq!(human::from_str::<String>(&format!("\"{}\"", escaped)).unwrap(), s);
+            }
+        }
+    }
+}
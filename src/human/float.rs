@@ -0,0 +1,206 @@
+// The float formatting of `FloatStyle::Deterministic`, which depends on nothing but the bits of the float.
+
+use std::str::FromStr;
+
+// Floats whose decimal exponent (in scientific notation) lies in this range are written without an exponent.
+const MIN_PLAIN_EXPONENT: i32 = -5;
+const MAX_PLAIN_EXPONENT: i32 = 15;
+
+// Write a finite float, using the fewest significant digits such that rounding the exact value of the float to that many
+// digits (with ties to even) yields a literal that decodes to the same float.
+pub(crate) fn write_deterministic(v: f64, out: &mut Vec<u8>) {
+    if v.is_sign_negative() {
+        out.push(b'-');
+    }
+    if v == 0.0 {
+        out.extend_from_slice(b"0.0");
+        return;
+    }
+
+    let (exact, k) = exact_digits(v.abs());
+    let (digits, k) = (1..exact.len())
+        .map(|n| round(&exact, k, n))
+        .find(|(digits, k)| parses_to(digits, *k, v.abs()))
+        .unwrap_or((exact, k));
+
+    let exponent = k - 1;
+    if (MIN_PLAIN_EXPONENT..=MAX_PLAIN_EXPONENT).contains(&exponent) {
+        if k <= 0 {
+            out.extend_from_slice(b"0.");
+            out.resize(out.len() + (-k) as usize, b'0');
+            out.extend_from_slice(&digits);
+        } else if k as usize >= digits.len() {
+            out.extend_from_slice(&digits);
+            out.resize(out.len() + (k as usize - digits.len()), b'0');
+            out.extend_from_slice(b".0");
+        } else {
+            out.extend_from_slice(&digits[..k as usize]);
+            out.push(b'.');
+            out.extend_from_slice(&digits[k as usize..]);
+        }
+    } else {
+        out.push(digits[0]);
+        out.push(b'.');
+        if digits.len() == 1 {
+            out.push(b'0');
+        } else {
+            out.extend_from_slice(&digits[1..]);
+        }
+        out.push(b'e');
+        out.extend_from_slice(exponent.to_string().as_bytes());
+    }
+}
+
+// The exact decimal digits (as ASCII, without trailing zeros) of a finite, positive float, and the exponent `k` such that
+// the float is `0.d1d2d3... * 10^k`. The float is `mantissa * 2^exponent`, which is `mantissa * 5^-exponent / 10^-exponent`
+// for negative exponents, so its decimal expansion is finite.
+fn exact_digits(v: f64) -> (Vec<u8>, i32) {
+    let bits = v.to_bits();
+    let biased = ((bits >> 52) & 0x7ff) as i32;
+    let fraction = bits & ((1 << 52) - 1);
+    let (mantissa, exponent) = if biased == 0 { (fraction, -1074) } else { (fraction | (1 << 52), biased - 1075) };
+
+    // Little-endian limbs in base 10^9.
+    let mut limbs = vec![mantissa % 1_000_000_000, mantissa / 1_000_000_000 % 1_000_000_000, mantissa / 1_000_000_000_000_000_000];
+    let mut remaining = exponent.unsigned_abs();
+    let (base, chunk, power) = if exponent >= 0 { (2, 29, 1 << 29) } else { (5, 13, 1_220_703_125) };
+    while remaining > 0 {
+        let n = remaining.min(chunk);
+        mul_small(&mut limbs, if n == chunk { power } else { (base as u64).pow(n) });
+        remaining -= n;
+    }
+
+    while limbs.len() > 1 && limbs[limbs.len() - 1] == 0 {
+        limbs.pop();
+    }
+    let mut digits = limbs[limbs.len() - 1].to_string().into_bytes();
+    for limb in limbs.iter().rev().skip(1) {
+        digits.extend_from_slice(format!("{:09}", limb).as_bytes());
+    }
+
+    let point = if exponent >= 0 { 0 } else { -exponent };
+    let k = digits.len() as i32 - point;
+    while digits.last() == Some(&b'0') {
+        digits.pop();
+    }
+    (digits, k)
+}
+
+fn mul_small(limbs: &mut Vec<u64>, factor: u64) {
+    let mut carry = 0;
+    for limb in limbs.iter_mut() {
+        let product = *limb * factor + carry;
+        *limb = product % 1_000_000_000;
+        carry = product / 1_000_000_000;
+    }
+    while carry > 0 {
+        limbs.push(carry % 1_000_000_000);
+        carry /= 1_000_000_000;
+    }
+}
+
+// Round `0.digits * 10^k` to `n` significant digits, with ties to even, and strip trailing zeros.
+fn round(digits: &[u8], k: i32, n: usize) -> (Vec<u8>, i32) {
+    let mut rounded = digits[..n].to_vec();
+    let up = match digits[n] {
+        b'0'..=b'4' => false,
+        b'5' if digits[n + 1..].iter().all(|d| *d == b'0') => (rounded[n - 1] - b'0') % 2 == 1,
+        _ => true,
+    };
+
+    let mut k = k;
+    if up {
+        match rounded.iter().rposition(|d| *d != b'9') {
+            Some(i) => {
+                rounded[i] += 1;
+                rounded.truncate(i + 1);
+            }
+            None => {
+                rounded = vec![b'1'];
+                k += 1;
+            }
+        }
+    }
+    while rounded.last() == Some(&b'0') {
+        rounded.pop();
+    }
+    (rounded, k)
+}
+
+// Decoding is correctly rounded, so this does not depend on the platform either.
+fn parses_to(digits: &[u8], k: i32, v: f64) -> bool {
+    let literal = format!("0.{}e{}", std::str::from_utf8(digits).unwrap(), k);
+    f64::from_str(&literal).is_ok_and(|parsed| parsed.to_bits() == v.to_bits())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(v: f64) -> String {
+        let mut out = Vec::new();
+        write_deterministic(v, &mut out);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn corpus() {
+        let two_53 = 9007199254740992.0;
+        let cases: &[(f64, &str)] = &[
+            (0.0, "0.0"),
+            (-0.0, "-0.0"),
+            (1.0, "1.0"),
+            (-1.5, "-1.5"),
+            (0.1, "0.1"),
+            (0.3, "0.3"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (100.0, "100.0"),
+            (123.456, "123.456"),
+            (1e-5, "0.00001"),
+            (1.5e-6, "1.5e-6"),
+            (1e15, "1000000000000000.0"),
+            (1e16, "1.0e16"),
+            (1e23, "1.0e23"),
+            (1e300, "1.0e300"),
+            (-1e300, "-1.0e300"),
+            (123456789012345680.0, "1.2345678901234568e17"),
+            (two_53 - 1.0, "9007199254740991.0"),
+            (two_53, "9007199254740992.0"),
+            // Not representable, this is the float nearest to 2^53 + 1, which is 2^53.
+            (two_53 + 1.0, "9007199254740992.0"),
+            (two_53 + 2.0, "9007199254740994.0"),
+            (5e-324, "5.0e-324"),
+            (1e-323, "1.0e-323"),
+            (2.225073858507201e-308, "2.225073858507201e-308"),
+            (f64::MIN_POSITIVE, "2.2250738585072014e-308"),
+            (f64::MAX, "1.7976931348623157e308"),
+            (f64::MIN, "-1.7976931348623157e308"),
+            (f64::EPSILON, "2.220446049250313e-16"),
+            (1.0 / 3.0, "0.3333333333333333"),
+            (2.0 / 3.0, "0.6666666666666666"),
+        ];
+
+        for (v, expected) in cases {
+            let formatted = format(*v);
+            assert_eq!(&formatted, expected, "{:e}", v);
+            assert_eq!(f64::from_str(&formatted).unwrap().to_bits(), v.to_bits(), "{}", formatted);
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        // Pseudo-random bit patterns, covering subnormals and most binades.
+        let mut bits = 1u64;
+        for i in 0..4096u64 {
+            let v = f64::from_bits(bits);
+            if v.is_finite() {
+                let formatted = format(v);
+                assert_eq!(f64::from_str(&formatted).unwrap().to_bits(), bits, "{}", formatted);
+                // 17 significant digits suffice for every float.
+                let mantissa = formatted.split('e').next().unwrap().replace(['-', '.'], "");
+                assert!(mantissa.trim_matches('0').len() <= 17, "{}", formatted);
+            }
+            bits = bits.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407 ^ i) & !(1 << 63);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::Value;
+use super::{Error, VVDeserializer};
+
+/// A valuable value as parsed from the human-readable encoding, except that integers too large for an `i64` are kept as their decimal digits rather than rejected.
+///
+/// Map entries are kept in the order in which they appear in the input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LosslessValue {
+    Nil,
+    Bool(bool),
+    Float(f64),
+    Int(i64),
+    /// A decimal integer literal outside the range of an `i64`, as its optional minus sign followed by its digits (without underscores and leading zeros).
+    BigInt(String),
+    Array(Vec<LosslessValue>),
+    Map(Vec<(LosslessValue, LosslessValue)>),
+}
+
+impl LosslessValue {
+    /// Convert into a [`Value`](crate::Value), or return `None` if this contains a [`BigInt`](LosslessValue::BigInt).
+    pub fn into_value(self) -> Option<Value> {
+        match self {
+            LosslessValue::Nil => Some(Value::Nil),
+            LosslessValue::Bool(b) => Some(Value::Bool(b)),
+            LosslessValue::Float(n) => Some(Value::Float(n)),
+            LosslessValue::Int(n) => Some(Value::Int(n)),
+            LosslessValue::BigInt(_) => None,
+            LosslessValue::Array(vs) => vs.into_iter().map(LosslessValue::into_value).collect::<Option<Vec<_>>>().map(Value::Array),
+            LosslessValue::Map(entries) => {
+                let mut m = std::collections::BTreeMap::new();
+                for (k, v) in entries {
+                    m.insert(k.into_value()?, v.into_value()?);
+                }
+                Some(Value::Map(m))
+            }
+        }
+    }
+}
+
+/// Parse a valuable value in the human-readable encoding, keeping out-of-range decimal integers as [`BigInt`](LosslessValue::BigInt)s.
+///
+/// Out-of-range hexadecimal and binary integers are still rejected. Like [`VVDeserializer`](VVDeserializer), this does not enforce that the input must be empty after the first valid code.
+pub fn parse_value_lossless(input: &[u8]) -> Result<LosslessValue, Error> {
+    LosslessValue::deserialize(&mut VVDeserializer::new_lossless(input))
+}
+
+struct LosslessVisitor;
+
+impl<'de> Visitor<'de> for LosslessVisitor {
+    type Value = LosslessValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a well-formed valuable value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(LosslessValue::Nil)
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<Self::Value, E> {
+        Ok(LosslessValue::Bool(b))
+    }
+
+    fn visit_i64<E: de::Error>(self, n: i64) -> Result<Self::Value, E> {
+        Ok(LosslessValue::Int(n))
+    }
+
+    fn visit_f64<E: de::Error>(self, n: f64) -> Result<Self::Value, E> {
+        Ok(LosslessValue::Float(n))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+        Ok(LosslessValue::Array(s.bytes().map(|b| LosslessValue::Int(b as i64)).collect()))
+    }
+
+    fn visit_bytes<E: de::Error>(self, s: &[u8]) -> Result<Self::Value, E> {
+        Ok(LosslessValue::Array(s.iter().map(|b| LosslessValue::Int(*b as i64)).collect()))
+    }
+
+    // Only reached for decimal integers that do not fit into an `i64`.
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        Ok(LosslessValue::BigInt(String::deserialize(deserializer)?))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut v = Vec::new();
+        while let Some(x) = seq.next_element()? {
+            v.push(x);
+        }
+        Ok(LosslessValue::Array(v))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(LosslessValue::Map(entries))
+    }
+}
+
+impl<'de> Deserialize<'de> for LosslessValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(LosslessVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::human::DecodeError;
+
+    #[test]
+    fn big_int() {
+        assert_eq!(
+            parse_value_lossless(b"1234567890123456789012345678901234567890").unwrap(),
+            LosslessValue::BigInt("1234567890123456789012345678901234567890".to_string()),
+        );
+        assert_eq!(
+            parse_value_lossless(b"[-99_999_999_999_999_999_999, 1, +9223372036854775808]").unwrap(),
+            LosslessValue::Array(vec![
+                LosslessValue::BigInt("-99999999999999999999".to_string()),
+                LosslessValue::Int(1),
+                LosslessValue::BigInt("9223372036854775808".to_string()),
+            ]),
+        );
+        assert_eq!(
+            parse_value_lossless(b"{\"a\": 1.5, @{nil}: 99999999999999999999}").unwrap(),
+            LosslessValue::Map(vec![
+                (LosslessValue::Array(vec![LosslessValue::Int(0x61)]), LosslessValue::Float(1.5)),
+                (LosslessValue::Map(vec![(LosslessValue::Nil, LosslessValue::Nil)]), LosslessValue::BigInt("99999999999999999999".to_string())),
+            ]),
+        );
+        assert_eq!(parse_value_lossless(b"0xffffffffffffffffff").unwrap_err().e, DecodeError::OutOfBoundsI64);
+
+        // Leading zeros are dropped.
+        assert_eq!(parse_value_lossless(b"0099999999999999999999").unwrap(), LosslessValue::BigInt("99999999999999999999".to_string()));
+        assert_eq!(parse_value_lossless(b"-0_099999999999999999999").unwrap(), LosslessValue::BigInt("-99999999999999999999".to_string()));
+        assert_eq!(parse_value_lossless(b"+0099999999999999999999").unwrap(), LosslessValue::BigInt("99999999999999999999".to_string()));
+
+        // An exponent makes the literal a float, whose mantissa must fit into an `i64`.
+        assert_eq!(parse_value_lossless(b"99999999999999999999e3").unwrap_err().e, DecodeError::OutOfBoundsI64);
+        assert_eq!(parse_value_lossless(b"[-99999999999999999999E1]").unwrap_err().e, DecodeError::OutOfBoundsI64);
+    }
+
+    #[test]
+    fn into_value() {
+        let input = b"{\"a\": [1, -2.5, nil, true], \"b\": @{1}}";
+        assert_eq!(
+            parse_value_lossless(input).unwrap().into_value(),
+            Some(Value::deserialize(&mut VVDeserializer::new(input)).unwrap()),
+        );
+        assert_eq!(parse_value_lossless(b"[99999999999999999999]").unwrap().into_value(), None);
+    }
+}
@@ -1,8 +1,13 @@
 use std::fmt;
+use std::io;
 
 use serde::ser::{self, Serializer, Serialize};
 use thiserror::Error;
 
+use super::escape::{escape_into, EscapeOptions};
+use crate::value::array_as_string;
+use crate::{ConvertError, Value};
+
 /// Everything that can go wrong during serialization of a valuable value into the human-readable encoding.
 #[derive(Error, Debug, PartialEq, Eq, Clone)]
 pub enum EncodeError {
@@ -14,6 +19,24 @@ pub enum EncodeError {
     OutOfBoundsCollection,
     #[error("collections must have a known length")]
     UnknownLength,
+    /// Attempted to encode a NaN whose bit pattern is not the canonic `u64::MAX` while [`preserve_nan_payloads`](VVSerializer::preserve_nan_payloads) was set. Holds the bit pattern.
+    #[error("the human-readable encoding cannot represent the NaN with bit pattern {0:#018x}")]
+    NanPayload(u64),
+    /// Attempted to encode NaN or an infinity under [`Profile::JsonCompat`](Profile::JsonCompat) without a placeholder.
+    #[error("JSON cannot represent NaN or infinite floats")]
+    NonFiniteFloat,
+    /// Attempted to encode a map key that is not a string under [`Profile::JsonCompat`](Profile::JsonCompat).
+    #[error("JSON map keys must be strings")]
+    NonStringKey,
+    /// Attempted to encode nil under [`Profile::JsonCompat`](Profile::JsonCompat).
+    #[error("nil has no representation that is valid both as JSON and as human-readable encoding")]
+    JsonNil,
+    /// Attempted to encode a string containing a control character other than a line feed or a tab under [`Profile::JsonCompat`](Profile::JsonCompat).
+    #[error("the control character {0:?} has no representation that is valid both as JSON and as human-readable encoding")]
+    JsonControlCharacter(char),
+    /// Writing the output failed, see [`write_all`](write_all).
+    #[error("failed to write output: {0}")]
+    Io(String),
 }
 
 impl serde::ser::Error for EncodeError {
@@ -22,12 +45,117 @@ impl serde::ser::Error for EncodeError {
     }
 }
 
+/// The line terminator written by a pretty-printing [`VVSerializer`](VVSerializer).
+///
+/// Like [`FloatStyle`](FloatStyle) and [`Profile`](Profile), this implements `Serialize` and `Deserialize`, so that it can be part of a configuration file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum LineEnding {
+    /// A single line feed (`\n`).
+    #[default]
+    Lf,
+    /// A carriage return followed by a line feed (`\r\n`).
+    CrLf,
+}
+
+/// How a [`VVSerializer`](VVSerializer) writes finite floats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum FloatStyle {
+    /// The shortest literal that decodes to the same float, as chosen by the `pretty_dtoa` crate. The exact digits and
+    /// the choice between plain and exponential notation may change between versions of that crate.
+    #[default]
+    Shortest,
+    /// A format that is fixed by this crate, for output that must stay byte-for-byte identical, e.g., golden files.
+    ///
+    /// The digits are those of the exact value of the float, rounded (with ties to even) to the fewest significant digits
+    /// such that the literal decodes to the same float. The result depends only on the bits of the float, not on the
+    /// platform or on other crates, and will not change in future versions. Floats in the range `1e-5 <= |x| < 1e16`
+    /// are written without exponent (`0.00001`, `123.0`), all others as one digit, a point, the remaining digits (at
+    /// least one) and an exponent without plus sign (`1.0e16`, `2.5e-7`). Zero is written as `0.0` or `-0.0`, infinities
+    /// and NaN as usual.
+    Deterministic,
+}
+
+/// Which subset of the human-readable encoding a [`VVSerializer`](VVSerializer) may use.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum Profile {
+    /// The full human-readable encoding.
+    #[default]
+    Full,
+    /// Output that is both valid human-readable encoding and valid JSON, decoding to the same value either way.
+    ///
+    /// Byte strings are written as arrays of ints, line feeds and tabs in strings are escaped, and pretty-printed
+    /// collections get no trailing commas. The serializer never writes comments, sets, raw strings or underscores in
+    /// numbers anyway. Everything without a representation in both encodings is an error: non-string map keys
+    /// ([`EncodeError::NonStringKey`](EncodeError::NonStringKey)), nil ([`EncodeError::JsonNil`](EncodeError::JsonNil)),
+    /// other control characters in strings ([`EncodeError::JsonControlCharacter`](EncodeError::JsonControlCharacter)),
+    /// and NaN and infinities ([`EncodeError::NonFiniteFloat`](EncodeError::NonFiniteFloat)), unless
+    /// `non_finite_placeholder` is set, in which case they are written as that string instead. Map keys that are arrays of
+    /// ints forming valid UTF-8 (such as the keys of a [`Value`](crate::Value) map) count as strings.
+    JsonCompat { non_finite_placeholder: Option<String> },
+}
+
 /// A structure that serializes valuable values in the [human-readable encoding](https://github.com/AljoschaMeyer/valuable-value#human-readable-encoding).
 pub struct VVSerializer {
     out: Vec<u8>,
     indentation: usize,
     current_indentation: usize,
     multiline: bool,
+    inline_structs_up_to: usize,
+    line_ending: LineEnding,
+    escape_line_feeds: bool,
+    max_width: Option<usize>,
+    preserve_nan_payloads: bool,
+    float_style: FloatStyle,
+    profile: Profile,
+    // Whether the value that is currently being written must stay on a single line, see `serialize_fitting`.
+    flat: bool,
+    // For each collection that is currently being written on a single line, where its contents start, or `None` if it must stay on a single line.
+    inline_starts: Vec<Option<usize>>,
+    // For each collection that is currently being written, the offset at which its contents start, and how many elements (or entries) have been written so far.
+    written: Vec<Level>,
+    // How many wrapper maps (see `serialize_wrapped`) are currently being written.
+    wrapping: usize,
+    // Where the line that is currently being written starts, i.e., the offset after the last line feed in `out`.
+    line_start: usize,
+    // How many UTF-8 continuation bytes have been written in total, and before `line_start`, see `column`.
+    continuation: usize,
+    line_continuation: usize,
+    // While trying to fit a value on a single line (see `serialize_fitting`): the greatest column the line may reach, and where the value starts.
+    fitting: Option<(usize, usize)>,
+    // Whether the current attempt to fit a value on a single line has been abandoned.
+    too_wide: bool,
+    // Line breaks that are inserted once the outermost value is complete, see `apply_breaks`.
+    breaks: Vec<Break>,
+    // How many characters follow the value that is currently being written on its line, as passed to `serialize_fitting`.
+    trailing: usize,
+    // How many bytes of output were moved after being written, lets tests check that layout stays linear.
+    #[cfg(test)]
+    rescanned: usize,
+}
+
+// A collection that is currently being written.
+struct Level {
+    // Where its contents start.
+    start: usize,
+    // How many elements (or entries) have been written so far.
+    written: usize,
+    // The `line_start` and `line_continuation` before its contents were started.
+    line_start: usize,
+    line_continuation: usize,
+    // Whether the last element is on its own line and still needs its comma and line ending, which are only written once it
+    // is known whether another element follows.
+    pending_comma: bool,
+    // How many characters follow each element on its line: the comma of elements on their own lines, otherwise the
+    // closing delimiters of this collection and of all enclosing collections that end on the same line.
+    trailing: usize,
+}
+
+// A line break to insert into the output, followed by the indentation of a nesting level.
+struct Break {
+    offset: usize,
+    // How many bytes at `offset` the break replaces.
+    replace: usize,
+    indentation: usize,
 }
 
 impl VVSerializer {
@@ -35,8 +163,396 @@ impl VVSerializer {
     ///
     /// Does pretty-printing if the indentation is greater than zero.
     pub fn new(out: Vec<u8>, indentation: usize) -> Self {
-        VVSerializer { out, indentation, current_indentation: 0, multiline: false }
+        let line_start = out.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+        let line_continuation = out[..line_start].iter().filter(|b| is_continuation(**b)).count();
+        let continuation = line_continuation + out[line_start..].iter().filter(|b| is_continuation(**b)).count();
+        VVSerializer {
+            out,
+            indentation,
+            current_indentation: 0,
+            multiline: false,
+            inline_structs_up_to: 0,
+            line_ending: LineEnding::Lf,
+            escape_line_feeds: false,
+            max_width: None,
+            preserve_nan_payloads: false,
+            float_style: FloatStyle::Shortest,
+            profile: Profile::Full,
+            flat: false,
+            inline_starts: Vec::new(),
+            written: Vec::new(),
+            wrapping: 0,
+            line_start,
+            continuation,
+            line_continuation,
+            fitting: None,
+            too_wide: false,
+            breaks: Vec::new(),
+            trailing: 0,
+            #[cfg(test)]
+            rescanned: 0,
+        }
+    }
+
+    /// When pretty-printing, render structs with at most `n` fields on a single line.
+    ///
+    /// Maps that do not originate from a struct are not affected by this.
+    pub fn inline_structs_up_to(mut self, n: usize) -> Self {
+        self.inline_structs_up_to = n;
+        self
+    }
+
+    /// Set the line terminator for pretty-printing, defaults to [`LineEnding::Lf`](LineEnding::Lf).
+    ///
+    /// This does not affect line feeds inside strings, combine [`LineEnding::CrLf`](LineEnding::CrLf) with
+    /// [`escape_line_feeds`](VVSerializer::escape_line_feeds) for output without any bare line feeds.
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Escape line feeds inside strings as `\n` instead of writing them as they are, see
+    /// [`EscapeOptions::escape_line_feeds`](super::EscapeOptions::escape_line_feeds). Without indentation, every value then
+    /// fits on a single line.
+    pub fn escape_line_feeds(mut self, escape: bool) -> Self {
+        self.escape_line_feeds = escape;
+        self
+    }
+
+    /// When pretty-printing, render nested values on a single line if the line then stays within `max_width` characters (including indentation, and the commas and closing delimiters that follow on the same line), and break them across lines only otherwise.
+    ///
+    /// Without this, collections with more than one item are always broken across lines. The outermost value is laid out as if no `max_width` was set, since its layout must be decided before its contents are known.
+    pub fn max_width(mut self, max_width: usize) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    /// Fail with [`EncodeError::NanPayload`](EncodeError::NanPayload) instead of writing `NaN` for a NaN whose bit pattern is not the canonic `u64::MAX`.
+    ///
+    /// The human-readable encoding has a single `NaN` literal, which decodes to the canonic NaN, so without this option the payload (and sign) of any other NaN is silently lost.
+    pub fn preserve_nan_payloads(mut self, preserve: bool) -> Self {
+        self.preserve_nan_payloads = preserve;
+        self
+    }
+
+    /// Set how finite floats are written, defaults to [`FloatStyle::Shortest`](FloatStyle::Shortest).
+    pub fn float_style(mut self, style: FloatStyle) -> Self {
+        self.float_style = style;
+        self
+    }
+
+    /// Set which subset of the encoding to write, defaults to [`Profile::Full`](Profile::Full).
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Return the encoding written so far.
+    pub fn into_inner(self) -> Vec<u8> {
+        self.out
+    }
+
+    // Serialize a nested value that will be followed by `trailing` more characters on its line. With a `max_width`, first try
+    // writing it on a single line, giving up as soon as that line turns out too long, then discard it and write the value again
+    // with the usual layout (in which its own nested values get the same treatment).
+    fn serialize_fitting<T>(&mut self, value: &T, trailing: usize) -> Result<(), EncodeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let outer = std::mem::replace(&mut self.trailing, trailing);
+        let result = self.serialize_fitting_inner(value);
+        self.trailing = outer;
+        result
+    }
+
+    // `serialize_fitting` once `trailing` has been set, so that the collections opened by the value can account for it.
+    fn serialize_fitting_inner<T>(&mut self, value: &T) -> Result<(), EncodeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let trailing = self.trailing;
+        let limit = match self.max_width {
+            Some(max_width) if self.indentation != 0 && !self.flat => max_width.checked_sub(trailing),
+            _ => return value.serialize(&mut *self),
+        };
+        let limit = match limit {
+            Some(limit) if self.column() <= limit => limit,
+            _ => return value.serialize(&mut *self),
+        };
+
+        let start = self.out.len();
+        let (line_start, line_continuation, continuation, breaks) = (self.line_start, self.line_continuation, self.continuation, self.breaks.len());
+        let (written, inline_starts, current_indentation, wrapping, multiline) = (self.written.len(), self.inline_starts.len(), self.current_indentation, self.wrapping, self.multiline);
+        self.flat = true;
+        self.fitting = Some((limit, start));
+        let result = value.serialize(&mut *self);
+        self.flat = false;
+        self.fitting = None;
+        self.multiline = multiline;
+
+        if self.too_wide || self.column() > limit || self.line_start > start {
+            #[cfg(test)]
+            {
+                self.rescanned += self.out.len() - start;
+            }
+            // The attempt may have been abandoned anywhere inside the value.
+            self.too_wide = false;
+            self.out.truncate(start);
+            self.line_start = line_start;
+            self.line_continuation = line_continuation;
+            self.continuation = continuation;
+            self.breaks.truncate(breaks);
+            self.written.truncate(written);
+            self.inline_starts.truncate(inline_starts);
+            self.current_indentation = current_indentation;
+            self.wrapping = wrapping;
+            value.serialize(&mut *self)?;
+        } else {
+            result?;
+        }
+        Ok(())
+    }
+
+    // While trying to fit a value on a single line, abandon the attempt as soon as the line is too long or has been broken.
+    fn check_width(&mut self) -> Result<(), EncodeError> {
+        if let Some((limit, start)) = self.fitting {
+            if self.column() > limit || self.line_start > start {
+                self.too_wide = true;
+                return Err(EncodeError::Message("line too wide".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    // How many characters the current line has so far.
+    fn column(&self) -> usize {
+        (self.out.len() - self.line_start) - (self.continuation - self.line_continuation)
+    }
+
+    // Account for text that was written from `start` onwards, which may contain line feeds and non-ASCII characters.
+    fn wrote_text(&mut self, start: usize) {
+        for (i, b) in self.out[start..].iter().enumerate() {
+            if is_continuation(*b) {
+                self.continuation += 1;
+            } else if *b == b'\n' {
+                self.line_start = start + i + 1;
+                self.line_continuation = self.continuation;
+            }
+        }
+    }
+
+    fn json_compat(&self) -> bool {
+        matches!(self.profile, Profile::JsonCompat { .. })
+    }
+
+    fn newline(&mut self) {
+        self.out.extend_from_slice(self.line_ending_bytes());
+        self.line_start = self.out.len();
+        self.line_continuation = self.continuation;
+    }
+
+    fn line_ending_bytes(&self) -> &'static [u8] {
+        match self.line_ending {
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf => b"\r\n",
+        }
+    }
+
+    // How many collections and wrapper maps are currently being written.
+    fn depth(&self) -> usize {
+        self.written.len() + self.wrapping
+    }
+
+    // Write the value of a single-entry wrapper map (`{"Some": ...}` or `{"Variant": ...}`) and close the map. When pretty-printing a value that spans several lines, the value goes on its own, indented lines, and the closing brace on a line of its own.
+    fn serialize_wrapped<T>(&mut self, value: &T) -> Result<(), EncodeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.indentation == 0 {
+            value.serialize(&mut *self)?;
+            self.out.push('}' as u8);
+            return Ok(());
+        }
+
+        // The space before the value is replaced by a line break should the value span several lines.
+        self.out.push(b' ');
+        let start = self.out.len();
+        let old = self.multiline;
+        self.wrapping += 1;
+        self.current_indentation += 1;
+        // The value is followed by the closing brace, and by whatever follows the wrapper map.
+        self.serialize_fitting(value, 1 + self.trailing)?;
+        self.current_indentation -= 1;
+        self.wrapping -= 1;
+        self.multiline = old;
+
+        if self.line_start > start {
+            self.break_at(start - 1, 1);
+
+            self.newline();
+            self.write_indentation();
+        }
+        self.out.push('}' as u8);
+        if self.depth() == 0 {
+            self.apply_breaks();
+        }
+        Ok(())
+    }
+
+    // Start writing the contents of a collection on separate lines, unless the current value must stay on a single line.
+    // `closing` is the number of characters of its closing delimiters.
+    fn open_multiline(&mut self, closing: usize) {
+        if self.flat {
+            return self.open_inline(false, closing);
+        }
+        self.written.push(Level { start: self.out.len(), written: 0, line_start: self.line_start, line_continuation: self.line_continuation, pending_comma: false, trailing: 1 });
+        if self.indentation != 0 {
+            self.newline();
+        }
+        self.multiline = true;
+        self.current_indentation += 1;
+    }
+
+    // Start writing the contents of a collection on the same line as its opening delimiter. If `breakable`, the contents are moved onto their own lines after all should they turn out to span several lines, see `close_inline`.
+    fn open_inline(&mut self, breakable: bool, closing: usize) {
+        self.multiline = false;
+        self.written.push(Level { start: self.out.len(), written: 0, line_start: self.line_start, line_continuation: self.line_continuation, pending_comma: false, trailing: self.trailing + closing });
+        if self.indentation != 0 {
+            self.inline_starts.push(if breakable { Some(self.out.len()) } else { None });
+            self.current_indentation += 1;
+        }
     }
+
+    // Called before writing the closing delimiter of a collection opened with `open_inline`.
+    fn close_inline(&mut self) {
+        if self.indentation != 0 {
+            self.current_indentation = self.current_indentation.saturating_sub(1);
+            if let Some(Some(start)) = self.inline_starts.pop() {
+                if self.line_start > start {
+                    self.break_at(start, 0);
+
+                    if !self.json_compat() {
+                        self.out.push(',' as u8);
+                    }
+                    self.newline();
+                    self.write_indentation();
+                }
+            }
+        }
+    }
+
+    // Replace `replace` bytes at the given offset by a line break, followed by the indentation of the next-deeper nesting
+    // level. The break is only recorded, inserting it right away would move everything after it, once for every nesting level.
+    fn break_at(&mut self, offset: usize, replace: usize) {
+        self.breaks.push(Break { offset, replace, indentation: self.current_indentation + 1 });
+    }
+
+    // Insert all recorded line breaks, in a single pass over the output. Called once the outermost value is complete, so no
+    // offsets into the output are held anywhere else.
+    fn apply_breaks(&mut self) {
+        if self.breaks.is_empty() {
+            return;
+        }
+        let mut breaks = std::mem::take(&mut self.breaks);
+        breaks.sort_by_key(|b| b.offset);
+
+        let mut out = Vec::with_capacity(self.out.len() + breaks.len() * (2 + (self.current_indentation + 1) * self.indentation));
+        let mut copied = 0;
+        for b in breaks {
+            out.extend_from_slice(&self.out[copied..b.offset]);
+            out.extend_from_slice(self.line_ending_bytes());
+            out.resize(out.len() + b.indentation * self.indentation, b' ');
+            copied = b.offset + b.replace;
+        }
+        out.extend_from_slice(&self.out[copied..]);
+        #[cfg(test)]
+        {
+            self.rescanned += self.out.len();
+        }
+
+        // Every break is followed by a line feed of the value it belongs to, so they all precede the current line.
+        self.line_start += out.len() - self.out.len();
+        self.out = out;
+    }
+
+    // Write whatever must precede the next element (or entry) of the innermost collection.
+    fn start_element(&mut self) -> Result<(), EncodeError> {
+        self.check_width()?;
+        let written = self.written.last().map_or(0, |level| level.written);
+        if let Some(level) = self.written.last_mut().filter(|level| level.pending_comma) {
+            level.pending_comma = false;
+            self.out.push(b',');
+            self.newline();
+        }
+        if self.multiline {
+            self.write_indentation();
+            if self.indentation == 0 && written > 0 {
+                self.out.push(',' as u8);
+            }
+        } else if written > 0 {
+            self.out.push(',' as u8);
+            if self.indentation != 0 {
+                self.out.push(' ' as u8);
+            }
+        }
+        Ok(())
+    }
+
+    // How many characters follow an element (or entry) of the innermost collection on its line.
+    fn element_trailing(&self) -> usize {
+        self.written.last().map_or(0, |level| level.trailing)
+    }
+
+    // Write whatever must follow an element (or entry) of the innermost collection.
+    fn finish_element(&mut self) -> Result<(), EncodeError> {
+        self.check_width()?;
+        let pending_comma = self.multiline && self.indentation != 0;
+        if let Some(level) = self.written.last_mut() {
+            level.written += 1;
+            level.pending_comma = pending_comma;
+        }
+        Ok(())
+    }
+
+    // Write whatever must precede the closing delimiter of the innermost collection.
+    fn end_collection(&mut self) {
+        let level = self.written.pop().unwrap_or(Level { start: self.out.len(), written: 0, line_start: self.line_start, line_continuation: self.line_continuation, pending_comma: false, trailing: 0 });
+        if self.multiline {
+            self.current_indentation = self.current_indentation.saturating_sub(1);
+            if level.written == 0 {
+                // Do not spread empty collections across several lines.
+                self.out.truncate(level.start);
+                self.line_start = level.line_start;
+                self.line_continuation = level.line_continuation;
+            } else {
+                if level.pending_comma {
+                    // JSON does not allow a comma after the last item.
+                    if !self.json_compat() {
+                        self.out.push(b',');
+                    }
+                    self.newline();
+                }
+                self.write_indentation();
+            }
+        } else {
+            self.close_inline();
+        }
+        if self.depth() == 0 {
+            self.apply_breaks();
+        }
+    }
+
+    fn write_indentation(&mut self) {
+        for _ in 0..self.current_indentation {
+            for _ in 0..self.indentation {
+                self.out.push(' ' as u8);
+            }
+        }
+    }
+}
+
+fn is_continuation(b: u8) -> bool {
+    (b & 0b1100_0000) == 0b1000_0000
 }
 
 /// Write human-readable encoding into a Vec.
@@ -51,6 +567,41 @@ where
     Ok(serializer.out)
 }
 
+/// Write the human-readable encodings of all values into the writer, each on a line of its own, and return the total number of bytes written.
+///
+/// The values are written without pretty-printing, and line feeds in strings are escaped, so every value is followed by exactly one line feed. Each value is encoded completely before any of it is written. If `flush_each` is set, the writer is flushed after every value, otherwise it is never flushed. On an error, all values before the one that caused it have been written (and flushed if `flush_each` is set), errors of the writer are reported as [`EncodeError::Io`](EncodeError::Io).
+///
+/// ```
+/// use valuable_value::human;
+///
+/// let mut out = Vec::new();
+/// assert_eq!(human::write_all(&mut out, vec!["a\nb", "c"], false).unwrap(), 11);
+/// assert_eq!(out, b"\"a\\nb\"\n\"c\"\n");
+/// ```
+pub fn write_all<W, I>(mut writer: W, values: I, flush_each: bool) -> Result<u64, EncodeError>
+where
+    W: io::Write,
+    I: IntoIterator,
+    I::Item: Serialize,
+{
+    let mut serializer = VVSerializer::new(Vec::new(), 0).escape_line_feeds(true);
+    let mut total = 0;
+    for value in values {
+        serializer.out.clear();
+        serializer.line_start = 0;
+        serializer.continuation = 0;
+        serializer.line_continuation = 0;
+        value.serialize(&mut serializer)?;
+        serializer.out.push(b'\n');
+        writer.write_all(&serializer.out).map_err(|e| EncodeError::Io(e.to_string()))?;
+        if flush_each {
+            writer.flush().map_err(|e| EncodeError::Io(e.to_string()))?;
+        }
+        total += serializer.out.len() as u64;
+    }
+    Ok(total)
+}
+
 impl<'a> Serializer for &'a mut VVSerializer {
     type Ok = ();
     type Error = EncodeError;
@@ -110,12 +661,26 @@ impl<'a> Serializer for &'a mut VVSerializer {
     }
 
     fn serialize_f64(self, v: f64) -> Result<(), EncodeError> {
+        if let Profile::JsonCompat { non_finite_placeholder } = &self.profile {
+            if !v.is_finite() {
+                return match non_finite_placeholder.clone() {
+                    Some(placeholder) => self.serialize_str(&placeholder),
+                    None => Err(EncodeError::NonFiniteFloat),
+                };
+            }
+        }
+
         if v.is_nan() {
+            if self.preserve_nan_payloads && v.to_bits() != u64::MAX {
+                return Err(EncodeError::NanPayload(v.to_bits()));
+            }
             self.out.extend_from_slice(b"NaN");
         } else if v == f64::INFINITY {
             self.out.extend_from_slice(b"Inf");
         } else if v == f64::NEG_INFINITY {
             self.out.extend_from_slice(b"-Inf");
+        } else if self.float_style == FloatStyle::Deterministic {
+            super::float::write_deterministic(v, &mut self.out);
         } else {
             let config = pretty_dtoa::FmtFloatConfig::default()
                 .add_point_zero(true);
@@ -130,88 +695,25 @@ impl<'a> Serializer for &'a mut VVSerializer {
     }
 
     fn serialize_str(self, v: &str) -> Result<(), EncodeError> {
+        let options = EscapeOptions::new().escape_line_feeds(self.escape_line_feeds);
         self.out.push('"' as u8);
-        for c in v.chars() {
-            if c == '\0' {
-                self.out.extend_from_slice(b"\\0");
-            } else if c == '\n' {
-                self.out.push('\n' as u8);
-            } else if c == '\t' {
-                self.out.push('\t' as u8);
-            } else if c == '\r' {
-                self.out.push('\r' as u8);
-            } else if c <= '\u{1f}' {
-                self.out.extend_from_slice(b"\\{");
-                if c <= '\u{0f}' {
-                    self.out.push('0' as u8);
-                } else {
-                    self.out.push('1' as u8);
-                }
-                let nibble = (c as u8) & 0x0f;
-                if nibble <= 9 {
-                    self.out.push(nibble + 0x30);
-                } else {
-                    self.out.push(nibble + 0x37);
-                }
-                self.out.push('}' as u8);
-            } else if c == '\u{7f}' {
-                self.out.extend_from_slice(b"\\{7f}");
-            } else if c == '\\' {
-                self.out.push('\\' as u8);
-                self.out.push('\\' as u8);
-            } else if c == '"' {
-                self.out.push('\\' as u8);
-                self.out.push('"' as u8);
-            } else {
-                self.out.extend_from_slice(c.to_string().as_bytes());
-            }
-        }
+        let start = self.out.len();
+        escape_into(v, &options, self.json_compat(), &mut self.out)?;
+        self.wrote_text(start);
         self.out.push('"' as u8);
         Ok(())
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<(), EncodeError> {
-        self.out.extend_from_slice(b"@[");
-
-        match v.len() {
-            0 => self.out.push(']' as u8),
-            1 => {
-                self.serialize_u8(v[0])?;
-                self.out.push(']' as u8);
-            }
-            _ if self.indentation == 0 => {
-                for i in v.iter() {
-                    self.serialize_u8(*i)?;
-                    self.out.extend_from_slice(b",");
-                }
-                self.out.pop(); // pop last comma
-                self.out.push(']' as u8);
-            }
-            _ => {
-                self.out.push('\n' as u8);
-                self.current_indentation += 1;
-
-                for i in v.iter() {
-                    for _ in 0..self.current_indentation {
-                        for _ in 0..self.indentation {
-                            self.out.push(' ' as u8);
-                        }
-                    }
-                    self.serialize_u8(*i)?;
-                    self.out.extend_from_slice(b",\n");
-                }
-
-                self.current_indentation -= 1;
-                for _ in 0..self.current_indentation {
-                    for _ in 0..self.indentation {
-                        self.out.push(' ' as u8);
-                    }
-                }
-                self.out.push(']' as u8);
-            }
+        // Byte strings are laid out exactly like arrays of the same ints.
+        if !self.json_compat() {
+            self.out.push('@' as u8);
         }
-
-        return Ok(());
+        let mut seq = self.serialize_seq(Some(v.len()))?;
+        for b in v.iter() {
+            ser::SerializeSeq::serialize_element(&mut seq, b)?;
+        }
+        ser::SerializeSeq::end(seq)
     }
 
     fn serialize_none(self) -> Result<(), EncodeError> {
@@ -222,16 +724,16 @@ impl<'a> Serializer for &'a mut VVSerializer {
     where
         T: ?Sized + Serialize,
     {
-        self.out.extend_from_slice(b"{\"Some\":");
-        if self.indentation != 0 {
-            self.out.push(' ' as u8);
-        }
-        value.serialize(&mut *self)?;
-        self.out.push('}' as u8);
-        Ok(())
+        self.out.push(b'{');
+        self.serialize_str("Some")?;
+        self.out.push(b':');
+        self.serialize_wrapped(value)
     }
 
     fn serialize_unit(self) -> Result<(), EncodeError> {
+        if self.json_compat() {
+            return Err(EncodeError::JsonNil);
+        }
         Ok(self.out.extend_from_slice(b"nil"))
     }
 
@@ -272,25 +774,14 @@ impl<'a> Serializer for &'a mut VVSerializer {
         self.out.push('{' as u8);
         variant.serialize(&mut *self)?;
         self.out.extend_from_slice(b":");
-        if self.indentation != 0 {
-            self.out.push(' ' as u8);
-        }
-        value.serialize(&mut *self)?;
-        self.out.push('}' as u8);
-        Ok(())
+        self.serialize_wrapped(value)
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
         self.out.push('[' as u8);
         match len {
-            Some(0 | 1) => self.multiline = false,
-            _ => {
-                if self.indentation != 0 {
-                    self.out.push('\n' as u8);
-                }
-                self.multiline = true;
-                self.current_indentation += 1;
-            }
+            Some(0 | 1) => self.open_inline(true, 1),
+            _ => self.open_multiline(1),
         }
         Ok(self)
     }
@@ -322,14 +813,8 @@ impl<'a> Serializer for &'a mut VVSerializer {
         }
         self.out.push('[' as u8);
         match len {
-            0 | 1 => self.multiline = false,
-            _ => {
-                if self.indentation != 0 {
-                    self.out.push('\n' as u8);
-                }
-                self.multiline = true;
-                self.current_indentation += 1;
-            }
+            0 | 1 => self.open_inline(true, 2),
+            _ => self.open_multiline(2),
         }
         Ok(self)
     }
@@ -337,14 +822,8 @@ impl<'a> Serializer for &'a mut VVSerializer {
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
         self.out.push('{' as u8);
         match len {
-            Some(0 | 1) => self.multiline = false,
-            _ => {
-                if self.indentation != 0 {
-                    self.out.push('\n' as u8);
-                }
-                self.multiline = true;
-                self.current_indentation += 1;
-            }
+            Some(0 | 1) => self.open_inline(true, 1),
+            _ => self.open_multiline(1),
         }
         Ok(self)
     }
@@ -354,7 +833,13 @@ impl<'a> Serializer for &'a mut VVSerializer {
         _name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
-        self.serialize_map(Some(len))
+        if self.indentation != 0 && len <= self.inline_structs_up_to {
+            self.out.push('{' as u8);
+            self.open_inline(len <= 1, 1);
+            Ok(self)
+        } else {
+            self.serialize_map(Some(len))
+        }
     }
 
     fn serialize_struct_variant(
@@ -372,14 +857,9 @@ impl<'a> Serializer for &'a mut VVSerializer {
         }
         self.out.push('{' as u8);
         match len {
-            0 | 1 => self.multiline = false,
-            _ => {
-                if self.indentation != 0 {
-                    self.out.push('\n' as u8);
-                }
-                self.multiline = true;
-                self.current_indentation += 1;
-            }
+            _ if self.indentation != 0 && len <= self.inline_structs_up_to => self.open_inline(len <= 1, 2),
+            0 | 1 => self.open_inline(true, 2),
+            _ => self.open_multiline(2),
         }
         Ok(self)
     }
@@ -393,40 +873,17 @@ impl<'a> ser::SerializeSeq for &'a mut VVSerializer {
     where
         T: ?Sized + Serialize,
     {
-        if self.multiline {
-            for _ in 0..self.current_indentation {
-                for _ in 0..self.indentation {
-                    self.out.push(' ' as u8);
-                }
-            }
-        }
+        self.start_element()?;
         let old = self.multiline;
-        value.serialize(&mut **self)?;
+        self.serialize_fitting(value, self.element_trailing())?;
         self.multiline = old;
-
-        if self.multiline {
-            self.out.push(',' as u8);
-            if self.indentation != 0 {
-                self.out.push('\n' as u8);
-            }
-        }
+        self.finish_element()?;
 
         Ok(())
     }
 
     fn end(self) -> Result<(), EncodeError> {
-        if self.multiline {
-            self.current_indentation -= 1;
-            for _ in 0..self.current_indentation {
-                for _ in 0..self.indentation {
-                    self.out.push(' ' as u8);
-                }
-            }
-        }
-
-        if *self.out.last().unwrap() == (',' as u8) {
-            self.out.pop(); // pop last comma
-        }
+        self.end_collection();
 
         self.out.push(']' as u8);
         Ok(())
@@ -490,16 +947,25 @@ impl<'a> ser::SerializeMap for &'a mut VVSerializer {
     where
         T: ?Sized + Serialize,
     {
-        if self.multiline {
-            for _ in 0..self.current_indentation {
-                for _ in 0..self.indentation {
-                    self.out.push(' ' as u8);
-                }
+        self.start_element()?;
+        if self.json_compat() {
+            // Strings are arrays of ints, so a key serialized as an array (e.g. a key of a `Value` map) may be a string as well.
+            let key = crate::to_value(key).map_err(|e| match e {
+                ConvertError::Message(msg) => EncodeError::Message(msg),
+                ConvertError::OutOfBoundsInt => EncodeError::OutOfBoundsInt,
+            })?;
+            match key {
+                Value::Array(vs) => match array_as_string(&vs) {
+                    Some(s) => self.serialize_str(&s)?,
+                    None => return Err(EncodeError::NonStringKey),
+                },
+                _ => return Err(EncodeError::NonStringKey),
             }
+        } else {
+            let old = self.multiline;
+            key.serialize(&mut **self)?;
+            self.multiline = old;
         }
-        let old = self.multiline;
-        key.serialize(&mut **self)?;
-        self.multiline = old;
 
         self.out.push(':' as u8);
         if self.indentation != 0 {
@@ -514,31 +980,14 @@ impl<'a> ser::SerializeMap for &'a mut VVSerializer {
         T: ?Sized + Serialize,
     {
         let old = self.multiline;
-        value.serialize(&mut **self)?;
+        self.serialize_fitting(value, self.element_trailing())?;
         self.multiline = old;
-
-        if self.multiline {
-            self.out.push(',' as u8);
-            if self.indentation != 0 {
-                self.out.push('\n' as u8);
-            }
-        }
+        self.finish_element()?;
         Ok(())
     }
 
     fn end(self) -> Result<(), EncodeError> {
-        if self.multiline {
-            self.current_indentation -= 1;
-            for _ in 0..self.current_indentation {
-                for _ in 0..self.indentation {
-                    self.out.push(' ' as u8);
-                }
-            }
-        }
-
-        if *self.out.last().unwrap() == (',' as u8) {
-            self.out.pop(); // pop last comma
-        }
+        self.end_collection();
 
         self.out.push('}' as u8);
         Ok(())
@@ -578,9 +1027,916 @@ impl<'a> ser::SerializeStructVariant for &'a mut VVSerializer {
     }
 }
 
-// #[test]
-// fn human_serialized() {
-//     println!("{}", std::str::from_utf8(&to_vec(&crate::test_type::new(), 0).unwrap()).unwrap());
-//     println!("{}", std::str::from_utf8(&to_vec(&crate::test_type::new(), 2).unwrap()).unwrap());
-//     panic!("This panic simply ensures that the above was indeed printed.");
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use serde::{Deserialize, Serialize};
+
+    // Accepts `limit` bytes, then fails.
+    struct LimitedWriter {
+        out: Vec<u8>,
+        limit: usize,
+        flushes: usize,
+    }
+
+    impl io::Write for LimitedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.limit - self.out.len());
+            if n == 0 && !buf.is_empty() {
+                return Err(io::Error::other("full"));
+            }
+            self.out.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_all_values() {
+        let records: Vec<(u32, String, Option<f64>)> = (0..1000u32).map(|i| (i * 7919, format!("line {}\nof {}", i, "\\".repeat((i % 3) as usize)), (i % 4 != 0).then(|| i as f64 / 8.0))).collect();
+        let read_all = |input: &[u8]| {
+            let mut des = crate::human::VVDeserializer::new(input);
+            let mut values: Vec<(u32, String, Option<f64>)> = Vec::new();
+            while des.end().is_err() {
+                values.push(Deserialize::deserialize(&mut des).unwrap());
+            }
+            values
+        };
+
+        let mut writer = LimitedWriter { out: Vec::new(), limit: usize::MAX, flushes: 0 };
+        let written = write_all(&mut writer, &records, false).unwrap();
+        assert_eq!(written, writer.out.len() as u64);
+        assert_eq!(writer.flushes, 0);
+        assert_eq!(writer.out.iter().filter(|b| **b == b'\n').count(), 1000);
+        assert_eq!(read_all(&writer.out), records);
+
+        let mut flushing = LimitedWriter { out: Vec::new(), limit: usize::MAX, flushes: 0 };
+        assert_eq!(write_all(&mut flushing, &records, true).unwrap(), written);
+        assert_eq!(flushing.flushes, 1000);
+        assert_eq!(flushing.out, writer.out);
+
+        // The writer fails in the middle of the 501st line.
+        let limit = writer.out.iter().enumerate().filter(|(_, b)| **b == b'\n').nth(499).unwrap().0 + 1;
+        let mut failing = LimitedWriter { out: Vec::new(), limit: limit + 3, flushes: 0 };
+        assert_eq!(write_all(&mut failing, &records, true).unwrap_err(), EncodeError::Io("full".to_string()));
+        assert_eq!(failing.flushes, 500);
+        assert_eq!(read_all(&failing.out[..limit]), &records[..500]);
+
+        // Encoding errors stop the writing just the same.
+        let mut out = Vec::new();
+        assert_eq!(write_all(&mut out, vec![1, u64::MAX, 2], false).unwrap_err(), EncodeError::OutOfBoundsInt);
+        assert_eq!(out, b"1\n");
+    }
+
+    #[derive(Serialize)]
+    struct Big {
+        a: u8,
+        b: Vec<u8>,
+    }
+
+    #[derive(Serialize)]
+    enum E {
+        Long(Vec<u8>),
+        Short(u8),
+    }
+
+    fn fitting<T: Serialize>(x: &T, max_width: usize) -> String {
+        let mut serializer = VVSerializer::new(Vec::new(), 2).max_width(max_width);
+        x.serialize(&mut serializer).unwrap();
+        String::from_utf8(serializer.out).unwrap()
+    }
+
+    fn pretty<T: Serialize>(x: &T) -> String {
+        String::from_utf8(to_vec(x, 2).unwrap()).unwrap()
+    }
+
+    #[derive(Serialize)]
+    struct Point {
+        x: i8,
+        y: i8,
+    }
+
+    #[derive(Serialize)]
+    enum Shape {
+        Circle { r: u8 },
+        Rect { w: u8, h: u8 },
+    }
+
+    #[test]
+    fn max_width() {
+        let arrays = vec![vec![1u8, 2, 3], (10..30).collect()];
+        assert_eq!(fitting(&arrays, 20), "[
+  [1, 2, 3],
+  [
+    10,
+    11,
+    12,
+    13,
+    14,
+    15,
+    16,
+    17,
+    18,
+    19,
+    20,
+    21,
+    22,
+    23,
+    24,
+    25,
+    26,
+    27,
+    28,
+    29,
+  ],
+]");
+        assert_eq!(fitting(&arrays, 100), "[
+  [1, 2, 3],
+  [10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29],
+]");
+        // The limit includes the indentation and the trailing comma.
+        assert_eq!(fitting(&vec![vec![1u8, 2, 3]; 2], 12), "[
+  [1, 2, 3],
+  [1, 2, 3],
+]");
+        assert_eq!(fitting(&vec![vec![1u8, 2, 3]; 2], 11), "[
+  [
+    1,
+    2,
+    3,
+  ],
+  [
+    1,
+    2,
+    3,
+  ],
+]");
+
+        // Breaking a value gives its nested values another chance to fit.
+        let nested = vec![(vec![1u8, 2], vec![3u8, 4], "a long string that does not fit")];
+        assert_eq!(fitting(&nested, 30), r#"[
+  [
+    [1, 2],
+    [3, 4],
+    "a long string that does not fit",
+  ],
+]"#);
+
+        let mut m = BTreeMap::new();
+        m.insert("short", vec![Some(1u8), None]);
+        m.insert("long", vec![Some(100u8); 5]);
+        assert_eq!(fitting(&m, 35), r#"{
+  "long": [
+    {"Some": 100},
+    {"Some": 100},
+    {"Some": 100},
+    {"Some": 100},
+    {"Some": 100},
+  ],
+  "short": [{"Some": 1}, "None"],
+}"#);
+        assert_eq!(fitting(&Some(vec![Shape::Rect { w: 1, h: 2 }, Shape::Circle { r: 3 }]), 40), r#"{"Some":
+  [
+    {"Rect": {"w": 1, "h": 2}},
+    {"Circle": {"r": 3}},
+  ]
+}"#);
+        assert_eq!(fitting(&Some(vec![Shape::Circle { r: 3 }, Shape::Circle { r: 4 }]), 54), r#"{"Some": [{"Circle": {"r": 3}}, {"Circle": {"r": 4}}]}"#);
+
+        assert_eq!(fitting(&Some(vec![Shape::Circle { r: 3 }, Shape::Circle { r: 4 }]), 53), r#"{"Some":
+  [
+    {"Circle": {"r": 3}},
+    {"Circle": {"r": 4}},
+  ]
+}"#);
+
+        // Strings with line feeds never fit on a single line.
+        assert_eq!(fitting(&vec![vec!["a\nb"], vec![]], 100), "[\n  [\n    \"a\nb\",\n  ],\n  [],\n]");
+
+        for max_width in 0..40 {
+            let enc = fitting(&m, max_width);
+            assert_eq!(crate::human::from_slice::<BTreeMap<String, Vec<Option<u8>>>>(enc.as_bytes()).unwrap().len(), 2);
+            let enc = fitting(&nested, max_width);
+            assert_eq!(crate::human::from_slice::<Vec<(Vec<u8>, Vec<u8>, String)>>(enc.as_bytes()).unwrap().len(), 1);
+        }
+    }
+
+    #[test]
+    fn inline_structs() {
+        let points = vec![Point { x: 1, y: 2 }, Point { x: -3, y: 4 }];
+        let render = |n: usize| {
+            let mut serializer = VVSerializer::new(Vec::new(), 2).inline_structs_up_to(n);
+            points.serialize(&mut serializer).unwrap();
+            String::from_utf8(serializer.out).unwrap()
+        };
+
+        assert_eq!(render(0), render(1));
+        assert_eq!(render(1), r#"[
+  {
+    "x": 1,
+    "y": 2,
+  },
+  {
+    "x": -3,
+    "y": 4,
+  },
+]"#);
+        assert_eq!(render(2), r#"[
+  {"x": 1, "y": 2},
+  {"x": -3, "y": 4},
+]"#);
+        assert_eq!(render(5), render(2));
+        let decode = |enc: String| crate::Value::deserialize(&mut crate::human::VVDeserializer::new(enc.as_bytes())).unwrap();
+        assert_eq!(decode(render(2)), decode(render(0)));
+
+        let mut serializer = VVSerializer::new(Vec::new(), 2).inline_structs_up_to(2);
+        vec![Shape::Circle { r: 1 }, Shape::Rect { w: 2, h: 3 }].serialize(&mut serializer).unwrap();
+        assert_eq!(String::from_utf8(serializer.out).unwrap(), r#"[
+  {"Circle": {"r": 1}},
+  {"Rect": {"w": 2, "h": 3}},
+]"#);
+
+        // Maps keep the default layout.
+        let mut m = std::collections::BTreeMap::new();
+        m.insert("x", 1);
+        m.insert("y", 2);
+        let mut serializer = VVSerializer::new(Vec::new(), 2).inline_structs_up_to(2);
+        m.serialize(&mut serializer).unwrap();
+        assert_eq!(String::from_utf8(serializer.out).unwrap(), "{\n  \"x\": 1,\n  \"y\": 2,\n}");
+
+        let mut serializer = VVSerializer::new(Vec::new(), 0).inline_structs_up_to(2);
+        points.serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.out, to_vec(&points, 0).unwrap());
+    }
+
+    #[test]
+    fn crlf() {
+        let mut m = std::collections::BTreeMap::new();
+        m.insert("a\nb", vec![Some(vec![1u8, 2]), None]);
+        m.insert("c", vec![]);
+        let x = (m, Big { a: 1, b: vec![3, 4] }, E::Long(vec![5, 6]), Bytes(&[7, 8, 9]));
+
+        // Line endings do not affect strings.
+        let mut serializer = VVSerializer::new(Vec::new(), 2).line_ending(LineEnding::CrLf);
+        x.serialize(&mut serializer).unwrap();
+        assert!(serializer.out.windows(5).any(|w| w == b"\"a\nb\""));
+
+        let mut serializer = VVSerializer::new(Vec::new(), 2).line_ending(LineEnding::CrLf).escape_line_feeds(true);
+        x.serialize(&mut serializer).unwrap();
+        let out = serializer.out;
+
+        assert!(out.windows(2).any(|w| w == b"\r\n"));
+        for (i, b) in out.iter().enumerate() {
+            if *b == ('\n' as u8) {
+                assert_eq!(out[i - 1], '\r' as u8);
+            }
+        }
+
+        let lf = to_vec(&x, 2).unwrap();
+        assert_eq!(String::from_utf8(out.clone()).unwrap().replace("\r\n", "\n").replace("\\n", "\n"), String::from_utf8(lf.clone()).unwrap());
+
+        let decode = |enc: &[u8]| crate::Value::deserialize(&mut crate::human::VVDeserializer::new(enc)).unwrap();
+        assert_eq!(decode(&out), decode(&lf));
+    }
+
+    // Serializes as a byte string rather than as a sequence.
+    struct Bytes(&'static [u8]);
+
+    impl Serialize for Bytes {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Layout<A, B> {
+        a: A,
+        b: B,
+    }
+
+    #[test]
+    fn complex_keys() {
+        use std::collections::BTreeMap;
+        use crate::Value;
+
+        let pair = Value::Array(vec![Value::Int(1), Value::Int(2)]);
+        let mut single = BTreeMap::new();
+        single.insert(pair.clone(), Value::Nil);
+        let single = Value::Map(single);
+
+        let mut m = BTreeMap::new();
+        m.insert(Value::Int(-3), Value::Nil);
+        m.insert(Value::Float(f64::NAN), Value::Float(1.5));
+        m.insert(Value::Array(vec![Value::Int(1), pair.clone()]), Value::Bool(true));
+        m.insert(single.clone(), pair.clone());
+        let m = Value::Map(m);
+
+        assert_eq!(pretty(&m), r#"{
+  NaN: 1.5,
+  -3: nil,
+  [
+    1,
+    [
+      1,
+      2,
+    ],
+  ]: true,
+  {
+    [
+      1,
+      2,
+    ]: nil,
+  }: [
+    1,
+    2,
+  ],
+}"#);
+
+        let mut outer = BTreeMap::new();
+        outer.insert(single.clone(), single.clone());
+        let outer = Value::Array(vec![Value::Map(outer)]);
+        assert_eq!(pretty(&outer), r#"[
+  {
+    {
+      [
+        1,
+        2,
+      ]: nil,
+    }: {
+      [
+        1,
+        2,
+      ]: nil,
+    },
+  },
+]"#);
+        assert_eq!(pretty(&Value::Array(vec![Value::Int(1)])), "[1]");
+        assert_eq!(pretty(&single), "{\n  [\n    1,\n    2,\n  ]: nil,\n}");
+
+        for v in [m, outer, single] {
+            for indentation in [0, 2] {
+                let enc = to_vec(&v, indentation).unwrap();
+                assert_eq!(crate::Value::deserialize(&mut crate::human::VVDeserializer::new(&enc)).unwrap(), v);
+            }
+        }
+    }
+
+    // Serializes empty collections in every position, and collections whose length hints are wrong.
+    struct Empties;
+
+    impl Serialize for Empties {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::{SerializeMap, SerializeSeq, SerializeTuple, SerializeTupleVariant, SerializeStructVariant};
+
+            struct EmptySeq;
+            impl Serialize for EmptySeq {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_seq(Some(0))?.end()
+                }
+            }
+
+            struct EmptyMap;
+            impl Serialize for EmptyMap {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_map(None)?.end()
+                }
+            }
+
+            struct EmptyTuple;
+            impl Serialize for EmptyTuple {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_tuple(0)?.end()
+                }
+            }
+
+            struct EmptyVariants;
+            impl Serialize for EmptyVariants {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    let mut seq = serializer.serialize_tuple(2)?;
+                    seq.serialize_element(&TupleVariant)?;
+                    seq.serialize_element(&StructVariant)?;
+                    seq.end()
+                }
+            }
+
+            struct TupleVariant;
+            impl Serialize for TupleVariant {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_tuple_variant("E", 0, "T", 0)?.end()
+                }
+            }
+
+            struct StructVariant;
+            impl Serialize for StructVariant {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_struct_variant("E", 1, "S", 0)?.end()
+                }
+            }
+
+            // Claims a single element, but has three, the middle one being a string of commas.
+            struct Lying;
+            impl Serialize for Lying {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    let mut seq = serializer.serialize_seq(Some(1))?;
+                    seq.serialize_element(&EmptySeq)?;
+                    seq.serialize_element(",,")?;
+                    seq.serialize_element(&EmptyMap)?;
+                    seq.end()
+                }
+            }
+
+            let mut map = serializer.serialize_map(Some(5))?;
+            map.serialize_entry(&EmptySeq, &EmptyMap)?;
+            map.serialize_entry(&EmptyMap, &EmptyTuple)?;
+            map.serialize_entry(&EmptyTuple, &EmptySeq)?;
+            map.serialize_entry(&1, &EmptyVariants)?;
+            map.serialize_entry(&2, &Lying)?;
+            map.end()
+        }
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
+    struct Cell {
+        x: i8,
+        y: i8,
+    }
+
+    #[test]
+    fn collection_keys() {
+        use std::collections::BTreeMap;
+        use crate::Value;
+
+        let mut by_pair = BTreeMap::new();
+        by_pair.insert((1, vec![2u8]), true);
+        by_pair.insert((3, vec![]), false);
+        let mut by_cell = BTreeMap::new();
+        by_cell.insert(Cell { x: 1, y: 2 }, vec![Cell { x: 3, y: 4 }]);
+        by_cell.insert(Cell { x: -1, y: 2 }, vec![]);
+        let mut by_map = BTreeMap::new();
+        by_map.insert(by_cell.clone(), by_pair.clone());
+        let x = (by_pair, by_cell, by_map);
+
+        let mut serializer = VVSerializer::new(Vec::new(), 2).inline_structs_up_to(2);
+        x.serialize(&mut serializer).unwrap();
+        assert_eq!(String::from_utf8(serializer.out).unwrap(), r#"[
+  {
+    [
+      1,
+      [2],
+    ]: true,
+    [
+      3,
+      [],
+    ]: false,
+  },
+  {
+    {"x": -1, "y": 2}: [],
+    {"x": 1, "y": 2}: [{"x": 3, "y": 4}],
+  },
+  {
+    {
+      {"x": -1, "y": 2}: [],
+      {"x": 1, "y": 2}: [{"x": 3, "y": 4}],
+    }: {
+      [
+        1,
+        [2],
+      ]: true,
+      [
+        3,
+        [],
+      ]: false,
+    },
+  },
+]"#);
+
+        for inline in [0, 2] {
+            for line_ending in [LineEnding::Lf, LineEnding::CrLf] {
+                for indentation in [0, 2] {
+                    let mut serializer = VVSerializer::new(Vec::new(), indentation).inline_structs_up_to(inline).line_ending(line_ending);
+                    x.serialize(&mut serializer).unwrap();
+                    let decoded = <(BTreeMap<(i32, Vec<u8>), bool>, BTreeMap<Cell, Vec<Cell>>, BTreeMap<BTreeMap<Cell, Vec<Cell>>, BTreeMap<(i32, Vec<u8>), bool>>)>::deserialize(&mut crate::human::VVDeserializer::new(&serializer.out)).unwrap();
+                    assert_eq!(decoded, x);
+                }
+            }
+        }
+
+        // Keys that are sets, strings, empty, or deeply nested.
+        let mut set = BTreeMap::new();
+        set.insert(Value::Int(1), Value::Nil);
+        set.insert(Value::from("ab"), Value::Nil);
+        let mut keyed = BTreeMap::new();
+        keyed.insert(Value::Map(set.clone()), Value::Map(set));
+        keyed.insert(Value::from("k"), Value::Nil);
+        keyed.insert(Value::Array(vec![]), Value::Nil);
+        keyed.insert(Value::Map(BTreeMap::new()), Value::Array(vec![Value::Map(BTreeMap::new())]));
+        keyed.insert(Value::Array(vec![Value::Array(vec![Value::Array(vec![])])]), Value::Bool(false));
+        let mut nested = BTreeMap::new();
+        nested.insert(Value::Map(keyed.clone()), Value::Map(keyed.clone()));
+
+        for v in [Value::Map(keyed), Value::Map(nested)] {
+            for indentation in [0, 2] {
+                let enc = to_vec(&v, indentation).unwrap();
+                assert_eq!(Value::deserialize(&mut crate::human::VVDeserializer::new(&enc)).unwrap(), v);
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    enum Weird {
+        #[serde(rename = "weird \"name\"\n")]
+        Unit,
+        #[serde(rename = "new\\type\t")]
+        Newtype(Option<u8>),
+        #[serde(rename = "\0tuple")]
+        Tuple(u8, u8),
+        #[serde(rename = "{struct}: \u{7f}")]
+        Struct { x: u8, y: Vec<u8> },
+    }
+
+    // Serializes through `serialize_some`, without being an `Option`.
+    struct AlwaysSome(Weird);
+
+    impl Serialize for AlwaysSome {
+        fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_some(&self.0)
+        }
+    }
+
+    #[test]
+    fn escaped_variant_names() {
+        fn render<T: Serialize>(x: &T, indentation: usize, inline: usize, line_ending: LineEnding) -> Vec<u8> {
+            let mut serializer = VVSerializer::new(Vec::new(), indentation).inline_structs_up_to(inline).line_ending(line_ending);
+            x.serialize(&mut serializer).unwrap();
+            serializer.out
+        }
+
+        let variants = vec![
+            Weird::Unit,
+            Weird::Newtype(Some(3)),
+            Weird::Tuple(1, 2),
+            Weird::Struct { x: 4, y: vec![5, 6] },
+        ];
+
+        for line_ending in [LineEnding::Lf, LineEnding::CrLf] {
+            for indentation in [0, 2] {
+                for inline in [0, 2] {
+                    let enc = render(&variants, indentation, inline, line_ending);
+                    assert_eq!(Vec::<Weird>::deserialize(&mut crate::human::VVDeserializer::new(&enc)).unwrap(), variants);
+
+                    for v in variants.iter() {
+                        let enc = render(&AlwaysSome(v.clone()), indentation, inline, line_ending);
+                        assert_eq!(Option::<Weird>::deserialize(&mut crate::human::VVDeserializer::new(&enc)).unwrap().as_ref(), Some(v));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_collections() {
+        assert_eq!(
+            String::from_utf8(to_vec(&Empties, 0).unwrap()).unwrap(),
+            r#"{[]:{},{}:[],[]:[],1:[{"T":[]},{"S":{}}],2:[[],",,",{}]}"#,
+        );
+        assert_eq!(pretty(&Empties), r#"{
+  []: {},
+  {}: [],
+  []: [],
+  1: [
+    {"T": []},
+    {"S": {}},
+  ],
+  2: [[], ",,", {}],
+}"#);
+
+        for indentation in [0, 2] {
+            let enc = to_vec(&Empties, indentation).unwrap();
+            assert!(crate::Value::deserialize(&mut crate::human::VVDeserializer::new(&enc)).is_ok());
+        }
+    }
+
+    #[test]
+    fn byte_string_layout() {
+        let arrays = pretty(&Layout { a: vec![1u8, 2], b: (vec![3u8], ()) });
+        let bytes = pretty(&Layout { a: Bytes(&[1, 2]), b: (Bytes(&[3]), ()) });
+
+        assert_eq!(bytes, r#"{
+  "a": @[
+    1,
+    2,
+  ],
+  "b": [
+    @[3],
+    nil,
+  ],
+}"#);
+        assert_eq!(bytes.replace('@', ""), arrays);
+        assert_eq!(to_vec(&Bytes(&[]), 2).unwrap(), b"@[]");
+        assert_eq!(to_vec(&Bytes(&[1, 2, 3]), 0).unwrap(), b"@[1,2,3]");
+    }
+
+    fn json_compat<T: Serialize>(x: &T, serializer: VVSerializer) -> Result<String, EncodeError> {
+        let mut serializer = serializer.profile(Profile::JsonCompat { non_finite_placeholder: None });
+        x.serialize(&mut serializer)?;
+        Ok(String::from_utf8(serializer.out).unwrap())
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json_compat_parses_as_json() {
+        #[derive(Serialize)]
+        struct Doc {
+            name: String,
+            escapes: String,
+            floats: Vec<f64>,
+            #[serde(with = "serde_bytes_like")]
+            bytes: Vec<u8>,
+            empty: Vec<u8>,
+            points: Vec<Point>,
+            shapes: Vec<Shape>,
+            labels: BTreeMap<String, E>,
+        }
+
+        mod serde_bytes_like {
+            pub fn serialize<S: serde::Serializer>(b: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(b)
+            }
+        }
+
+        let doc = Doc {
+            name: "json ✓".to_string(),
+            escapes: "a\nb\t\"q\" \\ \u{7f}".to_string(),
+            floats: vec![0.5, -0.0, 1e300, 123.0],
+            bytes: vec![255, 0, 7],
+            empty: vec![],
+            points: vec![Point { x: 1, y: -2 }],
+            shapes: vec![Shape::Circle { r: 3 }, Shape::Rect { w: 1, h: 2 }],
+            labels: vec![("a".to_string(), E::Long(vec![1, 2, 3])), ("b".to_string(), E::Short(4))].into_iter().collect(),
+        };
+
+        let layouts = [
+            VVSerializer::new(Vec::new(), 0),
+            VVSerializer::new(Vec::new(), 2),
+            VVSerializer::new(Vec::new(), 4).line_ending(LineEnding::CrLf),
+            VVSerializer::new(Vec::new(), 2).max_width(30),
+            VVSerializer::new(Vec::new(), 2).inline_structs_up_to(2).float_style(FloatStyle::Deterministic),
+        ];
+        for serializer in layouts {
+            let enc = json_compat(&doc, serializer).unwrap();
+            let json: serde_json::Value = serde_json::from_str(&enc).unwrap_or_else(|e| panic!("{}\n{}", e, enc));
+            assert_eq!(json, serde_json::to_value(&doc).unwrap(), "{}", enc);
+            assert_eq!(crate::human::from_str::<crate::Value>(&enc).unwrap(), crate::to_value(&doc).unwrap());
+        }
+
+        // Options keep their usual shape, which serde_json decodes as a string and a map.
+        let options = json_compat(&vec![Some(1), None], VVSerializer::new(Vec::new(), 2)).unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&options).unwrap(), serde_json::json!([{"Some": 1}, "None"]));
+    }
+
+    #[test]
+    fn json_compat_rejections() {
+        let compact = || VVSerializer::new(Vec::new(), 0);
+
+        assert_eq!(json_compat(&vec![1.0, f64::NAN], compact()).unwrap_err(), EncodeError::NonFiniteFloat);
+        assert_eq!(json_compat(&f64::INFINITY, compact()).unwrap_err(), EncodeError::NonFiniteFloat);
+        assert_eq!(json_compat(&f64::NEG_INFINITY, VVSerializer::new(Vec::new(), 2)).unwrap_err(), EncodeError::NonFiniteFloat);
+        let placeholder = VVSerializer::new(Vec::new(), 0).profile(Profile::JsonCompat { non_finite_placeholder: Some("not finite".to_string()) });
+        let mut serializer = placeholder;
+        vec![f64::NAN, 1.5, f64::INFINITY].serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.out, br#"["not finite",1.5,"not finite"]"#);
+
+        let int_keys: BTreeMap<u8, u8> = vec![(1, 2)].into_iter().collect();
+        assert_eq!(json_compat(&int_keys, compact()).unwrap_err(), EncodeError::NonStringKey);
+        let array_keys: BTreeMap<Vec<String>, u8> = vec![(vec!["a".to_string()], 2)].into_iter().collect();
+        assert_eq!(json_compat(&array_keys, compact()).unwrap_err(), EncodeError::NonStringKey);
+        let option_keys: BTreeMap<Option<String>, u8> = vec![(Some("a".to_string()), 2)].into_iter().collect();
+        assert_eq!(json_compat(&option_keys, compact()).unwrap_err(), EncodeError::NonStringKey);
+        let string_keys: BTreeMap<Option<String>, u8> = vec![(None, 2)].into_iter().collect();
+        assert_eq!(json_compat(&string_keys, compact()).unwrap(), r#"{"None":2}"#);
+        // Keys of a `Value` map are arrays of ints, which are strings if they form valid UTF-8.
+        assert_eq!(json_compat(&crate::vv!({"a" => 1, "é\n" => 2}), compact()).unwrap(), r#"{"a":1,"é\n":2}"#);
+        assert_eq!(json_compat(&crate::vv!({[0xff] => 1}), compact()).unwrap_err(), EncodeError::NonStringKey);
+        assert_eq!(json_compat(&crate::vv!({"a\r" => 1}), compact()).unwrap_err(), EncodeError::JsonControlCharacter('\r'));
+
+        assert_eq!(json_compat(&(), compact()).unwrap_err(), EncodeError::JsonNil);
+        assert_eq!(json_compat(&vec![Some(())], compact()).unwrap_err(), EncodeError::JsonNil);
+
+        assert_eq!(json_compat(&"a\rb", compact()).unwrap_err(), EncodeError::JsonControlCharacter('\r'));
+        assert_eq!(json_compat(&"\0", compact()).unwrap_err(), EncodeError::JsonControlCharacter('\0'));
+        assert_eq!(json_compat(&'\u{1f}', compact()).unwrap_err(), EncodeError::JsonControlCharacter('\u{1f}'));
+
+        // Without the profile, all of these are fine.
+        assert_eq!(String::from_utf8(to_vec(&(vec![f64::NAN], int_keys, (), "\r"), 0).unwrap()).unwrap(), "[[NaN],{1:2},nil,\"\r\"]");
+    }
+
+    // A value nested `depth` deep in options or in single-entry maps, around either a string or a multiline array.
+    struct Nested {
+        depth: usize,
+        maps: bool,
+        multiline: bool,
+    }
+
+    impl Serialize for Nested {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self.depth {
+                0 if self.multiline => [1, 2].serialize(serializer),
+                0 => "leaf".serialize(serializer),
+                _ if self.maps => {
+                    let mut map = serializer.serialize_map(Some(1))?;
+                    ser::SerializeMap::serialize_entry(&mut map, "k", &Nested { depth: self.depth - 1, ..*self })?;
+                    ser::SerializeMap::end(map)
+                }
+                _ => serializer.serialize_some(&Nested { depth: self.depth - 1, ..*self }),
+            }
+        }
+    }
+
+    // Encode with indentation, and return the encoding and how many bytes were moved after being written.
+    fn rescanned<T: Serialize>(value: &T) -> (String, usize) {
+        let mut serializer = VVSerializer::new(Vec::new(), 2);
+        value.serialize(&mut serializer).unwrap();
+        (String::from_utf8(serializer.out).unwrap(), serializer.rescanned)
+    }
+
+    #[test]
+    fn nested_layout_scales_linearly() {
+        assert_eq!(rescanned(&Nested { depth: 2, maps: false, multiline: false }).0, r#"{"Some": {"Some": "leaf"}}"#);
+        assert_eq!(rescanned(&Nested { depth: 2, maps: false, multiline: true }).0, "{\"Some\":\n  {\"Some\":\n    [\n      1,\n      2,\n    ]\n  }\n}");
+        assert_eq!(rescanned(&Nested { depth: 2, maps: true, multiline: false }).0, r#"{"k": {"k": "leaf"}}"#);
+        assert_eq!(rescanned(&Nested { depth: 2, maps: true, multiline: true }).0, "{\n  \"k\": {\n    \"k\": [\n      1,\n      2,\n    ],\n  },\n}");
+
+        // Deeply nested values need a larger stack than the test threads get by default.
+        std::thread::Builder::new().stack_size(256 << 20).spawn(|| {
+            for maps in [false, true] {
+                // Values that fit on a line are written once and never moved.
+                let (enc, rescanned_bytes) = rescanned(&Nested { depth: 4_000, maps, multiline: false });
+                assert_eq!(rescanned_bytes, 0, "{} of {} bytes rescanned", rescanned_bytes, enc.len());
+
+                // The line breaks of all nesting levels are inserted in one go.
+                let (enc, rescanned_bytes) = rescanned(&Nested { depth: 500, maps, multiline: true });
+                assert!(rescanned_bytes <= enc.len(), "{} of {} bytes rescanned", rescanned_bytes, enc.len());
+            }
+        }).unwrap().join().unwrap();
+    }
+
+    // Values nested in single-item collections that stay on the same line must leave room for all their closing delimiters.
+    #[test]
+    fn max_width_counts_enclosing_delimiters() {
+        use crate::vv;
+
+        for (v, max_width) in [
+            (vv!([[[1, 2, 3]], 4]), 12),
+            (vv!([{1 => [1, 2, 3]}, 4]), 15),
+            (vv!([[{1 => [[1, 2, 3]]}], 4]), 18),
+        ] {
+            for max_width in max_width..max_width + 8 {
+                let enc = fitting(&v, max_width);
+                assert!(enc.lines().all(|line| line.len() <= max_width), "{} at max_width {}", enc, max_width);
+                assert_eq!(crate::human::from_str::<crate::Value>(&enc).unwrap(), v);
+            }
+        }
+
+        let wrapped = Some(vec![Some(vec![1u8, 2, 3])]);
+        for max_width in 16..40 {
+            let enc = fitting(&wrapped, max_width);
+            assert!(enc.lines().all(|line| line.len() <= max_width), "{} at max_width {}", enc, max_width);
+        }
+
+        assert_eq!(fitting(&vv!([[[1, 2, 3]], 4]), 13), "[
+  [
+    [
+      1,
+      2,
+      3,
+    ],
+  ],
+  4,
+]");
+        assert_eq!(fitting(&vv!([[[1, 2, 3]], 4]), 14), "[
+  [[1, 2, 3]],
+  4,
+]");
+        assert_eq!(fitting(&vv!([{1 => [1, 2, 3]}, 4]), 16), "[
+  {
+    1: [
+      1,
+      2,
+      3,
+    ],
+  },
+  4,
+]");
+        assert_eq!(fitting(&vv!([{1 => [1, 2, 3]}, 4]), 17), "[
+  {1: [1, 2, 3]},
+  4,
+]");
+    }
+
+    #[test]
+    fn max_width_stops_early() {
+        let depth = 15;
+        let mut v = crate::Value::Array((0..10_000).map(crate::Value::Int).collect());
+        for _ in 0..depth {
+            let mut m = BTreeMap::new();
+            m.insert(crate::Value::from("k"), v);
+            v = crate::Value::Map(m);
+        }
+
+        let mut serializer = VVSerializer::new(Vec::new(), 2).max_width(40);
+        v.serialize(&mut serializer).unwrap();
+        let enc = serializer.out;
+        assert_eq!(crate::human::from_slice::<crate::Value>(&enc).unwrap(), v);
+        // Every attempt to fit a nested value on a single line is abandoned once it passes the limit, rather than writing
+        // the whole array on a line again for every level. Apart from that, the output is moved once to insert line breaks.
+        assert!(serializer.rescanned <= enc.len() + depth * 64, "{} bytes rescanned for {} bytes of output", serializer.rescanned, enc.len());
+    }
+
+    #[test]
+    fn nan_payloads() {
+        let canonic = f64::from_bits(u64::MAX);
+        let payload = f64::from_bits(0x7FF8_0000_0000_0001);
+
+        // All NaNs are written as `NaN`, which decodes to the canonic NaN.
+        for n in [canonic, payload, -payload] {
+            let enc = crate::human::to_vec(&n, 0).unwrap();
+            assert_eq!(enc, b"NaN");
+            assert_eq!(f64::deserialize(&mut crate::human::VVDeserializer::new(&enc)).unwrap().to_bits(), u64::MAX);
+        }
+
+        let encode = |n: f64| {
+            let mut serializer = VVSerializer::new(Vec::new(), 0).preserve_nan_payloads(true);
+            vec![n].serialize(&mut serializer).map(|_| serializer.out)
+        };
+        assert_eq!(encode(canonic).unwrap(), b"[NaN]");
+        assert_eq!(encode(1.5).unwrap(), b"[1.5]");
+        assert_eq!(encode(payload).unwrap_err(), EncodeError::NanPayload(0x7FF8_0000_0000_0001));
+        assert_eq!(encode(canonic.copysign(1.0)).unwrap_err(), EncodeError::NanPayload(0x7FFF_FFFF_FFFF_FFFF));
+    }
+
+    #[test]
+    fn deterministic_floats() {
+        let encode = |v: &crate::Value, indentation: usize| {
+            let mut serializer = VVSerializer::new(Vec::new(), indentation).float_style(FloatStyle::Deterministic);
+            v.serialize(&mut serializer).unwrap();
+            String::from_utf8(serializer.out).unwrap()
+        };
+
+        // Floats as map keys are written like all others, and keep their order.
+        let keys = [5e-324, 0.1, 1e16, -0.0, f64::INFINITY, 9007199254740993.0, 1e300];
+        let v = crate::Value::Map(keys.iter().map(|k| (crate::Value::Float(*k), crate::Value::Float(k / 4.0))).collect());
+        let enc = encode(&v, 0);
+        assert_eq!(enc, "{-0.0:-0.0,5.0e-324:0.0,0.1:0.025,9007199254740992.0:2251799813685248.0,1.0e16:2500000000000000.0,1.0e300:2.5e299,Inf:Inf}");
+        assert_eq!(crate::human::from_slice::<crate::Value>(enc.as_bytes()).unwrap(), v);
+        assert_eq!(encode(&v, 2), enc.replace("{", "{\n  ").replace(",", ",\n  ").replace(":", ": ").replace("Inf: Inf", "Inf: Inf,\n"));
+
+        assert_eq!(encode(&crate::Value::Float(f64::NAN), 0), "NaN");
+    }
+
+    #[test]
+    fn pretty_some() {
+        assert_eq!(pretty(&Some(3)), "{\"Some\": 3}");
+        assert_eq!(to_vec(&Some(Big { a: 1, b: vec![2, 3] }), 0).unwrap(), b"{\"Some\":{\"a\":1,\"b\":[2,3]}}");
+        assert_eq!(pretty(&Some(Big { a: 1, b: vec![2, 3] })), r#"{"Some":
+  {
+    "a": 1,
+    "b": [
+      2,
+      3,
+    ],
+  }
+}"#);
+    }
+
+    #[test]
+    fn pretty_newtype_variant() {
+        assert_eq!(pretty(&E::Short(3)), "{\"Short\": 3}");
+        assert_eq!(pretty(&vec![E::Long(vec![1, 2]), E::Short(3)]), r#"[
+  {"Long":
+    [
+      1,
+      2,
+    ]
+  },
+  {"Short": 3},
+]"#);
+    }
+
+    #[test]
+    fn pretty_nested_option() {
+        assert_eq!(pretty(&Some(Some(None::<u8>))), "{\"Some\": {\"Some\": \"None\"}}");
+        assert_eq!(pretty(&Some(Some(vec![1, 2]))), r#"{"Some":
+  {"Some":
+    [
+      1,
+      2,
+    ]
+  }
+}"#);
+    }
+}
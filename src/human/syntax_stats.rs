@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+
+/// A syntactic form of the human-readable encoding that is counted by [`SyntaxStats`](SyntaxStats).
+///
+/// These are the forms that a stricter profile of the encoding might reject, the forms that every document needs (quoted
+/// strings without escapes, decimal numbers, arrays, maps) are not counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SyntaxForm {
+    /// A comment, from a `#` to the end of the line.
+    Comment,
+    /// A comma directly before the `]` or `}` that closes a collection, including the lone comma of an empty collection such as `[,]`.
+    TrailingComma,
+    /// An integer literal starting with `0x`.
+    HexInt,
+    /// An integer literal starting with `0b`.
+    BinaryInt,
+    /// A quoted string literal that contains at least one escape sequence, e.g. `"a\nb"`.
+    EscapedString,
+    /// A raw string literal, e.g. `@"a\b"@`.
+    RawString,
+    /// A byte string literal, e.g. `@[1, 2]`, `@x0102` or `@b00000001`.
+    ByteString,
+    /// A set, e.g. `@{1, 2}`.
+    Set,
+    /// A [scalar extension](super::ScalarExtension) literal, e.g. `@t"2024-01-01"`.
+    ScalarExtension,
+}
+
+/// How often a [`SyntaxForm`](SyntaxForm) occurred, and where it occurred first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormStats {
+    pub count: usize,
+    /// The input position of the first byte of the first occurrence.
+    pub first: usize,
+}
+
+/// Which [syntactic forms](SyntaxForm) the values parsed by a [`VVDeserializer`](super::VVDeserializer) used, see
+/// [`VVDeserializer::collect_syntax_stats`](super::VVDeserializer::collect_syntax_stats).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyntaxStats {
+    forms: BTreeMap<SyntaxForm, FormStats>,
+}
+
+impl SyntaxStats {
+    /// Return how often the form occurred.
+    pub fn count(&self, form: SyntaxForm) -> usize {
+        self.forms.get(&form).map_or(0, |stats| stats.count)
+    }
+
+    /// Return the input position of the first occurrence of the form, or `None` if it did not occur.
+    pub fn first_position(&self, form: SyntaxForm) -> Option<usize> {
+        self.forms.get(&form).map(|stats| stats.first)
+    }
+
+    /// Iterate over all forms that occurred at least once, in the order in which [`SyntaxForm`](SyntaxForm) declares them.
+    pub fn iter(&self) -> impl Iterator<Item = (SyntaxForm, FormStats)> + '_ {
+        self.forms.iter().map(|(form, stats)| (*form, *stats))
+    }
+
+    /// Return whether none of the forms occurred.
+    pub fn is_empty(&self) -> bool {
+        self.forms.is_empty()
+    }
+
+    fn record(&mut self, form: SyntaxForm, position: usize) {
+        self.forms.entry(form).or_insert(FormStats { count: 0, first: position }).count += 1;
+    }
+
+    // Record the forms in a slice of input that has already been parsed successfully, and which starts at the given input
+    // position. Since the input is known to be valid, it suffices to tell the tokens apart, rather than to parse them.
+    pub(crate) fn scan(&mut self, input: &[u8], start: usize) {
+        let mut i = 0;
+        while i < input.len() {
+            match input[i] {
+                b'#' => {
+                    self.record(SyntaxForm::Comment, start + i);
+                    i = skip_comment(input, i);
+                }
+                b',' => {
+                    if matches!(next_token(input, i + 1), Some(b']' | b'}')) {
+                        self.record(SyntaxForm::TrailingComma, start + i);
+                    }
+                    i += 1;
+                }
+                b'"' => {
+                    let (end, escaped) = skip_escaping_string(input, i);
+                    if escaped {
+                        self.record(SyntaxForm::EscapedString, start + i);
+                    }
+                    i = end;
+                }
+                b'@' => match input.get(i + 1) {
+                    Some(b'"' | b'@') => {
+                        self.record(SyntaxForm::RawString, start + i);
+                        i = skip_raw_string(input, i);
+                    }
+                    Some(b'{') => {
+                        self.record(SyntaxForm::Set, start + i);
+                        i += 2;
+                    }
+                    Some(b'[') => {
+                        self.record(SyntaxForm::ByteString, start + i);
+                        i += 2;
+                    }
+                    Some(b'x' | b'b') => {
+                        self.record(SyntaxForm::ByteString, start + i);
+                        i = skip_word(input, i + 2);
+                    }
+                    // The string literal after the tag byte is handled by the next iteration.
+                    Some(_) => {
+                        self.record(SyntaxForm::ScalarExtension, start + i);
+                        i += 2;
+                    }
+                    None => i += 1,
+                },
+                b'0' if input.get(i + 1) == Some(&b'x') => {
+                    self.record(SyntaxForm::HexInt, start + i);
+                    i = skip_word(input, i + 2);
+                }
+                b'0' if input.get(i + 1) == Some(&b'b') => {
+                    self.record(SyntaxForm::BinaryInt, start + i);
+                    i = skip_word(input, i + 2);
+                }
+                b if is_word_byte(b) => i = skip_word(input, i),
+                _ => i += 1,
+            }
+        }
+    }
+}
+
+// Digits, letters, underscores and decimal points, i.e., everything that can continue a number literal or a keyword.
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'.'
+}
+
+fn skip_word(input: &[u8], mut i: usize) -> usize {
+    while i < input.len() && is_word_byte(input[i]) {
+        i += 1;
+    }
+    i
+}
+
+// Return the position after the line of the comment starting at `i`.
+fn skip_comment(input: &[u8], i: usize) -> usize {
+    input[i..].iter().position(|b| *b == b'\n').map_or(input.len(), |len| i + len + 1)
+}
+
+// Return the first byte after `i` that is neither whitespace nor part of a comment.
+fn next_token(input: &[u8], mut i: usize) -> Option<u8> {
+    loop {
+        match input.get(i) {
+            Some(b'\t' | b'\n' | b'\r' | b' ') => i += 1,
+            Some(b'#') => i = skip_comment(input, i),
+            other => return other.copied(),
+        }
+    }
+}
+
+// Return the position after the escaping string literal whose opening quote is at `i`, and whether it contains an escape sequence.
+fn skip_escaping_string(input: &[u8], mut i: usize) -> (usize, bool) {
+    let mut escaped = false;
+    i += 1;
+    while i < input.len() {
+        match input[i] {
+            b'"' => return (i + 1, escaped),
+            b'\\' => {
+                escaped = true;
+                i += 2;
+            }
+            _ => i += 1,
+        }
+    }
+    (i, escaped)
+}
+
+// Return the position after the raw string literal whose first `@` is at `i`. The literal ends at the first `"` that is
+// followed by as many `@` as it started with.
+fn skip_raw_string(input: &[u8], mut i: usize) -> usize {
+    let ats = input[i..].iter().take_while(|b| **b == b'@').count();
+    i += ats + 1;
+    while i < input.len() {
+        if input[i] == b'"' && input.len() > i + ats && input[i + 1..=i + ats].iter().all(|b| *b == b'@') {
+            return i + 1 + ats;
+        }
+        i += 1;
+    }
+    i
+}
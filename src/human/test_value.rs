@@ -12,6 +12,7 @@ pub enum TestValue {
     Nil(Spaces),
     Bool(Spaces, bool),
     Int(Spaces, Int),
+    /// Floats are always written in decimal, the grammar has no hexadecimal or binary float literals.
     Float(Spaces, Float),
     ByteString(Spaces, ByteString),
     Utf8String(Spaces, Utf8String),
@@ -0,0 +1,238 @@
+use thiserror::Error;
+
+use crate::value::Value;
+
+/// Everything that can go wrong when exporting a [`Value`](Value) as JSON.
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum JsonExportError {
+    /// JSON object keys must be strings, i.e., arrays of ints that form valid UTF-8.
+    #[error("JSON object keys must be UTF-8 strings")]
+    NonStringKey,
+    /// JSON has no representation of NaN.
+    #[error("JSON cannot represent NaN")]
+    NaN,
+    /// JSON has no representation of the infinities.
+    #[error("JSON cannot represent infinite floats")]
+    Infinity,
+}
+
+impl Value {
+    /// Render this value as JSON.
+    ///
+    /// Maps whose keys are all UTF-8 strings (arrays of ints between 0 and 255 that form valid UTF-8) become objects, arrays become arrays, and nil becomes `null`. Pretty-prints if the indentation is greater than zero.
+    pub fn to_json_string(&self, indentation: usize) -> Result<String, JsonExportError> {
+        let mut out = String::new();
+        write_json(self, indentation, 0, &mut out)?;
+        Ok(out)
+    }
+
+    /// Convert this value into a [`serde_json::Value`](serde_json::Value), which never fails.
+    ///
+    /// Like [`to_json_string`](Value::to_json_string), except that the conversion is lossy where JSON lacks a
+    /// representation. Map keys that are not UTF-8 strings become the string of their human-readable encoding (on a
+    /// single line, with [deterministic floats](crate::human::FloatStyle::Deterministic)), e.g. the map `{1: "a"}` becomes
+    /// the object `{"1": [97]}`. If several keys of a map become the same string, the entry whose key is greatest in the
+    /// canonic order is kept. NaN and the infinities become the strings `"NaN"`, `"Inf"` and `"-Inf"`.
+    #[cfg(feature = "serde_json")]
+    pub fn to_json_lossy(&self) -> serde_json::Value {
+        match self {
+            Value::Nil => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(*b),
+            Value::Int(n) => serde_json::Value::from(*n),
+            Value::Float(n) => match serde_json::Number::from_f64(*n) {
+                Some(n) => serde_json::Value::Number(n),
+                None => serde_json::Value::String(human_string(self)),
+            },
+            Value::Array(vs) => serde_json::Value::Array(vs.iter().map(Value::to_json_lossy).collect()),
+            Value::Map(m) => serde_json::Value::Object(m.iter().map(|(key, inner)| {
+                (key_to_string(key).unwrap_or_else(|_| human_string(key)), inner.to_json_lossy())
+            }).collect()),
+        }
+    }
+}
+
+#[cfg(feature = "serde_json")]
+fn human_string(v: &Value) -> String {
+    use serde::Serialize;
+    use crate::human::{FloatStyle, VVSerializer};
+
+    let mut serializer = VVSerializer::new(Vec::new(), 0).float_style(FloatStyle::Deterministic);
+    v.serialize(&mut serializer).expect("values can always be encoded");
+    String::from_utf8(serializer.into_inner()).expect("the human-readable encoding of a value is UTF-8")
+}
+
+fn write_json(v: &Value, indentation: usize, depth: usize, out: &mut String) -> Result<(), JsonExportError> {
+    match v {
+        Value::Nil => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Int(n) => out.push_str(itoa::Buffer::new().format(*n)),
+        Value::Float(n) => {
+            if n.is_nan() {
+                return Err(JsonExportError::NaN);
+            } else if n.is_infinite() {
+                return Err(JsonExportError::Infinity);
+            } else {
+                out.push_str(&pretty_dtoa::dtoa(*n, pretty_dtoa::FmtFloatConfig::default()));
+            }
+        }
+        Value::Array(vs) => {
+            out.push('[');
+            for (i, inner) in vs.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(indentation, depth + 1, out);
+                write_json(inner, indentation, depth + 1, out)?;
+            }
+            if !vs.is_empty() {
+                newline(indentation, depth, out);
+            }
+            out.push(']');
+        }
+        Value::Map(m) => {
+            out.push('{');
+            for (i, (key, inner)) in m.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(indentation, depth + 1, out);
+                write_json_string(&key_to_string(key)?, out);
+                out.push(':');
+                if indentation > 0 {
+                    out.push(' ');
+                }
+                write_json(inner, indentation, depth + 1, out)?;
+            }
+            if !m.is_empty() {
+                newline(indentation, depth, out);
+            }
+            out.push('}');
+        }
+    }
+
+    Ok(())
+}
+
+fn newline(indentation: usize, depth: usize, out: &mut String) {
+    if indentation > 0 {
+        out.push('\n');
+        for _ in 0..(indentation * depth) {
+            out.push(' ');
+        }
+    }
+}
+
+fn key_to_string(key: &Value) -> Result<String, JsonExportError> {
+    match key {
+        Value::Array(vs) => {
+            let mut bytes = Vec::with_capacity(vs.len());
+            for b in vs {
+                match b {
+                    Value::Int(n) if 0 <= *n && *n <= 255 => bytes.push(*n as u8),
+                    _ => return Err(JsonExportError::NonStringKey),
+                }
+            }
+            String::from_utf8(bytes).map_err(|_| JsonExportError::NonStringKey)
+        }
+        _ => Err(JsonExportError::NonStringKey),
+    }
+}
+
+fn write_json_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c <= '\u{1f}' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn string(s: &str) -> Value {
+        Value::Array(s.bytes().map(|b| Value::Int(b as i64)).collect())
+    }
+
+    #[test]
+    fn nested() {
+        let mut inner = BTreeMap::new();
+        inner.insert(string("a\"b"), Value::Array(vec![Value::Int(1), Value::Float(-0.5), Value::Nil]));
+        inner.insert(string("e"), Value::Map(BTreeMap::new()));
+        let mut outer = BTreeMap::new();
+        outer.insert(string("x"), Value::Map(inner));
+        outer.insert(string("y"), Value::Bool(true));
+        outer.insert(string("z"), Value::Array(vec![]));
+        let v = Value::Map(outer);
+
+        assert_eq!(v.to_json_string(0).unwrap(), r#"{"x":{"a\"b":[1,-0.5,null],"e":{}},"y":true,"z":[]}"#);
+        assert_eq!(v.to_json_string(2).unwrap(), r#"{
+  "x": {
+    "a\"b": [
+      1,
+      -0.5,
+      null
+    ],
+    "e": {}
+  },
+  "y": true,
+  "z": []
+}"#);
+    }
+
+    #[test]
+    fn errors() {
+        let mut m = BTreeMap::new();
+        m.insert(Value::Int(1), Value::Nil);
+        assert_eq!(Value::Map(m).to_json_string(0), Err(JsonExportError::NonStringKey));
+
+        let mut m = BTreeMap::new();
+        m.insert(Value::Array(vec![Value::Int(0xff)]), Value::Nil);
+        assert_eq!(Value::Map(m).to_json_string(0), Err(JsonExportError::NonStringKey));
+
+        assert_eq!(Value::Float(f64::NAN).to_json_string(0), Err(JsonExportError::NaN));
+        assert_eq!(Value::Array(vec![Value::Float(f64::NEG_INFINITY)]).to_json_string(0), Err(JsonExportError::Infinity));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn lossy() {
+        let mut ints = BTreeMap::new();
+        ints.insert(Value::Int(1), string("a"));
+        ints.insert(Value::Int(-20), Value::Nil);
+        ints.insert(Value::Float(0.1), Value::Bool(false));
+        assert_eq!(Value::Map(ints).to_json_lossy(), serde_json::json!({"1": [97], "-20": null, "0.1": false}));
+
+        let mut arrays = BTreeMap::new();
+        arrays.insert(Value::Array(vec![Value::Int(1000), Value::Int(2)]), Value::Int(3));
+        arrays.insert(Value::Array(vec![Value::Int(0xff)]), Value::Float(f64::NAN));
+        arrays.insert(Value::Array(vec![]), Value::Float(f64::NEG_INFINITY));
+        arrays.insert(string("s"), Value::Array(vec![Value::Float(-0.5), Value::Float(f64::INFINITY)]));
+        let mut nested = BTreeMap::new();
+        nested.insert(string("k"), Value::Array(vec![Value::Nil, Value::Float(1e300)]));
+        arrays.insert(Value::Map(nested), Value::Map(BTreeMap::new()));
+        let json = Value::Map(arrays).to_json_lossy();
+        assert_eq!(json, serde_json::json!({
+            "[1000,2]": 3,
+            "[255]": "NaN",
+            "": "-Inf",
+            "s": [-0.5, "Inf"],
+            "{[107]:[nil,1.0e300]}": {},
+        }));
+
+        // Keys that become the same string collide, the greater one wins.
+        let mut colliding = BTreeMap::new();
+        colliding.insert(Value::Int(1), Value::Int(0));
+        colliding.insert(string("1"), Value::Int(1));
+        assert_eq!(Value::Map(colliding).to_json_lossy(), serde_json::json!({"1": 1}));
+    }
+}
@@ -4,14 +4,60 @@
 //!
 //! There is no support for the [canonic encoding](https://github.com/AljoschaMeyer/valuable-value#canonic-encoding) because the serde API is not flexible enough to incorporate the required canonicity checks.
 //!
-//! Enable the `arbitrary` feature for an implementation of the [`Arbitrary`](arbitrary::Arbitrary) trait for the [`Value`](Value) type and further utilities for property testing.
+//! Values of any type that implements the serde traits can be encoded and decoded, and the [`Value`](Value) type can be used to inspect codes of unknown shape or to convert between the encodings:
+//!
+//! ```
+//! use serde::{Serialize, Deserialize};
+//! use valuable_value::{compact, human, Value};
+//!
+//! #[derive(Serialize, Deserialize, Debug, PartialEq)]
+//! enum Shape {
+//!     Circle { radius: f64 },
+//!     Polygon(Vec<(i64, i64)>),
+//! }
+//!
+//! let shapes = vec![Shape::Circle { radius: 1.5 }, Shape::Polygon(vec![(0, 0), (1, 0), (0, 1)])];
+//!
+//! let enc_human = human::to_vec(&shapes, 0).unwrap();
+//! let enc_compact = compact::to_vec(&shapes).unwrap();
+//! assert_eq!(human::from_slice::<Vec<Shape>>(&enc_human).unwrap(), shapes);
+//! assert_eq!(compact::from_slice::<Vec<Shape>>(&enc_compact).unwrap(), shapes);
+//!
+//! // Transcode from the human-readable encoding to the compact encoding.
+//! let v: Value = human::from_slice(&enc_human).unwrap();
+//! let transcoded = compact::to_vec(&v).unwrap();
+//! assert_eq!(compact::from_slice::<Value>(&transcoded).unwrap(), v);
+//! assert_eq!(compact::from_slice::<Vec<Shape>>(&transcoded).unwrap(), shapes);
+//! ```
+//!
+//! All serde attributes are supported, including internally tagged, adjacently tagged and untagged enums, and `#[serde(flatten)]`. Options are encoded as the string `"None"` and the map `{"Some": v}`. Serde decodes some attributes by first buffering values of unknown type (the contents of internally tagged and untagged enums, flattened fields, and the content of adjacently tagged enums if it precedes the tag), and the decoders cannot tell an encoded option from a string or map of that shape. They decode such values as strings and maps, so an `Option` inside buffered values fails to decode with an invalid type error. Options encoded with [`compact_options`](compact::VVSerializer::compact_options) do not have this problem. The [`Value`](Value) type stores strings as arrays of ints, so decoding such attributes from a value only works if the buffered values contain no strings other than map keys, see [`ValueDeserializer`](ValueDeserializer).
+//!
+//! Enable the `arbitrary` feature for an implementation of the [`Arbitrary`](arbitrary::Arbitrary) trait for the [`Value`](Value) type, further utilities for property testing, and ready-made [fuzzing entry points](fuzz).
 #![feature(total_cmp)]
 
 #[cfg(feature = "arbitrary")]
 pub mod test_type;
+#[cfg(any(test, feature = "arbitrary"))]
+pub mod testing;
+#[cfg(feature = "arbitrary")]
+pub mod fuzz;
 
+mod macros;
 mod value;
-pub use value::Value;
+pub use value::{Value, ValueDeserializer, GetError, PathSegment, ValueTypeError, ValueIndex, StringMapError, MergeStrategy};
+mod convert;
+pub use convert::{to_value, from_value, ConvertError, ValueSerializer, ValueRefDeserializer, SerializeArray, SerializeMap};
+mod json;
+pub use json::JsonExportError;
+mod truncated;
+mod schema;
+pub use schema::SchemaError;
+mod bounded;
+pub use bounded::BoundedValue;
 pub mod compact;
 pub mod human;
 mod helpers;
+#[cfg(test)]
+mod fixtures;
+#[cfg(test)]
+mod alloc_counter;
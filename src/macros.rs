@@ -0,0 +1,240 @@
+/// Construct a [`Value`](crate::Value) from a literal-like syntax.
+///
+/// `nil` becomes [`Value::Nil`](crate::Value::Nil), `[a, b, c]` an array and `{ key => value }` a map whose keys can be
+/// any values. Everything else is an expression that is converted with [`Value::from`](crate::Value), so booleans, ints,
+/// floats, strings and variables holding values can be spliced in directly. Arrays and maps may have a trailing comma.
+///
+/// ```
+/// use valuable_value::{vv, Value};
+///
+/// let name = "Alice";
+/// let v = vv!({
+///     "name" => name,
+///     "tags" => ["a", "b"],
+///     nil => { [1, 2] => -3, 4.5 => true },
+///     "empty" => [],
+/// });
+///
+/// assert_eq!(v.get_key("name"), Some(&Value::from("Alice")));
+/// assert_eq!(v[&Value::Nil][&vv!([1, 2])], Value::Int(-3));
+/// assert_eq!(vv!([nil, false, 1, 1.5,]), Value::Array(vec![Value::Nil, Value::Bool(false), Value::Int(1), Value::Float(1.5)]));
+/// ```
+///
+/// Integer literals have type `i32` unless suffixed, so larger ints need a suffix such as `1i64 << 40`. Values of types
+/// that can only be converted fallibly, such as `u64`, must be converted before splicing them in.
+///
+/// Every key needs a value:
+///
+/// ```compile_fail
+/// valuable_value::vv!({ "a" => 1, "b" });
+/// ```
+///
+/// ```compile_fail
+/// valuable_value::vv!({ "a" => });
+/// ```
+///
+/// Entries must be separated by commas:
+///
+/// ```compile_fail
+/// valuable_value::vv!({ "a" => 1 "b" => 2 });
+/// ```
+///
+/// ```compile_fail
+/// valuable_value::vv!([1 2]);
+/// ```
+///
+/// A map entry needs a key:
+///
+/// ```compile_fail
+/// valuable_value::vv!({ => 1 });
+/// ```
+#[macro_export]
+macro_rules! vv {
+    (nil) => {
+        $crate::Value::Nil
+    };
+
+    ([]) => {
+        $crate::Value::Array(::std::vec::Vec::new())
+    };
+
+    ([ $($tt:tt)+ ]) => {
+        $crate::Value::Array($crate::vv_internal!(@array [] $($tt)+))
+    };
+
+    ({}) => {
+        $crate::Value::Map(::std::collections::BTreeMap::new())
+    };
+
+    ({ $($tt:tt)+ }) => {
+        $crate::Value::Map({
+            let mut map = ::std::collections::BTreeMap::new();
+            $crate::vv_internal!(@map map () ($($tt)+));
+            map
+        })
+    };
+
+    ($other:expr) => {
+        $crate::Value::from($other)
+    };
+}
+
+// Munches the contents of arrays and maps one item at a time, in the style of `serde_json::json!`.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! vv_internal {
+    // Done with an array, with or without trailing comma.
+    (@array [$($items:expr,)*]) => {
+        ::std::vec![$($items,)*]
+    };
+    (@array [$($items:expr),*]) => {
+        ::std::vec![$($items),*]
+    };
+
+    // The next item is nil, an array or a map.
+    (@array [$($items:expr,)*] nil $($rest:tt)*) => {
+        $crate::vv_internal!(@array [$($items,)* $crate::vv!(nil)] $($rest)*)
+    };
+    (@array [$($items:expr,)*] [$($array:tt)*] $($rest:tt)*) => {
+        $crate::vv_internal!(@array [$($items,)* $crate::vv!([$($array)*])] $($rest)*)
+    };
+    (@array [$($items:expr,)*] {$($map:tt)*} $($rest:tt)*) => {
+        $crate::vv_internal!(@array [$($items,)* $crate::vv!({$($map)*})] $($rest)*)
+    };
+
+    // The next item is an expression, followed by a comma or the end of the array.
+    (@array [$($items:expr,)*] $next:expr, $($rest:tt)*) => {
+        $crate::vv_internal!(@array [$($items,)* $crate::vv!($next),] $($rest)*)
+    };
+    (@array [$($items:expr,)*] $last:expr) => {
+        $crate::vv_internal!(@array [$($items,)* $crate::vv!($last)])
+    };
+
+    // The comma after the previous item.
+    (@array [$($items:expr),*] , $($rest:tt)*) => {
+        $crate::vv_internal!(@array [$($items,)*] $($rest)*)
+    };
+
+    // Anything else, e.g. two items without a comma between them.
+    (@array [$($items:expr),*] $unexpected:tt $($rest:tt)*) => {
+        $crate::vv_unexpected!($unexpected)
+    };
+
+    // Done with a map.
+    (@map $map:ident () ()) => {};
+
+    // Insert an entry whose key and value have been munched, then continue after its comma.
+    (@map $map:ident [$($key:tt)+] ($value:expr) , $($rest:tt)*) => {
+        let _ = $map.insert($crate::vv!($($key)+), $value);
+        $crate::vv_internal!(@map $map () ($($rest)*));
+    };
+    (@map $map:ident [$($key:tt)+] ($value:expr) $unexpected:tt $($rest:tt)*) => {
+        $crate::vv_unexpected!($unexpected);
+    };
+    (@map $map:ident [$($key:tt)+] ($value:expr)) => {
+        let _ = $map.insert($crate::vv!($($key)+), $value);
+    };
+
+    // The value is nil, an array or a map.
+    (@map $map:ident ($($key:tt)+) (=> nil $($rest:tt)*)) => {
+        $crate::vv_internal!(@map $map [$($key)+] ($crate::vv!(nil)) $($rest)*);
+    };
+    (@map $map:ident ($($key:tt)+) (=> [$($array:tt)*] $($rest:tt)*)) => {
+        $crate::vv_internal!(@map $map [$($key)+] ($crate::vv!([$($array)*])) $($rest)*);
+    };
+    (@map $map:ident ($($key:tt)+) (=> {$($inner:tt)*} $($rest:tt)*)) => {
+        $crate::vv_internal!(@map $map [$($key)+] ($crate::vv!({$($inner)*})) $($rest)*);
+    };
+
+    // The value is an expression, followed by a comma or the end of the map.
+    (@map $map:ident ($($key:tt)+) (=> $value:expr , $($rest:tt)*)) => {
+        $crate::vv_internal!(@map $map [$($key)+] ($crate::vv!($value)) , $($rest)*);
+    };
+    (@map $map:ident ($($key:tt)+) (=> $value:expr)) => {
+        $crate::vv_internal!(@map $map [$($key)+] ($crate::vv!($value)));
+    };
+
+    // A key without a value, or a value without a key.
+    (@map $map:ident ($($key:tt)+) (=>)) => {
+        $crate::vv!();
+    };
+    (@map $map:ident ($($key:tt)+) ()) => {
+        $crate::vv!();
+    };
+    (@map $map:ident () (=> $($rest:tt)*)) => {
+        $crate::vv_unexpected!(=>);
+    };
+    (@map $map:ident ($($key:tt)*) (, $($rest:tt)*)) => {
+        $crate::vv_unexpected!(,);
+    };
+
+    // Munch one more token of the key.
+    (@map $map:ident ($($key:tt)*) ($next:tt $($rest:tt)*)) => {
+        $crate::vv_internal!(@map $map ($($key)* $next) ($($rest)*));
+    };
+}
+
+// Has no rule that accepts a token, so that the compiler points at the unexpected one.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! vv_unexpected {
+    () => {};
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::Value;
+
+    #[test]
+    fn literals() {
+        assert_eq!(vv!(nil), Value::Nil);
+        assert_eq!(vv!(true), Value::Bool(true));
+        assert_eq!(vv!(-7), Value::Int(-7));
+        assert_eq!(vv!(1i64 << 40), Value::Int(1 << 40));
+        assert_eq!(vv!(0.5), Value::Float(0.5));
+        assert_eq!(vv!("ab"), Value::from("ab"));
+        assert_eq!(vv!([]), Value::Array(vec![]));
+        assert_eq!(vv!({}), Value::Map(BTreeMap::new()));
+    }
+
+    #[test]
+    fn arrays() {
+        let expected = Value::Array(vec![Value::Nil, Value::Int(1), Value::Array(vec![Value::Array(vec![])]), Value::Map(BTreeMap::new())]);
+        assert_eq!(vv!([nil, 1, [[]], {}]), expected);
+        assert_eq!(vv!([nil, 1, [[],], {},]), expected);
+        assert_eq!(vv!([[1], [2, 3]]), Value::Array(vec![vv!([1]), vv!([2, 3])]));
+    }
+
+    #[test]
+    fn maps() {
+        let mut expected = BTreeMap::new();
+        expected.insert(Value::Int(2), Value::Nil);
+        expected.insert(Value::Int(-1), Value::Array(vec![Value::Bool(false)]));
+        expected.insert(Value::Nil, Value::Map(BTreeMap::new()));
+        expected.insert(Value::Array(vec![Value::Int(1)]), Value::Float(1.5));
+        expected.insert(Value::Map(BTreeMap::new()), Value::Int(0));
+
+        let v = vv!({ 2 => nil, -1 => [false], nil => {}, [1] => 1.5, {} => 0 });
+        assert_eq!(v, Value::Map(expected.clone()));
+        assert_eq!(vv!({ {} => 0, [1] => 1.5, nil => {}, -1 => [false], 2 => nil, }), Value::Map(expected));
+
+        // Later entries replace earlier ones with the same key.
+        assert_eq!(vv!({ 1 => 1, 1 => 2 }), vv!({ 1 => 2 }));
+        assert_eq!(vv!({ "a" => { "b" => { "c" => [] } } })["a"]["b"]["c"], vv!([]));
+    }
+
+    #[test]
+    fn splicing() {
+        let x = vv!([1, 2]);
+        let name = String::from("n");
+        let n = 3u8;
+        let v = vv!({ name.clone() => x.clone(), "len" => n, "first" => x[0usize].clone(), (n as i64 + 1) => nil });
+        assert_eq!(v["n"], x);
+        assert_eq!(v["len"], Value::Int(3));
+        assert_eq!(v["first"], Value::Int(1));
+        assert_eq!(v[&Value::Int(4)], Value::Nil);
+        assert_eq!(vv!([x, name, &b"ab"[..]]), Value::Array(vec![vv!([1, 2]), vv!("n"), Value::from(&b"ab"[..])]));
+    }
+}
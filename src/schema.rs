@@ -0,0 +1,152 @@
+use thiserror::Error;
+
+use crate::value::{render_path, Value};
+
+/// A violation of a schema, as reported by [`Value::validate_against`](Value::validate_against).
+///
+/// Paths are rendered in the [human-readable encoding](https://github.com/AljoschaMeyer/valuable-value#human-readable-encoding), with every segment that is a string followed by its text.
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum SchemaError {
+    /// The value at the path is of the wrong kind.
+    #[error("expected {expected} at path {path}")]
+    WrongType {
+        path: String,
+        expected: &'static str,
+    },
+    /// A field that the schema requires is missing, the path includes the key of the missing field.
+    #[error("missing field at path {0}")]
+    MissingField(String),
+    /// The schema that applies to the value at the path is not a valid schema.
+    #[error("invalid schema for path {0}")]
+    InvalidSchema(String),
+}
+
+fn string(s: &str) -> Value {
+    Value::Array(s.bytes().map(|b| Value::Int(b as i64)).collect())
+}
+
+impl Value {
+    /// Check whether this value conforms to a schema, collecting all violations.
+    ///
+    /// A schema is a map with a `"type"` entry whose value is one of the following strings:
+    ///
+    /// - `"any"`: any value,
+    /// - `"nil"`, `"bool"`, `"int"`, `"float"`, `"array"` or `"map"`: a value of that kind,
+    /// - `"string"`: an array of ints between 0 and 255 that form valid UTF-8.
+    ///
+    /// An `"array"` schema may have an `"items"` entry, a schema to which every item of the array must conform. A `"map"`
+    /// schema may have a `"fields"` entry, a map from keys to schemas: the value must have an entry for every one of these
+    /// keys, and the value of that entry must conform to the corresponding schema. Entries not mentioned in `"fields"` are
+    /// allowed. A schema with any other entries is invalid.
+    ///
+    /// The contents of a value of the wrong kind are not checked any further.
+    pub fn validate_against(&self, schema: &Value) -> Result<(), Vec<SchemaError>> {
+        let mut errors = Vec::new();
+        validate(self, schema, &mut Vec::new(), &mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn validate(v: &Value, schema: &Value, path: &mut Vec<Value>, errors: &mut Vec<SchemaError>) {
+    let invalid = |path: &[Value]| SchemaError::InvalidSchema(render_path(path));
+
+    let (s, kind) = match (schema, schema.get_str(&[string("type")])) {
+        (Value::Map(s), Ok(kind)) => (s, kind),
+        _ => return errors.push(invalid(path)),
+    };
+
+    let (expected, conforms, nested) = match kind.as_str() {
+        "any" => ("any", true, None),
+        "nil" => ("nil", matches!(v, Value::Nil), None),
+        "bool" => ("bool", matches!(v, Value::Bool(_)), None),
+        "int" => ("int", matches!(v, Value::Int(_)), None),
+        "float" => ("float", matches!(v, Value::Float(_)), None),
+        "string" => ("UTF-8 string", v.get_str(&[]).is_ok(), None),
+        "array" => ("array", matches!(v, Value::Array(_)), Some(string("items"))),
+        "map" => ("map", matches!(v, Value::Map(_)), Some(string("fields"))),
+        _ => return errors.push(invalid(path)),
+    };
+
+    if s.keys().any(|k| *k != string("type") && Some(k) != nested.as_ref()) {
+        return errors.push(invalid(path));
+    }
+
+    if !conforms {
+        return errors.push(SchemaError::WrongType { path: render_path(path), expected });
+    }
+
+    match (v, nested.and_then(|key| s.get(&key))) {
+        (Value::Array(items), Some(item_schema)) => {
+            for (i, item) in items.iter().enumerate() {
+                path.push(Value::Int(i as i64));
+                validate(item, item_schema, path, errors);
+                path.pop();
+            }
+        }
+        (Value::Map(m), Some(Value::Map(fields))) => {
+            for (key, field_schema) in fields.iter() {
+                path.push(key.clone());
+                match m.get(key) {
+                    Some(field) => validate(field, field_schema, path, errors),
+                    None => errors.push(SchemaError::MissingField(render_path(path))),
+                }
+                path.pop();
+            }
+        }
+        (Value::Map(_), Some(_)) => errors.push(invalid(path)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    fn human(input: &str) -> Value {
+        Value::deserialize(&mut crate::human::VVDeserializer::new(input.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn conforming() {
+        let schema = human(r#"{"type": "map", "fields": {
+            "name": {"type": "string"},
+            "scores": {"type": "array", "items": {"type": "int"}},
+            "extra": {"type": "any"},
+        }}"#);
+        let v = human(r#"{"name": "alice", "scores": [1, 2, 3], "extra": nil, "unchecked": 1.5}"#);
+        assert_eq!(v.validate_against(&schema), Ok(()));
+    }
+
+    #[test]
+    fn violations() {
+        let schema = human(r#"{"type": "map", "fields": {
+            "name": {"type": "int"},
+            "scores": {"type": "array", "items": {"type": "int"}},
+        }}"#);
+        let v = human(r#"{"name": "bob", "other": 3}"#);
+        assert_eq!(v.validate_against(&schema), Err(vec![
+            SchemaError::WrongType { path: r#"[[110,97,109,101] ("name")]"#.to_string(), expected: "int" },
+            SchemaError::MissingField(r#"[[115,99,111,114,101,115] ("scores")]"#.to_string()),
+        ]));
+
+        let v = human(r#"{"name": 1, "scores": [1, 2.0, false]}"#);
+        assert_eq!(v.validate_against(&schema), Err(vec![
+            SchemaError::WrongType { path: r#"[[115,99,111,114,101,115] ("scores"),1]"#.to_string(), expected: "int" },
+            SchemaError::WrongType { path: r#"[[115,99,111,114,101,115] ("scores"),2]"#.to_string(), expected: "int" },
+        ]));
+    }
+
+    #[test]
+    fn invalid_schemas() {
+        let v = human("[1]");
+        assert_eq!(v.validate_against(&human(r#"{"type": "list"}"#)), Err(vec![SchemaError::InvalidSchema("[]".to_string())]));
+        assert_eq!(v.validate_against(&human(r#"{"type": "array", "fields": {}}"#)), Err(vec![SchemaError::InvalidSchema("[]".to_string())]));
+        assert_eq!(v.validate_against(&human(r#"{"type": "array", "items": 0}"#)), Err(vec![SchemaError::InvalidSchema("[0]".to_string())]));
+        assert_eq!(v.validate_against(&human("[]")), Err(vec![SchemaError::InvalidSchema("[]".to_string())]));
+    }
+}
@@ -1,4 +1,4 @@
-//! This module provides [`TestType`](TestType), a type that uses all aspects of the serde data model and is intended for testing purposes.
+//! This module provides [`TestType`](TestType), a type that uses all aspects of the serde data model except chars (on which the two encodings disagree), and is intended for testing purposes.
 use std::collections::BTreeMap;
 use std::fmt;
 
@@ -16,7 +16,6 @@ pub struct TestType {
     a_i16: i16,
     a_i32: i32,
     a_i64: i64,
-    a_char: char,
     a_string: String,
     bytes0: Bytes,
     bytes1: Bytes,
@@ -137,7 +136,6 @@ pub fn new() -> TestType {
         a_i16: 0,
         a_i32: 0,
         a_i64: 0,
-        a_char: '@',
         a_string: "@@".to_string(),
         bytes0: Bytes(vec![]),
         bytes1: Bytes(vec![0]),
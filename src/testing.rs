@@ -0,0 +1,217 @@
+//! Property-testing utilities that check the human-readable and the compact encoding against each other.
+use core::fmt::Debug;
+
+#[cfg(feature = "arbitrary")]
+use arbitrary::{Arbitrary, Unstructured};
+use serde::{Serialize, Deserialize};
+
+use crate::{compact, human, Value};
+
+/// Check that the human-readable and the compact encoding agree on `x`.
+///
+/// Panics unless decoding the human-readable encoding of `x` yields the same [`Value`](crate::Value) as decoding its compact encoding, and unless transcoding that value from either encoding into the other is lossless. Values that cannot be encoded at all must be rejected by both encoders.
+pub fn assert_cross_encoding<T>(x: &T, indentation: usize)
+where
+    T: Serialize + Debug,
+{
+    let (enc_human, enc_compact) = match (human::to_vec(x, indentation), compact::to_vec(x)) {
+        (Ok(enc_human), Ok(enc_compact)) => (enc_human, enc_compact),
+        (Err(_), Err(_)) => return,
+        (enc_human, enc_compact) => {
+            panic!("only one encoder accepted {:?}\nhuman: {:?}\ncompact: {:?}", x, enc_human, enc_compact);
+        }
+    };
+
+    let from_human = decode_human(&enc_human);
+    let from_compact = decode_compact(&enc_compact);
+    if from_human != from_compact {
+        panic!(
+            "encodings disagree on {:?}\nhuman: {}\ncompact: {:?}\ndecoded from human: {:?}\ndecoded from compact: {:?}",
+            x, String::from_utf8_lossy(&enc_human), enc_compact, from_human, from_compact,
+        );
+    }
+
+    let to_compact = compact::to_vec(&from_human).unwrap();
+    let transcoded = decode_compact(&to_compact);
+    if transcoded != from_human {
+        panic!("transcoding from human to compact is lossy\noriginal: {:?}\ntranscoded: {:?}", from_human, transcoded);
+    }
+
+    let to_human = human::to_vec(&from_compact, indentation).unwrap();
+    let transcoded = decode_human(&to_human);
+    if transcoded != from_compact {
+        panic!("transcoding from compact to human is lossy\noriginal: {:?}\ntranscoded: {:?}", from_compact, transcoded);
+    }
+}
+
+/// Repeatedly generate values from the fuzzing input `data` via `gen`, and [check](assert_cross_encoding) each of them both with and without pretty-printing.
+///
+/// Stops once the input is exhausted, or once `gen` fails or does not consume any input.
+#[cfg(feature = "arbitrary")]
+pub fn check_cross_encoding<'a, T, F>(data: &'a [u8], mut gen: F)
+where
+    T: Serialize + Debug,
+    F: FnMut(&mut Unstructured<'a>) -> arbitrary::Result<T>,
+{
+    let mut u = Unstructured::new(data);
+    while !u.is_empty() {
+        let remaining = u.len();
+        match gen(&mut u) {
+            Ok(x) => {
+                assert_cross_encoding(&x, 0);
+                assert_cross_encoding(&x, 2);
+            }
+            Err(_) => return,
+        }
+
+        if u.len() == remaining {
+            return;
+        }
+    }
+}
+
+/// Check that encoding `v` in the compact encoding is stable.
+///
+/// Panics unless decoding the compact encoding of `v` (with all NaNs [normalized](Value::fix_nan)) consumes the whole encoding and yields an equal value, and unless encoding that decoded value again yields exactly the same bytes.
+pub fn assert_compact_roundtrip_stable(v: &Value) {
+    let mut v = v.clone();
+    v.fix_nan();
+
+    let enc = compact::to_vec(&v).unwrap();
+    let mut des = compact::VVDeserializer::new(&enc);
+    let decoded = match Value::deserialize(&mut des) {
+        Ok(decoded) => decoded,
+        Err(e) => panic!("failed to decode compact encoding {:?}\nerror: {:?}", enc, e),
+    };
+    if des.position() != enc.len() {
+        panic!("decoding did not consume the whole compact encoding\noriginal: {:?}\nencoding: {:?}", v, enc);
+    }
+    if decoded != v {
+        panic!("compact round trip is lossy\noriginal: {:?}\nencoding: {:?}\ndecoded: {:?}", v, enc, decoded);
+    }
+
+    let reencoded = compact::to_vec(&decoded).unwrap();
+    if reencoded != enc {
+        panic!("compact encoding is not idempotent\noriginal: {:?}\nencoding: {:?}\nreencoding: {:?}", v, enc, reencoded);
+    }
+}
+
+/// Repeatedly generate [`Value`](Value)s from the fuzzing input `data`, and [check](assert_compact_roundtrip_stable) each of them.
+///
+/// Stops once the input is exhausted, or once generating a value fails or does not consume any input.
+#[cfg(feature = "arbitrary")]
+pub fn check_compact_roundtrip_stable(data: &[u8]) {
+    let mut u = Unstructured::new(data);
+    while !u.is_empty() {
+        let remaining = u.len();
+        match Value::arbitrary(&mut u) {
+            Ok(v) => assert_compact_roundtrip_stable(&v),
+            Err(_) => return,
+        }
+
+        if u.len() == remaining {
+            return;
+        }
+    }
+}
+
+/// A xorshift generator for deterministic pseudo-random test inputs, so that failures are reproducible.
+#[cfg(test)]
+pub(crate) struct XorShift(u64);
+
+#[cfg(test)]
+impl XorShift {
+    /// The seed must not be zero, since zero is a fixed point.
+    pub(crate) fn new(seed: u64) -> Self {
+        XorShift(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// The low bytes of the next `len` outputs, as input for [`Unstructured`](Unstructured).
+    #[cfg(feature = "arbitrary")]
+    pub(crate) fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next_u64() as u8).collect()
+    }
+}
+
+fn decode_human(enc: &[u8]) -> Value {
+    match Value::deserialize(&mut human::VVDeserializer::new(enc)) {
+        Ok(v) => v,
+        Err(e) => panic!("failed to decode human-readable encoding {}\nerror: {:?}", String::from_utf8_lossy(enc), e),
+    }
+}
+
+fn decode_compact(enc: &[u8]) -> Value {
+    match Value::deserialize(&mut compact::VVDeserializer::new(enc)) {
+        Ok(v) => v,
+        Err(e) => panic!("failed to decode compact encoding {:?}\nerror: {:?}", enc, e),
+    }
+}
+
+#[cfg(all(test, feature = "arbitrary"))]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    use crate::test_type::{self, TestType};
+
+    // Deterministic pseudo-random input, so that failures are reproducible.
+    fn input(seed: u64) -> Vec<u8> {
+        XorShift::new(seed).bytes(4096)
+    }
+
+    #[test]
+    fn values() {
+        for seed in 1..64 {
+            check_cross_encoding(&input(seed), Value::arbitrary);
+        }
+    }
+
+    #[test]
+    fn compact_roundtrip_stable() {
+        assert_compact_roundtrip_stable(&Value::Float(f64::from_bits(0x7ff8_0000_0000_0001)));
+        for n in [0, 27, 28, -1, 127, 128, -129, 32767, 32768, i64::MIN, i64::MAX].iter() {
+            assert_compact_roundtrip_stable(&Value::Int(*n));
+        }
+        for len in [27, 28, 255, 256, 65535].iter() {
+            assert_compact_roundtrip_stable(&Value::Array(vec![Value::Nil; *len]));
+        }
+
+        for seed in 1..64 {
+            check_compact_roundtrip_stable(&input(seed));
+        }
+    }
+
+    #[test]
+    fn test_types() {
+        assert_cross_encoding(&test_type::new(), 0);
+        assert_cross_encoding(&test_type::new(), 2);
+
+        for seed in 1..64 {
+            check_cross_encoding(&input(seed), TestType::arbitrary);
+        }
+    }
+
+    #[test]
+    fn std_types() {
+        for seed in 1..64 {
+            check_cross_encoding(&input(seed), <(u8, i64, bool, String)>::arbitrary);
+            check_cross_encoding(&input(seed), <Option<Option<String>>>::arbitrary);
+            check_cross_encoding(&input(seed), <BTreeMap<String, Vec<i32>>>::arbitrary);
+        }
+    }
+
+    #[test]
+    // The encodings disagree on chars: the human-readable encoding writes a string, the compact encoding an int. This is
+    // why the test type contains no char.
+    fn chars() {
+        assert_eq!(decode_human(&human::to_vec(&'é', 0).unwrap()), Value::from("é"));
+        assert_eq!(decode_compact(&compact::to_vec(&'é').unwrap()), Value::Int(0xe9));
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::{btree_map, HashMap};
+use std::slice;
+
+use crate::value::Value;
+
+impl Value {
+    /// Render this value in the human-readable encoding, spending at most `max_bytes` bytes on it.
+    ///
+    /// Meant for logging values of arbitrary size. The value is rendered breadth-first: the items of a collection are filled in before the contents of any nested collection, and once the budget is spent, the remaining items of every collection are replaced by a comment such as `# … 4321 more` (or `# … 17 more entries` for maps). The result is valid human-readable encoding of the value with those items dropped. Map keys are always rendered in full and on a single line, entries whose key does not fit are dropped as a whole. The outermost value is rendered regardless of the budget, so the result only exceeds `max_bytes` if even the delimiters of the outermost collection and its comment do not fit.
+    ///
+    /// Apart from the item at which the budget runs out, only the parts of the value that end up in the result are visited, so this is cheap even for huge values. With an indentation of zero and a sufficient budget, the result is the same as that of [`human::to_vec`](crate::human::to_vec). Pretty-prints if the indentation is greater than zero, in which case every non-empty collection is spread across several lines.
+    pub fn to_human_string_truncated(&self, indentation: usize, max_bytes: usize) -> String {
+        let planned = plan(self, indentation, max_bytes);
+        let mut out = String::new();
+        render(self, indentation, 0, &planned, &mut out);
+        out
+    }
+}
+
+// The items of a collection, in the order in which they are rendered.
+enum Items<'a> {
+    Array(slice::Iter<'a, Value>),
+    Map(btree_map::Iter<'a, Value, Value>),
+}
+
+impl<'a> Items<'a> {
+    fn of(v: &'a Value) -> Option<Items<'a>> {
+        match v {
+            Value::Array(vs) if !vs.is_empty() => Some(Items::Array(vs.iter())),
+            Value::Map(m) if !m.is_empty() => Some(Items::Map(m.iter())),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Option<(Option<&'a Value>, &'a Value)> {
+        match self {
+            Items::Array(vs) => vs.next().map(|v| (None, v)),
+            Items::Map(m) => m.next().map(|(k, v)| (Some(k), v)),
+        }
+    }
+}
+
+fn len(v: &Value) -> usize {
+    match v {
+        Value::Array(vs) => vs.len(),
+        Value::Map(m) => m.len(),
+        _ => 0,
+    }
+}
+
+// The comment that replaces the `omitted` last items of a collection.
+fn marker(v: &Value, omitted: usize) -> String {
+    match v {
+        Value::Map(_) => format!("# … {} more entries", omitted),
+        _ => format!("# … {} more", omitted),
+    }
+}
+
+// Scalars, empty collections and map keys are rendered just like the human-readable encoding renders them without indentation.
+fn flat(v: &Value) -> String {
+    match crate::human::to_vec(v, 0) {
+        Ok(out) => String::from_utf8_lossy(&out).into_owned(),
+        Err(_) => format!("{:?}", v),
+    }
+}
+
+// How many bytes a value at the given depth takes up, not counting the items of a non-empty collection.
+fn own_cost(v: &Value, indentation: usize, depth: usize) -> usize {
+    match Items::of(v) {
+        None => flat(v).len(),
+        // The delimiters.
+        Some(_) if indentation == 0 => 2,
+        // The delimiters, and the newline and indentation before the closing one.
+        Some(_) => 3 + depth * indentation,
+    }
+}
+
+// How many bytes the item at position `i` of a collection at the given depth takes up, not counting the item itself.
+fn item_cost(indentation: usize, depth: usize, i: usize, key: Option<&Value>) -> usize {
+    let key = key.map_or(0, |k| flat(k).len() + if indentation == 0 { 1 } else { 2 });
+    if indentation == 0 {
+        key + (i > 0) as usize
+    } else {
+        // A newline, the indentation, and a trailing comma.
+        key + 1 + (depth + 1) * indentation + 1
+    }
+}
+
+// A non-empty collection that gets rendered, together with how many of its items get rendered.
+struct Collection<'a> {
+    v: &'a Value,
+    depth: usize,
+    rendered: usize,
+}
+
+impl<'a> Collection<'a> {
+    // How many bytes the comment about the omitted items takes up.
+    fn comment_cost(&self, indentation: usize) -> usize {
+        let omitted = len(self.v) - self.rendered;
+        if omitted == 0 {
+            return 0;
+        }
+        let comment = marker(self.v, omitted).len();
+        if indentation == 0 {
+            // Preceded by a comma unless it is the only item, and followed by a newline.
+            (self.rendered > 0) as usize + comment + 1
+        } else {
+            1 + (self.depth + 1) * indentation + comment
+        }
+    }
+}
+
+// Decide how many items of each collection to render, keyed by the address of the collection. Items are added in breadth-first
+// order while they fit into the budget, and then the last ones are dropped again until the comments about all omitted items
+// fit as well.
+fn plan(v: &Value, indentation: usize, max_bytes: usize) -> HashMap<*const Value, usize> {
+    // All collections that get rendered, in breadth-first order, so this doubles as the queue of collections to fill in.
+    let mut collections = Vec::new();
+    // For each rendered item in breadth-first order: the index of its collection, its own index in `collections` if it is a
+    // non-empty collection itself, and how many bytes it takes up.
+    let mut rendered = Vec::new();
+    let mut used = own_cost(v, indentation, 0);
+    if Items::of(v).is_some() {
+        collections.push(Collection { v, depth: 0, rendered: 0 });
+    }
+
+    let mut next = 0;
+    'fill: while next < collections.len() {
+        let (collection, depth) = (collections[next].v, collections[next].depth);
+        let mut items = match Items::of(collection) {
+            Some(items) => items,
+            None => break,
+        };
+        let mut i = 0;
+        while let Some((key, item)) = items.next() {
+            let cost = item_cost(indentation, depth, i, key) + own_cost(item, indentation, depth + 1);
+            if used + cost > max_bytes {
+                break 'fill;
+            }
+            used += cost;
+            i += 1;
+            collections[next].rendered = i;
+
+            let nested = Items::of(item).map(|_| {
+                collections.push(Collection { v: item, depth: depth + 1, rendered: 0 });
+                collections.len() - 1
+            });
+            rendered.push((next, nested, cost));
+        }
+        next += 1;
+    }
+
+    let mut total = used + collections.iter().map(|c| c.comment_cost(indentation)).sum::<usize>();
+    while total > max_bytes {
+        let (parent, nested, cost) = match rendered.pop() {
+            Some(last) => last,
+            None => break,
+        };
+        total -= cost;
+        // The items of a nested collection come after the collection itself, so they have all been dropped already, and it is
+        // the last collection.
+        if nested.is_some() {
+            if let Some(c) = collections.pop() {
+                total -= c.comment_cost(indentation);
+            }
+        }
+        total -= collections[parent].comment_cost(indentation);
+        collections[parent].rendered -= 1;
+        total += collections[parent].comment_cost(indentation);
+    }
+
+    collections.iter().map(|c| (c.v as *const Value, c.rendered)).collect()
+}
+
+fn newline(indentation: usize, depth: usize, out: &mut String) {
+    out.push('\n');
+    for _ in 0..(indentation * depth) {
+        out.push(' ');
+    }
+}
+
+fn render(v: &Value, indentation: usize, depth: usize, planned: &HashMap<*const Value, usize>, out: &mut String) {
+    let mut items = match Items::of(v) {
+        Some(items) => items,
+        None => return out.push_str(&flat(v)),
+    };
+    let (open, close) = match v {
+        Value::Map(_) => ('{', '}'),
+        _ => ('[', ']'),
+    };
+    let rendered = planned.get(&(v as *const Value)).copied().unwrap_or(0);
+
+    out.push(open);
+    for i in 0..rendered {
+        let (key, item) = match items.next() {
+            Some(next) => next,
+            None => break,
+        };
+        if indentation == 0 {
+            if i > 0 {
+                out.push(',');
+            }
+        } else {
+            newline(indentation, depth + 1, out);
+        }
+        if let Some(key) = key {
+            out.push_str(&flat(key));
+            out.push_str(if indentation == 0 { ":" } else { ": " });
+        }
+        render(item, indentation, depth + 1, planned, out);
+        if indentation != 0 {
+            out.push(',');
+        }
+    }
+
+    let omitted = len(v) - rendered;
+    if omitted > 0 {
+        if indentation == 0 {
+            if rendered > 0 {
+                out.push(',');
+            }
+        } else {
+            newline(indentation, depth + 1, out);
+        }
+        out.push_str(&marker(v, omitted));
+        // The comment extends to the end of the line.
+        if indentation == 0 {
+            out.push('\n');
+        }
+    }
+    if indentation != 0 {
+        newline(indentation, depth, out);
+    }
+    out.push(close);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::human;
+
+    // Whether `pruned` can be obtained from `v` by dropping trailing items of collections.
+    fn is_pruned(pruned: &Value, v: &Value) -> bool {
+        match (pruned, v) {
+            (Value::Array(ps), Value::Array(vs)) => ps.len() <= vs.len() && ps.iter().zip(vs).all(|(p, v)| is_pruned(p, v)),
+            (Value::Map(pm), Value::Map(m)) => pm.len() <= m.len() && pm.iter().zip(m).all(|((pk, p), (k, v))| pk == k && is_pruned(p, v)),
+            _ => pruned == v,
+        }
+    }
+
+    fn parse(s: &str) -> Value {
+        human::from_slice(s.as_bytes()).unwrap_or_else(|e| panic!("{:?} in {}", e, s))
+    }
+
+    // The numbers in all comments of a rendered value.
+    fn omitted(s: &str) -> Vec<usize> {
+        s.split("# … ").skip(1).map(|rest| rest.split(' ').next().unwrap().parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn within_budget() {
+        let v = parse(r#"{"a": [1, 2.5, nil], "b": {}, "c": [[true], []], [1, 2]: "key"}"#);
+        assert_eq!(v.to_human_string_truncated(0, 1000), flat(&v));
+        assert_eq!(parse(&v.to_human_string_truncated(2, 1000)), v);
+        assert_eq!(v.to_human_string_truncated(2, 1000), "{\n  [1,2]: [\n    107,\n    101,\n    121,\n  ],\n  [97]: [\n    1,\n    2.5,\n    nil,\n  ],\n  [98]: {},\n  [99]: [\n    [\n      true,\n    ],\n    [],\n  ],\n}");
+        assert_eq!(Value::Int(-17).to_human_string_truncated(0, 0), "-17");
+        assert_eq!(Value::Array(vec![]).to_human_string_truncated(2, 0), "[]");
+    }
+
+    #[test]
+    fn breadth_first() {
+        let row = |first: i64| Value::Array((first..first + 20).map(Value::Int).collect());
+        let v = Value::Array(vec![row(0), row(100), Value::Int(7), Value::Int(8)]);
+
+        assert_eq!(v.to_human_string_truncated(0, 30), "[# … 4 more\n]");
+        assert_eq!(v.to_human_string_truncated(0, 40), "[[# … 20 more\n],[# … 20 more\n],7,8]");
+        let s = v.to_human_string_truncated(0, 50);
+        assert_eq!(s, "[[0,1,2,3,4,# … 15 more\n],[# … 20 more\n],7,8]");
+        assert_eq!(parse(&s), parse("[[0, 1, 2, 3, 4], [], 7, 8]"));
+        assert_eq!(v.to_human_string_truncated(0, 1000), flat(&v));
+
+        assert_eq!(v.to_human_string_truncated(2, 0), "[\n  # … 4 more\n]");
+        assert_eq!(v.to_human_string_truncated(2, 60), "[\n  [\n    # … 20 more\n  ],\n  # … 3 more\n]");
+    }
+
+    #[test]
+    fn huge_array() {
+        let v = Value::Array((0..1_000_000).map(Value::Int).collect());
+        for indentation in [0, 4] {
+            for max_bytes in [0, 20, 1000, 100_000] {
+                let s = v.to_human_string_truncated(indentation, max_bytes);
+                assert!(s.len() <= max_bytes.max(30), "{} bytes for a budget of {}", s.len(), max_bytes);
+                // Filling the budget up to the length of a single item.
+                assert!(s.len() + 2 * 4 + 12 >= max_bytes, "only {} bytes for a budget of {}", s.len(), max_bytes);
+
+                let pruned = parse(&s);
+                assert!(is_pruned(&pruned, &v));
+                assert_eq!(omitted(&s), vec![1_000_000 - pruned.get_array(&[]).unwrap().len()]);
+            }
+        }
+    }
+
+    #[test]
+    fn deep_map() {
+        let mut v = Value::Nil;
+        for level in 0..1000 {
+            let mut m = std::collections::BTreeMap::new();
+            m.insert(Value::from("level"), Value::Int(level));
+            m.insert(Value::from("data"), Value::Array(vec![Value::Float(0.5); 10]));
+            m.insert(Value::from("next"), v);
+            v = Value::Map(m);
+        }
+
+        for indentation in [0, 2] {
+            for max_bytes in [50, 500, 5000] {
+                let s = v.to_human_string_truncated(indentation, max_bytes);
+                assert!(s.len() <= max_bytes, "{} bytes for a budget of {}", s.len(), max_bytes);
+                assert!(s.len() + 200 >= max_bytes, "only {} bytes for a budget of {}", s.len(), max_bytes);
+                assert!(is_pruned(&parse(&s), &v));
+            }
+        }
+
+        // The outer levels are complete before any deeper level shows up.
+        let s = v.to_human_string_truncated(0, 500);
+        assert!(s.starts_with(r#"{[100,97,116,97]:[0.5,0.5,0.5,0.5,0.5,0.5,0.5,0.5,0.5,0.5],[108,101,118,101,108]:999,"#), "{}", s);
+    }
+}
@@ -4,16 +4,41 @@ use serde::ser::SerializeSeq;
 use core::cmp::{self, Ordering};
 use Ordering::*;
 
+use std::convert::TryFrom;
+use std::iter::FromIterator;
 use std::fmt;
 use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+use std::str::FromStr;
 
-use serde::{Serialize, Serializer, Deserialize, Deserializer, de::{self, Visitor, SeqAccess}};
+use thiserror::Error;
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer, de::{self, DeserializeSeed, Visitor, SeqAccess}};
 
 /// A type for working with valuable values of arbitrary shape
 ///
 /// The implementations of `PartialEq` and `Eq` adhere to the [equality relation](https://github.com/AljoschaMeyer/valuable-value#equality) of the valuable value specification, and the implementations of `PartialOrd` and `Ord` (*both* of them) adhere to the [canonic linear order](https://github.com/AljoschaMeyer/valuable-value#canonic-linear-order). The [subvalue relation](https://github.com/AljoschaMeyer/valuable-value#subvalues) is implemented in additional methods that do not correspond to any trait.
 ///
-/// Serialization and deserialization can be performed via serde.
+/// Serialization and deserialization can be performed via serde. Maps are always serialized with their entries in ascending order of their keys, regardless of the order in which the entries were inserted, so equal values have equal encodings (as long as they do not contain NaNs with different bit patterns, see [`fix_nan`](Value::fix_nan)). Hashing or comparing encodings can rely on this.
+///
+/// There are no separate variants for byte strings and sets: a byte string decodes to an array of ints, and a set to a map whose values are all `Nil`. In particular, the empty set and the empty map are the same value, and the encoders always write it as the empty map (`{}`). Likewise, the empty byte string and the empty array are the same value, written as the empty array (`[]`).
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use valuable_value::{compact, human, Value};
+///
+/// let mut m = BTreeMap::new();
+/// m.insert(Value::from("tags"), Value::Array(vec![Value::Int(1), Value::Int(2)]));
+/// let v = Value::Map(m);
+///
+/// assert_eq!(human::from_slice::<Value>(b"{\"tags\": [1, 2]}").unwrap(), v);
+/// assert_eq!(compact::from_slice::<Value>(&compact::to_vec(&v).unwrap()).unwrap(), v);
+///
+/// assert_eq!(v.get_int(&[Value::from("tags"), Value::Int(1)]).unwrap(), 2);
+/// assert!(Value::Map(BTreeMap::new()).strict_subvalue(&v));
+/// ```
 #[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[derive(Clone)]
 pub enum Value {
@@ -27,6 +52,155 @@ pub enum Value {
 
 use Value::*;
 
+impl From<&str> for Value {
+    /// Represent a string as the array of its UTF-8 bytes.
+    fn from(s: &str) -> Self {
+        Array(s.bytes().map(|b| Int(b as i64)).collect())
+    }
+}
+
+impl From<String> for Value {
+    /// Represent a string as the array of its UTF-8 bytes.
+    fn from(s: String) -> Self {
+        Value::from(s.as_str())
+    }
+}
+
+impl From<&[u8]> for Value {
+    /// Represent a byte string as the array of its bytes.
+    fn from(b: &[u8]) -> Self {
+        Array(b.iter().map(|b| Int(i64::from(*b))).collect())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Bool(b)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Float(n)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(n: f32) -> Self {
+        Float(f64::from(n))
+    }
+}
+
+macro_rules! from_int {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Value {
+                fn from(n: $t) -> Self {
+                    Int(i64::from(n))
+                }
+            }
+        )*
+    };
+}
+
+// Only the types that convert into `i64` losslessly, see the `TryFrom<u64>` impl for the others.
+from_int!(i8, i16, i32, i64, u8, u16, u32);
+
+impl TryFrom<u64> for Value {
+    type Error = std::num::TryFromIntError;
+
+    /// Fails for ints greater than `i64::MAX`, which valuable values cannot represent.
+    fn try_from(n: u64) -> Result<Self, Self::Error> {
+        i64::try_from(n).map(Int)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(v: Vec<Value>) -> Self {
+        Array(v)
+    }
+}
+
+impl From<BTreeMap<Value, Value>> for Value {
+    fn from(m: BTreeMap<Value, Value>) -> Self {
+        Map(m)
+    }
+}
+
+impl FromIterator<Value> for Value {
+    /// Collect into an array, see also [`Value::array`](Value::array).
+    fn from_iter<I: IntoIterator<Item = Value>>(iter: I) -> Self {
+        Value::array(iter)
+    }
+}
+
+impl FromIterator<(Value, Value)> for Value {
+    /// Collect into a map, see also [`Value::map`](Value::map).
+    fn from_iter<I: IntoIterator<Item = (Value, Value)>>(iter: I) -> Self {
+        Value::map(iter)
+    }
+}
+
+/// The error of converting a [`Value`](Value) into a rust type via `TryFrom`, when the value is of a different kind than the type.
+#[derive(Error, Debug, PartialEq, Eq, Clone, Copy)]
+#[error("expected {expected}, found {found}")]
+pub struct ValueTypeError {
+    /// The kind the conversion requires: `"bool"`, `"int"`, `"float"`, `"array"` or `"map"`.
+    pub expected: &'static str,
+    /// The kind of the value: `"nil"`, `"bool"`, `"int"`, `"float"`, `"array"` or `"map"`.
+    pub found: &'static str,
+}
+
+/// The error of [`Value::to_string_map`](Value::to_string_map).
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum StringMapError {
+    /// The value is not a map. Holds its kind: `"nil"`, `"bool"`, `"int"`, `"float"` or `"array"`.
+    #[error("expected map, found {0}")]
+    NotAMap(&'static str),
+    /// A key is not a UTF-8 string. Holds the first such key, rendered in the human-readable encoding and truncated if it
+    /// was long, and its index in the (ascending) order of the keys.
+    #[error("key {key} at index {index} is not a UTF-8 string")]
+    NonStringKey { key: String, index: usize },
+}
+
+impl Value {
+    fn kind_name(&self) -> &'static str {
+        match self {
+            Nil => "nil",
+            Bool(_) => "bool",
+            Int(_) => "int",
+            Float(_) => "float",
+            Array(_) => "array",
+            Map(_) => "map",
+        }
+    }
+}
+
+macro_rules! try_from_value {
+    ($($t:ty => $variant:ident, $expected:expr;)*) => {
+        $(
+            impl TryFrom<Value> for $t {
+                type Error = ValueTypeError;
+
+                fn try_from(v: Value) -> Result<Self, ValueTypeError> {
+                    match v {
+                        $variant(inner) => Ok(inner),
+                        other => Err(ValueTypeError { expected: $expected, found: other.kind_name() }),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+try_from_value! {
+    bool => Bool, "bool";
+    i64 => Int, "int";
+    f64 => Float, "float";
+    Vec<Value> => Array, "array";
+    BTreeMap<Value, Value> => Map, "map";
+}
+
 impl fmt::Debug for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -46,6 +220,47 @@ impl fmt::Debug for Value {
     }
 }
 
+/// Writes the [human-readable encoding](https://github.com/AljoschaMeyer/valuable-value#human-readable-encoding) of the
+/// value, on a single line, or pretty-printed with an indentation of two spaces in the alternate form (`{:#}`). The output
+/// is exactly what [`human::to_vec`](crate::human::to_vec) produces.
+///
+/// ```
+/// use valuable_value::{vv, Value};
+///
+/// let v = vv!({ "a" => [1, -2.5], nil => f64::NEG_INFINITY });
+/// assert_eq!(format!("{}", v), "{nil:-Inf,[97]:[1,-2.5]}");
+/// assert_eq!(format!("{:#}", v), "{\n  nil: -Inf,\n  [97]: [\n    1,\n    -2.5,\n  ],\n}");
+/// ```
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indentation = if f.alternate() { 2 } else { 0 };
+        let enc = crate::human::to_vec(self, indentation).map_err(|_| fmt::Error)?;
+        f.write_str(std::str::from_utf8(&enc).map_err(|_| fmt::Error)?)
+    }
+}
+
+/// Parses the [human-readable encoding](https://github.com/AljoschaMeyer/valuable-value#human-readable-encoding), like
+/// [`human::from_str`](crate::human::from_str): only whitespace and comments may follow the value, anything else is
+/// reported as [`DecodeError::TrailingBytes`](crate::human::DecodeError::TrailingBytes). Errors carry the byte position
+/// at which decoding failed.
+///
+/// ```
+/// use valuable_value::{human::DecodeError, vv, Value};
+///
+/// let v: Value = "[1, nil] # items".parse().unwrap();
+/// assert_eq!(v, vv!([1, nil]));
+///
+/// let err = "[1, nil] 2".parse::<Value>().unwrap_err();
+/// assert_eq!((err.position, err.e), (9, DecodeError::TrailingBytes));
+/// ```
+impl FromStr for Value {
+    type Err = crate::human::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::human::from_str(s)
+    }
+}
+
 impl PartialEq for Value {
     /// Adheres to the [equality relation](https://github.com/AljoschaMeyer/valuable-value#equality).
     fn eq(&self, other: &Self) -> bool {
@@ -132,6 +347,40 @@ impl Ord for Value {
     }
 }
 
+impl Hash for Value {
+    /// Consistent with the [equality relation](https://github.com/AljoschaMeyer/valuable-value#equality): all NaNs hash
+    /// the same, whereas `0.0` and `-0.0` do not.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Nil => state.write_u8(0),
+            Bool(b) => {
+                state.write_u8(1);
+                b.hash(state);
+            }
+            Float(n) => {
+                state.write_u8(2);
+                if n.is_nan() {
+                    u64::MAX.hash(state);
+                } else {
+                    n.to_bits().hash(state);
+                }
+            }
+            Int(n) => {
+                state.write_u8(3);
+                n.hash(state);
+            }
+            Array(vs) => {
+                state.write_u8(4);
+                vs.hash(state);
+            }
+            Map(m) => {
+                state.write_u8(5);
+                m.hash(state);
+            }
+        }
+    }
+}
+
 impl Value {
     /// Implements the [subvalue relation](https://github.com/AljoschaMeyer/valuable-value#subvalues) on the valuable values.
     pub fn subvalue_cmp(&self, other: &Self) -> Option<Ordering> {
@@ -402,153 +651,1698 @@ impl Value {
     }
 }
 
-impl Serialize for Value {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
+impl Value {
+    /// Compute a least upper bound of all given values according to the [subvalue relation](https://github.com/AljoschaMeyer/valuable-value#subvalues), by folding over [`least_common_supervalue`](Value::least_common_supervalue).
+    ///
+    /// Returns `None` if there are no values, or if some of them have no common supervalue.
+    pub fn lub_all<'a>(values: impl IntoIterator<Item = &'a Value>) -> Option<Value> {
+        let mut values = values.into_iter();
+        let mut acc = values.next()?.clone();
+        for v in values {
+            acc = acc.least_common_supervalue(v)?;
+        }
+        Some(acc)
+    }
+
+    /// Compute a greatest lower bound of all given values according to the [subvalue relation](https://github.com/AljoschaMeyer/valuable-value#subvalues), by folding over [`greatest_common_subvalue`](Value::greatest_common_subvalue).
+    ///
+    /// Returns `None` if there are no values, or if some of them have no common subvalue.
+    pub fn glb_all<'a>(values: impl IntoIterator<Item = &'a Value>) -> Option<Value> {
+        let mut values = values.into_iter();
+        let mut acc = values.next()?.clone();
+        for v in values {
+            acc = acc.greatest_common_subvalue(v)?;
+        }
+        Some(acc)
+    }
+}
+
+impl Value {
+    /// Convert between an array of maps (rows) and a map of arrays (columns).
+    ///
+    /// An array of maps becomes a map from every key occurring in any of the maps to the array of the corresponding values, and a map of arrays becomes an array of maps with one entry per key. Ragged inputs are padded with `Nil`: a key missing from some row yields `Nil` in its column, and a column shorter than the longest one yields `Nil` in the remaining rows. The empty array and the empty map transpose into each other.
+    ///
+    /// Returns `None` if this is neither an array of maps nor a map of arrays.
+    pub fn transpose(&self) -> Option<Value> {
         match self {
-            Nil => serializer.serialize_unit(),
-            Bool(b) => serializer.serialize_bool(*b),
-            Int(n) => serializer.serialize_i64(*n),
-            Float(n) => serializer.serialize_f64(*n),
-            Array(a) => {
-                let mut s = serializer.serialize_seq(Some(a.len()))?;
-                for v in a {
-                    s.serialize_element(v)?;
+            Array(rows) => {
+                let mut columns = BTreeMap::new();
+                for (i, row) in rows.iter().enumerate() {
+                    match row {
+                        Map(m) => {
+                            for (k, v) in m.iter() {
+                                let column = columns.entry(k.clone()).or_insert_with(|| vec![Nil; rows.len()]);
+                                column[i] = v.clone();
+                            }
+                        }
+                        _ => return None,
+                    }
                 }
-                s.end()
+                Some(Map(columns.into_iter().map(|(k, column)| (k, Array(column))).collect()))
             }
-            Map(m) => {
-                let mut s = serializer.serialize_map(Some(m.len()))?;
-                for (k, v) in m {
-                    s.serialize_entry(k, v)?;
+            Map(columns) => {
+                let mut len = 0;
+                for column in columns.values() {
+                    match column {
+                        Array(vs) => len = len.max(vs.len()),
+                        _ => return None,
+                    }
                 }
-                s.end()
+
+                let mut rows = vec![BTreeMap::new(); len];
+                for (k, column) in columns.iter() {
+                    if let Array(vs) = column {
+                        for (i, row) in rows.iter_mut().enumerate() {
+                            row.insert(k.clone(), vs.get(i).cloned().unwrap_or(Nil));
+                        }
+                    }
+                }
+                Some(Array(rows.into_iter().map(Map).collect()))
             }
+            _ => None,
         }
     }
 }
 
-struct ValueVisitor;
+impl Value {
+    /// Rewrite every NaN in this value (including those nested in arrays and maps, and in map keys) to the canonic NaN, whose bit pattern is `u64::MAX`.
+    ///
+    /// Leaves everything else unchanged.
+    pub fn fix_nan(&mut self) {
+        match self {
+            Float(n) => {
+                if n.is_nan() {
+                    *n = f64::from_bits(u64::MAX);
+                }
+            }
+            Array(vs) => {
+                for v in vs.iter_mut() {
+                    v.fix_nan();
+                }
+            }
+            Map(m) => {
+                // Keys cannot be mutated in place, but all NaNs are equal, so fixing them does not change the order of the keys.
+                *m = std::mem::take(m).into_iter().map(|(mut k, mut v)| {
+                    k.fix_nan();
+                    v.fix_nan();
+                    (k, v)
+                }).collect();
+            }
+            _ => {}
+        }
+    }
 
-impl<'de> Visitor<'de> for ValueVisitor {
-    type Value = Value;
+    /// Determine whether two values have byte-for-byte identical encodings once their NaNs are [fixed](Value::fix_nan).
+    ///
+    /// The crate does not implement the [canonic encoding](https://github.com/AljoschaMeyer/valuable-value#canonic-encoding), so this compares the compact encodings of the NaN-fixed values, which encode ints minimally and maps in ascending key order. This always agrees with `==`, but gives the guarantee on the level of bytes, e.g., for content-addressing schemes that hash those encodings.
+    pub fn canonic_equal(&self, other: &Value) -> bool {
+        fn encode(v: &Value) -> Vec<u8> {
+            let mut v = v.clone();
+            v.fix_nan();
+            crate::compact::to_vec(&v).expect("encoding a Value cannot fail")
+        }
 
-    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.write_str("a well-formed valuable value")
+        encode(self) == encode(other)
     }
+}
 
-    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
-        Ok(Nil)
-    }
+/// How [`Value::merge_with`](Value::merge_with) combines two values.
+///
+/// Maps are always merged entry by entry. The default strategy replaces arrays wholesale and treats nil like any other
+/// value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MergeStrategy {
+    concat_arrays: bool,
+    nil_removes: bool,
+}
 
-    fn visit_bool<E: de::Error>(self, b: bool) -> Result<Self::Value, E> {
-        Ok(Bool(b))
+impl MergeStrategy {
+    /// Create the default [`MergeStrategy`](MergeStrategy).
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    fn visit_i8<E: de::Error>(self, n: i8) -> Result<Self::Value, E> {
-        Ok(Int(n as i64))
+    /// Append the items of an overriding array to the array it overrides, instead of replacing it. Since strings are
+    /// arrays, this concatenates strings as well.
+    pub fn concat_arrays(mut self, concat: bool) -> Self {
+        self.concat_arrays = concat;
+        self
     }
 
-    fn visit_i16<E: de::Error>(self, n: i16) -> Result<Self::Value, E> {
-        Ok(Int(n as i64))
+    /// Let an overriding map entry whose value is nil remove the entry with the same key, instead of setting its value to
+    /// nil. Nils nested in entries that are newly inserted are kept.
+    pub fn nil_removes(mut self, remove: bool) -> Self {
+        self.nil_removes = remove;
+        self
     }
+}
 
-    fn visit_i32<E: de::Error>(self, n: i32) -> Result<Self::Value, E> {
-        Ok(Int(n as i64))
+impl Value {
+    /// Recursively merge `other` into this value with the default [`MergeStrategy`](MergeStrategy), e.g., to layer
+    /// configuration files.
+    ///
+    /// If both values are maps, each entry of `other` is merged into the entry with the same key, or inserted if there is
+    /// none. Otherwise, `other` replaces this value.
+    ///
+    /// ```
+    /// use valuable_value::Value;
+    ///
+    /// let mut config: Value = r#"{"db": {"host": "localhost", "port": 5432}, "tags": ["a"]}"#.parse().unwrap();
+    /// config.merge(r#"{"db": {"port": 6543}, "tags": ["b"]}"#.parse().unwrap());
+    /// assert_eq!(config, r#"{"db": {"host": "localhost", "port": 6543}, "tags": ["b"]}"#.parse().unwrap());
+    /// ```
+    pub fn merge(&mut self, other: Value) {
+        self.merge_with(other, &MergeStrategy::new())
     }
 
-    fn visit_i64<E: de::Error>(self, n: i64) -> Result<Self::Value, E> {
-        Ok(Int(n))
+    /// Recursively merge `other` into this value, see [`merge`](Value::merge), with the given [`MergeStrategy`](MergeStrategy).
+    pub fn merge_with(&mut self, other: Value, strategy: &MergeStrategy) {
+        match (self, other) {
+            (Map(m), Map(other)) => {
+                for (k, v) in other {
+                    if strategy.nil_removes && v == Nil {
+                        m.remove(&k);
+                    } else if let Some(existing) = m.get_mut(&k) {
+                        existing.merge_with(v, strategy);
+                    } else {
+                        m.insert(k, v);
+                    }
+                }
+            }
+            (Array(vs), Array(other)) if strategy.concat_arrays => vs.extend(other),
+            (this, other) => *this = other,
+        }
     }
+}
 
-    fn visit_u8<E: de::Error>(self, n: u8) -> Result<Self::Value, E> {
-        Ok(Int(n as i64))
+impl Value {
+    /// Create the value `nil`.
+    pub fn nil() -> Value {
+        Nil
     }
 
-    fn visit_u16<E: de::Error>(self, n: u16) -> Result<Self::Value, E> {
-        Ok(Int(n as i64))
+    /// Create a bool.
+    pub fn bool(b: bool) -> Value {
+        Bool(b)
     }
 
-    fn visit_u32<E: de::Error>(self, n: u32) -> Result<Self::Value, E> {
-        Ok(Int(n as i64))
+    /// Create an int from any type that converts losslessly into an `i64`.
+    pub fn int(n: impl Into<i64>) -> Value {
+        Int(n.into())
     }
 
-    fn visit_u64<E: de::Error>(self, n: u64) -> Result<Self::Value, E> {
-        Ok(Int(n as i64))
+    /// Create a float.
+    pub fn float(n: f64) -> Value {
+        Float(n)
     }
 
-    fn visit_f32<E: de::Error>(self, n: f32) -> Result<Self::Value, E> {
-        Ok(Float(n as f64))
+    /// Create an array of the given items, preallocating room for the lower bound of the iterator's size hint.
+    pub fn array(items: impl IntoIterator<Item = Value>) -> Value {
+        let items = items.into_iter();
+        let mut vs = Vec::with_capacity(items.size_hint().0);
+        vs.extend(items);
+        Array(vs)
     }
 
-    fn visit_f64<E: de::Error>(self, n: f64) -> Result<Self::Value, E> {
-        Ok(Float(n))
+    /// Create a map of the given entries. If several entries have equal keys, only the last of them is kept.
+    pub fn map(entries: impl IntoIterator<Item = (Value, Value)>) -> Value {
+        Map(entries.into_iter().collect())
     }
 
-    fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
-        self.visit_bytes(s.as_bytes())
+    /// Create a map whose keys are the given strings, in the representation that the encoders use for strings.
+    pub fn from_string_map(m: BTreeMap<String, Value>) -> Value {
+        Map(m.into_iter().map(|(k, v)| (Value::from(k), v)).collect())
     }
 
-    fn visit_bytes<E: de::Error>(self, s: &[u8]) -> Result<Self::Value, E> {
-        let mut v = Vec::with_capacity(s.len());
-        for b in s {
-            v.push(Int(*b as i64));
+    /// Convert a map whose keys are all UTF-8 strings into a map with `String` keys, the inverse of
+    /// [`from_string_map`](Value::from_string_map).
+    ///
+    /// ```
+    /// use valuable_value::{Value, StringMapError};
+    ///
+    /// let v: Value = r#"{"name": "x", "size": 3}"#.parse().unwrap();
+    /// assert_eq!(v.to_string_map().unwrap()["size"], Value::Int(3));
+    ///
+    /// let v: Value = r#"{"a": 1, 2: 2, [255]: 3}"#.parse().unwrap();
+    /// assert_eq!(v.to_string_map(), Err(StringMapError::NonStringKey { key: "2".to_string(), index: 0 }));
+    /// ```
+    pub fn to_string_map(&self) -> Result<BTreeMap<String, Value>, StringMapError> {
+        let m = match self {
+            Map(m) => m,
+            other => return Err(StringMapError::NotAMap(other.kind_name())),
+        };
+        let mut out = BTreeMap::new();
+        for (index, (k, v)) in m.iter().enumerate() {
+            match k.get_str(&[]) {
+                Ok(k) => {
+                    out.insert(k, v.clone());
+                }
+                Err(_) => return Err(StringMapError::NonStringKey { key: render_value(k), index }),
+            }
         }
-        Ok(Array(v))
+        Ok(out)
     }
+}
 
-    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
-        let mut v = match seq.size_hint() {
-            Some(len) => Vec::with_capacity(len),
-            None => Vec::new(),
-        };
+impl Value {
+    /// Create an empty array with room for at least `capacity` items, so that pushing up to that many items does not reallocate.
+    ///
+    /// There is no counterpart for maps: a `BTreeMap` allocates its nodes as entries are inserted and cannot reserve room in advance.
+    pub fn array_with_capacity(capacity: usize) -> Value {
+        Array(Vec::with_capacity(capacity))
+    }
 
-        while let Some(x) = seq.next_element()? {
-            v.push(x);
+    /// Reserve room for at least `additional` more items if this value is an array, see [`Vec::reserve`](Vec::reserve). Returns whether this value is an array, other values are left unchanged.
+    ///
+    /// Like [`array_with_capacity`](Value::array_with_capacity), this has no counterpart for maps.
+    pub fn reserve_array(&mut self, additional: usize) -> bool {
+        match self {
+            Array(vs) => {
+                vs.reserve(additional);
+                true
+            }
+            _ => false,
         }
-
-        return Ok(Array(v));
     }
+}
 
-    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
-        let mut m = BTreeMap::new();
+impl Value {
+    /// If this value is a map, split it into a map of the entries whose keys are less than `key`, and a map of the entries whose keys are greater than or equal to `key`. Returns `None` for all other values.
+    ///
+    /// The entries are cloned, use [`BTreeMap::split_off`](BTreeMap::split_off) to split an owned map without cloning.
+    pub fn split_at_key(&self, key: &Value) -> Option<(Value, Value)> {
+        match self {
+            Map(m) => {
+                let before = m.range(..key).map(|(k, v)| (k.clone(), v.clone())).collect();
+                let after = m.range(key..).map(|(k, v)| (k.clone(), v.clone())).collect();
+                Some((Map(before), Map(after)))
+            }
+            _ => None,
+        }
+    }
+}
 
-        while let Some((k, v)) = map.next_entry()? {
-            m.insert(k, v);
+impl Value {
+    /// If this value is an array, count how often each int occurs among its items. Returns `None` for all other values.
+    ///
+    /// Items that are not ints (including nested arrays of ints) are not counted.
+    pub fn int_histogram(&self) -> Option<BTreeMap<i64, usize>> {
+        match self {
+            Array(vs) => {
+                let mut histogram = BTreeMap::new();
+                for v in vs.iter() {
+                    if let Int(n) = v {
+                        *histogram.entry(*n).or_insert(0) += 1;
+                    }
+                }
+                Some(histogram)
+            }
+            _ => None,
         }
+    }
+}
 
-        return Ok(Map(m));
+impl Value {
+    /// Apply a function to every string in this value (including those nested in arrays and maps, and in map keys), replacing each string for which it returns `Some`. Returns the number of replaced strings.
+    ///
+    /// A string is an array of ints between 0 and 255 that form valid UTF-8, this includes the empty array. Other arrays are searched for nested strings instead. If replacing map keys makes two keys equal, only the entry that comes later in the original map is kept.
+    pub fn transform_strings(&mut self, mut f: impl FnMut(&str) -> Option<String>) -> usize {
+        transform_strings(self, &mut f)
     }
 }
 
-impl<'de> Deserialize<'de> for Value {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        deserializer.deserialize_any(ValueVisitor)
+fn transform_strings(v: &mut Value, f: &mut dyn FnMut(&str) -> Option<String>) -> usize {
+    let mut count = 0;
+    let mut stack = vec![v];
+
+    while let Some(current) = stack.pop() {
+        match current {
+            Array(vs) => {
+                let bytes: Option<Vec<u8>> = vs.iter().map(|b| match b {
+                    Int(n) if 0 <= *n && *n <= 255 => Some(*n as u8),
+                    _ => None,
+                }).collect();
+
+                match bytes.map(String::from_utf8) {
+                    Some(Ok(string)) => {
+                        if let Some(replacement) = f(&string) {
+                            *vs = replacement.bytes().map(|b| Int(b as i64)).collect();
+                            count += 1;
+                        }
+                    }
+                    _ => stack.extend(vs.iter_mut()),
+                }
+            }
+            Map(m) => {
+                // Keys cannot be mutated in place, so the map is rebuilt with the transformed keys.
+                *m = std::mem::take(m).into_iter().map(|(mut k, v)| {
+                    count += transform_strings(&mut k, f);
+                    (k, v)
+                }).collect();
+                stack.extend(m.values_mut());
+            }
+            _ => {}
+        }
     }
+
+    count
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl Value {
+    /// Return all values nested in this value (including this value itself, and map keys) that satisfy the predicate, in depth-first order.
+    ///
+    /// A value comes before the values nested in it, the items of an array come in order, and the entries of a map come in the order of their keys, each key before its value.
+    pub fn find_all(&self, pred: impl Fn(&Value) -> bool) -> Vec<&Value> {
+        let mut found = Vec::new();
+        depth_first(self, &mut |v| {
+            if pred(v) {
+                found.push(v);
+            }
+            false
+        });
+        found
+    }
 
-    #[test]
-    fn eq() {
-        assert!(Float(-0.0f64) != Float(0.0f64));
-        let negative_nan = f64::from_bits(u64::MAX);
-        let positive_nan = negative_nan.copysign(1.0);
-        assert_eq!(Float(positive_nan), Float(negative_nan));
+    /// Return the first value in the order of [`find_all`](Value::find_all) that satisfies the predicate, without looking any further.
+    pub fn find_first(&self, pred: impl Fn(&Value) -> bool) -> Option<&Value> {
+        let mut found = None;
+        depth_first(self, &mut |v| {
+            if pred(v) {
+                found = Some(v);
+            }
+            found.is_some()
+        });
+        found
     }
+}
 
-    #[test]
-    fn cmp() {
-        assert!(Nil < Bool(false));
+// Visit a value and all values nested in it in depth-first order, until `f` returns `true`.
+fn depth_first<'a>(v: &'a Value, f: &mut dyn FnMut(&'a Value) -> bool) {
+    let mut stack = vec![v];
 
-        assert!(Bool(false) < Bool(true));
+    while let Some(current) = stack.pop() {
+        if f(current) {
+            return;
+        }
+        // Pushed in reverse, so that they are popped in order.
+        match current {
+            Array(vs) => stack.extend(vs.iter().rev()),
+            Map(m) => {
+                for (k, v) in m.iter().rev() {
+                    stack.push(v);
+                    stack.push(k);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A step into an array or a map, see [`Value::first_difference`](Value::first_difference).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PathSegment {
+    /// The item at an index of an array.
+    Index(usize),
+    /// The value of an entry of a map.
+    Key(Value),
+}
+
+impl Value {
+    /// Return the path to the first place (in the [canonic linear order](https://github.com/AljoschaMeyer/valuable-value#canonic-linear-order)) where this value and the other one differ, or `None` if they are equal.
+    ///
+    /// If an array is longer than the other one, the path ends with the first index that only it has. If a key occurs in only one of two maps, the path ends with that key. If two values are not equal and not both arrays or both maps, the path ends at those values.
+    pub fn first_difference(&self, other: &Value) -> Option<Vec<PathSegment>> {
+        let mut path = Vec::new();
+        if first_difference(self, other, &mut path) {
+            Some(path)
+        } else {
+            None
+        }
+    }
+}
+
+// Push the path to the first difference onto `path` and return true, or return false if the values are equal.
+fn first_difference(a: &Value, b: &Value, path: &mut Vec<PathSegment>) -> bool {
+    match (a, b) {
+        (Array(xs), Array(ys)) => {
+            for (i, (x, y)) in xs.iter().zip(ys.iter()).enumerate() {
+                path.push(PathSegment::Index(i));
+                if first_difference(x, y, path) {
+                    return true;
+                }
+                path.pop();
+            }
+            if xs.len() != ys.len() {
+                path.push(PathSegment::Index(xs.len().min(ys.len())));
+                return true;
+            }
+            false
+        }
+        (Map(xs), Map(ys)) => {
+            let mut xs = xs.iter().peekable();
+            let mut ys = ys.iter().peekable();
+            loop {
+                match (xs.peek(), ys.peek()) {
+                    (None, None) => return false,
+                    (Some((k, _)), None) | (None, Some((k, _))) => {
+                        path.push(PathSegment::Key((*k).clone()));
+                        return true;
+                    }
+                    (Some((kx, x)), Some((ky, y))) => match kx.cmp(ky) {
+                        Less => {
+                            path.push(PathSegment::Key((*kx).clone()));
+                            return true;
+                        }
+                        Greater => {
+                            path.push(PathSegment::Key((*ky).clone()));
+                            return true;
+                        }
+                        Equal => {
+                            path.push(PathSegment::Key((*kx).clone()));
+                            if first_difference(x, y, path) {
+                                return true;
+                            }
+                            path.pop();
+                            xs.next();
+                            ys.next();
+                        }
+                    },
+                }
+            }
+        }
+        _ => a != b,
+    }
+}
+
+/// Everything that can go wrong when looking up a value at a path with one of the typed getters of [`Value`](Value).
+///
+/// Paths are rendered in the [human-readable encoding](https://github.com/AljoschaMeyer/valuable-value#human-readable-encoding), with every segment that is a string followed by its text.
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum GetError {
+    /// There is no value at the path.
+    #[error("no value at path {0}")]
+    Missing(String),
+    /// The value at the path (or at a prefix of it) is of the wrong kind. The path is rendered up to and including the segment that could not be followed, or in full if the value at its end is of the wrong kind.
+    #[error("expected {expected} at path {path}")]
+    WrongType {
+        path: String,
+        expected: &'static str,
+    },
+}
+
+// Render a path for an error message, rendering each segment like `render_value`, so that string keys are followed by their text.
+pub(crate) fn render_path(path: &[Value]) -> String {
+    format!("[{}]", path.iter().map(render_value).collect::<Vec<_>>().join(","))
+}
+
+// Values rendered into error messages are truncated to roughly this many bytes.
+const MAX_RENDERED: usize = 64;
+
+// Truncate a string that is to be captured in an error to at most `MAX_RENDERED` bytes, keeping it valid UTF-8.
+fn truncate_str(s: &str) -> &str {
+    let mut end = s.len().min(MAX_RENDERED);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+// Capture a string for an error, as a bounded value.
+pub(crate) fn captured_str(s: &str) -> Value {
+    Value::from(truncate_str(s))
+}
+
+// Render a value in the human-readable encoding for an error message, truncated to roughly `MAX_RENDERED` bytes. Non-empty strings are followed by their text.
+pub(crate) fn render_value(v: &Value) -> String {
+    let mut out = match crate::human::to_vec(v, 0) {
+        Ok(out) => String::from_utf8_lossy(&out).into_owned(),
+        Err(_) => format!("{:?}", v),
+    };
+    if out.len() > MAX_RENDERED {
+        out.truncate(truncate_str(&out).len());
+        out.push_str("...");
+    }
+    if let Ok(s) = v.get_str(&[]) {
+        if !s.is_empty() {
+            out.push_str(&format!(" ({:?})", truncate_str(&s)));
+        }
+    }
+    out
+}
+
+impl Value {
+    /// Look up the value at the given path, where each path component is either an `Int` index into an array, or a key of a map.
+    pub fn pointer(&self, path: &[Value]) -> Option<&Value> {
+        self.lookup(path).ok()
+    }
+
+    fn lookup(&self, path: &[Value]) -> Result<&Value, GetError> {
+        let mut current = self;
+        for (i, component) in path.iter().enumerate() {
+            current = match (current, component) {
+                (Array(v), Int(n)) => {
+                    match usize::try_from(*n).ok().and_then(|n| v.get(n)) {
+                        Some(inner) => inner,
+                        None => return Err(GetError::Missing(render_path(&path[..i + 1]))),
+                    }
+                }
+                (Array(_), _) => return Err(GetError::WrongType {
+                    path: render_path(&path[..i + 1]),
+                    expected: "int index",
+                }),
+                (Map(m), key) => {
+                    match m.get(key) {
+                        Some(inner) => inner,
+                        None => return Err(GetError::Missing(render_path(&path[..i + 1]))),
+                    }
+                }
+                _ => return Err(GetError::WrongType {
+                    path: render_path(&path[..i + 1]),
+                    expected: "array or map",
+                }),
+            };
+        }
+        Ok(current)
+    }
+
+    fn wrong_type(path: &[Value], expected: &'static str) -> GetError {
+        GetError::WrongType { path: render_path(path), expected }
+    }
+
+    /// Look up the int at the given [path](Value::pointer).
+    pub fn get_int(&self, path: &[Value]) -> Result<i64, GetError> {
+        match self.lookup(path)? {
+            Int(n) => Ok(*n),
+            _ => Err(Value::wrong_type(path, "int")),
+        }
+    }
+
+    /// Look up the bool at the given [path](Value::pointer).
+    pub fn get_bool(&self, path: &[Value]) -> Result<bool, GetError> {
+        match self.lookup(path)? {
+            Bool(b) => Ok(*b),
+            _ => Err(Value::wrong_type(path, "bool")),
+        }
+    }
+
+    /// Look up the float at the given [path](Value::pointer).
+    pub fn get_float(&self, path: &[Value]) -> Result<f64, GetError> {
+        match self.lookup(path)? {
+            Float(n) => Ok(*n),
+            _ => Err(Value::wrong_type(path, "float")),
+        }
+    }
+
+    /// Look up the array at the given [path](Value::pointer) and interpret it as a UTF-8 string, i.e., as an array of ints between 0 and 255 that form valid UTF-8.
+    pub fn get_str(&self, path: &[Value]) -> Result<String, GetError> {
+        if let Array(v) = self.lookup(path)? {
+            let mut bytes = Vec::with_capacity(v.len());
+            for b in v {
+                match b {
+                    Int(n) if 0 <= *n && *n <= 255 => bytes.push(*n as u8),
+                    _ => return Err(Value::wrong_type(path, "UTF-8 string")),
+                }
+            }
+            if let Ok(s) = String::from_utf8(bytes) {
+                return Ok(s);
+            }
+        }
+        Err(Value::wrong_type(path, "UTF-8 string"))
+    }
+
+    /// Look up the array at the given [path](Value::pointer).
+    pub fn get_array(&self, path: &[Value]) -> Result<&Vec<Value>, GetError> {
+        match self.lookup(path)? {
+            Array(v) => Ok(v),
+            _ => Err(Value::wrong_type(path, "array")),
+        }
+    }
+
+    /// Look up the map at the given [path](Value::pointer).
+    pub fn get_map(&self, path: &[Value]) -> Result<&BTreeMap<Value, Value>, GetError> {
+        match self.lookup(path)? {
+            Map(m) => Ok(m),
+            _ => Err(Value::wrong_type(path, "map")),
+        }
+    }
+}
+
+/// A type that can select a nested value in a [`Value`](Value), see [`Value::get`](Value::get).
+///
+/// A `usize` is an index into an array, a `&Value` is a key of a map, and a `&str` is a key of a map in the representation that the encoders use for strings.
+pub trait ValueIndex {
+    /// Return the selected value, or `None` if `v` is of the wrong kind or has no such item or entry.
+    fn index_into<'v>(&self, v: &'v Value) -> Option<&'v Value>;
+
+    /// Return a mutable reference to the selected value, or `None` if `v` is of the wrong kind or has no such item or entry.
+    fn index_into_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value>;
+}
+
+impl ValueIndex for usize {
+    fn index_into<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        match v {
+            Array(items) => items.get(*self),
+            _ => None,
+        }
+    }
+
+    fn index_into_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        match v {
+            Array(items) => items.get_mut(*self),
+            _ => None,
+        }
+    }
+}
+
+impl ValueIndex for &Value {
+    fn index_into<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        match v {
+            Map(m) => m.get(*self),
+            _ => None,
+        }
+    }
+
+    fn index_into_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        match v {
+            Map(m) => m.get_mut(*self),
+            _ => None,
+        }
+    }
+}
+
+impl ValueIndex for &str {
+    fn index_into<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        (&Value::from(*self)).index_into(v)
+    }
+
+    fn index_into_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        (&Value::from(*self)).index_into_mut(v)
+    }
+}
+
+impl Value {
+    /// Return the item of an array or the value of an entry of a map selected by the [index](ValueIndex), or `None` if there is none.
+    ///
+    /// Unlike indexing with `[]`, this does not panic.
+    pub fn get(&self, index: impl ValueIndex) -> Option<&Value> {
+        index.index_into(self)
+    }
+
+    /// Return a mutable reference to the item of an array or the value of an entry of a map selected by the [index](ValueIndex), or `None` if there is none.
+    pub fn get_mut(&mut self, index: impl ValueIndex) -> Option<&mut Value> {
+        index.index_into_mut(self)
+    }
+
+    /// If this value is an array, return the item at the given index.
+    pub fn get_index(&self, i: usize) -> Option<&Value> {
+        match self {
+            Array(v) => v.get(i),
+            _ => None,
+        }
+    }
+
+    /// If this value is an array, return a mutable reference to the item at the given index.
+    pub fn get_index_mut(&mut self, i: usize) -> Option<&mut Value> {
+        match self {
+            Array(v) => v.get_mut(i),
+            _ => None,
+        }
+    }
+
+    /// If this value is a map, return the value of the entry whose key is the given string, i.e., the array of its UTF-8 bytes. This is how the encoders represent struct field names.
+    ///
+    /// Unlike [`get_str`](Value::get_str), which looks up a string at a path, this looks up a value by a string key.
+    pub fn get_key(&self, key: &str) -> Option<&Value> {
+        self.get(&Value::from(key))
+    }
+
+    /// If this value is a map, return a mutable reference to the value of the entry whose key is the given string, see [`get_key`](Value::get_key).
+    pub fn get_key_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.get_mut(&Value::from(key))
+    }
+}
+
+impl Value {
+    /// Return whether this value is `nil`.
+    pub fn is_nil(&self) -> bool {
+        matches!(self, Nil)
+    }
+
+    /// Return whether this value is a bool.
+    pub fn is_bool(&self) -> bool {
+        matches!(self, Bool(_))
+    }
+
+    /// Return whether this value is an int.
+    pub fn is_int(&self) -> bool {
+        matches!(self, Int(_))
+    }
+
+    /// Return whether this value is a float, including NaN and the infinities.
+    pub fn is_float(&self) -> bool {
+        matches!(self, Float(_))
+    }
+
+    /// Return whether this value is an array (which includes strings).
+    pub fn is_array(&self) -> bool {
+        matches!(self, Array(_))
+    }
+
+    /// Return whether this value is a map.
+    pub fn is_map(&self) -> bool {
+        matches!(self, Map(_))
+    }
+
+    /// Return the bool if this value is a bool.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Return the int if this value is an int.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Return the float if this value is a float. Ints are not converted, see [`as_f64_lossy`](Value::as_f64_lossy).
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Float(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Return the float if this value is a float, or the int converted to the nearest float if this value is an int. Ints whose absolute value exceeds 2^53 may not be represented exactly.
+    pub fn as_f64_lossy(&self) -> Option<f64> {
+        match self {
+            Float(n) => Some(*n),
+            Int(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+
+    /// Return the int if this value is an int, or the float as an int if this value is a float that denotes an integer
+    /// within the range of an `i64`, i.e., that is finite, has no fractional part and lies in `[-2^63, 2^63)`.
+    ///
+    /// The valuable value specification never considers ints and floats equal, so this is a policy of this crate rather
+    /// than of the data model. `-0.0` coerces to `0`, NaNs and infinities do not coerce.
+    pub fn coerce_int(&self) -> Option<i64> {
+        match self {
+            Int(n) => Some(*n),
+            Float(n) if n.is_finite() && n.fract() == 0.0 && *n >= -(2f64.powi(63)) && *n < 2f64.powi(63) => Some(*n as i64),
+            _ => None,
+        }
+    }
+
+    /// Return the float if this value is a float, or the int as a float if this value is an int that a float represents
+    /// exactly.
+    ///
+    /// All ints whose absolute value is at most 2^53 are exact, larger ones only if their trailing binary digits are zero.
+    /// Use [`as_f64_lossy`](Value::as_f64_lossy) to round the others to the nearest float instead. Every float returned
+    /// for an int converts back to that int with [`coerce_int`](Value::coerce_int).
+    pub fn coerce_float(&self) -> Option<f64> {
+        match self {
+            Float(n) => Some(*n),
+            Int(n) => {
+                let f = *n as f64;
+                (f as i128 == *n as i128).then_some(f)
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the items if this value is an array.
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Array(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the items if this value is an array, for modifying them in place.
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Array(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the entries if this value is a map.
+    pub fn as_map(&self) -> Option<&BTreeMap<Value, Value>> {
+        match self {
+            Map(m) => Some(m),
+            _ => None,
+        }
+    }
+
+    /// Return the entries if this value is a map, for modifying them in place.
+    pub fn as_map_mut(&mut self) -> Option<&mut BTreeMap<Value, Value>> {
+        match self {
+            Map(m) => Some(m),
+            _ => None,
+        }
+    }
+}
+
+/// Index into an array, panicking if this value is not an array or if the index is out of bounds, see [`get_index`](Value::get_index) for a non-panicking alternative.
+impl Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, i: usize) -> &Value {
+        match self {
+            Array(v) => match v.get(i) {
+                Some(item) => item,
+                None => panic!("index {} out of bounds for an array of length {}", i, v.len()),
+            },
+            _ => panic!("cannot index into {} with {}", render_value(self), i),
+        }
+    }
+}
+
+impl IndexMut<usize> for Value {
+    fn index_mut(&mut self, i: usize) -> &mut Value {
+        match self {
+            Array(v) => {
+                let len = v.len();
+                match v.get_mut(i) {
+                    Some(item) => item,
+                    None => panic!("index {} out of bounds for an array of length {}", i, len),
+                }
+            }
+            _ => panic!("cannot index into {} with {}", render_value(self), i),
+        }
+    }
+}
+
+/// Look up a key in a map, panicking if this value is not a map or if there is no entry with that key, see [`get`](Value::get) for a non-panicking alternative.
+impl Index<&Value> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &Value) -> &Value {
+        match self.get(key) {
+            Some(v) => v,
+            None => panic!("no entry with key {} in {}", render_value(key), render_value(self)),
+        }
+    }
+}
+
+impl IndexMut<&Value> for Value {
+    fn index_mut(&mut self, key: &Value) -> &mut Value {
+        if !matches!(self, Map(m) if m.contains_key(key)) {
+            panic!("no entry with key {} in {}", render_value(key), render_value(self));
+        }
+        self.get_mut(key).unwrap()
+    }
+}
+
+/// Look up a string key in a map, panicking if this value is not a map or if there is no entry with that key, see [`get_key`](Value::get_key) for a non-panicking alternative.
+impl Index<&str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        &self[&Value::from(key)]
+    }
+}
+
+impl IndexMut<&str> for Value {
+    fn index_mut(&mut self, key: &str) -> &mut Value {
+        &mut self[&Value::from(key)]
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Nil => serializer.serialize_unit(),
+            Bool(b) => serializer.serialize_bool(*b),
+            Int(n) => serializer.serialize_i64(*n),
+            Float(n) => serializer.serialize_f64(*n),
+            Array(a) => {
+                let mut s = serializer.serialize_seq(Some(a.len()))?;
+                for v in a {
+                    s.serialize_element(v)?;
+                }
+                s.end()
+            }
+            Map(m) => {
+                let mut s = serializer.serialize_map(Some(m.len()))?;
+                let mut previous: Option<&Value> = None;
+                for (k, v) in m {
+                    debug_assert!(previous.is_none_or(|previous| previous < k), "map keys must be serialized in ascending order");
+                    previous = Some(k);
+                    s.serialize_entry(k, v)?;
+                }
+                s.end()
+            }
+        }
+    }
+}
+
+// Decodes a value whose nested values may be at most `remaining` levels deep, see `Value::deserialize_bounded`.
+#[derive(Clone, Copy)]
+struct ValueVisitor {
+    remaining: usize,
+    max_depth: usize,
+}
+
+impl ValueVisitor {
+    const UNBOUNDED: ValueVisitor = ValueVisitor { remaining: usize::MAX, max_depth: usize::MAX };
+
+    // The seed for the values directly nested in the one being visited.
+    fn nested(self) -> ValueSeed {
+        ValueSeed { remaining: self.remaining, max_depth: self.max_depth }
+    }
+}
+
+// Decodes a value that may take up `remaining` more levels of nesting, or fails if there are none left.
+struct ValueSeed {
+    remaining: usize,
+    max_depth: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for ValueSeed {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        match self.remaining.checked_sub(1) {
+            Some(remaining) => deserializer.deserialize_any(ValueVisitor { remaining, max_depth: self.max_depth }),
+            None => Err(de::Error::custom(format!("values may not be nested more than {} levels deep", self.max_depth))),
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a well-formed valuable value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Nil)
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<Self::Value, E> {
+        Ok(Bool(b))
+    }
+
+    fn visit_i8<E: de::Error>(self, n: i8) -> Result<Self::Value, E> {
+        Ok(Int(n as i64))
+    }
+
+    fn visit_i16<E: de::Error>(self, n: i16) -> Result<Self::Value, E> {
+        Ok(Int(n as i64))
+    }
+
+    fn visit_i32<E: de::Error>(self, n: i32) -> Result<Self::Value, E> {
+        Ok(Int(n as i64))
+    }
+
+    fn visit_i64<E: de::Error>(self, n: i64) -> Result<Self::Value, E> {
+        Ok(Int(n))
+    }
+
+    fn visit_u8<E: de::Error>(self, n: u8) -> Result<Self::Value, E> {
+        Ok(Int(n as i64))
+    }
+
+    fn visit_u16<E: de::Error>(self, n: u16) -> Result<Self::Value, E> {
+        Ok(Int(n as i64))
+    }
+
+    fn visit_u32<E: de::Error>(self, n: u32) -> Result<Self::Value, E> {
+        Ok(Int(n as i64))
+    }
+
+    fn visit_u64<E: de::Error>(self, n: u64) -> Result<Self::Value, E> {
+        Ok(Int(n as i64))
+    }
+
+    fn visit_f32<E: de::Error>(self, n: f32) -> Result<Self::Value, E> {
+        Ok(Float(n as f64))
+    }
+
+    fn visit_f64<E: de::Error>(self, n: f64) -> Result<Self::Value, E> {
+        Ok(Float(n))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+        self.visit_bytes(s.as_bytes())
+    }
+
+    fn visit_bytes<E: de::Error>(self, s: &[u8]) -> Result<Self::Value, E> {
+        let mut v = Vec::with_capacity(s.len());
+        for b in s {
+            v.push(Int(*b as i64));
+        }
+        Ok(Array(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut v = match seq.size_hint() {
+            Some(len) => Vec::with_capacity(len),
+            None => Vec::new(),
+        };
+
+        while let Some(x) = seq.next_element_seed(self.nested())? {
+            v.push(x);
+        }
+
+        return Ok(Array(v));
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut m = BTreeMap::new();
+
+        while let Some((k, v)) = map.next_entry_seed(self.nested(), self.nested())? {
+            m.insert(k, v);
+        }
+
+        return Ok(Map(m));
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor::UNBOUNDED)
+    }
+}
+
+impl Value {
+    /// Deserialize a value, failing if it is nested more than `max_depth` levels deep, where a value that is not nested
+    /// in any collection has depth one (and the value wrapped in an option counts as nested in it).
+    ///
+    /// Decoding into a `Value` recurses once per level of nesting, through both the deserializer and this visitor.
+    /// Failing as soon as the limit is exceeded keeps the stack usage proportional to `max_depth`, so that untrusted input
+    /// cannot overflow the stack.
+    ///
+    /// ```
+    /// use valuable_value::{human, Value};
+    ///
+    /// let v = Value::deserialize_bounded(&mut human::VVDeserializer::new(b"[[1], 2]"), 3).unwrap();
+    /// assert_eq!(v, human::from_str::<Value>("[[1], 2]").unwrap());
+    /// assert!(Value::deserialize_bounded(&mut human::VVDeserializer::new(b"[[[1]], 2]"), 3).is_err());
+    /// ```
+    pub fn deserialize_bounded<'de, D>(deserializer: D, max_depth: usize) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        ValueSeed { remaining: max_depth, max_depth }.deserialize(deserializer)
+    }
+}
+
+impl<'de, E: de::Error> de::IntoDeserializer<'de, E> for Value {
+    type Deserializer = ValueDeserializer<E>;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        ValueDeserializer(self, PhantomData)
+    }
+}
+
+impl<'de, E: de::Error> de::IntoDeserializer<'de, E> for ValueDeserializer<E> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+/// A deserializer that decodes a rust value from a [`Value`](Value), obtained via [`IntoDeserializer`](serde::de::IntoDeserializer).
+///
+/// Strings, options and enums are expected in the same shape as the encodings use: strings as arrays of ints that form valid UTF-8, options as the string `"None"` or a map from `"Some"` to the wrapped value, and enum variants as the string of the variant name or a map from the variant name to its contents.
+///
+/// Types that serde decodes without knowing what to expect, such as untagged enums or the contents of internally tagged enums and flattened fields, see strings as arrays of ints, except for map keys that form valid UTF-8, which are reported as strings. Such types therefore work with field names and tags, but not with strings elsewhere.
+///
+/// Behaves exactly like the [`ValueRefDeserializer`](crate::ValueRefDeserializer) of a reference to the value.
+pub struct ValueDeserializer<E>(Value, PhantomData<E>);
+
+// Interpret an array as a string, i.e., as an array of ints between 0 and 255 that form valid UTF-8.
+pub(crate) fn array_as_string(vs: &[Value]) -> Option<String> {
+    let bytes: Option<Vec<u8>> = vs.iter().map(|b| match b {
+        Int(n) if 0 <= *n && *n <= 255 => Some(*n as u8),
+        _ => None,
+    }).collect();
+    String::from_utf8(bytes?).ok()
+}
+
+// Whether the value is the string `"None"` or a map with the single key `"Some"`, i.e., an encoded option.
+pub(crate) fn is_option(v: &Value) -> bool {
+    match v {
+        Array(vs) => array_as_string(vs).as_deref() == Some("None"),
+        Map(m) => match m.keys().next() {
+            Some(Array(k)) => m.len() == 1 && array_as_string(k).as_deref() == Some("Some"),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+// Implement the `Deserializer` methods that take only a visitor by calling them on a `ValueRefDeserializer` of the value.
+macro_rules! forward_to_ref {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                crate::ValueRefDeserializer::<E>::new(&self.0).$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, E: de::Error> Deserializer<'de> for ValueDeserializer<E> {
+    type Error = E;
+
+    forward_to_ref! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128 deserialize_f32 deserialize_f64
+        deserialize_char deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf deserialize_option
+        deserialize_unit deserialize_seq deserialize_map deserialize_identifier deserialize_ignored_any
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        crate::ValueRefDeserializer::<E>::new(&self.0).deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        crate::ValueRefDeserializer::<E>::new(&self.0).deserialize_newtype_struct(name, visitor)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        crate::ValueRefDeserializer::<E>::new(&self.0).deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(self, name: &'static str, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        crate::ValueRefDeserializer::<E>::new(&self.0).deserialize_tuple_struct(name, len, visitor)
+    }
+
+    fn deserialize_struct<V>(self, name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        crate::ValueRefDeserializer::<E>::new(&self.0).deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        crate::ValueRefDeserializer::<E>::new(&self.0).deserialize_enum(name, variants, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::XorShift;
+
+    #[test]
+    fn eq() {
+        assert!(Float(-0.0f64) != Float(0.0f64));
+        let negative_nan = f64::from_bits(u64::MAX);
+        let positive_nan = negative_nan.copysign(1.0);
+        assert_eq!(Float(positive_nan), Float(negative_nan));
+    }
+
+    fn hash_of(v: &Value) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        v.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn hash() {
+        let negative_nan = f64::from_bits(u64::MAX);
+        let positive_nan = negative_nan.copysign(1.0);
+        assert_eq!(hash_of(&Float(positive_nan)), hash_of(&Float(negative_nan)));
+        assert_eq!(hash_of(&Float(f64::NAN)), hash_of(&Float(f64::from_bits(0x7ff0_0000_0000_0001))));
+        assert_ne!(hash_of(&Float(0.0)), hash_of(&Float(-0.0)));
+
+        let v: Value = crate::human::from_str("{[1, NaN]: {-0.0: nil}, 0.0: [[], {}]}").unwrap();
+        let mut w = v.clone();
+        w.fix_nan();
+        assert_eq!(hash_of(&v), hash_of(&w));
+
+        let mut set = std::collections::HashSet::new();
+        assert!(set.insert(v));
+        assert!(!set.insert(w));
+        assert!(set.insert(Array(vec![])));
+        assert!(set.insert(Map(BTreeMap::new())));
+        assert!(!set.insert(Value::from("")));
+        assert_eq!(set.len(), 3);
+    }
+
+    // Rewrite every NaN to a NaN with a different bit pattern, yielding an equal value.
+    #[cfg(feature = "arbitrary")]
+    fn flip_nans(v: &Value) -> Value {
+        match v {
+            Float(n) if n.is_nan() => Float(f64::from_bits(n.to_bits() ^ 1 ^ (1 << 63))),
+            Array(vs) => Array(vs.iter().map(flip_nans).collect()),
+            Map(m) => Map(m.iter().map(|(k, v)| (flip_nans(k), flip_nans(v))).collect()),
+            v => v.clone(),
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn hash_consistent_with_eq() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let data = XorShift::new(0x2545_f491_4f6c_dd1d).bytes(1 << 12);
+
+        let mut u = Unstructured::new(&data);
+        let mut values = vec![];
+        while let Ok(v) = Value::arbitrary(&mut u) {
+            let flipped = flip_nans(&v);
+            assert_eq!(v, flipped);
+            assert_eq!(hash_of(&v), hash_of(&flipped));
+            values.push(v);
+            if u.is_empty() {
+                break;
+            }
+        }
+
+        for a in values.iter() {
+            for b in values.iter() {
+                if a == b {
+                    assert_eq!(hash_of(a), hash_of(b), "{:?} {:?}", a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn string_maps() {
+        let mut m = BTreeMap::new();
+        m.insert("b".to_string(), Int(1));
+        m.insert("ä".to_string(), Value::from_string_map(BTreeMap::new()));
+        m.insert("".to_string(), Nil);
+        m.insert("🦀 crab".to_string(), Value::from("claws"));
+        let v = Value::from_string_map(m.clone());
+        assert_eq!(v, crate::human::from_str(r#"{"b": 1, "ä": {}, "": nil, "🦀 crab": "claws"}"#).unwrap());
+        assert_eq!(v.to_string_map().unwrap(), m);
+        assert_eq!(Value::from_string_map(v.to_string_map().unwrap()), v);
+
+        // Keys written as arrays of bytes are strings as well.
+        let v: Value = crate::human::from_str("{[]: 0, [195, 164]: 1, [97]: 2}").unwrap();
+        let m = v.to_string_map().unwrap();
+        assert_eq!(m.keys().collect::<Vec<_>>(), vec!["", "a", "ä"]);
+
+        let not_utf8: Value = crate::human::from_str(r#"{"a": 0, [97, 255]: 1}"#).unwrap();
+        assert_eq!(not_utf8.to_string_map(), Err(StringMapError::NonStringKey { key: "[97,255]".to_string(), index: 1 }));
+        let not_bytes: Value = crate::human::from_str(r#"{"a": 0, [256]: 1}"#).unwrap();
+        assert_eq!(not_bytes.to_string_map(), Err(StringMapError::NonStringKey { key: "[256]".to_string(), index: 1 }));
+
+        let ints: Value = crate::human::from_str(r#"{"z": nil, -1: nil, 2: nil}"#).unwrap();
+        let err = ints.to_string_map().unwrap_err();
+        assert_eq!(err, StringMapError::NonStringKey { key: "-1".to_string(), index: 0 });
+        assert_eq!(err.to_string(), "key -1 at index 0 is not a UTF-8 string");
+        let nested: Value = crate::human::from_str(r#"{"a": 0, {"k": nil}: 1}"#).unwrap();
+        assert_eq!(nested.to_string_map(), Err(StringMapError::NonStringKey { key: r#"{[107]:nil}"#.to_string(), index: 1 }));
+
+        assert_eq!(Value::from("a").to_string_map(), Err(StringMapError::NotAMap("array")));
+        assert_eq!(Nil.to_string_map(), Err(StringMapError::NotAMap("nil")));
+    }
+
+    #[test]
+    fn merge() {
+        let parse = |s: &str| -> Value { s.parse().unwrap() };
+        let base = parse(r#"{
+            "a": {"b": {"c": 1, "d": [1, 2], "e": {"f": nil}}, "g": true},
+            "h": [{"i": 0}],
+            "j": nil,
+        }"#);
+        let layer = parse(r#"{
+            "a": {"b": {"c": 2, "d": [3], "e": {"k": 4}}, "l": 0.5},
+            "h": [{"m": 1}],
+            "j": {"n": nil},
+            "o": nil,
+        }"#);
+
+        let mut v = base.clone();
+        v.merge(layer.clone());
+        assert_eq!(v, parse(r#"{
+            "a": {"b": {"c": 2, "d": [3], "e": {"f": nil, "k": 4}}, "g": true, "l": 0.5},
+            "h": [{"m": 1}],
+            "j": {"n": nil},
+            "o": nil,
+        }"#));
+
+        let mut v = base.clone();
+        v.merge_with(layer, &MergeStrategy::new().concat_arrays(true));
+        assert_eq!(v["a"]["b"]["d"], parse("[1, 2, 3]"));
+        assert_eq!(v["h"], parse(r#"[{"i": 0}, {"m": 1}]"#));
+
+        // Mixed kinds replace each other, whichever way round, and merging into a scalar replaces it.
+        let mut v = base.clone();
+        v.merge(parse(r#"{"a": {"b": [0], "g": {"p": 1}}, "h": {"q": 2}}"#));
+        assert_eq!(v["a"], parse(r#"{"b": [0], "g": {"p": 1}}"#));
+        assert_eq!(v["h"], parse(r#"{"q": 2}"#));
+        let mut v = base.clone();
+        v.merge_with(parse(r#"{"a": 1, "h": {}, "j": "s"}"#), &MergeStrategy::new().concat_arrays(true));
+        assert_eq!((&v["a"], &v["h"], &v["j"]), (&Int(1), &parse("{}"), &Value::from("s")));
+        let mut v = Value::from("ab");
+        v.merge_with(Value::from("c"), &MergeStrategy::new().concat_arrays(true));
+        assert_eq!(v, Value::from("abc"));
+        let mut v = Int(3);
+        v.merge(base.clone());
+        assert_eq!(v, base);
+        let mut v = base.clone();
+        v.merge(Nil);
+        assert_eq!(v, Nil);
+
+        let mut v = base.clone();
+        v.merge_with(parse(r#"{"a": {"b": {"c": nil, "e": {"f": nil}}, "g": nil}, "j": nil, "r": nil, "s": {"t": nil}}"#), &MergeStrategy::new().nil_removes(true));
+        assert_eq!(v, parse(r#"{
+            "a": {"b": {"d": [1, 2], "e": {}}},
+            "h": [{"i": 0}],
+            "s": {"t": nil},
+        }"#));
+    }
+
+    #[test]
+    fn render_values() {
+        assert_eq!(render_value(&Value::from("foo")), r#"[102,111,111] ("foo")"#);
+        assert_eq!(render_value(&Array(vec![])), "[]");
+        assert_eq!(render_value(&Array(vec![Int(-1), Nil])), "[-1,nil]");
+
+        let long = render_value(&Array(vec![Int(1000); 100]));
+        assert!(long.starts_with("[1000,1000,"));
+        assert!(long.ends_with("..."));
+        assert!(long.len() <= MAX_RENDERED + 3);
+
+        assert_eq!(captured_str(&"\u{e9}".repeat(100)), Value::from("\u{e9}".repeat(MAX_RENDERED / 2).as_str()));
+
+        let mut m = BTreeMap::new();
+        m.insert(Value::from("foo"), Bool(true));
+        let err = Map(m).get_int(&[Value::from("foo")]).unwrap_err();
+        assert!(err.to_string().contains("[102,111,111]"));
+    }
+
+    #[test]
+    fn coercion() {
+        let two_53 = 1i64 << 53;
+        assert_eq!(Float(two_53 as f64).coerce_int(), Some(two_53));
+        assert_eq!(Float(two_53 as f64 + 2.0).coerce_int(), Some(two_53 + 2));
+        assert_eq!(Int(two_53).coerce_float(), Some(two_53 as f64));
+        assert_eq!(Int(-two_53).coerce_float(), Some(-two_53 as f64));
+        assert_eq!(Int(two_53 + 1).coerce_float(), None);
+        assert_eq!(Int(two_53 + 2).coerce_float(), Some(two_53 as f64 + 2.0));
+        assert_eq!(Int(two_53 + 1).as_f64_lossy(), Some(two_53 as f64));
+
+        assert_eq!(Float(2f64.powi(63)).coerce_int(), None);
+        assert_eq!(Float(-(2f64.powi(63))).coerce_int(), Some(i64::MIN));
+        assert_eq!(Int(i64::MIN).coerce_float(), Some(-(2f64.powi(63))));
+        assert_eq!(Int(i64::MAX).coerce_float(), None);
+        assert_eq!(Float(1e300).coerce_int(), None);
+
+        assert_eq!(Float(-0.0).coerce_int(), Some(0));
+        assert_eq!(Float(0.0).coerce_int(), Some(0));
+        assert_eq!(Int(0).coerce_float().map(f64::to_bits), Some(0.0f64.to_bits()));
+        assert_eq!(Float(f64::NAN).coerce_int(), None);
+        assert_eq!(Float(f64::INFINITY).coerce_int(), None);
+        assert_eq!(Float(f64::NEG_INFINITY).coerce_int(), None);
+        assert_eq!(Float(1.5).coerce_int(), None);
+        assert_eq!(Float(-1.5).coerce_int(), None);
+        assert_eq!(Float(1.5).coerce_float(), Some(1.5));
+        assert!(Float(f64::NAN).coerce_float().unwrap().is_nan());
+
+        assert_eq!(Int(-7).coerce_int(), Some(-7));
+        assert_eq!(Nil.coerce_int(), None);
+        assert_eq!(Bool(true).coerce_float(), None);
+        assert_eq!(Value::from("1").coerce_int(), None);
+    }
+
+    #[test]
+    fn parse() {
+        use crate::human::DecodeError;
+
+        let parse = |s: &str| s.parse::<Value>().map_err(|err| (err.position, err.e));
+        assert_eq!(parse("nil"), Ok(Nil));
+        assert_eq!(parse("  # leading\n{\"a\": 1.5} # trailing\n\t \n"), Ok(crate::vv!({ "a" => 1.5 })));
+        assert_eq!(parse("[1, # one\n 2,]  "), Ok(crate::vv!([1, 2])));
+        assert_eq!(parse("true false"), Err((5, DecodeError::TrailingBytes)));
+        assert_eq!(parse("[] ]"), Err((3, DecodeError::TrailingBytes)));
+        assert_eq!(parse("1 # fine\nx"), Err((9, DecodeError::TrailingBytes)));
+        assert_eq!(parse("[1, x]").unwrap_err().0, 4);
+        assert_eq!(parse(" # nothing\n").unwrap_err().1, DecodeError::EmptyInput);
+
+        let v = crate::vv!({ [1] => [nil, -2.5], "b" => {} });
+        assert_eq!(format!("{:#}", v).parse::<Value>(), Ok(v));
+    }
+
+    #[test]
+    fn deserialize_bounded() {
+        let human = |s: &str, max_depth| Value::deserialize_bounded(&mut crate::human::VVDeserializer::new(s.as_bytes()), max_depth);
+        assert_eq!(human("1", 1).unwrap(), Int(1));
+        assert_eq!(human("[]", 1).unwrap(), Array(vec![]));
+        assert!(human("[1]", 1).is_err());
+        assert_eq!(human("[1, {2: [3]}]", 4).unwrap(), crate::vv!([1, { 2 => [3] }]));
+        assert!(human("[1, {2: [3]}]", 3).is_err());
+        assert!(human("{[1]: 2}", 2).is_err());
+        assert!(human("{\"Some\": [1]}", 2).is_err());
+        assert!(human("1", 0).is_err());
+
+        let err = human("[[[]]]", 2).unwrap_err();
+        assert_eq!(err.e, crate::human::DecodeError::Message("values may not be nested more than 2 levels deep".to_string()));
+        let err = Value::deserialize_bounded(&mut crate::compact::VVDeserializer::new(&[0b101_00001, 0b101_00001, 0b101_00000]), 2).unwrap_err();
+        assert_eq!(err.e, crate::compact::DecodeError::Message("values may not be nested more than 2 levels deep".to_string()));
+    }
+
+    // At the limit, decoding fits into a stack that is far too small for decoding inputs without limits.
+    #[test]
+    fn deserialize_bounded_stack() {
+        const MAX_DEPTH: usize = 50;
+        let nested = |depth: usize| format!("{}nil{}", "[".repeat(depth - 1), "]".repeat(depth - 1));
+
+        std::thread::Builder::new().stack_size(1 << 20).spawn(move || {
+            let at_limit = nested(MAX_DEPTH);
+            let v = Value::deserialize_bounded(&mut crate::human::VVDeserializer::new(at_limit.as_bytes()), MAX_DEPTH).unwrap();
+            let enc = crate::compact::to_vec(&v).unwrap();
+            assert_eq!(Value::deserialize_bounded(&mut crate::compact::VVDeserializer::new(&enc), MAX_DEPTH).unwrap(), v);
+
+            let too_deep = nested(100_000);
+            assert!(Value::deserialize_bounded(&mut crate::human::VVDeserializer::new(too_deep.as_bytes()), MAX_DEPTH).is_err());
+            let too_deep = vec![0b101_00001; 100_000];
+            assert!(Value::deserialize_bounded(&mut crate::compact::VVDeserializer::new(&too_deep), MAX_DEPTH).is_err());
+        }).unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Nil.to_string(), "nil");
+        assert_eq!(format!("{:#}", Bool(false)), "false");
+        assert_eq!(Float(f64::NAN).to_string(), "NaN");
+        assert_eq!(Float(f64::INFINITY).to_string(), "Inf");
+        assert_eq!(Float(-0.0).to_string(), "-0.0");
+        assert_eq!(Array(vec![]).to_string(), "[]");
+
+        let v: Value = crate::human::from_str(r#"{"b": [1, 2.5e300, -Inf], "a": {nil: @{}}}"#).unwrap();
+        for (s, indentation) in [(v.to_string(), 0), (format!("{:#}", v), 2)] {
+            assert_eq!(s.as_bytes(), &crate::human::to_vec(&v, indentation).unwrap()[..]);
+            assert_eq!(crate::human::from_str::<Value>(&s).unwrap(), v);
+        }
+        assert_eq!(v.to_string(), "{[97]:{nil:{}},[98]:[1,2.5e300,-Inf]}");
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn display_roundtrip() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let data = XorShift::new(0x9e37_79b9_7f4a_7c15).bytes(1 << 16);
+
+        let mut u = Unstructured::new(&data);
+        while let Ok(v) = Value::arbitrary(&mut u) {
+            for s in [v.to_string(), format!("{:#}", v)] {
+                assert_eq!(crate::human::from_str::<Value>(&s).unwrap(), v, "{}", s);
+            }
+            if u.is_empty() {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn find() {
+        let v: Value = crate::human::from_slice(br#"{
+            "a": [3, 12, [40, -7, {"deep": 99}]],
+            "b": 11,
+            15: [1.5, 100.0, nil],
+        }"#).unwrap();
+        let ints_over = |threshold: i64| move |v: &Value| matches!(v, Int(n) if *n > threshold);
+
+        // The key 15 comes first, since ints sort before arrays. Floats are not ints, and the bytes of the string keys count as well.
+        assert_eq!(v.find_all(ints_over(10)), vec![&Int(15), &Int(97), &Int(12), &Int(40), &Int(100), &Int(101), &Int(101), &Int(112), &Int(99), &Int(98), &Int(11)]);
+        assert_eq!(v.find_all(ints_over(100)), vec![&Int(101), &Int(101), &Int(112)]);
+        assert!(v.find_all(ints_over(1000)).is_empty());
+
+        assert_eq!(v.find_first(ints_over(10)), Some(&Int(15)));
+        assert_eq!(v.find_first(ints_over(50)), Some(&Int(97)));
+        assert_eq!(v.find_first(|v| matches!(v, Float(n) if *n > 2.0)), Some(&Float(100.0)));
+        assert_eq!(v.find_first(ints_over(1000)), None);
+
+        // Values come before the values nested in them.
+        assert_eq!(v.find_first(|v| matches!(v, Map(_))), Some(&v));
+        assert_eq!(v.find_all(|v| matches!(v, Map(_))).len(), 2);
+
+        let visited = std::cell::Cell::new(0);
+        assert_eq!(v.find_first(|v| {
+            visited.set(visited.get() + 1);
+            *v == Int(12)
+        }), Some(&Int(12)));
+        assert_eq!(visited.get(), 11);
+    }
+
+    #[test]
+    fn constructors() {
+        assert_eq!(Value::nil(), Nil);
+        assert_eq!(Value::bool(true), Bool(true));
+        assert_eq!(Value::int(3u8), Int(3));
+        assert_eq!(Value::int(-3i32), Int(-3));
+        assert_eq!(Value::float(0.5), Float(0.5));
+
+        let a = Value::array((0..10).map(Value::int));
+        assert_eq!(a, Array((0..10).map(Int).collect()));
+        match a {
+            Array(vs) => assert!(vs.capacity() >= 10),
+            _ => unreachable!(),
+        }
+
+        let m = Value::map(vec![(Value::int(2), Value::nil()), (Value::int(1), Value::bool(false)), (Value::int(2), Value::bool(true))]);
+        match &m {
+            Map(entries) => {
+                assert_eq!(entries.keys().collect::<Vec<_>>(), vec![&Int(1), &Int(2)]);
+                assert_eq!(entries[&Int(2)], Bool(true));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn capacity() {
+        let mut v = Value::array_with_capacity(100);
+        match &v {
+            Array(vs) => {
+                assert!(vs.is_empty());
+                assert_eq!(vs.capacity(), Vec::<Value>::with_capacity(100).capacity());
+            }
+            _ => panic!("expected an array"),
+        }
+
+        assert!(v.reserve_array(200));
+        match &v {
+            Array(vs) => assert!(vs.capacity() >= 200),
+            _ => panic!("expected an array"),
+        }
+
+        let mut m = Map(BTreeMap::new());
+        assert!(!m.reserve_array(10));
+        assert_eq!(m, Map(BTreeMap::new()));
+    }
+
+    #[test]
+    fn split_at_key() {
+        let m: BTreeMap<Value, Value> = (0..5).map(|i| (Int(i * 2), Bool(i % 2 == 0))).collect();
+        let map = |range: std::ops::Range<i64>| Map(range.map(|i| (Int(i * 2), Bool(i % 2 == 0))).collect());
+        let v = Map(m);
+
+        assert_eq!(v.split_at_key(&Int(4)), Some((map(0..2), map(2..5))));
+        assert_eq!(v.split_at_key(&Int(5)), Some((map(0..3), map(3..5))));
+        assert_eq!(v.split_at_key(&Int(-1)), Some((map(0..0), map(0..5))));
+        assert_eq!(v.split_at_key(&Array(vec![])), Some((map(0..5), map(0..0))));
+        assert_eq!(v.split_at_key(&Nil), Some((map(0..0), map(0..5))));
+
+        assert_eq!(Array(vec![Int(1)]).split_at_key(&Int(1)), None);
+        assert_eq!(Nil.split_at_key(&Int(1)), None);
+    }
+
+    #[test]
+    fn int_histogram() {
+        let v = Array(vec![Int(3), Int(-1), Int(3), Nil, Int(0), Array(vec![Int(3)]), Int(3), Int(-1), Float(3.0)]);
+        let expected: BTreeMap<i64, usize> = vec![(-1, 2), (0, 1), (3, 3)].into_iter().collect();
+        assert_eq!(v.int_histogram(), Some(expected));
+
+        assert_eq!(Array(vec![]).int_histogram(), Some(BTreeMap::new()));
+        assert_eq!(Int(3).int_histogram(), None);
+        assert_eq!(Map(BTreeMap::new()).int_histogram(), None);
+    }
+
+    #[test]
+    fn map_encoding_is_insertion_order_independent() {
+        let keys = vec![
+            Nil,
+            Bool(true),
+            Bool(false),
+            Float(-1.5),
+            Float(f64::INFINITY),
+            Int(-7),
+            Int(0),
+            Int(300),
+            Value::from("a"),
+            Value::from(""),
+            Value::from("ab"),
+            Array(vec![Nil, Int(1)]),
+            Map(BTreeMap::new()),
+        ];
+        let build = |order: &[usize]| {
+            let mut m = BTreeMap::new();
+            for i in order {
+                let mut inner = BTreeMap::new();
+                for j in order.iter().rev() {
+                    inner.insert(keys[*j].clone(), Int(*j as i64));
+                }
+                m.insert(keys[*i].clone(), Map(inner));
+            }
+            Map(m)
+        };
+
+        let forward: Vec<usize> = (0..keys.len()).collect();
+        let backward: Vec<usize> = forward.iter().rev().copied().collect();
+        let shuffled: Vec<usize> = (0..keys.len()).map(|i| (i * 5) % keys.len()).collect();
+
+        let compact = crate::compact::to_vec(&build(&forward)).unwrap();
+        let human = crate::human::to_vec(&build(&forward), 2).unwrap();
+        for order in [&backward, &shuffled].iter() {
+            assert_eq!(crate::compact::to_vec(&build(order)).unwrap(), compact);
+            assert_eq!(crate::human::to_vec(&build(order), 2).unwrap(), human);
+        }
+
+        // The entries are encoded in ascending order of their keys.
+        let entries: Vec<(Value, Value)> = crate::compact::from_slice(&compact).unwrap();
+        assert_eq!(entries.len(), keys.len());
+        assert!(entries.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn cmp() {
+        assert!(Nil < Bool(false));
+
+        assert!(Bool(false) < Bool(true));
         assert!(Bool(true) < Float(f64::NEG_INFINITY));
 
         assert!(Float(f64::NAN) < Float(f64::NEG_INFINITY));
@@ -564,4 +2358,590 @@ mod tests {
 
         assert!(Array(Vec::new()) < Map(BTreeMap::new()));
     }
+
+    // Deterministic pseudo-random values that are all comparable to each other, i.e., maps from small ints to arrays of ints.
+    fn comparable_values(seed: u64, count: usize) -> Vec<Value> {
+        let mut rng = XorShift::new(seed);
+        let mut next = move |bound: u64| rng.next_u64() % bound;
+
+        (0..count).map(|_| {
+            let mut m = BTreeMap::new();
+            for _ in 0..next(4) {
+                let arr = (0..next(4)).map(|_| Int(next(10) as i64 - 5)).collect();
+                m.insert(Int(next(4) as i64), Array(arr));
+            }
+            Map(m)
+        }).collect()
+    }
+
+    #[test]
+    fn bound_folds() {
+        assert_eq!(Value::lub_all(&[]), None);
+        assert_eq!(Value::glb_all(&[]), None);
+        assert_eq!(Value::lub_all(&[Int(3)]), Some(Int(3)));
+        assert_eq!(Value::lub_all(&[Int(3), Int(7), Int(-2)]), Some(Int(7)));
+        assert_eq!(Value::glb_all(&[Int(3), Int(7), Int(-2)]), Some(Int(-2)));
+        assert_eq!(Value::lub_all(&[Int(3), Nil]), None);
+        assert_eq!(Value::glb_all(&[Int(3), Bool(true), Int(4)]), None);
+
+        for seed in 1..200 {
+            let vs = comparable_values(seed, 5);
+            let lub = Value::lub_all(&vs).unwrap();
+            let glb = Value::glb_all(&vs).unwrap();
+
+            for v in vs.iter() {
+                assert!(v.subvalue(&lub));
+                assert!(glb.subvalue(v));
+            }
+
+            // commutativity
+            let mut reversed = vs.clone();
+            reversed.reverse();
+            assert_eq!(Value::lub_all(&reversed), Some(lub.clone()));
+            assert_eq!(Value::glb_all(&reversed), Some(glb.clone()));
+
+            // associativity
+            let right_lub = vs[0].least_common_supervalue(&Value::lub_all(&vs[1..]).unwrap()).unwrap();
+            let right_glb = vs[0].greatest_common_subvalue(&Value::glb_all(&vs[1..]).unwrap()).unwrap();
+            assert_eq!(right_lub, lub);
+            assert_eq!(right_glb, glb);
+        }
+    }
+
+    fn string(s: &str) -> Value {
+        Array(s.bytes().map(|b| Int(b as i64)).collect())
+    }
+
+    #[test]
+    fn transform_strings() {
+        let mut inner = BTreeMap::new();
+        inner.insert(string("a cat"), string("cats and dogs"));
+        inner.insert(string("bird"), Array(vec![string("cat"), Int(3)]));
+        let mut outer = BTreeMap::new();
+        outer.insert(string("cat"), Map(inner));
+        outer.insert(Int(0), Array(vec![Int(0xff), Int(0xfe)]));
+        outer.insert(Int(1), Array(vec![Int(0x63), Int(0x61), Int(0x74), Int(256)]));
+        let mut v = Map(outer);
+
+        let count = v.transform_strings(|s| {
+            if s.contains("cat") {
+                Some(s.replace("cat", "dog"))
+            } else {
+                None
+            }
+        });
+        assert_eq!(count, 4);
+
+        let mut inner = BTreeMap::new();
+        inner.insert(string("a dog"), string("dogs and dogs"));
+        inner.insert(string("bird"), Array(vec![string("dog"), Int(3)]));
+        let mut outer = BTreeMap::new();
+        outer.insert(string("dog"), Map(inner));
+        outer.insert(Int(0), Array(vec![Int(0xff), Int(0xfe)]));
+        outer.insert(Int(1), Array(vec![Int(0x63), Int(0x61), Int(0x74), Int(256)]));
+        assert_eq!(v, Map(outer));
+
+        let mut v = Array(vec![Int(0xff), Float(1.0)]);
+        assert_eq!(v.transform_strings(|_| Some("x".to_string())), 0);
+        assert_eq!(v, Array(vec![Int(0xff), Float(1.0)]));
+    }
+
+    #[test]
+    fn first_difference() {
+        let document = |leaf: Value| {
+            let mut rows = vec![];
+            for i in 0..20 {
+                let mut row = BTreeMap::new();
+                row.insert(string("id"), Int(i));
+                row.insert(string("tags"), Array(vec![string("a"), string("b")]));
+                let mut nested = BTreeMap::new();
+                nested.insert(string("leaf"), if i == 13 { leaf.clone() } else { Nil });
+                row.insert(string("nested"), Map(nested));
+                rows.push(Map(row));
+            }
+            let mut m = BTreeMap::new();
+            m.insert(string("rows"), Array(rows));
+            m.insert(string("version"), Int(1));
+            Map(m)
+        };
+
+        let a = document(Float(1.0));
+        assert_eq!(a.first_difference(&a.clone()), None);
+        assert_eq!(a.first_difference(&document(Float(1.5))), Some(vec![
+            PathSegment::Key(string("rows")),
+            PathSegment::Index(13),
+            PathSegment::Key(string("nested")),
+            PathSegment::Key(string("leaf")),
+        ]));
+
+        assert_eq!(Array(vec![Int(1)]).first_difference(&Array(vec![Int(1), Int(2)])), Some(vec![PathSegment::Index(1)]));
+        let mut m = BTreeMap::new();
+        m.insert(Int(2), Nil);
+        let mut n = m.clone();
+        n.insert(Int(1), Nil);
+        assert_eq!(Map(m.clone()).first_difference(&Map(n)), Some(vec![PathSegment::Key(Int(1))]));
+        assert_eq!(Map(m).first_difference(&Array(vec![])), Some(vec![]));
+    }
+
+    #[test]
+    fn into_deserializer() {
+        use serde::de::IntoDeserializer;
+
+        let mut m = BTreeMap::new();
+        m.insert(Int(1), Array(vec![Bool(true), Float(0.5)]));
+        m.insert(Nil, Map(BTreeMap::new()));
+        let v = Map(m);
+        let decoded = Value::deserialize(IntoDeserializer::<de::value::Error>::into_deserializer(v.clone())).unwrap();
+        assert_eq!(decoded, v);
+
+        let pair = <(bool, f64)>::deserialize(IntoDeserializer::<de::value::Error>::into_deserializer(Array(vec![Bool(true), Float(0.5)]))).unwrap();
+        assert_eq!(pair, (true, 0.5));
+    }
+
+    // Accepts only byte strings, like `serde_bytes::ByteBuf`.
+    #[derive(Debug, PartialEq)]
+    struct ByteBuf(Vec<u8>);
+
+    impl<'de> Deserialize<'de> for ByteBuf {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct ByteBufVisitor;
+
+            impl<'de> Visitor<'de> for ByteBufVisitor {
+                type Value = ByteBuf;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a byte string")
+                }
+
+                fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<ByteBuf, E> {
+                    Ok(ByteBuf(v))
+                }
+            }
+
+            deserializer.deserialize_byte_buf(ByteBufVisitor)
+        }
+    }
+
+    #[test]
+    fn owned_and_borrowed_deserializers_agree() {
+        use serde::de::IntoDeserializer;
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Meters(u16);
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Record {
+            bytes: ByteBuf,
+            length: Meters,
+            #[serde(default)]
+            skipped: de::IgnoredAny,
+            name: Option<String>,
+        }
+
+        let v: Value = crate::human::from_str(r#"{"bytes": [0, 255], "length": 7, "skipped": {1: [2]}, "name": {"Some": "x"}}"#).unwrap();
+        let expected = Record { bytes: ByteBuf(vec![0, 255]), length: Meters(7), skipped: de::IgnoredAny, name: Some("x".to_string()) };
+        assert_eq!(Record::deserialize(IntoDeserializer::<de::value::Error>::into_deserializer(v.clone())).unwrap(), expected);
+        assert_eq!(Record::deserialize(IntoDeserializer::<de::value::Error>::into_deserializer(&v)).unwrap(), expected);
+        assert_eq!(crate::from_value::<Record>(&v).unwrap(), expected);
+
+        let not_bytes = Array(vec![Int(256)]);
+        let owned = ByteBuf::deserialize(IntoDeserializer::<de::value::Error>::into_deserializer(not_bytes.clone())).unwrap_err();
+        let borrowed = ByteBuf::deserialize(IntoDeserializer::<de::value::Error>::into_deserializer(&not_bytes)).unwrap_err();
+        assert_eq!(owned.to_string(), borrowed.to_string());
+    }
+
+    #[test]
+    fn into_deserializer_strings_options_enums() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        enum Shape {
+            Empty,
+            Circle(u8),
+            Rect { w: u8, h: u8 },
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Row {
+            name: String,
+            initial: char,
+            shapes: Vec<Shape>,
+            size: Option<u8>,
+            missing: Option<u8>,
+        }
+
+        fn decode<'de, T: Deserialize<'de>>(v: Value) -> Result<T, de::value::Error> {
+            T::deserialize(de::IntoDeserializer::into_deserializer(v))
+        }
+
+        // Produce the value via the human-readable encoding, so that it has the same shape as decoded input.
+        let enc = crate::human::to_vec(&vec![Some(1u8), None], 0).unwrap();
+        let options: Value = crate::human::from_slice(&enc).unwrap();
+        assert_eq!(decode::<Vec<Option<u8>>>(options).unwrap(), vec![Some(1), None]);
+
+        let input = br#"{"name": "x", "initial": "\{e9}", "shapes": ["Empty", {"Circle": 3}, {"Rect": {"w": 1, "h": 2}}], "size": {"Some": 4}, "missing": "None"}"#;
+        let v: Value = crate::human::from_slice(input).unwrap();
+        assert_eq!(decode::<Row>(v).unwrap(), Row {
+            name: "x".to_string(),
+            initial: '\u{e9}',
+            shapes: vec![Shape::Empty, Shape::Circle(3), Shape::Rect { w: 1, h: 2 }],
+            size: Some(4),
+            missing: None,
+        });
+
+        assert!(decode::<String>(Array(vec![Int(0xff)])).is_err());
+        assert!(decode::<Shape>(string("Square")).is_err());
+        assert!(decode::<Shape>(Array(vec![Nil])).is_err());
+        assert!(decode::<Option<u8>>(string("Some")).is_err());
+    }
+
+    #[test]
+    fn transpose() {
+        let row = |x: i64, y: i64| {
+            let mut m = BTreeMap::new();
+            m.insert(string("x"), Int(x));
+            m.insert(string("y"), Int(y));
+            Map(m)
+        };
+        let rows = Array(vec![row(1, 2), row(3, 4)]);
+
+        let mut columns = BTreeMap::new();
+        columns.insert(string("x"), Array(vec![Int(1), Int(3)]));
+        columns.insert(string("y"), Array(vec![Int(2), Int(4)]));
+        let columns = Map(columns);
+
+        assert_eq!(rows.transpose(), Some(columns.clone()));
+        assert_eq!(columns.transpose(), Some(rows));
+
+        assert_eq!(Array(vec![]).transpose(), Some(Map(BTreeMap::new())));
+        assert_eq!(Map(BTreeMap::new()).transpose(), Some(Array(vec![])));
+        assert_eq!(Int(1).transpose(), None);
+        assert_eq!(Array(vec![Map(BTreeMap::new()), Int(1)]).transpose(), None);
+        let mut m = BTreeMap::new();
+        m.insert(string("x"), Nil);
+        assert_eq!(Map(m).transpose(), None);
+    }
+
+    #[test]
+    fn transpose_ragged() {
+        let mut first = BTreeMap::new();
+        first.insert(string("x"), Int(1));
+        let mut second = BTreeMap::new();
+        second.insert(string("y"), Int(2));
+        let rows = Array(vec![Map(first), Map(second)]);
+
+        let mut columns = BTreeMap::new();
+        columns.insert(string("x"), Array(vec![Int(1), Nil]));
+        columns.insert(string("y"), Array(vec![Nil, Int(2)]));
+        assert_eq!(rows.transpose(), Some(Map(columns)));
+
+        let mut columns = BTreeMap::new();
+        columns.insert(string("x"), Array(vec![Int(1)]));
+        columns.insert(string("y"), Array(vec![Int(2), Int(3)]));
+        let mut first = BTreeMap::new();
+        first.insert(string("x"), Int(1));
+        first.insert(string("y"), Int(2));
+        let mut second = BTreeMap::new();
+        second.insert(string("x"), Nil);
+        second.insert(string("y"), Int(3));
+        assert_eq!(Map(columns).transpose(), Some(Array(vec![Map(first), Map(second)])));
+    }
+
+    #[test]
+    fn fix_nan() {
+        let nans = [
+            f64::NAN,
+            -f64::NAN,
+            f64::from_bits(0x7ff0_0000_0000_0001),
+            f64::from_bits(0xfff8_0000_0000_0001),
+            f64::from_bits(u64::MAX),
+        ];
+
+        let mut m = BTreeMap::new();
+        m.insert(Array(vec![Float(nans[2])]), Float(nans[3]));
+        m.insert(Int(1), Array(nans.iter().map(|n| Float(*n)).collect()));
+        let mut v = Array(vec![Float(nans[0]), Map(m), Float(1.5), Float(-0.0)]);
+        v.fix_nan();
+
+        fn check(v: &Value, nans: &mut usize) {
+            match v {
+                Float(n) if n.is_nan() => {
+                    assert_eq!(n.to_bits(), u64::MAX);
+                    *nans += 1;
+                }
+                Array(vs) => vs.iter().for_each(|v| check(v, nans)),
+                Map(m) => m.iter().for_each(|(k, v)| {
+                    check(k, nans);
+                    check(v, nans);
+                }),
+                _ => {}
+            }
+        }
+        let mut count = 0;
+        check(&v, &mut count);
+        assert_eq!(count, 8);
+
+        if let Array(vs) = &v {
+            assert_eq!(vs[2], Float(1.5));
+            assert!(matches!(vs[3], Float(n) if n.to_bits() == (-0.0f64).to_bits()));
+        }
+    }
+
+    #[test]
+    fn canonic_equal() {
+        let nan = |bits: u64| Float(f64::from_bits(bits));
+        let map = |entries: &[(Value, Value)]| Map(entries.iter().cloned().collect());
+
+        let mut values = comparable_values(7, 30);
+        values.extend(vec![
+            Nil,
+            Float(0.0),
+            Float(-0.0),
+            nan(u64::MAX),
+            nan(0x7FF8_0000_0000_0000),
+            nan(0xFFF0_0000_0000_0001),
+            Array(vec![nan(0x7FF0_0000_0000_0001), Int(1)]),
+            Array(vec![nan(u64::MAX), Int(1)]),
+            Int(27),
+            Int(28),
+            Int(-1),
+            Value::from("abc"),
+            map(&[(nan(0x7FF8_0000_0000_0001), Int(1)), (Int(2), Value::from("x"))]),
+            map(&[(Int(2), Value::from("x")), (nan(0xFFF8_0000_0000_0000), Int(1))]),
+            map(&[(Int(2), Value::from("x"))]),
+        ]);
+
+        for a in values.iter() {
+            for b in values.iter() {
+                assert_eq!(a.canonic_equal(b), a == b, "{:?} {:?}", a, b);
+            }
+        }
+
+        assert!(nan(1 | 0x7FF0_0000_0000_0000).canonic_equal(&nan(u64::MAX)));
+        assert!(!Float(0.0).canonic_equal(&Float(-0.0)));
+        assert!(values[values.len() - 3].canonic_equal(&values[values.len() - 2]));
+    }
+
+    #[test]
+    fn typed_getters() {
+        let mut inner = BTreeMap::new();
+        inner.insert(string("port"), Int(8080));
+        inner.insert(string("debug"), Bool(true));
+        inner.insert(string("ratio"), Float(0.5));
+        inner.insert(string("name"), string("foo"));
+        inner.insert(string("hosts"), Array(vec![string("a"), string("b")]));
+        let mut outer = BTreeMap::new();
+        outer.insert(string("server"), Map(inner.clone()));
+        let v = Map(outer);
+
+        assert_eq!(v.get_int(&[string("server"), string("port")]), Ok(8080));
+        assert_eq!(v.get_bool(&[string("server"), string("debug")]), Ok(true));
+        assert_eq!(v.get_float(&[string("server"), string("ratio")]), Ok(0.5));
+        assert_eq!(v.get_str(&[string("server"), string("name")]), Ok("foo".to_string()));
+        assert_eq!(v.get_str(&[string("server"), string("hosts"), Int(1)]), Ok("b".to_string()));
+        assert_eq!(v.get_array(&[string("server"), string("hosts")]).unwrap().len(), 2);
+        assert_eq!(v.get_map(&[string("server")]), Ok(&inner));
+        assert_eq!(v.get_map(&[]).unwrap().len(), 1);
+        assert_eq!(v.pointer(&[string("server"), string("port")]), Some(&Int(8080)));
+
+        assert_eq!(v.get_int(&[string("client"), string("port")]), Err(GetError::Missing(r#"[[99,108,105,101,110,116] ("client")]"#.to_string())));
+        assert_eq!(v.get_str(&[string("server"), string("hosts"), Int(2)]), Err(GetError::Missing(r#"[[115,101,114,118,101,114] ("server"),[104,111,115,116,115] ("hosts"),2]"#.to_string())));
+        assert_eq!(v.pointer(&[string("server"), string("hosts"), Int(-1)]), None);
+
+        assert_eq!(v.get_bool(&[string("server"), string("port")]), Err(GetError::WrongType {
+            path: r#"[[115,101,114,118,101,114] ("server"),[112,111,114,116] ("port")]"#.to_string(),
+            expected: "bool",
+        }));
+        assert!(matches!(v.get_str(&[string("server"), string("port")]), Err(GetError::WrongType { .. })));
+        assert_eq!(v.get_int(&[string("server"), string("port"), string("x")]), Err(GetError::WrongType {
+            path: r#"[[115,101,114,118,101,114] ("server"),[112,111,114,116] ("port"),[120] ("x")]"#.to_string(),
+            expected: "array or map",
+        }));
+        assert_eq!(v.get_int(&[string("server"), string("port"), Int(0), Int(1)]), Err(GetError::WrongType {
+            path: r#"[[115,101,114,118,101,114] ("server"),[112,111,114,116] ("port"),0]"#.to_string(),
+            expected: "array or map",
+        }));
+        assert_eq!(v.get_str(&[string("server"), string("hosts"), string("first")]), Err(GetError::WrongType {
+            path: r#"[[115,101,114,118,101,114] ("server"),[104,111,115,116,115] ("hosts"),[102,105,114,115,116] ("first")]"#.to_string(),
+            expected: "int index",
+        }));
+        assert_eq!(v.get_str(&[string("server"), string("hosts"), Nil]), Err(GetError::WrongType {
+            path: r#"[[115,101,114,118,101,114] ("server"),[104,111,115,116,115] ("hosts"),nil]"#.to_string(),
+            expected: "int index",
+        }));
+    }
+
+    #[test]
+    fn as_accessors() {
+        #[derive(Serialize)]
+        struct Reading {
+            ok: bool,
+            samples: Vec<Vec<f64>>,
+            counts: BTreeMap<u8, ()>,
+        }
+
+        let reading = Reading { ok: true, samples: vec![vec![0.5, 1.5], vec![]], counts: vec![(3, ())].into_iter().collect() };
+        let mut v: Value = crate::compact::from_slice(&crate::compact::to_vec(&reading).unwrap()).unwrap();
+
+        assert_eq!(v["ok"].as_bool(), Some(true));
+        let samples = v["samples"].as_array().unwrap();
+        assert_eq!(samples[0].as_array().unwrap()[1].as_f64(), Some(1.5));
+        assert_eq!(samples[1].as_array().map(Vec::len), Some(0));
+        let counts = v["counts"].as_map().unwrap();
+        assert_eq!(counts.keys().next().and_then(Value::as_i64), Some(3));
+        assert!(counts[&Int(3)].is_nil());
+
+        assert_eq!(v["ok"].as_i64(), None);
+        assert_eq!(Int(1).as_f64(), None);
+        assert_eq!(Float(1.0).as_i64(), None);
+        assert_eq!(v.as_array(), None);
+        assert_eq!(v["samples"].as_map(), None);
+        assert!(!v["ok"].is_nil());
+
+        // The accessors borrow the nested values rather than cloning them.
+        let inner: *const Value = &v["samples"][0];
+        assert!(std::ptr::eq(&v["samples"].as_array().unwrap()[0], inner));
+
+        v["samples"].as_array_mut().unwrap()[0].as_array_mut().unwrap().push(Float(2.5));
+        v.as_map_mut().unwrap().remove(&Value::from("counts"));
+        assert_eq!(v["samples"][0], Array(vec![Float(0.5), Float(1.5), Float(2.5)]));
+        assert_eq!(v.as_map().unwrap().len(), 2);
+        assert_eq!(Nil.as_array_mut(), None);
+        assert_eq!(Nil.as_map_mut(), None);
+    }
+
+    #[test]
+    fn inspection() {
+        let values = [Nil, Bool(false), Int(-1), Float(-0.0), Array(vec![]), Map(BTreeMap::new())];
+        let kinds = values.iter().map(|v| [v.is_nil(), v.is_bool(), v.is_int(), v.is_float(), v.is_array(), v.is_map()]).collect::<Vec<_>>();
+        for (i, kind) in kinds.iter().enumerate() {
+            for (j, is) in kind.iter().enumerate() {
+                assert_eq!(*is, i == j, "{:?}", values[i]);
+            }
+        }
+        assert!(string("strings are arrays").is_array());
+
+        // Negative zero and NaN payloads come back bit for bit.
+        assert_eq!(Float(-0.0).as_f64().map(f64::to_bits), Some((-0.0f64).to_bits()));
+        assert_eq!(Float(-0.0).as_f64_lossy().map(f64::to_bits), Some((-0.0f64).to_bits()));
+        let payload = f64::from_bits(0x7FF8_0000_0000_0001);
+        assert!(Float(payload).is_float());
+        assert_eq!(Float(payload).as_f64().map(f64::to_bits), Some(payload.to_bits()));
+        assert_eq!(Float(f64::NEG_INFINITY).as_f64(), Some(f64::NEG_INFINITY));
+
+        assert_eq!(Int(0).as_f64(), None);
+        assert_eq!(Int(0).as_f64_lossy().map(f64::to_bits), Some(0.0f64.to_bits()));
+        assert_eq!(Int(-3).as_f64_lossy(), Some(-3.0));
+        assert_eq!(Int((1 << 53) + 1).as_f64_lossy(), Some((1u64 << 53) as f64));
+        assert_eq!(Int(i64::MIN).as_f64_lossy(), Some(-9223372036854775808.0));
+        assert_eq!(Float(1.0).as_i64(), None);
+        assert_eq!(Bool(true).as_f64_lossy(), None);
+        assert_eq!(string("1").as_f64_lossy(), None);
+    }
+
+    #[test]
+    fn from_primitives() {
+        let v: Value = 42.into();
+        assert_eq!(v, Int(42));
+        assert_eq!(Value::array(vec![1.into(), true.into(), 0.5.into(), "a".into()]), Array(vec![Int(1), Bool(true), Float(0.5), string("a")]));
+
+        assert_eq!(Value::from(-1i8), Int(-1));
+        assert_eq!(Value::from(i16::MIN), Int(-32768));
+        assert_eq!(Value::from(u8::MAX), Int(255));
+        assert_eq!(Value::from(u16::MAX), Int(65535));
+        assert_eq!(Value::from(u32::MAX), Int(4294967295));
+        assert_eq!(Value::from(i64::MIN), Int(i64::MIN));
+        assert_eq!(Value::from(1.5f32), Float(1.5));
+        assert_eq!(Value::from(false), Bool(false));
+
+        assert_eq!(Value::try_from(i64::MAX as u64), Ok(Int(i64::MAX)));
+        assert!(Value::try_from(i64::MAX as u64 + 1).is_err());
+
+        assert_eq!(Value::from("ab".to_string()), string("ab"));
+        assert_eq!(Value::from(&b"\xffa"[..]), Array(vec![Int(255), Int(97)]));
+        assert_eq!(Value::from(&b""[..]), Array(vec![]));
+        assert_eq!((1..3).map(Value::from).collect::<Value>(), Array(vec![Int(1), Int(2)]));
+        assert_eq!(vec![(Int(2), Nil), (Int(1), Nil)].into_iter().collect::<Value>(), Value::map(vec![(Int(1), Nil), (Int(2), Nil)]));
+
+        assert_eq!(Value::from(vec![Nil]), Array(vec![Nil]));
+        let mut m = BTreeMap::new();
+        m.insert(Nil, Nil);
+        assert_eq!(Value::from(m.clone()), Map(m));
+    }
+
+    #[test]
+    fn try_from_value() {
+        use std::convert::TryInto;
+
+        let n: i64 = Int(-4).try_into().unwrap();
+        assert_eq!(n, -4);
+        assert_eq!(bool::try_from(Bool(true)), Ok(true));
+        assert_eq!(f64::try_from(Float(-0.0)).map(f64::to_bits), Ok((-0.0f64).to_bits()));
+        assert_eq!(Vec::<Value>::try_from(string("ab")), Ok(vec![Int(97), Int(98)]));
+        assert_eq!(BTreeMap::try_from(Map(BTreeMap::new())), Ok(BTreeMap::new()));
+
+        let err = i64::try_from(Map(BTreeMap::new())).unwrap_err();
+        assert_eq!(err, ValueTypeError { expected: "int", found: "map" });
+        assert_eq!(err.to_string(), "expected int, found map");
+        // Ints and floats are not converted into each other.
+        assert_eq!(f64::try_from(Int(1)), Err(ValueTypeError { expected: "float", found: "int" }));
+        assert_eq!(i64::try_from(Float(1.0)), Err(ValueTypeError { expected: "int", found: "float" }));
+        assert_eq!(bool::try_from(Nil), Err(ValueTypeError { expected: "bool", found: "nil" }));
+        assert_eq!(Vec::<Value>::try_from(Bool(false)), Err(ValueTypeError { expected: "array", found: "bool" }));
+        assert_eq!(BTreeMap::try_from(Array(vec![])), Err(ValueTypeError { expected: "map", found: "array" }));
+    }
+
+    #[test]
+    fn value_index() {
+        let mut config = Value::map(vec![
+            (string("port"), Int(8080)),
+            (string("hosts"), Value::array(vec![string("a"), string("b")])),
+            (Int(1), Bool(true)),
+        ]);
+
+        assert_eq!(config.get(&Value::from("port")), Some(&Int(8080)));
+        assert_eq!(config.get("port"), Some(&Int(8080)));
+        assert_eq!(config.get(&Int(1)), Some(&Bool(true)));
+        assert_eq!(config.get("hosts").and_then(|hosts| hosts.get(1)), Some(&string("b")));
+        assert_eq!(config.get("hosts").and_then(|hosts| hosts.get(2)), None);
+        assert_eq!(config.get("missing"), None);
+        assert_eq!(config.get(0), None);
+        assert_eq!(config["hosts"].get("a"), None);
+        assert_eq!(Int(3).get(&Int(3)), None);
+
+        *config.get_mut("hosts").and_then(|hosts| hosts.get_mut(0)).unwrap() = string("c");
+        *config.get_mut(&Int(1)).unwrap() = Nil;
+        assert_eq!(config["hosts"][0], string("c"));
+        assert!(config[&Int(1)].is_nil());
+        assert_eq!(config.get_mut(0), None);
+    }
+
+    #[test]
+    fn indexing() {
+        let mut v = Value::map(vec![
+            (string("foo"), Value::array((0..5).map(Value::int))),
+            (Int(7), Bool(true)),
+        ]);
+
+        assert_eq!(v["foo"][3], Int(3));
+        assert_eq!(v[&Int(7)], Bool(true));
+        assert_eq!(v.get(&string("foo")).and_then(|a| a.get_index(4)), Some(&Int(4)));
+        assert_eq!(v.get_key("foo").and_then(|a| a.get_index(5)), None);
+        assert_eq!(v.get_key("bar"), None);
+        assert_eq!(v.get(&Int(8)), None);
+        assert_eq!(v["foo"].get_key("foo"), None);
+        assert_eq!(v.get_index(0), None);
+
+        v["foo"][3] = Nil;
+        *v.get_key_mut("foo").unwrap().get_index_mut(4).unwrap() = Float(0.5);
+        *v.get_mut(&Int(7)).unwrap() = Bool(false);
+        assert_eq!(v["foo"], Array(vec![Int(0), Int(1), Int(2), Nil, Float(0.5)]));
+        assert_eq!(v[&Int(7)], Bool(false));
+        assert_eq!(v.get_index_mut(0), None);
+
+        let missing = std::panic::catch_unwind(|| v["bar"].clone());
+        assert!(missing.is_err());
+        let out_of_bounds = std::panic::catch_unwind(|| v["foo"][5].clone());
+        assert!(out_of_bounds.is_err());
+        let not_an_array = std::panic::catch_unwind(|| v[&Int(7)][0].clone());
+        assert!(not_an_array.is_err());
+    }
 }
@@ -0,0 +1,257 @@
+//! Loads a realistic configuration file in the human-readable encoding, validates and edits it, and writes it back.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Serialize};
+use valuable_value::compact::IntWidth;
+use valuable_value::human::{FloatStyle, LineEnding, Profile};
+use valuable_value::{compact, human, Value};
+
+const CONFIG: &str = r#"# Configuration of the ingest service.
+{
+    "name": "ingest",
+    # Omitted keys fall back to their defaults.
+    "listen": {"host": "0.0.0.0", "port": 8080},
+    "timeout": 2500, # milliseconds
+    "log": {"File": {"path": "/var/log/ingest.log", "rotate": {"Some": 7}}},
+    "backends": {
+        "primary": {"url": "https://a.example", "weight": 3, "tags": ["eu", "ssd"]},
+        "fallback": {"url": "https://b.example", "tls": false},
+    },
+    "secret": @x00ff10ab,
+    "features": ["tracing", "metrics", "tracing"],
+}
+"#;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Config {
+    name: String,
+    listen: Listen,
+    #[serde(with = "millis")]
+    timeout: Duration,
+    #[serde(default)]
+    log: Log,
+    backends: BTreeMap<String, Backend>,
+    #[serde(with = "bytes")]
+    secret: Vec<u8>,
+    #[serde(default)]
+    features: BTreeSet<String>,
+    #[serde(default)]
+    retries: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Listen {
+    host: String,
+    port: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+enum Log {
+    #[default]
+    Stderr,
+    File { path: String, rotate: Option<u32> },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Backend {
+    url: String,
+    #[serde(default = "default_weight")]
+    weight: u32,
+    #[serde(default = "default_tls")]
+    tls: bool,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_tls() -> bool {
+    true
+}
+
+// Durations are stored as an int of milliseconds.
+mod millis {
+    use super::*;
+
+    pub fn serialize<S: serde::Serializer>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(d.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+// Secrets are written as byte strings rather than as arrays of ints.
+mod bytes {
+    use super::*;
+
+    pub fn serialize<S: serde::Serializer>(b: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(b)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        Vec::<u8>::deserialize(deserializer)
+    }
+}
+
+fn key(s: &str) -> Value {
+    Value::from(s)
+}
+
+fn validate(config: &Config) -> Result<(), String> {
+    if config.listen.port == 0 {
+        return Err("port must not be zero".to_string());
+    }
+    if config.backends.values().all(|b| b.weight == 0) {
+        return Err("at least one backend needs a positive weight".to_string());
+    }
+    Ok(())
+}
+
+fn load() -> Config {
+    human::from_slice(CONFIG.as_bytes()).unwrap()
+}
+
+#[test]
+fn load_with_defaults() {
+    let config = load();
+    validate(&config).unwrap();
+
+    assert_eq!(config.name, "ingest");
+    assert_eq!(config.listen, Listen { host: "0.0.0.0".to_string(), port: 8080 });
+    assert_eq!(config.timeout, Duration::from_millis(2500));
+    assert_eq!(config.log, Log::File { path: "/var/log/ingest.log".to_string(), rotate: Some(7) });
+    assert_eq!(config.backends["primary"], Backend {
+        url: "https://a.example".to_string(),
+        weight: 3,
+        tls: true,
+        tags: vec!["eu".to_string(), "ssd".to_string()],
+    });
+    assert_eq!(config.backends["fallback"], Backend {
+        url: "https://b.example".to_string(),
+        weight: 1,
+        tls: false,
+        tags: vec![],
+    });
+    assert_eq!(config.secret, vec![0x00, 0xff, 0x10, 0xab]);
+    // Duplicates collapse when decoding into a set.
+    assert_eq!(config.features.iter().map(String::as_str).collect::<Vec<_>>(), vec!["metrics", "tracing"]);
+    assert_eq!(config.retries, None);
+
+    let minimal: Config = human::from_slice(br#"{"name": "x", "listen": {"host": "::1", "port": 1}, "timeout": 0, "backends": {}, "secret": @[]}"#).unwrap();
+    assert_eq!(minimal.log, Log::Stderr);
+    assert!(minimal.features.is_empty());
+    assert_eq!(validate(&minimal), Err("at least one backend needs a positive weight".to_string()));
+}
+
+#[test]
+fn edit_as_value() {
+    let mut v: Value = human::from_slice(CONFIG.as_bytes()).unwrap();
+
+    // Untyped access sees the file as written, without defaults.
+    assert_eq!(v.get_int(&[key("listen"), key("port")]).unwrap(), 8080);
+    assert_eq!(v.get_str(&[key("backends"), key("primary"), key("tags"), Value::Int(1)]).unwrap(), "ssd");
+    assert!(v.pointer(&[key("backends"), key("fallback"), key("weight")]).is_none());
+    assert!(v.get_int(&[key("name")]).is_err());
+    assert_eq!(v.get_array(&[key("features")]).unwrap().len(), 3);
+
+    if let Value::Map(m) = &mut v {
+        if let Some(Value::Map(listen)) = m.get_mut(&key("listen")) {
+            listen.insert(key("port"), Value::Int(9090));
+        }
+        m.insert(key("retries"), Value::Map(std::iter::once((key("Some"), Value::Int(3))).collect()));
+        m.remove(&key("log"));
+    }
+
+    let config = Config::deserialize(v.into_deserializer()).map_err(|e: serde::de::value::Error| e.to_string()).unwrap();
+    validate(&config).unwrap();
+    assert_eq!(config.listen.port, 9090);
+    assert_eq!(config.retries, Some(3));
+    assert_eq!(config.log, Log::Stderr);
+}
+
+#[test]
+fn write_back() {
+    let mut config = load();
+    config.timeout = Duration::from_secs(5);
+    config.features.insert("profiling".to_string());
+    config.backends.get_mut("fallback").unwrap().tags.push("spare".to_string());
+
+    for indentation in [0, 4] {
+        let enc = human::to_vec(&config, indentation).unwrap();
+        let text = String::from_utf8(enc.clone()).unwrap();
+        let secret = &text[text.find("\"secret\":").unwrap() + "\"secret\":".len()..];
+        assert!(secret.trim_start().starts_with("@["), "secret is not written as a byte string: {}", text);
+        assert_eq!(text.contains('\n'), indentation != 0);
+        assert_eq!(human::from_slice::<Config>(&enc).unwrap(), config);
+
+        // Reading the pretty-printed file and converting it to the compact encoding loses nothing either.
+        let v: Value = human::from_slice(&enc).unwrap();
+        assert_eq!(v.get_int(&[key("timeout")]).unwrap(), 5000);
+        assert_eq!(compact::from_slice::<Config>(&compact::to_vec(&v).unwrap()).unwrap(), config);
+    }
+
+    let pretty = String::from_utf8(human::to_vec(&config, 4).unwrap()).unwrap();
+    assert!(pretty.starts_with("{\n    \"name\": \"ingest\",\n    \"listen\": {\n"), "{}", pretty);
+}
+
+// The encoder settings of a downstream protocol, as they appear in its configuration file.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(default)]
+struct EncoderSettings {
+    int_width: IntWidth,
+    line_ending: LineEnding,
+    float_style: FloatStyle,
+    profile: Profile,
+    indentation: usize,
+}
+
+impl EncoderSettings {
+    fn human(&self) -> human::VVSerializer {
+        human::VVSerializer::new(Vec::new(), self.indentation).line_ending(self.line_ending).float_style(self.float_style).profile(self.profile.clone())
+    }
+
+    fn compact(&self) -> compact::VVSerializer {
+        compact::VVSerializer::new(Vec::new()).int_width(self.int_width)
+    }
+}
+
+#[test]
+fn encoder_settings() {
+    let settings: EncoderSettings = human::from_str(r#"{
+        "int_width": "Fixed4",
+        "float_style": "Deterministic",
+        "profile": {"JsonCompat": {"non_finite_placeholder": {"Some": "n/a"}}},
+        "indentation": 2,
+    }"#).unwrap();
+    assert_eq!(settings, EncoderSettings {
+        int_width: IntWidth::Fixed4,
+        line_ending: LineEnding::Lf,
+        float_style: FloatStyle::Deterministic,
+        profile: Profile::JsonCompat { non_finite_placeholder: Some("n/a".to_string()) },
+        indentation: 2,
+    });
+    assert_eq!(human::from_str::<EncoderSettings>("{}").unwrap(), EncoderSettings::default());
+
+    // The settings survive a round trip through both encodings.
+    for indentation in [0, 4] {
+        let enc = human::to_vec(&settings, indentation).unwrap();
+        assert_eq!(human::from_slice::<EncoderSettings>(&enc).unwrap(), settings);
+    }
+    assert_eq!(compact::from_slice::<EncoderSettings>(&compact::to_vec(&settings).unwrap()).unwrap(), settings);
+
+    // And they configure the serializers.
+    let mut serializer = settings.human();
+    vec![f64::NAN, 1e16].serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), b"[\n  \"n/a\",\n  1.0e16\n]");
+
+    let mut serializer = settings.compact();
+    7u8.serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner().len(), 5);
+}
@@ -0,0 +1,222 @@
+//! Encodes and decodes empty collections, and collections of empty collections, in every mode of both encodings.
+//!
+//! Every encoding must decode to the original value, both typed and as a `Value`, and re-encoding a decoded `Value` must
+//! reproduce its encoding byte for byte.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use valuable_value::human::{LineEnding, Profile};
+use valuable_value::{compact, human, to_value, Value};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+struct Bytes(#[serde(with = "bytes")] Vec<u8>);
+
+mod bytes {
+    use super::*;
+
+    pub fn serialize<S: serde::Serializer>(b: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(b)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        Vec::<u8>::deserialize(deserializer)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct NoFields {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct NoItems();
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+enum Variants {
+    Tuple(),
+    Struct {},
+    Newtype(Vec<u8>),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Everything {
+    array: Vec<u8>,
+    map: BTreeMap<String, u8>,
+    set: BTreeSet<u8>,
+    bytes: Bytes,
+    string: String,
+    nested_arrays: Vec<Vec<Vec<u8>>>,
+    nested_maps: BTreeMap<String, BTreeMap<String, u8>>,
+    no_fields: NoFields,
+    no_items: NoItems,
+    variants: Vec<Variants>,
+    wrapped: Option<Vec<u8>>,
+}
+
+fn everything() -> Everything {
+    Everything {
+        array: vec![],
+        map: BTreeMap::new(),
+        set: BTreeSet::new(),
+        bytes: Bytes(vec![]),
+        string: String::new(),
+        nested_arrays: vec![vec![], vec![vec![]]],
+        nested_maps: vec![("a".to_string(), BTreeMap::new()), ("b".to_string(), BTreeMap::new())].into_iter().collect(),
+        no_fields: NoFields {},
+        no_items: NoItems(),
+        variants: vec![Variants::Tuple(), Variants::Struct {}, Variants::Newtype(vec![])],
+        wrapped: Some(vec![]),
+    }
+}
+
+fn empty_values() -> Vec<Value> {
+    let map = |entries: Vec<(Value, Value)>| Value::Map(entries.into_iter().collect());
+    vec![
+        Value::Array(vec![]),
+        map(vec![]),
+        Value::Array(vec![Value::Array(vec![])]),
+        Value::Array(vec![map(vec![]), map(vec![])]),
+        map(vec![(Value::Array(vec![]), map(vec![]))]),
+        map(vec![(map(vec![]), Value::Array(vec![])), (Value::Array(vec![]), Value::Array(vec![]))]),
+        map(vec![(Value::Int(1), Value::Array(vec![map(vec![]), Value::Array(vec![])]))]),
+    ]
+}
+
+fn human_modes() -> Vec<(&'static str, human::VVSerializer)> {
+    let json = Profile::JsonCompat { non_finite_placeholder: None };
+    vec![
+        ("indentation 0", human::VVSerializer::new(Vec::new(), 0)),
+        ("indentation 2", human::VVSerializer::new(Vec::new(), 2)),
+        ("crlf", human::VVSerializer::new(Vec::new(), 2).line_ending(LineEnding::CrLf)),
+        ("max width", human::VVSerializer::new(Vec::new(), 2).max_width(12)),
+        ("inline structs", human::VVSerializer::new(Vec::new(), 2).inline_structs_up_to(3)),
+        ("json, indentation 0", human::VVSerializer::new(Vec::new(), 0).profile(json.clone())),
+        ("json, indentation 2", human::VVSerializer::new(Vec::new(), 2).profile(json)),
+    ]
+}
+
+fn compact_modes() -> Vec<(&'static str, compact::VVSerializer)> {
+    vec![
+        ("compact", compact::VVSerializer::new(Vec::new())),
+        ("compact options", compact::VVSerializer::new(Vec::new()).compact_options(true)),
+    ]
+}
+
+fn encode_human<T: Serialize>(x: &T, mode: &str) -> Result<String, human::EncodeError> {
+    let mut serializer = human_modes().into_iter().find(|(m, _)| *m == mode).unwrap().1;
+    x.serialize(&mut serializer)?;
+    Ok(String::from_utf8(serializer.into_inner()).unwrap())
+}
+
+// Typed values and `Value`s are checked alike, but strings only survive as strings in the typed encoding, so the
+// re-encoded `Value` is compared with the encoding of the `Value` corresponding to `x`.
+fn check_human<T: Serialize + DeserializeOwned + PartialEq + Debug>(x: &T) {
+    for (mode, _) in human_modes() {
+        let text = match encode_human(x, mode) {
+            Err(human::EncodeError::NonStringKey) if mode.starts_with("json") => continue,
+            result => result.unwrap(),
+        };
+
+        assert_eq!(&human::from_str::<T>(&text).unwrap(), x, "{}: {}", mode, text);
+        let v: Value = human::from_str(&text).unwrap();
+        assert_eq!(v, to_value(x).unwrap(), "{}: {}", mode, text);
+        // Under the JSON profile, this fails for both if `x` has string keys, which become arrays of ints in a `Value`.
+        assert_eq!(encode_human(&v, mode).ok(), encode_human(&to_value(x).unwrap(), mode).ok(), "{}", mode);
+    }
+}
+
+fn check_compact<T: Serialize + DeserializeOwned + PartialEq + Debug>(x: &T) {
+    for (mode, _) in compact_modes() {
+        let enc = encode_compact(x, mode);
+
+        let mut des = compact::VVDeserializer::new(&enc).compact_options(mode == "compact options");
+        assert_eq!(&T::deserialize(&mut des).unwrap(), x, "{}: {:?}", mode, enc);
+        des.end().unwrap();
+
+        // Compact options drop the wrappers of options, which decoding as a `Value` does not restore.
+        if mode == "compact" {
+            let v: Value = compact::from_slice(&enc).unwrap();
+            assert_eq!(v, to_value(x).unwrap());
+            assert_eq!(encode_compact(&v, mode), encode_compact(&to_value(x).unwrap(), mode));
+        }
+    }
+}
+
+fn encode_compact<T: Serialize>(x: &T, mode: &str) -> Vec<u8> {
+    let mut serializer = compact_modes().into_iter().find(|(m, _)| *m == mode).unwrap().1;
+    x.serialize(&mut serializer).unwrap();
+    serializer.into_inner()
+}
+
+#[test]
+fn typed() {
+    let x = everything();
+    check_human(&x);
+    check_compact(&x);
+
+    check_human(&Vec::<u8>::new());
+    check_compact(&Vec::<u8>::new());
+    check_human(&BTreeMap::<u8, u8>::new());
+    check_compact(&BTreeMap::<u8, u8>::new());
+    check_human(&Bytes(vec![]));
+    check_compact(&Bytes(vec![]));
+    check_human(&vec![Bytes(vec![]), Bytes(vec![])]);
+    check_compact(&vec![Bytes(vec![]), Bytes(vec![])]);
+    check_human(&NoFields {});
+    check_compact(&NoFields {});
+    check_human(&Variants::Struct {});
+    check_compact(&Variants::Struct {});
+}
+
+#[test]
+fn values() {
+    for v in empty_values() {
+        check_compact(&v);
+        check_human(&v);
+        assert_eq!(valuable_value::from_value::<Value>(&v).unwrap(), v);
+    }
+}
+
+#[test]
+fn pretty_layout() {
+    let pretty = |x: &Everything| String::from_utf8(human::to_vec(x, 2).unwrap()).unwrap();
+    assert_eq!(pretty(&everything()), r#"{
+  "array": [],
+  "map": {},
+  "set": [],
+  "bytes": @[],
+  "string": "",
+  "nested_arrays": [
+    [],
+    [[]],
+  ],
+  "nested_maps": {
+    "a": {},
+    "b": {},
+  },
+  "no_fields": {},
+  "no_items": [],
+  "variants": [
+    {"Tuple": []},
+    {"Struct": {}},
+    {"Newtype": []},
+  ],
+  "wrapped": {"Some": []},
+}"#);
+}
+
+#[test]
+fn sets_are_maps() {
+    // A set is a map whose values are all nil, so the empty set is the empty map, and encoders write it as such.
+    for input in [&b"@{}"[..], b"@{,}", b"{}", b"{,}"] {
+        assert_eq!(human::from_slice::<Value>(input).unwrap(), Value::Map(BTreeMap::new()));
+    }
+    assert_eq!(human::to_vec(&Value::Map(BTreeMap::new()), 0).unwrap(), b"{}");
+
+    let empty_set = [compact::tags::SET];
+    let empty_map = compact::to_vec(&Value::Map(BTreeMap::new())).unwrap();
+    assert_eq!(empty_map, [compact::tags::MAP]);
+    assert_eq!(compact::from_slice::<Value>(&empty_set).unwrap(), Value::Map(BTreeMap::new()));
+    assert_eq!(compact::from_slice::<BTreeMap<u8, ()>>(&empty_set).unwrap(), BTreeMap::new());
+}
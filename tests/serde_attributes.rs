@@ -0,0 +1,275 @@
+//! Round trips types using the common serde attributes through both encodings and through the `Value` type.
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use serde::de::{DeserializeOwned, IntoDeserializer};
+use serde::{Deserialize, Serialize};
+use valuable_value::{compact, human, Value};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Renamed {
+    some_field: u8,
+    #[serde(rename = "other")]
+    other_field: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Skipping {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    maybe: Option<u8>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    list: Vec<u8>,
+    #[serde(skip)]
+    cache: u32,
+    flag: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "type")]
+enum Internal {
+    Struct { x: u8, list: Vec<i8> },
+    Newtype(Renamed),
+    Unit,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "t", content = "c")]
+enum Adjacent {
+    Struct { x: u8, o: Option<u8> },
+    Newtype(Option<u8>),
+    Tuple(u8, String),
+    Unit,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+enum Untagged {
+    Int(i64),
+    Str(String),
+    Struct { x: u8, flag: bool },
+    Counts(BTreeMap<String, u8>),
+    List(Vec<Untagged>),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(transparent)]
+struct Transparent {
+    inner: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Flattened {
+    a: u8,
+    o: Option<u8>,
+    #[serde(flatten)]
+    renamed: Renamed,
+    #[serde(flatten)]
+    rest: BTreeMap<String, Vec<u8>>,
+}
+
+// Strings and maps that look like encoded options, in all the places that serde buffers.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "type")]
+enum Named {
+    Named { name: String, counts: BTreeMap<String, u8> },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct NamedFields {
+    name: String,
+    counts: BTreeMap<String, u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct FlattenedNamed {
+    a: u8,
+    #[serde(flatten)]
+    fields: NamedFields,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Wrapper {
+    internal: Vec<Internal>,
+    adjacent: Vec<Adjacent>,
+    flattened: Option<Flattened>,
+}
+
+fn from_value<T: DeserializeOwned>(v: Value) -> Result<T, String> {
+    T::deserialize(v.into_deserializer()).map_err(|e: serde::de::value::Error| e.to_string())
+}
+
+// Round trips through the human-readable encoding with and without indentation, and checks that both decode to the same `Value`.
+fn round_trip_human<T: Serialize + DeserializeOwned + Debug + PartialEq>(x: &T) -> Value {
+    let mut values = vec![];
+    for indentation in [0, 2] {
+        let enc = human::to_vec(x, indentation).unwrap();
+        assert_eq!(&human::from_slice::<T>(&enc).unwrap(), x, "human: {}", String::from_utf8_lossy(&enc));
+        values.push(human::from_slice::<Value>(&enc).unwrap());
+    }
+    assert_eq!(values[0], values[1], "human value");
+    values.pop().unwrap()
+}
+
+// Round trips through both encodings, and checks that both decode to the same `Value`.
+fn round_trip_encodings<T: Serialize + DeserializeOwned + Debug + PartialEq>(x: &T) -> Value {
+    let enc = compact::to_vec(x).unwrap();
+    assert_eq!(&compact::from_slice::<T>(&enc).unwrap(), x, "compact");
+    let v = compact::from_slice::<Value>(&enc).unwrap();
+    assert_eq!(round_trip_human(x), v, "human value");
+    v
+}
+
+// Additionally decodes from the `Value`, which only works if no strings end up in content that serde buffers.
+fn round_trip<T: Serialize + DeserializeOwned + Debug + PartialEq>(x: &T) {
+    let v = round_trip_encodings(x);
+    assert_eq!(&from_value::<T>(v).unwrap(), x, "value");
+}
+
+fn renamed() -> Renamed {
+    Renamed { some_field: 1, other_field: true }
+}
+
+fn flattened() -> Flattened {
+    Flattened {
+        a: 1,
+        o: None,
+        renamed: Renamed { some_field: 2, other_field: false },
+        rest: vec![("extra".to_string(), vec![1, 2]), ("more".to_string(), vec![])].into_iter().collect(),
+    }
+}
+
+#[test]
+fn renames() {
+    round_trip(&renamed());
+    round_trip(&Renamed { some_field: 0, other_field: false });
+
+    let enc = human::to_vec(&renamed(), 0).unwrap();
+    assert_eq!(String::from_utf8(enc).unwrap(), r#"{"someField":1,"other":true}"#);
+}
+
+#[test]
+fn skipping_and_defaults() {
+    round_trip(&Skipping { maybe: None, list: vec![], cache: 0, flag: true });
+    round_trip(&Skipping { maybe: Some(3), list: vec![4, 5], cache: 0, flag: false });
+
+    let skipped = Skipping { maybe: None, list: vec![], cache: 7, flag: true };
+    assert_eq!(String::from_utf8(human::to_vec(&skipped, 0).unwrap()).unwrap(), r#"{"flag":true}"#);
+    assert_eq!(compact::from_slice::<Skipping>(&compact::to_vec(&skipped).unwrap()).unwrap().cache, 0);
+}
+
+#[test]
+fn internally_tagged() {
+    round_trip(&Internal::Struct { x: 1, list: vec![] });
+    round_trip(&Internal::Struct { x: 1, list: vec![2, -3] });
+    round_trip(&Internal::Newtype(renamed()));
+    round_trip(&Internal::Newtype(Renamed { some_field: 5, other_field: false }));
+    round_trip(&Internal::Unit);
+}
+
+#[test]
+// The encoders write the tag before the content, so serde does not need to buffer the content. The `Value` type sorts the
+// content before the tag though, so options in the content cannot be decoded from a value, see `options_in_buffered_content`.
+fn adjacently_tagged() {
+    round_trip_encodings(&Adjacent::Struct { x: 1, o: None });
+    round_trip_encodings(&Adjacent::Struct { x: 1, o: Some(9) });
+    round_trip_encodings(&Adjacent::Newtype(None));
+    round_trip_encodings(&Adjacent::Newtype(Some(4)));
+    round_trip_encodings(&Adjacent::Tuple(2, "two".to_string()));
+    round_trip(&Adjacent::Unit);
+}
+
+#[test]
+fn untagged() {
+    round_trip(&Untagged::Int(-3));
+    round_trip_encodings(&Untagged::Str("hi".to_string()));
+    round_trip(&Untagged::Struct { x: 2, flag: true });
+    round_trip(&Untagged::Counts(vec![("a".to_string(), 1)].into_iter().collect()));
+    round_trip(&Untagged::List(vec![Untagged::Int(1), Untagged::List(vec![Untagged::Int(2)])]));
+    round_trip_encodings(&Untagged::List(vec![Untagged::Int(1), Untagged::List(vec![]), Untagged::Str("".to_string())]));
+}
+
+#[test]
+fn transparent() {
+    round_trip(&Transparent { inner: None });
+    round_trip(&Transparent { inner: Some("q".to_string()) });
+    assert_eq!(human::to_vec(&Transparent { inner: None }, 0).unwrap(), br#""None""#.to_vec());
+}
+
+#[test]
+// Serde writes structs with flattened fields as maps of unknown length.
+fn flatten() {
+    round_trip(&flattened());
+    round_trip_encodings(&Flattened { o: Some(3), renamed: renamed(), rest: BTreeMap::new(), ..flattened() });
+}
+
+#[test]
+fn combined() {
+    round_trip_encodings(&Wrapper {
+        internal: vec![Internal::Unit, Internal::Struct { x: 0, list: vec![1] }, Internal::Newtype(renamed())],
+        adjacent: vec![Adjacent::Newtype(None), Adjacent::Unit],
+        flattened: Some(flattened()),
+    });
+    round_trip(&Wrapper {
+        internal: vec![Internal::Struct { x: 0, list: vec![-1] }],
+        adjacent: vec![Adjacent::Unit],
+        flattened: Some(flattened()),
+    });
+    round_trip(&Wrapper { internal: vec![], adjacent: vec![], flattened: None });
+}
+
+// Serde buffers the contents of internally tagged and untagged enums and of flattened fields without asking the decoder for
+// options, so strings and maps that look like encoded options must decode as plain strings and maps there.
+#[test]
+fn option_lookalikes_in_buffered_content() {
+    let counts = |key: &str| -> BTreeMap<String, u8> { vec![(key.to_string(), 1)].into_iter().collect() };
+    for (name, key) in [("None", "Some"), ("Some", "None"), ("x", "Some")] {
+        round_trip_encodings(&Named::Named { name: name.to_string(), counts: counts(key) });
+        round_trip_encodings(&FlattenedNamed { a: 1, fields: NamedFields { name: name.to_string(), counts: counts(key) } });
+    }
+    round_trip_encodings(&Untagged::Str("None".to_string()));
+    round_trip_encodings(&Untagged::Counts(counts("Some")));
+    round_trip_encodings(&Untagged::List(vec![Untagged::Str("None".to_string()), Untagged::Counts(counts("Some"))]));
+
+    assert_eq!(human::from_slice::<Untagged>(br#"{"Some": 1}"#).unwrap(), Untagged::Counts(counts("Some")));
+    assert_eq!(human::from_slice::<Value>(br#"{"Some": "None"}"#).unwrap(), Value::Map(vec![(Value::from("Some"), Value::from("None"))].into_iter().collect()));
+}
+
+// The flip side: options inside buffered content cannot be told apart from strings and maps, and fail to decode. Options
+// encoded by `compact_options` are nil or the plain value, which serde handles just fine.
+#[test]
+fn options_in_buffered_content() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(tag = "type")]
+    enum Optional {
+        Struct { o: Option<u8> },
+    }
+
+    for x in [Optional::Struct { o: None }, Optional::Struct { o: Some(1) }] {
+        let err = human::from_slice::<Optional>(&human::to_vec(&x, 0).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("invalid type"), "{}", err);
+        let err = compact::from_slice::<Optional>(&compact::to_vec(&x).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("invalid type"), "{}", err);
+
+        let mut serializer = compact::VVSerializer::new(Vec::new()).compact_options(true);
+        x.serialize(&mut serializer).unwrap();
+        let enc = serializer.into_inner();
+        assert_eq!(Optional::deserialize(&mut compact::VVDeserializer::new(&enc).compact_options(true)).unwrap(), x);
+    }
+
+    let v = round_trip_encodings(&Adjacent::Newtype(Some(4)));
+    assert!(from_value::<Adjacent>(v).unwrap_err().contains("invalid type"));
+}
+
+// The `Value` type represents strings as arrays of ints, so types that serde decodes without knowing what to expect see arrays
+// wherever strings were encoded, except for map keys.
+#[test]
+fn strings_in_buffered_values() {
+    let v: Value = human::from_slice(br#"{"type": "Named", "name": "x", "counts": {}}"#).unwrap();
+    assert_eq!(from_value::<Named>(v).unwrap_err(), "invalid type: sequence, expected a string");
+
+    // Untagged enums cannot tell strings from arrays of ints.
+    let v = round_trip_encodings(&Untagged::Str("hi".to_string()));
+    assert_eq!(from_value::<Untagged>(v).unwrap(), Untagged::List(vec![Untagged::Int(104), Untagged::Int(105)]));
+}